@@ -1,14 +1,25 @@
 use std::collections::HashSet;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
 use dfps_configuration::load_env;
-use dfps_core::fhir::Bundle;
-use dfps_ingestion::validation::{ValidationSeverity, validate_bundle};
+use dfps_core::fhir::{
+    IssueSeverity, IssueType, OperationOutcome, OperationOutcomeIssue, Reference,
+};
+use dfps_ingestion::{
+    BulkExport, CsvMapping, ExtensionProjection, IngestionError, ValidationMode, csv_to_staging,
+    hl7v2::{self, Hl7MappingOptions, Message, MessageReader},
+    validation::{Profiles, ValidationReport, ValidationRules, ValidationSeverity},
+};
 use dfps_observability::{PipelineMetrics, log_no_match, log_pipeline_output};
-use dfps_pipeline::bundle_to_mapped_sr;
+use dfps_pipeline::{
+    Deidentifier, IngestionMode, PipelineError, PipelineOptions, PipelineOutput, ProvenanceContext,
+    StreamOptions, VersionLedger, staging_to_mapped_sr_with_options, stream_mapped_sr,
+    track_versions, validate_and_map_sr,
+};
+use dfps_terminology::{CodeSystemRegistry, TerminologyStore, registry, store};
 use log::{LevelFilter, info, warn};
 use serde::Serialize;
 
@@ -17,14 +28,83 @@ use serde::Serialize;
     name = "map_bundles",
     about = "Ingest FHIR bundles and emit staging + mapping rows"
 )]
-
 struct Args {
-    /// NDJSON file containing FHIR Bundles (defaults to stdin)
+    /// FHIR Bundles or bare resources, one after another (NDJSON) or as a
+    /// JSON array (defaults to stdin); entries are streamed, not loaded whole
     #[arg(value_name = "INPUT")]
     input: Option<PathBuf>,
     /// Log level for env_logger (error,warn,info,debug,trace)
     #[arg(long, value_name = "LEVEL", default_value = "info")]
     log_level: String,
+    /// Extensions to project into staging columns (`column=url,...`);
+    /// overrides DFPS_SR_EXTENSION_COLUMNS
+    #[arg(long, value_name = "SPEC")]
+    extension_columns: Option<String>,
+    /// Quarantine entries that cannot be staged (emitted as
+    /// `quarantined_entry` records) instead of aborting on the first one
+    #[arg(long)]
+    partial: bool,
+    /// Read INPUT as a Bulk Data export (directory or manifest.json):
+    /// ServiceRequest files joined with their Patients and Encounters
+    #[arg(long)]
+    bulk_export: bool,
+    /// Read INPUT (or stdin) as HL7 v2 ORM^O01/OMI^O23 messages, MLLP-framed
+    /// or one segment per line; each message is mapped as its own Bundle
+    #[arg(long, conflicts_with = "bulk_export")]
+    hl7v2: bool,
+    /// UTC offset (`Z`, `+hh:mm`, `-hh:mm`) assumed for HL7 v2 timestamps
+    /// without one; otherwise such timestamps keep only their date
+    #[arg(long, value_name = "OFFSET", requires = "hl7v2")]
+    hl7_local_offset: Option<String>,
+    /// Read INPUT (or stdin) as a CSV/TSV order extract mapped by this
+    /// TOML/JSON column spec; `.tsv` inputs default to tab-separated
+    #[arg(long, value_name = "SPEC", conflicts_with_all = ["bulk_export", "hl7v2"])]
+    csv_mapping: Option<PathBuf>,
+    /// Entries staged and mapped together (ServiceRequests per Bundle with
+    /// --bulk-export); bounds memory on large inputs
+    #[arg(long, value_name = "N", default_value_t = StreamOptions::DEFAULT_WINDOW_ENTRIES)]
+    window_entries: usize,
+    /// JSON file of ServiceRequest versions from earlier runs (created if
+    /// missing, rewritten on success); unchanged orders are skipped and every
+    /// order gets a `change` record
+    #[arg(long, value_name = "PATH")]
+    ledger: Option<PathBuf>,
+    /// TOML/JSON file of site validation rules and ValueSet bindings added to
+    /// the built-in checks; overrides DFPS_VALIDATION_RULES
+    #[arg(long, value_name = "PATH")]
+    validation_rules: Option<PathBuf>,
+    /// StructureDefinition (snapshot) and ValueSet JSON file or directory;
+    /// resources are checked against the profiles in their meta.profile;
+    /// overrides DFPS_PROFILES
+    #[arg(long, value_name = "PATH")]
+    profiles: Option<PathBuf>,
+    /// Canonical URL of a loaded profile applied to resources of its type
+    /// that declare none (repeatable); overrides DFPS_DEFAULT_PROFILES
+    #[arg(long = "profile", value_name = "URL", requires = "profiles")]
+    default_profiles: Vec<String>,
+    /// Report validation issues and quarantined entries as one FHIR
+    /// `operation_outcome` record per Bundle (or window) instead of
+    /// `validation_issue` records; a failed run ends with one too
+    #[arg(long)]
+    operation_outcome: bool,
+    /// TOML/JSON de-identification config: ids are pseudonymized with the
+    /// key in DFPS_DEID_KEY, dates shifted per patient and free text stripped
+    /// or scrubbed before staging; overrides DFPS_DEID_CONFIG
+    #[arg(long, value_name = "PATH")]
+    deid_config: Option<PathBuf>,
+    /// Also emit a FHIR `provenance` record per staged order, naming the
+    /// input position it was read from and this run
+    #[arg(long)]
+    provenance_resources: bool,
+    /// TOML/JSON code system registry (URL, aliases/OIDs, name, version,
+    /// license tier, source kind) merged with the built-ins; overrides
+    /// DFPS_CODE_SYSTEMS
+    #[arg(long, value_name = "PATH")]
+    code_systems: Option<PathBuf>,
+    /// FHIR CodeSystem/ValueSet JSON file or directory for code-level ValueSet
+    /// membership and displays; overrides DFPS_TERMINOLOGY
+    #[arg(long, value_name = "PATH")]
+    terminology: Option<PathBuf>,
 }
 
 #[derive(Serialize)]
@@ -38,81 +118,379 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     load_env("app.cli").map_err(|err| format!("dfps_cli env error: {err}"))?;
     let args = Args::parse();
     init_logging(&args.log_level)?;
-    let reader: Box<dyn BufRead> = match &args.input {
-        Some(path) => Box::new(BufReader::new(File::open(path)?)),
-        None => Box::new(BufReader::new(io::stdin())),
-    };
+    let result = run(&args);
+    if args.operation_outcome
+        && let Err(err) = &result
+    {
+        write_json(
+            &mut io::stdout().lock(),
+            "operation_outcome",
+            &failure_outcome(err.as_ref()),
+        )?;
+    }
+    result
+}
 
-    let mut dims_seen: HashSet<String> = HashSet::new();
+fn run(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    registry::install(match &args.code_systems {
+        Some(path) => CodeSystemRegistry::load(path)?,
+        None => CodeSystemRegistry::from_env()?,
+    })?;
+    store::install(match &args.terminology {
+        Some(path) => TerminologyStore::load(path)?,
+        None => TerminologyStore::from_env()?,
+    })?;
+    let run = ProvenanceContext::start().with_source_uri(match &args.input {
+        Some(path) => path.display().to_string(),
+        None => "stdin".to_string(),
+    });
+    let mut options = PipelineOptions {
+        projection: match &args.extension_columns {
+            Some(spec) => ExtensionProjection::parse(spec)?,
+            None => ExtensionProjection::from_env()?,
+        },
+        ingestion: if args.partial {
+            IngestionMode::PartialSuccess
+        } else {
+            IngestionMode::Atomic
+        },
+        stream: StreamOptions {
+            window_entries: args.window_entries,
+            ..StreamOptions::default()
+        },
+        rules: match &args.validation_rules {
+            Some(path) => ValidationRules::load(path)?,
+            None => ValidationRules::from_env()?,
+        }
+        .with_profiles(match &args.profiles {
+            Some(path) => args
+                .default_profiles
+                .iter()
+                .try_fold(Profiles::load(path)?, |profiles, url| {
+                    profiles.with_default(url)
+                })?,
+            None => Profiles::from_env()?,
+        }),
+        deid: match &args.deid_config {
+            Some(path) => Some(Deidentifier::load(path)?),
+            None => Deidentifier::from_env()?,
+        },
+        provenance: Some(run.clone()),
+    };
+    info!(
+        "run_id={} source={}",
+        run.run_id,
+        run.source_uri.as_deref().unwrap_or_default()
+    );
     let stdout = io::stdout();
-    let mut handle = stdout.lock();
-    let mut metrics = PipelineMetrics::default();
-
-    for line in reader.lines() {
-        let raw = line?;
-        let trimmed = raw.trim();
-        if trimmed.is_empty() {
-            continue;
+    let mut sink = RecordSink {
+        handle: stdout.lock(),
+        dims_seen: HashSet::new(),
+        metrics: PipelineMetrics::default(),
+        ledger: args.ledger.as_ref().map(VersionLedger::load).transpose()?,
+        operation_outcome: args.operation_outcome,
+        provenance_resources: args.provenance_resources,
+    };
+
+    if args.bulk_export {
+        map_bulk_export(args, &mut options, &run, &mut sink)?;
+    } else if let Some(spec) = &args.csv_mapping {
+        map_csv(args, spec, &options, &run, &mut sink)?;
+    } else if args.hl7v2 {
+        map_hl7v2(args, &mut options, &run, &mut sink)?;
+    } else {
+        map_stream(args, &options, &mut sink)?;
+    }
+
+    let RecordSink {
+        mut handle,
+        metrics,
+        ledger,
+        ..
+    } = sink;
+    if let (Some(path), Some(ledger)) = (&args.ledger, &ledger) {
+        ledger.save(path)?;
+    }
+    info!(
+        target: "dfps_pipeline",
+        "pipeline_complete bundles={} automap={} review={} nomatch={}",
+        metrics.bundle_count,
+        metrics.auto_mapped,
+        metrics.needs_review,
+        metrics.no_match
+    );
+    write_json(&mut handle, "metrics_summary", &metrics)?;
+
+    Ok(())
+}
+
+/// `--bulk-export`: one Bundle per window of ServiceRequests, joined with
+/// their Patients and Encounters.
+fn map_bulk_export(
+    args: &Args,
+    options: &mut PipelineOptions,
+    run: &ProvenanceContext,
+    sink: &mut RecordSink<impl Write>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = args
+        .input
+        .as_ref()
+        .ok_or("--bulk-export needs the export directory or manifest as INPUT")?;
+    let export = BulkExport::open(path)?;
+    for skipped in export.unjoined_types() {
+        warn!("bulk export: {skipped} files are not ingested");
+    }
+    if !export.manifest().error.is_empty() {
+        warn!(
+            "bulk export lists {} error file(s); those resources are missing from the export",
+            export.manifest().error.len()
+        );
+    }
+    for (index, bundle) in export
+        .service_request_bundles(args.window_entries)?
+        .enumerate()
+    {
+        let bundle = bundle?;
+        options.provenance = Some(run.for_bundle(index));
+        let (report, mut output) = validate_and_map_sr(&bundle, options)?;
+        sink.emit(index, &report, &mut output, true)?;
+    }
+    Ok(())
+}
+
+/// `--csv-mapping`: the whole extract is staged and mapped as one batch.
+fn map_csv(
+    args: &Args,
+    spec: &Path,
+    options: &PipelineOptions,
+    run: &ProvenanceContext,
+    sink: &mut RecordSink<impl Write>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut mapping = CsvMapping::load(spec)?;
+    let tsv = args
+        .input
+        .as_ref()
+        .is_some_and(|path| path.extension().is_some_and(|ext| ext == "tsv"));
+    if mapping.delimiter().is_none() && tsv {
+        mapping = mapping.with_delimiter(b'\t');
+    }
+    let mut staging = csv_to_staging(open_input(args)?, &mapping, ValidationMode::default())?;
+    run.stamp_csv(&mut staging);
+    if staging.rows_rejected > 0 {
+        warn!(
+            "csv extract: {} of {} row(s) rejected",
+            staging.rows_rejected, staging.rows_read
+        );
+    }
+    let mut report = ValidationReport::default();
+    for mut row in staging.issues {
+        // Issues quote row values, so they leave no more identified than
+        // the rows do.
+        if let Some(deid) = &options.deid {
+            deid.deidentify_csv_issue(&mut row);
         }
-        let bundle: Bundle = serde_json::from_str(trimmed)?;
-        let validation = validate_bundle(&bundle);
+        if args.operation_outcome {
+            let mut issue = row.issue;
+            issue.message = format!("line {}: {}", row.line, issue.message);
+            report.issues.push(issue);
+        } else {
+            write_json(&mut sink.handle, "validation_issue", &row)?;
+        }
+    }
+    let mut output = staging_to_mapped_sr_with_options(staging.rows, options);
+    sink.emit(0, &report, &mut output, true)
+}
+
+/// `--hl7v2`: each message is converted and mapped as its own Bundle.
+fn map_hl7v2(
+    args: &Args,
+    options: &mut PipelineOptions,
+    run: &ProvenanceContext,
+    sink: &mut RecordSink<impl Write>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mapping = match &args.hl7_local_offset {
+        Some(offset) => Hl7MappingOptions::default().with_local_offset(offset)?,
+        None => Hl7MappingOptions::default(),
+    };
+    for (index, message) in MessageReader::new(open_input(args)?).enumerate() {
+        let bundle = Message::parse(&message?)
+            .map_err(Into::into)
+            .and_then(|message| hl7v2::message_to_bundle_with_options(&message, &mapping))
+            .map_err(|err| format!("HL7 v2 message {index}: {err}"))?;
+        options.provenance = Some(run.for_bundle(index));
+        let (report, mut output) = validate_and_map_sr(&bundle, options)?;
+        sink.emit(index, &report, &mut output, true)?;
+    }
+    Ok(())
+}
+
+/// Default input: Bundles and bare resources streamed window by window.
+fn map_stream(
+    args: &Args,
+    options: &PipelineOptions,
+    sink: &mut RecordSink<impl Write>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for window in stream_mapped_sr(open_input(args)?, options) {
+        let mut window = window?;
+        sink.emit(
+            window.header.index,
+            &window.report,
+            &mut window.output,
+            window.last,
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the records for each mapped Bundle (or window of one) to stdout.
+struct RecordSink<W> {
+    handle: W,
+    dims_seen: HashSet<String>,
+    metrics: PipelineMetrics,
+    /// Set by `--ledger`.
+    ledger: Option<VersionLedger>,
+    /// Set by `--operation-outcome`.
+    operation_outcome: bool,
+    /// Set by `--provenance-resources`.
+    provenance_resources: bool,
+}
+
+impl<W: Write> RecordSink<W> {
+    /// `closes_bundle` is false for streamed windows before a Bundle's last,
+    /// so metrics count each Bundle once.
+    fn emit(
+        &mut self,
+        bundle: usize,
+        validation: &ValidationReport,
+        output: &mut PipelineOutput,
+        closes_bundle: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let handle = &mut self.handle;
         if validation.has_errors() {
             warn!(
-                "validation detected {} issue(s) ({} errors).",
+                "validation detected {} issue(s) ({} errors) in bundle {}.",
                 validation.issues.len(),
                 validation
                     .issues
                     .iter()
                     .filter(|issue| matches!(issue.severity, ValidationSeverity::Error))
-                    .count()
+                    .count(),
+                bundle
             );
         } else if !validation.issues.is_empty() {
             info!(
-                "validation reported {} warning(s)/info messages.",
-                validation.issues.len()
+                "validation reported {} warning(s)/info messages in bundle {}.",
+                validation.issues.len(),
+                bundle
             );
         }
-        for issue in &validation.issues {
-            write_json(&mut handle, "validation_issue", issue)?;
+        if self.operation_outcome {
+            write_json(
+                handle,
+                "operation_outcome",
+                &bundle_outcome(validation, output),
+            )?;
+        } else {
+            for issue in &validation.issues {
+                write_json(handle, "validation_issue", issue)?;
+            }
+        }
+        if let Some(ledger) = &mut self.ledger {
+            track_versions(output, ledger);
+            for change in &output.changes {
+                write_json(handle, "change", change)?;
+            }
+        }
+        if closes_bundle {
+            log_pipeline_output(
+                &output.flats,
+                &output.exploded_codes,
+                &output.mapping_results,
+                &mut self.metrics,
+            );
+        } else {
+            self.metrics.record_rows(
+                &output.flats,
+                &output.exploded_codes,
+                &output.mapping_results,
+            );
         }
-        let output = bundle_to_mapped_sr(&bundle)?;
-        log_pipeline_output(
-            &output.flats,
-            &output.exploded_codes,
-            &output.mapping_results,
-            &mut metrics,
-        );
 
+        for entry in &output.quarantine {
+            warn!("quarantined entry {}: {}", entry.index, entry.error);
+            write_json(handle, "quarantined_entry", entry)?;
+        }
         for flat in &output.flats {
-            write_json(&mut handle, "staging_flat", flat)?;
+            write_json(handle, "staging_flat", flat)?;
         }
         for code in &output.exploded_codes {
-            write_json(&mut handle, "staging_code", code)?;
+            write_json(handle, "staging_code", code)?;
+        }
+        if self.provenance_resources {
+            for flat in &output.flats {
+                if let Some(provenance) = &flat.provenance {
+                    let target = Reference {
+                        reference: Some(format!("ServiceRequest/{}", flat.sr_id)),
+                        display: None,
+                    };
+                    write_json(handle, "provenance", &provenance.to_fhir(target))?;
+                }
+            }
         }
         for mapping in &output.mapping_results {
-            write_json(&mut handle, "mapping_result", mapping)?;
+            write_json(handle, "mapping_result", mapping)?;
             if matches!(mapping.state, dfps_core::mapping::MappingState::NoMatch) {
                 log_no_match(mapping);
             }
         }
         for concept in &output.dim_concepts {
-            if dims_seen.insert(concept.ncit_id.clone()) {
-                write_json(&mut handle, "dim_concept", concept)?;
+            if self.dims_seen.insert(concept.ncit_id.clone()) {
+                write_json(handle, "dim_concept", concept)?;
             }
         }
+        Ok(())
     }
+}
 
-    info!(
-        target: "dfps_pipeline",
-        "pipeline_complete bundles={} automap={} review={} nomatch={}",
-        metrics.bundle_count,
-        metrics.auto_mapped,
-        metrics.needs_review,
-        metrics.no_match
-    );
-    write_json(&mut handle, "metrics_summary", &metrics)?;
+/// Validation issues of one Bundle (or window) plus the errors of its
+/// quarantined entries as a single OperationOutcome; `All OK` when there are
+/// none.
+fn bundle_outcome(validation: &ValidationReport, output: &PipelineOutput) -> OperationOutcome {
+    let mut outcome = OperationOutcome::from(validation);
+    if output.quarantine.is_empty() {
+        return outcome;
+    }
+    if validation.issues.is_empty() {
+        outcome.issue.clear();
+    }
+    for entry in &output.quarantine {
+        outcome.issue.extend(entry.error_outcome_issues());
+    }
+    outcome
+}
 
-    Ok(())
+/// OperationOutcome for an error that ended the run.
+fn failure_outcome(err: &(dyn std::error::Error + 'static)) -> OperationOutcome {
+    let ingestion = match err.downcast_ref::<PipelineError>() {
+        Some(PipelineError::Ingestion(source)) => Some(source),
+        None => err.downcast_ref::<IngestionError>(),
+    };
+    match ingestion {
+        Some(source) => OperationOutcome::from(source),
+        None => OperationOutcome::new(vec![OperationOutcomeIssue::new(
+            IssueSeverity::Error,
+            IssueType::Exception,
+            err.to_string(),
+        )]),
+    }
+}
+
+/// INPUT, or stdin when no path was given.
+fn open_input(args: &Args) -> io::Result<Box<dyn BufRead>> {
+    Ok(match &args.input {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(BufReader::new(io::stdin())),
+    })
 }
 
 fn init_logging(level: &str) -> Result<(), Box<dyn std::error::Error>> {
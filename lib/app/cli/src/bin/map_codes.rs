@@ -6,6 +6,7 @@ use clap::Parser;
 use dfps_configuration::load_env;
 use dfps_core::staging::StgSrCodeExploded;
 use dfps_mapping::{explain_staging_code, map_staging_codes_with_summary};
+use dfps_terminology::{CodeSystemRegistry, TerminologyStore, registry, store};
 
 #[derive(Parser)]
 #[command(name = "map_codes", about = "Map staging codes to NCIt concepts")]
@@ -19,11 +20,27 @@ struct Args {
     /// Number of candidates to include when explaining mappings
     #[arg(long, default_value_t = 5)]
     explain_top: usize,
+    /// TOML/JSON code system registry merged with the built-ins; overrides
+    /// DFPS_CODE_SYSTEMS
+    #[arg(long, value_name = "PATH")]
+    code_systems: Option<PathBuf>,
+    /// FHIR CodeSystem/ValueSet JSON file or directory for code-level ValueSet
+    /// membership and displays; overrides DFPS_TERMINOLOGY
+    #[arg(long, value_name = "PATH")]
+    terminology: Option<PathBuf>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     load_env("app.cli").map_err(|err| format!("dfps_cli env error: {err}"))?;
     let args = Args::parse();
+    registry::install(match &args.code_systems {
+        Some(path) => CodeSystemRegistry::load(path)?,
+        None => CodeSystemRegistry::from_env()?,
+    })?;
+    store::install(match &args.terminology {
+        Some(path) => TerminologyStore::load(path)?,
+        None => TerminologyStore::from_env()?,
+    })?;
     let reader: Box<dyn BufRead> = match &args.input {
         Some(path) => Box::new(BufReader::new(File::open(path)?)),
         None => Box::new(BufReader::new(io::stdin())),
@@ -8,20 +8,25 @@ use std::{
 use axum::{
     Json, Router,
     body::Bytes,
-    extract::State,
-    http::StatusCode,
+    extract::{Query, State},
+    http::{StatusCode, header},
     response::{IntoResponse, Response},
     routing::{get, post},
 };
 use dfps_core::{
-    fhir::Bundle,
+    fhir::{Bundle, IssueSeverity, IssueType, OperationOutcome, OperationOutcomeIssue},
     mapping::{DimNCITConcept, MappingResult, MappingState},
     staging::{StgServiceRequestFlat, StgSrCodeExploded},
+    value::FhirInstant,
 };
 use dfps_observability::{PipelineMetrics, log_no_match, log_pipeline_output};
-use dfps_pipeline::{PipelineError, bundle_to_mapped_sr};
+use dfps_pipeline::{
+    Deidentifier, ExtensionProjection, IngestionMode, PipelineError, PipelineOptions, Profiles,
+    ProvenanceContext, QuarantinedEntry, ValidationReport, ValidationRules, validate_and_map_sr,
+};
+use dfps_terminology::{CodeSystemRegistry, TerminologyStore, list_code_systems, registry, store};
 use log::{error, info, warn};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use thiserror::Error;
 use tokio::{net::TcpListener, sync::Mutex};
@@ -74,19 +79,45 @@ pub enum ServerError {
     },
     #[error("server error: {0}")]
     Serve(#[source] std::io::Error),
+    #[error("configuration error: {0}")]
+    Config(String),
 }
 
 #[derive(Clone)]
 pub struct ApiState {
     metrics: Arc<Mutex<PipelineMetrics>>,
+    projection: Arc<ExtensionProjection>,
+    deid: Option<Arc<Deidentifier>>,
+    rules: Arc<ValidationRules>,
 }
 
 impl ApiState {
     pub fn new() -> Self {
+        Self::with_projection(ExtensionProjection::default())
+    }
+
+    /// State whose `/api/map-bundles` rows carry the given extension columns.
+    pub fn with_projection(projection: ExtensionProjection) -> Self {
         Self {
             metrics: Arc::new(Mutex::new(PipelineMetrics::default())),
+            projection: Arc::new(projection),
+            deid: None,
+            rules: Arc::new(ValidationRules::default()),
         }
     }
+
+    /// De-identify every Bundle before it is staged (see [`Deidentifier`]).
+    pub fn with_deidentifier(mut self, deid: Deidentifier) -> Self {
+        self.deid = Some(Arc::new(deid));
+        self
+    }
+
+    /// Site rules, bindings and profiles checked, next to the built-in
+    /// validation, for every Bundle.
+    pub fn with_rules(mut self, rules: ValidationRules) -> Self {
+        self.rules = Arc::new(rules);
+        self
+    }
 }
 
 impl Default for ApiState {
@@ -105,7 +136,45 @@ pub async fn run(config: ApiServerConfig) -> Result<(), ServerError> {
         .await
         .map_err(|source| ServerError::Bind { addr, source })?;
 
-    let router = router(ApiState::default());
+    let code_systems =
+        CodeSystemRegistry::from_env().map_err(|err| ServerError::Config(err.to_string()))?;
+    info!(
+        target: "dfps_api",
+        "code system registry entries={}",
+        code_systems.entries().len()
+    );
+    registry::install(code_systems).map_err(|err| ServerError::Config(err.to_string()))?;
+    let terminology =
+        TerminologyStore::from_env().map_err(|err| ServerError::Config(err.to_string()))?;
+    info!(
+        target: "dfps_api",
+        "terminology store code_systems={} value_sets={}",
+        terminology.code_systems().count(),
+        terminology.value_sets().count()
+    );
+    store::install(terminology).map_err(|err| ServerError::Config(err.to_string()))?;
+    let projection =
+        ExtensionProjection::from_env().map_err(|err| ServerError::Config(err.to_string()))?;
+    let mut state = ApiState::with_projection(projection);
+    if let Some(deid) =
+        Deidentifier::from_env().map_err(|err| ServerError::Config(err.to_string()))?
+    {
+        info!(target: "dfps_api", "de-identifying bundles before staging");
+        state = state.with_deidentifier(deid);
+    }
+    let profiles = Profiles::from_env().map_err(|err| ServerError::Config(err.to_string()))?;
+    let rules = ValidationRules::from_env()
+        .map_err(|err| ServerError::Config(err.to_string()))?
+        .with_profiles(profiles);
+    info!(
+        target: "dfps_api",
+        "validation rules={} bindings={} profiles={}",
+        rules.rules().len(),
+        rules.bindings().len(),
+        rules.profiles().len()
+    );
+    state = state.with_rules(rules);
+    let router = router(state);
 
     axum::serve(listener, router.into_make_service())
         .with_graceful_shutdown(shutdown_signal())
@@ -121,6 +190,7 @@ pub fn router(state: ApiState) -> Router {
         .route("/health", get(health))
         .route("/metrics/summary", get(metrics_summary))
         .route("/api/map-bundles", post(map_bundles))
+        .route("/api/code-systems", get(code_systems))
         .with_state(state)
 }
 
@@ -142,8 +212,89 @@ async fn metrics_summary(State(state): State<ApiState>) -> impl IntoResponse {
     Json(metrics)
 }
 
-async fn map_bundles(State(state): State<ApiState>, body: Bytes) -> Result<Response, ApiError> {
+/// Active code system registry entries (built-ins plus `DFPS_CODE_SYSTEMS`).
+async fn code_systems() -> impl IntoResponse {
+    let request_id = Uuid::new_v4();
+    let entries = list_code_systems();
+    info!(
+        target: "dfps_api",
+        "request_id={request_id} code_systems entries={}",
+        entries.len()
+    );
+    Json(entries)
+}
+
+/// Query parameters for `/api/map-bundles`.
+#[derive(Debug, Default, Deserialize)]
+struct MapBundlesParams {
+    /// `partial` quarantines entries that cannot be staged instead of failing
+    /// the request.
+    mode: Option<String>,
+    /// `operation-outcome` returns errors, and the per-request `outcome`, as
+    /// FHIR OperationOutcome resources.
+    format: Option<String>,
+}
+
+/// Shape of error bodies and of the `outcome` summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResponseFormat {
+    /// `{code, message, request_id}` error bodies, no `outcome`.
+    Dfps,
+    OperationOutcome,
+}
+
+async fn map_bundles(
+    state: State<ApiState>,
+    Query(params): Query<MapBundlesParams>,
+    body: Bytes,
+) -> Response {
     let request_id = Uuid::new_v4();
+    let format = match params.format.as_deref() {
+        None | Some("json") => ResponseFormat::Dfps,
+        Some("operation-outcome") => ResponseFormat::OperationOutcome,
+        Some(other) => {
+            return ApiError::invalid_query(
+                format!("unknown format '{other}'; expected 'json' or 'operation-outcome'"),
+                request_id,
+            )
+            .into_response();
+        }
+    };
+    match map_bundles_with_format(state, params, body, request_id, format).await {
+        Ok(response) => response,
+        Err(err) if format == ResponseFormat::OperationOutcome => {
+            err.into_operation_outcome_response()
+        }
+        Err(err) => err.into_response(),
+    }
+}
+
+async fn map_bundles_with_format(
+    State(state): State<ApiState>,
+    params: MapBundlesParams,
+    body: Bytes,
+    request_id: Uuid,
+    format: ResponseFormat,
+) -> Result<Response, ApiError> {
+    let ingestion = match params.mode.as_deref() {
+        None | Some("atomic") => IngestionMode::Atomic,
+        Some("partial") => IngestionMode::PartialSuccess,
+        Some(other) => {
+            return Err(ApiError::invalid_query(
+                format!("unknown mode '{other}'; expected 'atomic' or 'partial'"),
+                request_id,
+            ));
+        }
+    };
+    let run = ProvenanceContext::new(request_id.to_string(), FhirInstant::now())
+        .with_source_uri(format!("urn:dfps:request:{request_id}"));
+    let mut options = PipelineOptions {
+        projection: (*state.projection).clone(),
+        ingestion,
+        rules: (*state.rules).clone(),
+        deid: state.deid.as_deref().cloned(),
+        ..PipelineOptions::default()
+    };
     let bundles = parse_bundles(&body, request_id)?;
     if bundles.is_empty() {
         return Err(ApiError::invalid_json(
@@ -161,10 +312,23 @@ async fn map_bundles(State(state): State<ApiState>, body: Bytes) -> Result<Respo
     let mut dims_seen: HashSet<String> = HashSet::new();
     let mut request_metrics = PipelineMetrics::default();
 
-    for bundle in bundles {
-        let output = bundle_to_mapped_sr(&bundle).map_err(|err| match err {
-            PipelineError::Ingestion(source) => ApiError::ingestion(source.to_string(), request_id),
+    for (index, bundle) in bundles.into_iter().enumerate() {
+        options.provenance = Some(run.for_bundle(index));
+        let (report, output) = validate_and_map_sr(&bundle, &options).map_err(|err| match err {
+            PipelineError::Ingestion(source) => ApiError::ingestion(
+                source.to_string(),
+                OperationOutcome::from(&source),
+                request_id,
+            ),
         })?;
+        if !report.issues.is_empty() {
+            warn!(
+                target: "dfps_api",
+                "request_id={request_id} bundle={index} validation_issues={}",
+                report.issues.len()
+            );
+        }
+        response.validation.push(report);
 
         log_pipeline_output(
             &output.flats,
@@ -181,6 +345,15 @@ async fn map_bundles(State(state): State<ApiState>, body: Bytes) -> Result<Respo
             }
         }
         response.mapping_results.extend(output.mapping_results);
+        for entry in &output.quarantine {
+            warn!(
+                target: "dfps_api",
+                "request_id={request_id} quarantined entry={} error={}",
+                entry.index,
+                entry.error
+            );
+        }
+        response.quarantine.extend(output.quarantine);
 
         for concept in output.dim_concepts {
             if dims_seen.insert(concept.ncit_id.clone()) {
@@ -201,24 +374,56 @@ async fn map_bundles(State(state): State<ApiState>, body: Bytes) -> Result<Respo
     }
     info!(
         target: "dfps_api",
-        "request_id={request_id} map_bundles complete bundles={} flats={} mappings={} automap={} needs_review={} no_match={}",
+        "request_id={request_id} map_bundles complete bundles={} flats={} quarantined={} mappings={} automap={} needs_review={} no_match={}",
         request_metrics.bundle_count,
         response.flats.len(),
+        response.quarantine.len(),
         response.mapping_results.len(),
         request_metrics.auto_mapped,
         request_metrics.needs_review,
         request_metrics.no_match
     );
 
+    if format == ResponseFormat::OperationOutcome {
+        response.outcome = Some(request_outcome(
+            &response.validation,
+            &response.quarantine,
+            request_id,
+        ));
+    }
+
     Ok(Json(response).into_response())
 }
 
-fn shutdown_signal() -> impl std::future::Future<Output = ()> {
-    async {
-        match tokio::signal::ctrl_c().await {
-            Ok(()) => info!(target: "dfps_api", "received shutdown signal"),
-            Err(err) => warn!(target: "dfps_api", "failed waiting for ctrl_c: {err}"),
-        }
+/// `outcome` for a successful request: the validation issues of every Bundle
+/// and the errors of every quarantined entry, or a single informational issue
+/// when there are none.
+fn request_outcome(
+    validation: &[ValidationReport],
+    quarantine: &[QuarantinedEntry],
+    request_id: Uuid,
+) -> OperationOutcome {
+    let issues: Vec<OperationOutcomeIssue> = validation
+        .iter()
+        .flat_map(|report| report.issues.iter().map(OperationOutcomeIssue::from))
+        .chain(
+            quarantine
+                .iter()
+                .flat_map(QuarantinedEntry::error_outcome_issues),
+        )
+        .collect();
+    let outcome = if issues.is_empty() {
+        OperationOutcome::all_ok()
+    } else {
+        OperationOutcome::new(issues)
+    };
+    outcome.with_id(request_id.to_string())
+}
+
+async fn shutdown_signal() {
+    match tokio::signal::ctrl_c().await {
+        Ok(()) => info!(target: "dfps_api", "received shutdown signal"),
+        Err(err) => warn!(target: "dfps_api", "failed waiting for ctrl_c: {err}"),
     }
 }
 
@@ -228,6 +433,13 @@ struct MapBundlesResponse {
     exploded_codes: Vec<StgSrCodeExploded>,
     mapping_results: Vec<MappingResult>,
     dim_concepts: Vec<DimNCITConcept>,
+    /// Entries set aside by `?mode=partial`.
+    quarantine: Vec<QuarantinedEntry>,
+    /// One report per input Bundle, in order.
+    validation: Vec<ValidationReport>,
+    /// Set by `?format=operation-outcome`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<OperationOutcome>,
 }
 
 #[derive(Debug, Serialize)]
@@ -243,8 +455,14 @@ enum ApiError {
         message: String,
         request_id: Uuid,
     },
+    InvalidQuery {
+        message: String,
+        request_id: Uuid,
+    },
     Ingestion {
         message: String,
+        /// The ingestion error as FHIR issues, for `?format=operation-outcome`.
+        outcome: Box<OperationOutcome>,
         request_id: Uuid,
     },
     #[allow(dead_code)]
@@ -267,7 +485,19 @@ impl ApiError {
         }
     }
 
-    fn ingestion(message: impl Into<String>, request_id: Uuid) -> Self {
+    fn invalid_query(message: impl Into<String>, request_id: Uuid) -> Self {
+        let message = message.into();
+        warn!(
+            target: "dfps_api",
+            "request_id={request_id} invalid query: {message}"
+        );
+        Self::InvalidQuery {
+            message,
+            request_id,
+        }
+    }
+
+    fn ingestion(message: impl Into<String>, outcome: OperationOutcome, request_id: Uuid) -> Self {
         let message = message.into();
         warn!(
             target: "dfps_api",
@@ -275,6 +505,7 @@ impl ApiError {
         );
         Self::Ingestion {
             message,
+            outcome: Box::new(outcome),
             request_id,
         }
     }
@@ -291,6 +522,56 @@ impl ApiError {
             request_id,
         }
     }
+
+    /// The error as an `application/fhir+json` OperationOutcome whose `id` is
+    /// the request id; the HTTP status is the same as for the DFPS body.
+    fn into_operation_outcome_response(self) -> Response {
+        let (status, outcome, request_id) = match self {
+            ApiError::InvalidJson {
+                message,
+                request_id,
+            } => (
+                StatusCode::BAD_REQUEST,
+                single_issue(IssueType::Structure, message),
+                request_id,
+            ),
+            ApiError::InvalidQuery {
+                message,
+                request_id,
+            } => (
+                StatusCode::BAD_REQUEST,
+                single_issue(IssueType::Invalid, message),
+                request_id,
+            ),
+            ApiError::Ingestion {
+                outcome,
+                request_id,
+                ..
+            } => (StatusCode::UNPROCESSABLE_ENTITY, *outcome, request_id),
+            ApiError::Internal {
+                message,
+                request_id,
+            } => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                single_issue(IssueType::Exception, message),
+                request_id,
+            ),
+        };
+        (
+            status,
+            [(header::CONTENT_TYPE, "application/fhir+json")],
+            Json(outcome.with_id(request_id.to_string())),
+        )
+            .into_response()
+    }
+}
+
+fn single_issue(code: IssueType, message: String) -> OperationOutcome {
+    OperationOutcome::new(vec![OperationOutcomeIssue::new(
+        IssueSeverity::Error,
+        code,
+        message,
+    )])
 }
 
 impl IntoResponse for ApiError {
@@ -308,9 +589,22 @@ impl IntoResponse for ApiError {
                 }),
             )
                 .into_response(),
+            ApiError::InvalidQuery {
+                message,
+                request_id,
+            } => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    code: "invalid_query",
+                    message,
+                    request_id,
+                }),
+            )
+                .into_response(),
             ApiError::Ingestion {
                 message,
                 request_id,
+                ..
             } => (
                 StatusCode::UNPROCESSABLE_ENTITY,
                 Json(ErrorResponse {
@@ -1,3 +1,5 @@
+use dfps_core::provenance::SourceProvenance;
+use dfps_core::value::{FhirDate, FhirDateTime};
 use serde::{Deserialize, Serialize};
 
 use crate::keys::{DimCodeKey, DimEncounterKey, DimNCITKey, DimPatientKey};
@@ -12,5 +14,26 @@ pub struct FactServiceRequest {
     pub status: String,
     pub intent: String,
     pub description: String,
-    pub ordered_at: Option<String>,
+    pub ordered_at: Option<FhirDateTime>,
+    /// UTC calendar day of `ordered_at`, for day-level bucketing; `None` when
+    /// the source is only precise to a month or year.
+    #[serde(default)]
+    pub ordered_on: Option<FhirDate>,
+    /// Provenance of the staged ServiceRequest row.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<SourceProvenance>,
+}
+
+/// One row per (result resource, order it fulfils), linking Observation,
+/// DiagnosticReport and ImagingStudy back to `FactServiceRequest.sr_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FactOrderResult {
+    pub sr_id: String,
+    pub patient_key: DimPatientKey,
+    /// `Observation`, `DiagnosticReport` or `ImagingStudy`.
+    pub result_type: String,
+    pub result_id: String,
+    pub status: Option<String>,
+    /// Effective start (or `started` for studies), falling back to `issued`.
+    pub observed_at: Option<FhirDateTime>,
 }
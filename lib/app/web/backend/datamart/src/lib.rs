@@ -1,6 +1,7 @@
 pub mod dim;
 pub mod fact;
 pub mod keys;
+pub mod store;
 
 use std::collections::{BTreeMap, HashMap};
 
@@ -16,6 +17,7 @@ use dfps_pipeline::PipelineOutput;
 pub use dim::*;
 pub use fact::*;
 pub use keys::*;
+pub use store::*;
 
 #[derive(Debug, Default, Clone)]
 pub struct Dims {
@@ -81,45 +83,47 @@ pub fn from_pipeline_output(output: &PipelineOutput) -> (Dims, Vec<FactServiceRe
     let mut facts = Vec::new();
 
     for result in &output.mapping_results {
-        if let Some((code_key, sr_id)) = code_lookup.get(&result.code_element_id) {
-            if let Some(flat) = sr_lookup.get(sr_id) {
-                let patient_key = patient_lookup[&flat.patient_id];
-                let encounter_key = flat
-                    .encounter_id
-                    .as_ref()
-                    .and_then(|id| encounter_lookup.get(id).copied());
-
-                let ncit_key = match (result.state, result.ncit_id.as_ref()) {
-                    (MappingState::NoMatch, _) | (_, None) => {
+        if let Some((code_key, sr_id)) = code_lookup.get(&result.code_element_id)
+            && let Some(flat) = sr_lookup.get(sr_id)
+        {
+            let patient_key = patient_lookup[&flat.patient_id];
+            let encounter_key = flat
+                .encounter_id
+                .as_ref()
+                .and_then(|id| encounter_lookup.get(id).copied());
+
+            let ncit_key = match (result.state, result.ncit_id.as_ref()) {
+                (MappingState::NoMatch, _) | (_, None) => {
+                    ncit_dims
+                        .entry(no_match_key.0)
+                        .or_insert_with(DimNCIT::no_match);
+                    Some(no_match_key)
+                }
+                (_, Some(id)) => {
+                    let entry = ncit_lookup.entry(id.clone()).or_insert_with(|| {
+                        let key = DimNCITKey::from_ncit_id(id);
                         ncit_dims
-                            .entry(no_match_key.0)
-                            .or_insert_with(DimNCIT::no_match);
-                        Some(no_match_key)
-                    }
-                    (_, Some(id)) => {
-                        let entry = ncit_lookup.entry(id.clone()).or_insert_with(|| {
-                            let key = DimNCITKey::from_ncit_id(id);
-                            ncit_dims
-                                .entry(key.0)
-                                .or_insert_with(|| DimNCIT::unknown(id));
-                            key
-                        });
-                        Some(*entry)
-                    }
-                };
-
-                facts.push(FactServiceRequest {
-                    sr_id: flat.sr_id.clone(),
-                    patient_key,
-                    encounter_key,
-                    code_key: *code_key,
-                    ncit_key,
-                    status: flat.status.clone(),
-                    intent: flat.intent.clone(),
-                    description: flat.description.clone(),
-                    ordered_at: flat.ordered_at.clone(),
-                });
-            }
+                            .entry(key.0)
+                            .or_insert_with(|| DimNCIT::unknown(id));
+                        key
+                    });
+                    Some(*entry)
+                }
+            };
+
+            facts.push(FactServiceRequest {
+                sr_id: flat.sr_id.clone(),
+                patient_key,
+                encounter_key,
+                code_key: *code_key,
+                ncit_key,
+                status: flat.status.clone(),
+                intent: flat.intent.clone(),
+                description: flat.description.clone(),
+                ordered_at: flat.ordered_at.clone(),
+                ordered_on: flat.ordered_at.as_ref().and_then(|at| at.utc_date()),
+                provenance: flat.provenance.clone(),
+            });
         }
     }
 
@@ -133,6 +137,87 @@ pub fn from_pipeline_output(output: &PipelineOutput) -> (Dims, Vec<FactServiceRe
     (dims, facts)
 }
 
+/// Link result resources to the orders in `output.flats` they were based on.
+///
+/// Results naming an order that is not in the run are skipped; the patient key
+/// comes from the result subject, falling back to the order's patient.
+pub fn order_result_facts(output: &PipelineOutput) -> Vec<FactOrderResult> {
+    let sr_lookup: HashMap<&str, &StgServiceRequestFlat> = output
+        .flats
+        .iter()
+        .map(|flat| (flat.sr_id.as_str(), flat))
+        .collect();
+    link_order_results(output, |sr_id| {
+        sr_lookup
+            .get(sr_id)
+            .map(|flat| DimPatientKey::from_patient_id(&flat.patient_id))
+    })
+}
+
+/// Result facts for the orders `order_patient` knows, keyed to the order's
+/// patient when the result has no subject.
+pub(crate) fn link_order_results(
+    output: &PipelineOutput,
+    order_patient: impl Fn(&str) -> Option<DimPatientKey>,
+) -> Vec<FactOrderResult> {
+    let observations = output.results.observations.iter().map(|row| {
+        (
+            "Observation",
+            &row.observation_id,
+            &row.sr_ids,
+            row.patient_id.as_ref(),
+            row.status.as_ref(),
+            row.effective_start
+                .clone()
+                .or_else(|| row.issued.as_ref().map(|at| at.as_date_time().clone())),
+        )
+    });
+    let reports = output.results.diagnostic_reports.iter().map(|row| {
+        (
+            "DiagnosticReport",
+            &row.report_id,
+            &row.sr_ids,
+            row.patient_id.as_ref(),
+            row.status.as_ref(),
+            row.effective_start
+                .clone()
+                .or_else(|| row.issued.as_ref().map(|at| at.as_date_time().clone())),
+        )
+    });
+    let studies = output.results.imaging_studies.iter().map(|row| {
+        (
+            "ImagingStudy",
+            &row.study_id,
+            &row.sr_ids,
+            row.patient_id.as_ref(),
+            row.status.as_ref(),
+            row.started.clone(),
+        )
+    });
+
+    let mut facts = Vec::new();
+    for (result_type, result_id, sr_ids, patient_id, status, observed_at) in
+        observations.chain(reports).chain(studies)
+    {
+        for sr_id in sr_ids {
+            let Some(order_patient_key) = order_patient(sr_id) else {
+                continue;
+            };
+            facts.push(FactOrderResult {
+                sr_id: sr_id.clone(),
+                patient_key: patient_id
+                    .map(|id| DimPatientKey::from_patient_id(id))
+                    .unwrap_or(order_patient_key),
+                result_type: result_type.to_string(),
+                result_id: result_id.clone(),
+                status: status.cloned(),
+                observed_at: observed_at.clone(),
+            });
+        }
+    }
+    facts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,7 +226,7 @@ mod tests {
             DimNCITConcept, MappingResult, MappingSourceVersion, MappingState, MappingStrategy,
             MappingThresholds,
         },
-        staging::{StgServiceRequestFlat, StgSrCodeExploded},
+        staging::{StgObservationFlat, StgServiceRequestFlat, StgSrCodeExploded},
     };
 
     fn sample_output() -> PipelineOutput {
@@ -153,13 +238,15 @@ mod tests {
                 status: "active".into(),
                 intent: "order".into(),
                 description: "PET-CT".into(),
-                ordered_at: Some("2024-05-01T12:00:00Z".into()),
+                ordered_at: Some("2024-05-01T23:30:00-02:00".parse().unwrap()),
+                ..Default::default()
             }],
             exploded_codes: vec![StgSrCodeExploded {
                 sr_id: "SR-1".into(),
                 system: Some("http://loinc.org".into()),
                 code: Some("24606-6".into()),
                 display: Some("FDG uptake".into()),
+                provenance: None,
             }],
             mapping_results: vec![MappingResult {
                 code_element_id: "SR-1::http://loinc.org::24606-6".into(),
@@ -173,12 +260,14 @@ mod tests {
                 reason: None,
                 license_tier: None,
                 source_kind: None,
+                provenance: None,
             }],
             dim_concepts: vec![DimNCITConcept {
                 ncit_id: "C1234".into(),
                 preferred_name: "FDG Uptake".into(),
                 semantic_group: "Procedure".into(),
             }],
+            ..Default::default()
         }
     }
 
@@ -194,6 +283,10 @@ mod tests {
         let fact = &facts[0];
         assert!(fact.ncit_key.is_some());
         assert_eq!(fact.status, "active");
+        assert_eq!(
+            fact.ordered_on.as_ref().map(|day| day.as_str()),
+            Some("2024-05-02")
+        );
     }
 
     fn sample_no_match_output() -> PipelineOutput {
@@ -206,12 +299,14 @@ mod tests {
                 intent: "order".into(),
                 description: "Unknown code".into(),
                 ordered_at: None,
+                ..Default::default()
             }],
             exploded_codes: vec![StgSrCodeExploded {
                 sr_id: "SR-2".into(),
                 system: Some("http://example.test/system".into()),
                 code: Some("UNKNOWN".into()),
                 display: Some("Unknown code".into()),
+                provenance: None,
             }],
             mapping_results: vec![MappingResult {
                 code_element_id: "SR-2::http://example.test/system::UNKNOWN".into(),
@@ -225,8 +320,10 @@ mod tests {
                 reason: Some("unknown_code_system".into()),
                 license_tier: None,
                 source_kind: None,
+                provenance: None,
             }],
             dim_concepts: vec![],
+            ..Default::default()
         }
     }
 
@@ -244,4 +341,33 @@ mod tests {
             .expect("no-match dim present");
         assert_eq!(sentinel.ncit_id, "NO_MATCH");
     }
+
+    #[test]
+    fn order_results_link_to_known_orders_only() {
+        let mut output = sample_output();
+        output.results.observations = vec![
+            StgObservationFlat {
+                observation_id: "OBS-1".into(),
+                sr_ids: vec!["SR-1".into()],
+                status: Some("final".into()),
+                issued: Some("2024-05-02T12:00:00Z".parse().unwrap()),
+                ..Default::default()
+            },
+            StgObservationFlat {
+                observation_id: "OBS-ORPHAN".into(),
+                sr_ids: vec!["SR-MISSING".into()],
+                ..Default::default()
+            },
+        ];
+
+        let facts = order_result_facts(&output);
+        assert_eq!(facts.len(), 1);
+        let fact = &facts[0];
+        assert_eq!(fact.result_id, "OBS-1");
+        assert_eq!(fact.patient_key, DimPatientKey::from_patient_id("PAT-1"));
+        assert_eq!(
+            fact.observed_at.as_ref().map(|at| at.as_str()),
+            Some("2024-05-02T12:00:00Z")
+        );
+    }
 }
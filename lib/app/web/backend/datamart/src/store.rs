@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, HashMap};
+
+use dfps_pipeline::{ChangeKind, PipelineOutput};
+
+use crate::{
+    Dims,
+    dim::{DimCode, DimEncounter, DimNCIT, DimPatient},
+    fact::{FactOrderResult, FactServiceRequest},
+    from_pipeline_output,
+    keys::DimPatientKey,
+    link_order_results,
+};
+
+/// Star schema accumulated over several pipeline runs.
+///
+/// Facts are upserted per order: every order in `output.flats` replaces the
+/// facts stored for its `sr_id`, and `deleted` change records (see
+/// `dfps_pipeline::track_versions`) remove them, so re-submitting a Bundle
+/// never duplicates facts. Result facts are replaced per result resource.
+/// Dims only grow.
+#[derive(Debug, Default, Clone)]
+pub struct Datamart {
+    patients: BTreeMap<u64, DimPatient>,
+    encounters: BTreeMap<u64, DimEncounter>,
+    codes: BTreeMap<u64, DimCode>,
+    ncit: BTreeMap<u64, DimNCIT>,
+    facts: BTreeMap<String, Vec<FactServiceRequest>>,
+    order_patients: HashMap<String, DimPatientKey>,
+    /// Keyed by (result type, result id).
+    order_results: BTreeMap<(String, String), Vec<FactOrderResult>>,
+}
+
+impl Datamart {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Upsert the orders and results of one pipeline run.
+    pub fn apply(&mut self, output: &PipelineOutput) {
+        for change in &output.changes {
+            if change.change == ChangeKind::Deleted {
+                self.facts.remove(&change.sr_id);
+                self.order_patients.remove(&change.sr_id);
+                for facts in self.order_results.values_mut() {
+                    facts.retain(|fact| fact.sr_id != change.sr_id);
+                }
+            }
+        }
+        self.order_results.retain(|_, facts| !facts.is_empty());
+
+        let (dims, facts) = from_pipeline_output(output);
+        merge(&mut self.patients, dims.patients, |dim| dim.key.0);
+        merge(&mut self.encounters, dims.encounters, |dim| dim.key.0);
+        merge(&mut self.codes, dims.codes, |dim| dim.key.0);
+        merge(&mut self.ncit, dims.ncit, |dim| dim.key.0);
+
+        let mut by_order: HashMap<String, Vec<FactServiceRequest>> = HashMap::new();
+        for fact in facts {
+            by_order.entry(fact.sr_id.clone()).or_default().push(fact);
+        }
+        for flat in &output.flats {
+            let facts = by_order.remove(&flat.sr_id).unwrap_or_default();
+            self.facts.insert(flat.sr_id.clone(), facts);
+            self.order_patients.insert(
+                flat.sr_id.clone(),
+                DimPatientKey::from_patient_id(&flat.patient_id),
+            );
+        }
+
+        let results = &output.results;
+        let resubmitted = results
+            .observations
+            .iter()
+            .map(|row| ("Observation", &row.observation_id))
+            .chain(
+                results
+                    .diagnostic_reports
+                    .iter()
+                    .map(|row| ("DiagnosticReport", &row.report_id)),
+            )
+            .chain(
+                results
+                    .imaging_studies
+                    .iter()
+                    .map(|row| ("ImagingStudy", &row.study_id)),
+            );
+        for (result_type, result_id) in resubmitted {
+            self.order_results
+                .remove(&(result_type.to_string(), result_id.clone()));
+        }
+        let linked = link_order_results(output, |sr_id| self.order_patients.get(sr_id).copied());
+        for fact in linked {
+            self.order_results
+                .entry((fact.result_type.clone(), fact.result_id.clone()))
+                .or_default()
+                .push(fact);
+        }
+    }
+
+    pub fn dims(&self) -> Dims {
+        Dims {
+            patients: self.patients.values().cloned().collect(),
+            encounters: self.encounters.values().cloned().collect(),
+            codes: self.codes.values().cloned().collect(),
+            ncit: self.ncit.values().cloned().collect(),
+        }
+    }
+
+    /// Current facts, ordered by `sr_id`.
+    pub fn facts(&self) -> impl Iterator<Item = &FactServiceRequest> {
+        self.facts.values().flatten()
+    }
+
+    pub fn order_results(&self) -> impl Iterator<Item = &FactOrderResult> {
+        self.order_results.values().flatten()
+    }
+
+    pub fn contains_order(&self, sr_id: &str) -> bool {
+        self.facts.contains_key(sr_id)
+    }
+}
+
+fn merge<T>(target: &mut BTreeMap<u64, T>, dims: Vec<T>, key: impl Fn(&T) -> u64) {
+    for dim in dims {
+        target.entry(key(&dim)).or_insert(dim);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfps_core::{fhir::Bundle, staging::StgObservationFlat};
+    use dfps_pipeline::{VersionLedger, bundle_to_mapped_sr, track_versions};
+    use serde_json::json;
+
+    fn bundle(entries: serde_json::Value) -> Bundle {
+        serde_json::from_value(json!({
+            "resourceType": "Bundle",
+            "type": "transaction",
+            "entry": entries
+        }))
+        .unwrap()
+    }
+
+    fn put_sr(version: &str, status: &str) -> serde_json::Value {
+        json!({
+            "request": { "method": "PUT", "url": "ServiceRequest/SR-1" },
+            "resource": {
+                "resourceType": "ServiceRequest",
+                "id": "SR-1",
+                "meta": { "versionId": version },
+                "status": status,
+                "intent": "order",
+                "subject": { "reference": "Patient/PAT-1" },
+                "code": { "coding": [{ "system": "http://loinc.org", "code": "24606-6" }] }
+            }
+        })
+    }
+
+    fn run(mart: &mut Datamart, ledger: &mut VersionLedger, bundle: &Bundle) -> Vec<ChangeKind> {
+        let mut output = bundle_to_mapped_sr(bundle).expect("pipeline output");
+        track_versions(&mut output, ledger);
+        mart.apply(&output);
+        output.changes.iter().map(|change| change.change).collect()
+    }
+
+    #[test]
+    fn resubmission_upserts_instead_of_appending() {
+        let mut mart = Datamart::new();
+        let mut ledger = VersionLedger::new();
+        let v1 = bundle(json!([put_sr("1", "active")]));
+
+        assert_eq!(run(&mut mart, &mut ledger, &v1), vec![ChangeKind::Created]);
+        assert_eq!(
+            run(&mut mart, &mut ledger, &v1),
+            vec![ChangeKind::Unchanged]
+        );
+        assert_eq!(mart.facts().count(), 1);
+
+        let v2 = bundle(json!([put_sr("2", "completed")]));
+        assert_eq!(run(&mut mart, &mut ledger, &v2), vec![ChangeKind::Updated]);
+        let statuses: Vec<_> = mart.facts().map(|fact| fact.status.as_str()).collect();
+        assert_eq!(statuses, vec!["completed"]);
+
+        let delete = bundle(json!([
+            { "request": { "method": "DELETE", "url": "ServiceRequest/SR-1" } }
+        ]));
+        assert_eq!(
+            run(&mut mart, &mut ledger, &delete),
+            vec![ChangeKind::Deleted]
+        );
+        assert_eq!(mart.facts().count(), 0);
+        assert!(!mart.contains_order("SR-1"));
+    }
+
+    #[test]
+    fn results_link_to_orders_from_earlier_runs() {
+        let mut mart = Datamart::new();
+        let mut ledger = VersionLedger::new();
+        run(
+            &mut mart,
+            &mut ledger,
+            &bundle(json!([put_sr("1", "active")])),
+        );
+
+        let mut output = PipelineOutput::default();
+        output.results.observations = vec![StgObservationFlat {
+            observation_id: "OBS-1".into(),
+            sr_ids: vec!["SR-1".into()],
+            ..Default::default()
+        }];
+        mart.apply(&output);
+        mart.apply(&output);
+
+        let results: Vec<_> = mart.order_results().collect();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].patient_key,
+            DimPatientKey::from_patient_id("PAT-1")
+        );
+    }
+}
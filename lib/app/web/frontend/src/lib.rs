@@ -12,23 +12,14 @@ use state::AppState;
 
 pub async fn run() -> std::io::Result<()> {
     if let Err(err) = dfps_configuration::load_env("app.web.frontend") {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("dfps_web_frontend env error: {err}"),
-        ));
+        return Err(std::io::Error::other(format!(
+            "dfps_web_frontend env error: {err}"
+        )));
     }
-    let config = AppConfig::from_env().map_err(|err| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("frontend config error: {err}"),
-        )
-    })?;
-    let client = BackendClient::from_config(&config).map_err(|err| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("failed to create backend client: {err}"),
-        )
-    })?;
+    let config = AppConfig::from_env()
+        .map_err(|err| std::io::Error::other(format!("frontend config error: {err}")))?;
+    let client = BackendClient::from_config(&config)
+        .map_err(|err| std::io::Error::other(format!("failed to create backend client: {err}")))?;
     let listen_addr = config.listen_addr.clone();
     let state = AppState::new(config, client);
 
@@ -255,13 +255,15 @@ mod tests {
                 status: "active".into(),
                 intent: "order".into(),
                 description: "PET-CT".into(),
-                ordered_at: Some("2024-05-01T12:00:00Z".into()),
+                ordered_at: Some("2024-05-01T12:00:00Z".parse().unwrap()),
+                ..Default::default()
             }],
             exploded_codes: vec![StgSrCodeExploded {
                 sr_id: "SR-1".into(),
                 system: Some("http://loinc.org".into()),
                 code: Some("24606-6".into()),
                 display: Some("FDG uptake".into()),
+                provenance: None,
             }],
             mapping_results: vec![MappingResult {
                 code_element_id: "SR-1::http://loinc.org::24606-6".into(),
@@ -275,6 +277,7 @@ mod tests {
                 reason: None,
                 license_tier: None,
                 source_kind: None,
+                provenance: None,
             }],
             dim_concepts: vec![DimNCITConcept {
                 ncit_id: "C1234".into(),
@@ -79,7 +79,7 @@ pub struct NoMatchRowView {
 impl MappingResultsView {
     pub fn from_response(response: &MapBundlesResponse) -> Self {
         let request_summary = summarize_flats(&response.flats);
-        let code_lookup = build_code_lookup(&response);
+        let code_lookup = build_code_lookup(response);
         let concept_lookup = response
             .dim_concepts
             .iter()
@@ -232,7 +232,8 @@ mod tests {
                 status: "active".into(),
                 intent: "order".into(),
                 description: "PET-CT".into(),
-                ordered_at: Some("2024-05-01T12:00:00Z".into()),
+                ordered_at: Some("2024-05-01T12:00:00Z".parse().unwrap()),
+                ..Default::default()
             },
             StgServiceRequestFlat {
                 sr_id: "SR-2".into(),
@@ -242,6 +243,7 @@ mod tests {
                 intent: "order".into(),
                 description: "Unknown".into(),
                 ordered_at: None,
+                ..Default::default()
             },
         ];
 
@@ -251,12 +253,14 @@ mod tests {
                 system: Some("http://loinc.org".into()),
                 code: Some("24606-6".into()),
                 display: Some("FDG uptake".into()),
+                provenance: None,
             },
             StgSrCodeExploded {
                 sr_id: "SR-2".into(),
                 system: Some("http://loinc.org".into()),
                 code: Some("99999-9".into()),
                 display: Some("Unknown code".into()),
+                provenance: None,
             },
         ];
 
@@ -273,6 +277,7 @@ mod tests {
                 reason: None,
                 license_tier: None,
                 source_kind: None,
+                provenance: None,
             },
             MappingResult {
                 code_element_id: "SR-2::http://loinc.org::99999-9".into(),
@@ -286,6 +291,7 @@ mod tests {
                 reason: Some("missing_system_or_code".into()),
                 license_tier: None,
                 source_kind: None,
+                provenance: None,
             },
         ];
 
@@ -392,13 +392,15 @@ mod tests {
 
     #[test]
     fn render_page_shows_metrics_and_no_match_details() {
-        let mut metrics = PipelineMetrics::default();
-        metrics.bundle_count = 3;
-        metrics.flats_count = 4;
-        metrics.mapping_count = 5;
-        metrics.auto_mapped = 2;
-        metrics.needs_review = 1;
-        metrics.no_match = 2;
+        let metrics = PipelineMetrics {
+            bundle_count: 3,
+            flats_count: 4,
+            mapping_count: 5,
+            auto_mapped: 2,
+            needs_review: 1,
+            no_match: 2,
+            ..PipelineMetrics::default()
+        };
 
         let results = MappingResultsView {
             request_summary: ServiceRequestSummary {
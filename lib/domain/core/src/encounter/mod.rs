@@ -6,23 +6,125 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::value::{EncounterId, PatientId};
+use crate::value::{
+    BusinessIdentifier, ClinicalCode, EncounterId, FhirPeriod, PatientId, ResourceReference,
+};
 
 #[cfg(feature = "dummy")]
 use fake::Dummy;
 
-/// Minimal encounter entity.
+/// Encounter status, per FHIR R4 `encounter-status`.
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncounterStatus {
+    Planned,
+    Arrived,
+    Triaged,
+    InProgress,
+    OnLeave,
+    Finished,
+    Cancelled,
+    EnteredInError,
+    Unknown,
+}
+
+impl EncounterStatus {
+    pub fn as_fhir_code(&self) -> &'static str {
+        match self {
+            Self::Planned => "planned",
+            Self::Arrived => "arrived",
+            Self::Triaged => "triaged",
+            Self::InProgress => "in-progress",
+            Self::OnLeave => "onleave",
+            Self::Finished => "finished",
+            Self::Cancelled => "cancelled",
+            Self::EnteredInError => "entered-in-error",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Parse a FHIR `encounter-status` code, tolerating case and `_` separators.
+    pub fn from_fhir_code(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "planned" => Some(Self::Planned),
+            "arrived" => Some(Self::Arrived),
+            "triaged" => Some(Self::Triaged),
+            "in-progress" | "in_progress" => Some(Self::InProgress),
+            "onleave" | "on-leave" | "on_leave" => Some(Self::OnLeave),
+            "finished" => Some(Self::Finished),
+            "cancelled" | "canceled" => Some(Self::Cancelled),
+            "entered-in-error" | "entered_in_error" => Some(Self::EnteredInError),
+            "unknown" => Some(Self::Unknown),
+            _ => None,
+        }
+    }
+}
+
+/// Encounter entity.
 /// Links a patient to a point-in-time encounter/context.
 #[cfg_attr(feature = "dummy", derive(Dummy))]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Encounter {
     pub id: EncounterId,
     pub patient_id: PatientId,
-    // Future: class, type, period, location, etc.
+    #[serde(default)]
+    pub identifiers: Vec<BusinessIdentifier>,
+    #[serde(default)]
+    pub status: Option<EncounterStatus>,
+    /// Encounter class (`AMB`, `IMP`, `EMER`, ...).
+    #[serde(default)]
+    pub class: Option<ClinicalCode>,
+    #[serde(default)]
+    pub encounter_types: Vec<ClinicalCode>,
+    #[serde(default)]
+    pub period: Option<FhirPeriod>,
+    /// Organization responsible for the encounter.
+    #[serde(default)]
+    pub service_provider: Option<ResourceReference>,
 }
 
 impl Encounter {
     pub fn new(id: EncounterId, patient_id: PatientId) -> Self {
-        Self { id, patient_id }
+        Self {
+            id,
+            patient_id,
+            identifiers: Vec::new(),
+            status: None,
+            class: None,
+            encounter_types: Vec::new(),
+            period: None,
+            service_provider: None,
+        }
+    }
+
+    pub fn with_identifiers(mut self, identifiers: Vec<BusinessIdentifier>) -> Self {
+        self.identifiers = identifiers;
+        self
+    }
+
+    pub fn with_status(mut self, status: Option<EncounterStatus>) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_class(mut self, class: Option<ClinicalCode>) -> Self {
+        self.class = class;
+        self
+    }
+
+    pub fn with_types(mut self, encounter_types: Vec<ClinicalCode>) -> Self {
+        self.encounter_types = encounter_types;
+        self
+    }
+
+    pub fn with_period(mut self, period: Option<FhirPeriod>) -> Self {
+        self.period = period;
+        self
+    }
+
+    pub fn with_service_provider(mut self, service_provider: Option<ResourceReference>) -> Self {
+        self.service_provider = service_provider;
+        self
     }
 }
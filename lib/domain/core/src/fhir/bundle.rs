@@ -0,0 +1,139 @@
+//! Bundle-level semantics: `Bundle.type` and the `entry.request`,
+//! `entry.search` and `entry.response` components.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// FHIR `Bundle.type` value set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BundleType {
+    Document,
+    Message,
+    Transaction,
+    TransactionResponse,
+    Batch,
+    BatchResponse,
+    History,
+    Searchset,
+    Collection,
+}
+
+impl BundleType {
+    pub fn as_fhir_code(self) -> &'static str {
+        match self {
+            Self::Document => "document",
+            Self::Message => "message",
+            Self::Transaction => "transaction",
+            Self::TransactionResponse => "transaction-response",
+            Self::Batch => "batch",
+            Self::BatchResponse => "batch-response",
+            Self::History => "history",
+            Self::Searchset => "searchset",
+            Self::Collection => "collection",
+        }
+    }
+
+    pub fn from_fhir_code(code: &str) -> Option<Self> {
+        Some(match code {
+            "document" => Self::Document,
+            "message" => Self::Message,
+            "transaction" => Self::Transaction,
+            "transaction-response" => Self::TransactionResponse,
+            "batch" => Self::Batch,
+            "batch-response" => Self::BatchResponse,
+            "history" => Self::History,
+            "searchset" => Self::Searchset,
+            "collection" => Self::Collection,
+            _ => return None,
+        })
+    }
+
+    /// Transaction and batch bundles carry an `entry.request` per entry.
+    pub fn requires_request(self) -> bool {
+        matches!(self, Self::Transaction | Self::Batch)
+    }
+
+    /// Resource type the first entry must hold, if the type prescribes one.
+    pub fn required_first_resource(self) -> Option<&'static str> {
+        match self {
+            Self::Document => Some("Composition"),
+            Self::Message => Some("MessageHeader"),
+            _ => None,
+        }
+    }
+}
+
+/// HTTP verbs allowed in `Bundle.entry.request.method`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HttpVerb {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Patch,
+}
+
+impl HttpVerb {
+    pub fn from_fhir_code(code: &str) -> Option<Self> {
+        Some(match code {
+            "GET" => Self::Get,
+            "HEAD" => Self::Head,
+            "POST" => Self::Post,
+            "PUT" => Self::Put,
+            "DELETE" => Self::Delete,
+            "PATCH" => Self::Patch,
+            _ => return None,
+        })
+    }
+}
+
+/// `Bundle.entry.request`: the interaction a transaction/batch entry asks for.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleEntryRequest {
+    pub method: Option<String>,
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub if_none_match: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub if_modified_since: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub if_match: Option<String>,
+    /// Conditional-create query (`identifier=system|value`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub if_none_exist: Option<String>,
+}
+
+impl BundleEntryRequest {
+    pub fn verb(&self) -> Option<HttpVerb> {
+        self.method.as_deref().and_then(HttpVerb::from_fhir_code)
+    }
+}
+
+/// `Bundle.entry.search`: why an entry is in a searchset.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleEntrySearch {
+    /// `match`, `include` or `outcome`.
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+/// `Bundle.entry.response`: result of processing a transaction/batch entry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleEntryResponse {
+    /// HTTP status line, e.g. `201 Created`.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// OperationOutcome describing a failure, as raw JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Value>,
+}
@@ -0,0 +1,179 @@
+//! Decoding of choice elements (`occurrence[x]`, `value[x]`, ...).
+//!
+//! Choice enums are flattened into their parent object. A plain
+//! `#[serde(flatten)] Option<Enum>` turns a malformed value into `None`, so
+//! fields use [`deserialize`] instead: it takes only the enum's own keys,
+//! leaves every other key to the parent, and fails when a key is present but
+//! its value does not decode or more than one type is given.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, DeserializeOwned, Deserializer, MapAccess, Visitor};
+use serde_json::{Map, Value};
+
+/// `deserialize_with` for a flattened `Option` of choice enum `T`.
+pub(crate) fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    struct ChoiceVisitor<T> {
+        keys: &'static [&'static str],
+        marker: PhantomData<T>,
+    }
+
+    impl<'de, T: DeserializeOwned> Visitor<'de> for ChoiceVisitor<T> {
+        type Value = Option<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "at most one of {}", self.keys.join(", "))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Option<T>, A::Error> {
+            let mut given = Map::new();
+            while let Some((key, value)) = map.next_entry::<String, Value>()? {
+                given.insert(key, value);
+            }
+            if given.len() > 1 {
+                let keys: Vec<_> = given.keys().map(String::as_str).collect();
+                return Err(de::Error::custom(format!(
+                    "{} are alternatives; give only one",
+                    keys.join(" and ")
+                )));
+            }
+            let Some(key) = given.keys().next().cloned() else {
+                return Ok(None);
+            };
+            serde_json::from_value(Value::Object(given))
+                .map(Some)
+                .map_err(|err| de::Error::custom(format!("{key}: {err}")))
+        }
+    }
+
+    // Asking for a struct with the variant names as fields makes a flattened
+    // parent hand over just those keys (and remove them from its own map).
+    deserializer.deserialize_struct(
+        "Choice",
+        keys::<T>(),
+        ChoiceVisitor {
+            keys: keys::<T>(),
+            marker: PhantomData,
+        },
+    )
+}
+
+/// JSON keys of choice enum `T` (its variant names), as declared to its
+/// derived `Deserialize`.
+fn keys<T: DeserializeOwned>() -> &'static [&'static str] {
+    match T::deserialize(KeysOf) {
+        Err(Keys(keys)) => keys,
+        Ok(_) => &[],
+    }
+}
+
+/// Deserializer that only records the variant names it is asked for.
+struct KeysOf;
+
+#[derive(Debug)]
+struct Keys(&'static [&'static str]);
+
+impl fmt::Display for Keys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "variants {:?}", self.0)
+    }
+}
+
+impl std::error::Error for Keys {}
+
+impl de::Error for Keys {
+    fn custom<M: fmt::Display>(_: M) -> Self {
+        Keys(&[])
+    }
+}
+
+impl<'de> Deserializer<'de> for KeysOf {
+    type Error = Keys;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Keys> {
+        Err(Keys(&[]))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        variants: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Keys> {
+        Err(Keys(variants))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::{Map, Value, json};
+
+    use crate::fhir::{ServiceRequest, ServiceRequestOccurrence};
+
+    #[derive(Debug, Deserialize)]
+    struct Holder {
+        #[serde(flatten, deserialize_with = "super::deserialize")]
+        occurrence: Option<ServiceRequestOccurrence>,
+        #[serde(flatten)]
+        rest: Map<String, Value>,
+    }
+
+    #[test]
+    fn takes_only_its_own_keys() {
+        let holder: Holder = serde_json::from_value(json!({
+            "occurrenceDateTime": "2024-05-01",
+            "occurrenceString": "tomorrow",
+            "status": "active"
+        }))
+        .unwrap();
+        assert!(matches!(
+            holder.occurrence,
+            Some(ServiceRequestOccurrence::DateTime(_))
+        ));
+        assert_eq!(
+            holder.rest.keys().collect::<Vec<_>>(),
+            ["occurrenceString", "status"]
+        );
+    }
+
+    #[test]
+    fn malformed_or_repeated_values_are_errors() {
+        let err = serde_json::from_value::<ServiceRequest>(json!({
+            "resourceType": "ServiceRequest",
+            "occurrenceDateTime": "yesterday"
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("occurrenceDateTime"), "{err}");
+
+        let err = serde_json::from_value::<ServiceRequest>(json!({
+            "resourceType": "ServiceRequest",
+            "quantityQuantity": { "value": "two" }
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("quantityQuantity"), "{err}");
+
+        assert!(
+            serde_json::from_value::<ServiceRequest>(json!({
+                "resourceType": "ServiceRequest",
+                "occurrenceDateTime": "2024-05-01",
+                "occurrencePeriod": { "start": "2024-05-01" }
+            }))
+            .is_err()
+        );
+        let sr: ServiceRequest =
+            serde_json::from_value(json!({ "resourceType": "ServiceRequest" })).unwrap();
+        assert!(sr.occurrence.is_none() && sr.quantity.is_none());
+    }
+}
@@ -0,0 +1,241 @@
+//! FHIR R4 general-purpose datatypes shared by the modelled resources.
+//!
+//! Only the element sets needed by the resources in this module tree are
+//! modelled; every field is optional so partially populated payloads still
+//! deserialize and can be reported on by validation instead of failing decode.
+
+use serde::{Deserialize, Serialize};
+
+use super::choice;
+use crate::value::{FhirDateTime, FhirInstant, FhirPeriod};
+
+/// Code representation following FHIR `Coding`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Coding {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+/// Text + list of codings per FHIR `CodeableConcept`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeableConcept {
+    #[serde(default)]
+    pub coding: Vec<Coding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Simple `Reference` type: `"ResourceType/id"` string plus optional label.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reference {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+/// FHIR `Identifier` (business identifiers such as placer/filler numbers).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Identifier {
+    #[serde(rename = "use", default, skip_serializing_if = "Option::is_none")]
+    pub identifier_use: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub identifier_type: Option<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<Period>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assigner: Option<Box<Reference>>,
+}
+
+/// FHIR `Period`; the typed value object lives in [`crate::value`].
+pub type Period = FhirPeriod;
+
+/// FHIR `Quantity` (also used for the `Duration` profile).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quantity {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+/// FHIR `Ratio` of two quantities.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ratio {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numerator: Option<Quantity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denominator: Option<Quantity>,
+}
+
+/// FHIR `Range` with optional low/high bounds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Range {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low: Option<Quantity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub high: Option<Quantity>,
+}
+
+/// Author choice for `Annotation.author[x]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AnnotationAuthor {
+    #[serde(rename = "authorReference")]
+    Reference(Reference),
+    #[serde(rename = "authorString")]
+    String(String),
+}
+
+/// FHIR `Annotation` (free-text note with optional author/time).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Annotation {
+    #[serde(flatten, deserialize_with = "choice::deserialize")]
+    pub author: Option<AnnotationAuthor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<FhirDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Bounds choice for `Timing.repeat.bounds[x]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TimingBounds {
+    #[serde(rename = "boundsDuration")]
+    Duration(Quantity),
+    #[serde(rename = "boundsRange")]
+    Range(Range),
+    #[serde(rename = "boundsPeriod")]
+    Period(Period),
+}
+
+/// FHIR `Timing.repeat` schedule definition.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimingRepeat {
+    #[serde(flatten, deserialize_with = "choice::deserialize")]
+    pub bounds: Option<TimingBounds>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count_max: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_max: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_max: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period_max: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period_unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub day_of_week: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub time_of_day: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub when: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+}
+
+/// FHIR `Timing` (explicit events and/or a repeating schedule).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timing {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event: Vec<FhirDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<TimingRepeat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<CodeableConcept>,
+}
+
+impl Timing {
+    /// Earliest known instant for the schedule: first `event`, else the start of
+    /// a `boundsPeriod`.
+    pub fn first_instant(&self) -> Option<&FhirDateTime> {
+        self.event.iter().min().or_else(|| {
+            match self
+                .repeat
+                .as_ref()
+                .and_then(|repeat| repeat.bounds.as_ref())
+            {
+                Some(TimingBounds::Period(period)) => period.start.as_ref(),
+                _ => None,
+            }
+        })
+    }
+
+    /// Latest known instant for the schedule: last `event`, else the end of a
+    /// `boundsPeriod`.
+    pub fn last_instant(&self) -> Option<&FhirDateTime> {
+        if self.event.len() > 1 {
+            return self.event.iter().max();
+        }
+        match self
+            .repeat
+            .as_ref()
+            .and_then(|repeat| repeat.bounds.as_ref())
+        {
+            Some(TimingBounds::Period(period)) => period.end.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// Choice type for `effective[x]` on event resources (Observation, DiagnosticReport).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Effective {
+    #[serde(rename = "effectiveDateTime")]
+    DateTime(FhirDateTime),
+    #[serde(rename = "effectivePeriod")]
+    Period(Period),
+    #[serde(rename = "effectiveInstant")]
+    Instant(FhirInstant),
+}
+
+impl Effective {
+    /// Earliest instant described by the effective time, if any.
+    pub fn start(&self) -> Option<&FhirDateTime> {
+        match self {
+            Self::DateTime(value) => Some(value),
+            Self::Period(period) => period.start.as_ref(),
+            Self::Instant(value) => Some(value.as_date_time()),
+        }
+    }
+
+    /// Latest instant described by the effective time; only periods have one.
+    pub fn end(&self) -> Option<&FhirDateTime> {
+        match self {
+            Self::Period(period) => period.end.as_ref(),
+            Self::DateTime(_) | Self::Instant(_) => None,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! FHIR R4 `DiagnosticReport`, the signed report that closes an order.
+
+use serde::{Deserialize, Serialize};
+
+use super::choice;
+use super::datatypes::{CodeableConcept, Effective, Identifier, Reference};
+use super::extension::{Extension, Meta};
+use crate::value::FhirInstant;
+
+/// FHIR DiagnosticReport resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticReport {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifier_extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identifier: Vec<Identifier>,
+    /// Orders this report fulfils (typically `ServiceRequest/<id>`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub based_on: Vec<Reference>,
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub category: Vec<CodeableConcept>,
+    pub code: Option<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encounter: Option<Reference>,
+    #[serde(flatten, deserialize_with = "choice::deserialize")]
+    pub effective: Option<Effective>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued: Option<FhirInstant>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub performer: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub results_interpreter: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub specimen: Vec<Reference>,
+    /// Observations reported on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub result: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imaging_study: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conclusion: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conclusion_code: Vec<CodeableConcept>,
+}
+
+impl Default for DiagnosticReport {
+    fn default() -> Self {
+        Self {
+            resource_type: "DiagnosticReport".to_string(),
+            id: None,
+            meta: None,
+            extension: Vec::new(),
+            modifier_extension: Vec::new(),
+            identifier: Vec::new(),
+            based_on: Vec::new(),
+            status: None,
+            category: Vec::new(),
+            code: None,
+            subject: None,
+            encounter: None,
+            effective: None,
+            issued: None,
+            performer: Vec::new(),
+            results_interpreter: Vec::new(),
+            specimen: Vec::new(),
+            result: Vec::new(),
+            imaging_study: Vec::new(),
+            conclusion: None,
+            conclusion_code: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! FHIR R4 `Encounter` covering status, class, timing and provider context.
+
+use serde::{Deserialize, Serialize};
+
+use super::datatypes::{CodeableConcept, Coding, Identifier, Period, Reference};
+use super::extension::{Extension, Meta};
+
+/// FHIR Encounter resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Encounter {
+    #[serde(rename = "resourceType", default = "encounter_resource_type")]
+    pub resource_type: String,
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifier_extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identifier: Vec<Identifier>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// `Encounter.class` is a single `Coding` in R4 (e.g. `AMB`, `IMP`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<Coding>,
+    #[serde(rename = "type", default, skip_serializing_if = "Vec::is_empty")]
+    pub encounter_type: Vec<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<Period>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_provider: Option<Reference>,
+}
+
+fn encounter_resource_type() -> String {
+    "Encounter".to_string()
+}
+
+impl Default for Encounter {
+    fn default() -> Self {
+        Self {
+            resource_type: encounter_resource_type(),
+            id: None,
+            meta: None,
+            extension: Vec::new(),
+            modifier_extension: Vec::new(),
+            identifier: Vec::new(),
+            status: None,
+            class: None,
+            encounter_type: Vec::new(),
+            subject: None,
+            period: None,
+            service_provider: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_class_period_and_provider() {
+        let encounter: Encounter = serde_json::from_value(serde_json::json!({
+            "resourceType": "Encounter",
+            "id": "e1",
+            "status": "finished",
+            "class": {
+                "system": "http://terminology.hl7.org/CodeSystem/v3-ActCode",
+                "code": "AMB"
+            },
+            "type": [{ "text": "PET/CT visit" }],
+            "subject": { "reference": "Patient/p1" },
+            "period": { "start": "2024-05-01T08:00:00Z", "end": "2024-05-01T10:00:00Z" },
+            "serviceProvider": { "reference": "Organization/o1" }
+        }))
+        .expect("encounter decodes");
+
+        assert_eq!(
+            encounter.class.as_ref().and_then(|c| c.code.as_deref()),
+            Some("AMB")
+        );
+        assert!(
+            encounter
+                .period
+                .as_ref()
+                .is_some_and(|period| period.is_well_ordered())
+        );
+        assert_eq!(encounter.encounter_type.len(), 1);
+    }
+}
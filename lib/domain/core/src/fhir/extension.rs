@@ -0,0 +1,262 @@
+//! FHIR `Extension`, `modifierExtension` and `Meta`.
+//!
+//! Every modelled resource carries `meta`, `extension` and `modifierExtension`
+//! so site-specific data survives a decode/encode round trip;
+//! [`DomainResource`] gives URL-based access to them.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::choice;
+use super::datatypes::{CodeableConcept, Coding, Identifier, Period, Quantity, Reference};
+use crate::value::{FhirDate, FhirDateTime, FhirDecimal, FhirInstant};
+
+/// Choice type for `Extension.value[x]` (the primitive and general-purpose
+/// datatypes used by site extensions).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ExtensionValue {
+    #[serde(rename = "valueString")]
+    String(String),
+    #[serde(rename = "valueCode")]
+    Code(String),
+    #[serde(rename = "valueId")]
+    Id(String),
+    #[serde(rename = "valueUri")]
+    Uri(String),
+    #[serde(rename = "valueUrl")]
+    Url(String),
+    #[serde(rename = "valueCanonical")]
+    Canonical(String),
+    #[serde(rename = "valueMarkdown")]
+    Markdown(String),
+    #[serde(rename = "valueBoolean")]
+    Boolean(bool),
+    #[serde(rename = "valueInteger")]
+    Integer(i64),
+    #[serde(rename = "valuePositiveInt")]
+    PositiveInt(u64),
+    #[serde(rename = "valueDecimal")]
+    Decimal(FhirDecimal),
+    #[serde(rename = "valueDate")]
+    Date(FhirDate),
+    #[serde(rename = "valueDateTime")]
+    DateTime(FhirDateTime),
+    #[serde(rename = "valueInstant")]
+    Instant(FhirInstant),
+    #[serde(rename = "valueCoding")]
+    Coding(Coding),
+    #[serde(rename = "valueCodeableConcept")]
+    CodeableConcept(CodeableConcept),
+    #[serde(rename = "valueIdentifier")]
+    Identifier(Identifier),
+    #[serde(rename = "valueReference")]
+    Reference(Reference),
+    #[serde(rename = "valueQuantity")]
+    Quantity(Quantity),
+    #[serde(rename = "valuePeriod")]
+    Period(Period),
+}
+
+impl ExtensionValue {
+    /// Text of string-like values (`string`, `code`, `id`, `uri`, `url`,
+    /// `canonical`, `markdown`).
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value)
+            | Self::Code(value)
+            | Self::Id(value)
+            | Self::Uri(value)
+            | Self::Url(value)
+            | Self::Canonical(value)
+            | Self::Markdown(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Self::Integer(value) => Some(*value),
+            Self::PositiveInt(value) => i64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_coding(&self) -> Option<&Coding> {
+        match self {
+            Self::Coding(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_codeable_concept(&self) -> Option<&CodeableConcept> {
+        match self {
+            Self::CodeableConcept(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_reference(&self) -> Option<&Reference> {
+        match self {
+            Self::Reference(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_date_time(&self) -> Option<&FhirDateTime> {
+        match self {
+            Self::DateTime(value) => Some(value),
+            Self::Instant(value) => Some(value.as_date_time()),
+            _ => None,
+        }
+    }
+}
+
+/// FHIR `Extension`; complex extensions nest further extensions instead of a value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Extension {
+    pub url: String,
+    #[serde(flatten, deserialize_with = "choice::deserialize")]
+    pub value: Option<ExtensionValue>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extension: Vec<Extension>,
+    /// `value[x]` types outside [`ExtensionValue`], kept as raw JSON.
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl Extension {
+    pub fn new(url: impl Into<String>, value: ExtensionValue) -> Self {
+        Self {
+            url: url.into(),
+            value: Some(value),
+            ..Self::default()
+        }
+    }
+
+    /// First nested extension with `url` (complex extensions).
+    pub fn child(&self, url: &str) -> Option<&Extension> {
+        self.extension.iter().find(|child| child.url == url)
+    }
+}
+
+/// FHIR `Meta` (resource metadata maintained by the source server).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Meta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<FhirInstant>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profile: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub security: Vec<Coding>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tag: Vec<Coding>,
+}
+
+/// Resources carrying `meta`, `extension` and `modifierExtension`.
+pub trait DomainResource {
+    fn meta(&self) -> Option<&Meta>;
+    fn extensions(&self) -> &[Extension];
+    fn modifier_extensions(&self) -> &[Extension];
+
+    /// First `extension` with `url`.
+    fn extension(&self, url: &str) -> Option<&Extension> {
+        self.extensions().iter().find(|ext| ext.url == url)
+    }
+
+    /// Every `extension` with `url`, in document order.
+    fn extensions_by_url<'a>(&'a self, url: &'a str) -> impl Iterator<Item = &'a Extension> {
+        self.extensions().iter().filter(move |ext| ext.url == url)
+    }
+
+    /// First `modifierExtension` with `url`.
+    fn modifier_extension(&self, url: &str) -> Option<&Extension> {
+        self.modifier_extensions().iter().find(|ext| ext.url == url)
+    }
+
+    /// Value of the first `extension` with `url`.
+    fn extension_value(&self, url: &str) -> Option<&ExtensionValue> {
+        self.extension(url).and_then(|ext| ext.value.as_ref())
+    }
+
+    /// Whether `meta.profile` claims conformance to `profile`.
+    fn claims_profile(&self, profile: &str) -> bool {
+        self.meta()
+            .is_some_and(|meta| meta.profile.iter().any(|claimed| claimed == profile))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extension_value_choices_roundtrip() {
+        let raw = serde_json::json!([
+            { "url": "https://dfps.example/ext/priority-override", "valueCode": "stat" },
+            { "url": "https://dfps.example/ext/protocol", "extension": [
+                { "url": "id", "valueString": "PROTO-7" },
+                { "url": "arm", "valueInteger": 2 }
+            ] },
+            { "url": "https://dfps.example/ext/consent-form", "valueAttachment": { "url": "consent.pdf" } }
+        ]);
+        let extensions: Vec<Extension> = serde_json::from_value(raw.clone()).unwrap();
+
+        assert_eq!(
+            extensions[0]
+                .value
+                .as_ref()
+                .and_then(ExtensionValue::as_str),
+            Some("stat")
+        );
+        let protocol = &extensions[1];
+        assert!(protocol.value.is_none());
+        assert_eq!(
+            protocol
+                .child("arm")
+                .and_then(|arm| arm.value.as_ref())
+                .and_then(ExtensionValue::as_integer),
+            Some(2)
+        );
+        assert!(extensions[2].value.is_none());
+        assert!(extensions[2].other.contains_key("valueAttachment"));
+        assert_eq!(serde_json::to_value(&extensions).unwrap(), raw);
+    }
+
+    #[test]
+    fn malformed_values_are_errors_and_decimals_keep_their_digits() {
+        let err = serde_json::from_value::<Extension>(serde_json::json!({
+            "url": "https://dfps.example/ext/visit", "valueDateTime": "last week"
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("valueDateTime"), "{err}");
+        assert!(
+            serde_json::from_value::<Extension>(serde_json::json!({
+                "url": "https://dfps.example/ext/dose", "valueDecimal": "1,5"
+            }))
+            .is_err()
+        );
+
+        let dose: Extension = serde_json::from_str(
+            r#"{ "url": "https://dfps.example/ext/dose", "valueDecimal": 0.30000000000000004 }"#,
+        )
+        .unwrap();
+        let Some(ExtensionValue::Decimal(value)) = &dose.value else {
+            panic!("decimal value");
+        };
+        assert_eq!(value.as_str(), "0.30000000000000004");
+        assert!(dose.other.is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+//! FHIR R4 `ImagingStudy` describing the DICOM study acquired for an order.
+
+use serde::{Deserialize, Serialize};
+
+use super::datatypes::{CodeableConcept, Coding, Identifier, Reference};
+use super::extension::{Extension, Meta};
+use crate::value::FhirDateTime;
+
+/// One DICOM instance within a series.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImagingStudyInstance {
+    pub uid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sop_class: Option<Coding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// One DICOM series within the study.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImagingStudySeries {
+    pub uid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<u32>,
+    pub modality: Option<Coding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number_of_instances: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_site: Option<Coding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started: Option<FhirDateTime>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instance: Vec<ImagingStudyInstance>,
+}
+
+/// FHIR ImagingStudy resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImagingStudy {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifier_extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identifier: Vec<Identifier>,
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modality: Vec<Coding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encounter: Option<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started: Option<FhirDateTime>,
+    /// Orders this study fulfils (typically `ServiceRequest/<id>`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub based_on: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub referrer: Option<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number_of_series: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number_of_instances: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub procedure_code: Vec<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reason_code: Vec<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub series: Vec<ImagingStudySeries>,
+}
+
+impl Default for ImagingStudy {
+    fn default() -> Self {
+        Self {
+            resource_type: "ImagingStudy".to_string(),
+            id: None,
+            meta: None,
+            extension: Vec::new(),
+            modifier_extension: Vec::new(),
+            identifier: Vec::new(),
+            status: None,
+            modality: Vec::new(),
+            subject: None,
+            encounter: None,
+            started: None,
+            based_on: Vec::new(),
+            referrer: None,
+            number_of_series: None,
+            number_of_instances: None,
+            procedure_code: Vec::new(),
+            reason_code: Vec::new(),
+            description: None,
+            series: Vec::new(),
+        }
+    }
+}
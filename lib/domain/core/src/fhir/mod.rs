@@ -4,95 +4,108 @@
 //! diagrams in `docs/system-design/fhir/architecture/system-architecture.md`,
 //! `docs/system-design/fhir/models/data-model-er.md`, and the sequence flow
 //! described in `docs/system-design/fhir/behavior/sequence-servicerequest.md`.
-//! They keep only the subset of fields required to parse raw Bundles and feed
-//! staging + domain models without committing to a full FHIR implementation.
+//! `ServiceRequest` models the complete R4 element set (see `service_request.rs`)
+//! because its clinical fields drive staging decisions; `Patient` and
+//! `Encounter` carry the identifiers, demographics and encounter context used
+//! by cohort analytics. `Observation`, `DiagnosticReport` and `ImagingStudy`
+//! close the order-to-result loop through their `basedOn` references,
+//! `OperationOutcome` carries errors and validation results back to FHIR
+//! clients, and `Provenance` records where staged orders came from. Every
+//! modelled resource keeps `meta`, `extension` and `modifierExtension` (see
+//! [`DomainResource`]) so site extensions are not lost on decode. `Bundle`
+//! keeps entries as passthrough JSON; [`Resource`] is the typed view used by
+//! every consumer that needs to know what an entry holds.
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-/// Code representation following FHIR `Coding`.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Coding {
-    pub system: Option<String>,
-    pub code: Option<String>,
-    pub display: Option<String>,
-}
-
-/// Text + list of codings per FHIR `CodeableConcept`.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CodeableConcept {
-    #[serde(default)]
-    pub coding: Vec<Coding>,
-    pub text: Option<String>,
-}
-
-/// Simple `Reference` type: `"ResourceType/id"` string plus optional label.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Reference {
-    pub reference: Option<String>,
-    pub display: Option<String>,
-}
-
-/// Minimal FHIR Patient resource for linking IDs inside Bundles.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Patient {
-    #[serde(rename = "resourceType", default = "patient_resource_type")]
-    pub resource_type: String,
-    pub id: Option<String>,
-}
-
-fn patient_resource_type() -> String {
-    "Patient".to_string()
-}
+mod bundle;
+mod choice;
+mod datatypes;
+mod diagnostic_report;
+mod encounter;
+mod extension;
+mod imaging_study;
+mod observation;
+mod operation_outcome;
+mod patient;
+mod provenance;
+mod resource;
+mod service_request;
 
-/// Minimal FHIR Encounter resource.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Encounter {
-    #[serde(rename = "resourceType", default = "encounter_resource_type")]
-    pub resource_type: String,
-    pub id: Option<String>,
-}
-
-fn encounter_resource_type() -> String {
-    "Encounter".to_string()
-}
-
-/// Minimal FHIR ServiceRequest subset.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ServiceRequest {
-    #[serde(rename = "resourceType")]
-    pub resource_type: String,
-    pub id: Option<String>,
-
-    pub status: Option<String>,
-    pub intent: Option<String>,
-
-    pub subject: Option<Reference>,
-    pub encounter: Option<Reference>,
-    pub requester: Option<Reference>,
-    #[serde(default)]
-    pub supporting_info: Vec<Reference>,
-
-    pub code: Option<CodeableConcept>,
-    #[serde(default)]
-    pub category: Vec<CodeableConcept>,
-    pub description: Option<String>,
-    pub authored_on: Option<String>,
-}
+pub use bundle::{
+    BundleEntryRequest, BundleEntryResponse, BundleEntrySearch, BundleType, HttpVerb,
+};
+pub use datatypes::{
+    Annotation, AnnotationAuthor, CodeableConcept, Coding, Effective, Identifier, Period, Quantity,
+    Range, Ratio, Reference, Timing, TimingBounds, TimingRepeat,
+};
+pub use diagnostic_report::DiagnosticReport;
+pub use encounter::Encounter;
+pub use extension::{DomainResource, Extension, ExtensionValue, Meta};
+pub use imaging_study::{ImagingStudy, ImagingStudyInstance, ImagingStudySeries};
+pub use observation::{Observation, ObservationValue};
+pub use operation_outcome::{IssueSeverity, IssueType, OperationOutcome, OperationOutcomeIssue};
+pub use patient::{MRN_IDENTIFIER_TYPE, Patient, PatientDeceased};
+pub use provenance::{Provenance, ProvenanceAgent, ProvenanceEntity};
+pub use resource::{FhirResource, Resource};
+pub use service_request::{
+    ServiceRequest, ServiceRequestAsNeeded, ServiceRequestOccurrence, ServiceRequestQuantity,
+};
 
 /// Bundle entry that stores passthrough JSON resources.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BundleEntry {
     #[serde(default)]
     pub full_url: Option<String>,
     #[serde(default)]
     pub resource: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<BundleEntrySearch>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<BundleEntryRequest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<BundleEntryResponse>,
+}
+
+impl BundleEntry {
+    /// Entry holding the JSON encoding of `resource`.
+    pub fn from_resource(resource: impl Into<Resource>) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            resource: Some(resource.into().to_value()?),
+            ..Self::default()
+        })
+    }
+
+    /// `resourceType` of the entry's resource, read without decoding it.
+    ///
+    /// Bundle lookups compare it exactly: FHIR type names are case-sensitive,
+    /// so a `"patient"` entry is an unknown type, not a Patient.
+    pub fn resource_type(&self) -> Option<&str> {
+        self.resource.as_ref().and_then(resource::resource_type_of)
+    }
+
+    /// Logical id of the entry's resource, read without decoding it.
+    pub fn resource_id(&self) -> Option<&str> {
+        self.resource.as_ref().and_then(resource::resource_id_of)
+    }
+
+    /// `search.mode = include`: the resource was pulled in as context for the
+    /// search matches rather than matching the search itself.
+    pub fn is_search_include(&self) -> bool {
+        self.search
+            .as_ref()
+            .and_then(|search| search.mode.as_deref())
+            == Some("include")
+    }
+
+    /// Decode the entry's resource; `None` when the entry carries no resource.
+    pub fn decode(&self) -> Option<Result<Resource, serde_json::Error>> {
+        self.resource
+            .as_ref()
+            .map(|value| Resource::from_value(value.clone()))
+    }
 }
 
 /// Minimal Bundle representation containing arbitrary entries.
@@ -101,6 +114,8 @@ pub struct BundleEntry {
 pub struct Bundle {
     #[serde(rename = "resourceType", default = "bundle_resource_type")]
     pub resource_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     #[serde(rename = "type")]
     pub bundle_type: Option<String>,
     #[serde(default)]
@@ -112,26 +127,111 @@ fn bundle_resource_type() -> String {
 }
 
 impl Bundle {
+    /// Parsed `Bundle.type`; `None` when absent or not in the value set.
+    pub fn kind(&self) -> Option<BundleType> {
+        self.bundle_type
+            .as_deref()
+            .and_then(BundleType::from_fhir_code)
+    }
+
     /// Iterate over ServiceRequest resources within the bundle.
     pub fn iter_servicerequests(
         &self,
     ) -> impl Iterator<Item = Result<ServiceRequest, serde_json::Error>> + '_ {
-        self.entry.iter().filter_map(|entry| {
-            entry.resource.as_ref().and_then(|resource| {
-                let resource_type = resource
-                    .get("resourceType")
-                    .and_then(Value::as_str)
-                    .unwrap_or_default();
-                if resource_type == "ServiceRequest" {
-                    Some(serde_json::from_value(resource.clone()))
-                } else {
-                    None
-                }
+        self.iter_resources::<ServiceRequest>()
+    }
+
+    /// Iterate over Patient resources within the bundle.
+    pub fn iter_patients(&self) -> impl Iterator<Item = Result<Patient, serde_json::Error>> + '_ {
+        self.iter_resources::<Patient>()
+    }
+
+    /// Iterate over Encounter resources within the bundle.
+    pub fn iter_encounters(
+        &self,
+    ) -> impl Iterator<Item = Result<Encounter, serde_json::Error>> + '_ {
+        self.iter_resources::<Encounter>()
+    }
+
+    /// Iterate over Observation resources within the bundle.
+    pub fn iter_observations(
+        &self,
+    ) -> impl Iterator<Item = Result<Observation, serde_json::Error>> + '_ {
+        self.iter_resources::<Observation>()
+    }
+
+    /// Iterate over DiagnosticReport resources within the bundle.
+    pub fn iter_diagnostic_reports(
+        &self,
+    ) -> impl Iterator<Item = Result<DiagnosticReport, serde_json::Error>> + '_ {
+        self.iter_resources::<DiagnosticReport>()
+    }
+
+    /// Iterate over ImagingStudy resources within the bundle.
+    pub fn iter_imaging_studies(
+        &self,
+    ) -> impl Iterator<Item = Result<ImagingStudy, serde_json::Error>> + '_ {
+        self.iter_resources::<ImagingStudy>()
+    }
+
+    /// Decode every entry resource; unmodelled types come back as
+    /// [`Resource::Unknown`].
+    pub fn resources(&self) -> impl Iterator<Item = Result<Resource, serde_json::Error>> + '_ {
+        self.entry.iter().filter_map(BundleEntry::decode)
+    }
+
+    /// Iterate over resources of type `T`.
+    pub fn iter_resources<T: FhirResource>(
+        &self,
+    ) -> impl Iterator<Item = Result<T, serde_json::Error>> + '_ {
+        self.entry
+            .iter()
+            .filter(|entry| entry.resource_type() == Some(T::RESOURCE_TYPE))
+            .filter_map(decode_as)
+    }
+
+    /// First resource of type `T` whose id is `id`.
+    pub fn find<T: FhirResource>(&self, id: &str) -> Option<Result<T, serde_json::Error>> {
+        self.entry
+            .iter()
+            .find(|entry| {
+                entry.resource_type() == Some(T::RESOURCE_TYPE) && entry.resource_id() == Some(id)
             })
-        })
+            .and_then(decode_as)
+    }
+
+    /// First resource with the given `resourceType` and id, typed when modelled.
+    pub fn find_resource(
+        &self,
+        resource_type: &str,
+        id: &str,
+    ) -> Option<Result<Resource, serde_json::Error>> {
+        self.entry
+            .iter()
+            .find(|entry| {
+                entry.resource_type() == Some(resource_type) && entry.resource_id() == Some(id)
+            })
+            .and_then(BundleEntry::decode)
+    }
+
+    /// Ids of every entry whose `resourceType` matches, without decoding them.
+    pub fn resource_ids<'a>(&'a self, resource_type: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entry
+            .iter()
+            .filter(move |entry| entry.resource_type() == Some(resource_type))
+            .filter_map(BundleEntry::resource_id)
     }
 }
 
+fn decode_as<T: FhirResource>(entry: &BundleEntry) -> Option<Result<T, serde_json::Error>> {
+    entry.decode().map(|decoded| {
+        decoded.map(|resource| {
+            T::from_resource_owned(resource)
+                .expect("entry resourceType was checked against T::RESOURCE_TYPE")
+        })
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,6 +240,7 @@ mod tests {
     fn bundle_iterates_servicerequests() {
         let bundle = Bundle {
             resource_type: "Bundle".into(),
+            id: None,
             bundle_type: Some("collection".into()),
             entry: vec![
                 BundleEntry {
@@ -151,6 +252,7 @@ mod tests {
                         "intent": "order",
                         "subject": { "reference": "Patient/p1" }
                     })),
+                    ..Default::default()
                 },
                 BundleEntry {
                     full_url: None,
@@ -158,6 +260,7 @@ mod tests {
                         "resourceType": "Patient",
                         "id": "p1"
                     })),
+                    ..Default::default()
                 },
             ],
         };
@@ -167,5 +270,48 @@ mod tests {
             .map(|sr| sr.unwrap().id.unwrap())
             .collect();
         assert_eq!(collected, vec!["sr-1"]);
+
+        let patients: Vec<_> = bundle
+            .iter_patients()
+            .map(|patient| patient.unwrap().id.unwrap())
+            .collect();
+        assert_eq!(patients, vec!["p1"]);
+        assert_eq!(bundle.iter_encounters().count(), 0);
+    }
+
+    #[test]
+    fn bundle_decodes_typed_resources_and_looks_up_by_id() {
+        let bundle: Bundle = serde_json::from_value(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                { "resource": { "resourceType": "Patient", "id": "p1" } },
+                { "resource": { "resourceType": "Patient", "id": "p2" } },
+                { "resource": { "resourceType": "Specimen", "id": "s1" } },
+                { "resource": { "resourceType": "patient", "id": "p3" } },
+                { "fullUrl": "urn:uuid:empty" }
+            ]
+        }))
+        .unwrap();
+
+        let types: Vec<_> = bundle
+            .resources()
+            .map(|resource| resource.unwrap().resource_type().to_string())
+            .collect();
+        assert_eq!(types, ["Patient", "Patient", "Specimen", "patient"]);
+
+        let p2 = bundle.find::<Patient>("p2").unwrap().unwrap();
+        assert_eq!(p2.id.as_deref(), Some("p2"));
+        assert!(bundle.find::<Patient>("missing").is_none());
+        assert!(bundle.find::<Encounter>("p1").is_none());
+
+        let specimen = bundle.find_resource("Specimen", "s1").unwrap().unwrap();
+        assert!(matches!(specimen, Resource::Unknown(_)));
+        assert_eq!(
+            bundle.resource_ids("Patient").collect::<Vec<_>>(),
+            ["p1", "p2"]
+        );
+        assert!(bundle.find::<Patient>("p3").is_none());
+        assert!(bundle.find_resource("Patient", "p3").is_none());
     }
 }
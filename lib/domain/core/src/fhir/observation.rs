@@ -0,0 +1,164 @@
+//! FHIR R4 `Observation`, the per-measurement result of an order.
+
+use serde::{Deserialize, Serialize};
+
+use super::choice;
+use super::datatypes::{
+    Annotation, CodeableConcept, Effective, Identifier, Period, Quantity, Range, Ratio, Reference,
+};
+use super::extension::{Extension, Meta};
+use crate::value::{FhirDateTime, FhirInstant};
+
+/// Choice type for `Observation.value[x]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ObservationValue {
+    #[serde(rename = "valueQuantity")]
+    Quantity(Quantity),
+    #[serde(rename = "valueCodeableConcept")]
+    CodeableConcept(CodeableConcept),
+    #[serde(rename = "valueString")]
+    String(String),
+    #[serde(rename = "valueBoolean")]
+    Boolean(bool),
+    #[serde(rename = "valueInteger")]
+    Integer(i64),
+    #[serde(rename = "valueRange")]
+    Range(Range),
+    #[serde(rename = "valueRatio")]
+    Ratio(Ratio),
+    #[serde(rename = "valueDateTime")]
+    DateTime(FhirDateTime),
+    #[serde(rename = "valuePeriod")]
+    Period(Period),
+}
+
+/// FHIR Observation resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Observation {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifier_extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identifier: Vec<Identifier>,
+    /// Orders this observation fulfils (typically `ServiceRequest/<id>`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub based_on: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub part_of: Vec<Reference>,
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub category: Vec<CodeableConcept>,
+    pub code: Option<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encounter: Option<Reference>,
+    #[serde(flatten, deserialize_with = "choice::deserialize")]
+    pub effective: Option<Effective>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued: Option<FhirInstant>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub performer: Vec<Reference>,
+    #[serde(flatten, deserialize_with = "choice::deserialize")]
+    pub value: Option<ObservationValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_absent_reason: Option<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interpretation: Vec<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub note: Vec<Annotation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_site: Option<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub specimen: Option<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub has_member: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub derived_from: Vec<Reference>,
+}
+
+impl Default for Observation {
+    fn default() -> Self {
+        Self {
+            resource_type: "Observation".to_string(),
+            id: None,
+            meta: None,
+            extension: Vec::new(),
+            modifier_extension: Vec::new(),
+            identifier: Vec::new(),
+            based_on: Vec::new(),
+            part_of: Vec::new(),
+            status: None,
+            category: Vec::new(),
+            code: None,
+            subject: None,
+            encounter: None,
+            effective: None,
+            issued: None,
+            performer: Vec::new(),
+            value: None,
+            data_absent_reason: None,
+            interpretation: Vec::new(),
+            note: Vec::new(),
+            body_site: None,
+            method: None,
+            specimen: None,
+            has_member: Vec::new(),
+            derived_from: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_value_and_effective_choices() {
+        let observation: Observation = serde_json::from_value(serde_json::json!({
+            "resourceType": "Observation",
+            "id": "obs-1",
+            "basedOn": [{ "reference": "ServiceRequest/SR-1" }],
+            "status": "final",
+            "code": { "coding": [{ "system": "http://loinc.org", "code": "81555-5" }] },
+            "effectiveDateTime": "2024-05-02T09:30:00Z",
+            "valueQuantity": { "value": 7.4, "unit": "g/mL", "system": "http://unitsofmeasure.org", "code": "g/mL" }
+        }))
+        .expect("observation decodes");
+
+        assert!(matches!(
+            observation.value,
+            Some(ObservationValue::Quantity(_))
+        ));
+        assert_eq!(
+            observation
+                .effective
+                .as_ref()
+                .and_then(Effective::start)
+                .map(FhirDateTime::as_str),
+            Some("2024-05-02T09:30:00Z")
+        );
+        let encoded = serde_json::to_value(&observation).unwrap();
+        assert_eq!(encoded["valueQuantity"]["unit"], "g/mL");
+    }
+
+    #[test]
+    fn malformed_value_is_an_error() {
+        let err = serde_json::from_value::<Observation>(serde_json::json!({
+            "resourceType": "Observation",
+            "status": "final",
+            "valueQuantity": { "value": "high" }
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("valueQuantity"), "{err}");
+    }
+}
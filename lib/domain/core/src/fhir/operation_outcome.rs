@@ -0,0 +1,207 @@
+//! FHIR R4 `OperationOutcome`, the standard envelope for errors and
+//! validation results returned to FHIR clients.
+
+use serde::{Deserialize, Serialize};
+
+use super::datatypes::CodeableConcept;
+use super::extension::{Extension, Meta};
+
+/// `OperationOutcome.issue.severity` (required binding `issue-severity`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueSeverity {
+    Fatal,
+    Error,
+    Warning,
+    Information,
+}
+
+/// `OperationOutcome.issue.code` (required binding `issue-type`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueType {
+    Invalid,
+    Structure,
+    Required,
+    Value,
+    Invariant,
+    Security,
+    Login,
+    Unknown,
+    Expired,
+    Forbidden,
+    Suppressed,
+    Processing,
+    NotSupported,
+    Duplicate,
+    MultipleMatches,
+    NotFound,
+    Deleted,
+    TooLong,
+    CodeInvalid,
+    Extension,
+    TooCostly,
+    BusinessRule,
+    Conflict,
+    Transient,
+    LockError,
+    NoStore,
+    Exception,
+    Timeout,
+    Incomplete,
+    Throttled,
+    Informational,
+}
+
+/// One `OperationOutcome.issue`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationOutcomeIssue {
+    pub severity: IssueSeverity,
+    pub code: IssueType,
+    /// Coded detail, e.g. the DFPS issue id behind a generic `code`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<String>,
+    /// FHIRPath expressions of the elements the issue is about.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expression: Vec<String>,
+}
+
+impl OperationOutcomeIssue {
+    pub fn new(severity: IssueSeverity, code: IssueType, diagnostics: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code,
+            details: None,
+            diagnostics: Some(diagnostics.into()),
+            expression: Vec::new(),
+        }
+    }
+
+    pub fn with_details(mut self, details: CodeableConcept) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn with_expression(mut self, expression: impl Into<String>) -> Self {
+        self.expression.push(expression.into());
+        self
+    }
+
+    /// `fatal` or `error`.
+    pub fn is_error(&self) -> bool {
+        matches!(self.severity, IssueSeverity::Fatal | IssueSeverity::Error)
+    }
+}
+
+/// FHIR OperationOutcome resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationOutcome {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifier_extension: Vec<Extension>,
+    /// At least one issue in a conformant instance; see [`Self::all_ok`].
+    #[serde(default)]
+    pub issue: Vec<OperationOutcomeIssue>,
+}
+
+impl OperationOutcome {
+    pub fn new(issue: Vec<OperationOutcomeIssue>) -> Self {
+        Self {
+            issue,
+            ..Self::default()
+        }
+    }
+
+    /// Outcome reporting success, with the single informational issue FHIR
+    /// requires when there is nothing else to say.
+    pub fn all_ok() -> Self {
+        Self::new(vec![OperationOutcomeIssue::new(
+            IssueSeverity::Information,
+            IssueType::Informational,
+            "All OK",
+        )])
+    }
+
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Whether any issue is `fatal` or `error`.
+    pub fn has_errors(&self) -> bool {
+        self.issue.iter().any(OperationOutcomeIssue::is_error)
+    }
+}
+
+impl Default for OperationOutcome {
+    fn default() -> Self {
+        Self {
+            resource_type: "OperationOutcome".to_string(),
+            id: None,
+            meta: None,
+            extension: Vec::new(),
+            modifier_extension: Vec::new(),
+            issue: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fhir::{Coding, Resource};
+    use serde_json::json;
+
+    #[test]
+    fn issues_round_trip_with_fhir_codes() {
+        let outcome = OperationOutcome::new(vec![
+            OperationOutcomeIssue::new(
+                IssueSeverity::Error,
+                IssueType::CodeInvalid,
+                "unknown status",
+            )
+            .with_expression("ServiceRequest.status")
+            .with_details(CodeableConcept {
+                coding: vec![Coding {
+                    system: Some("https://dfps.example/issue".into()),
+                    code: Some("VAL_SR_STATUS_INVALID".into()),
+                    display: None,
+                }],
+                text: None,
+            }),
+        ]);
+        let value = serde_json::to_value(&outcome).unwrap();
+        assert_eq!(value["resourceType"], "OperationOutcome");
+        assert_eq!(value["issue"][0]["severity"], "error");
+        assert_eq!(value["issue"][0]["code"], "code-invalid");
+        assert_eq!(
+            value["issue"][0]["expression"],
+            json!(["ServiceRequest.status"])
+        );
+        assert!(outcome.has_errors());
+
+        let decoded = Resource::from_value(value).unwrap();
+        assert_eq!(decoded.as_type::<OperationOutcome>(), Some(&outcome));
+    }
+
+    #[test]
+    fn all_ok_is_informational() {
+        let outcome = OperationOutcome::all_ok();
+        assert!(!outcome.has_errors());
+        assert_eq!(
+            serde_json::to_value(&outcome).unwrap()["issue"][0]["code"],
+            "informational"
+        );
+    }
+}
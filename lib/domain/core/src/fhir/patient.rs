@@ -0,0 +1,146 @@
+//! FHIR R4 `Patient` with the demographics needed for cohort analytics.
+
+use serde::{Deserialize, Serialize};
+
+use super::choice;
+use super::datatypes::{Identifier, Reference};
+use super::extension::{Extension, Meta};
+use crate::value::{FhirDate, FhirDateTime};
+
+/// `Identifier.type` code (HL7 v2 table 0203) marking a medical record number.
+pub const MRN_IDENTIFIER_TYPE: &str = "MR";
+
+/// Choice type for `Patient.deceased[x]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PatientDeceased {
+    #[serde(rename = "deceasedBoolean")]
+    Boolean(bool),
+    #[serde(rename = "deceasedDateTime")]
+    DateTime(FhirDateTime),
+}
+
+/// FHIR Patient resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Patient {
+    #[serde(rename = "resourceType", default = "patient_resource_type")]
+    pub resource_type: String,
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifier_extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identifier: Vec<Identifier>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gender: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub birth_date: Option<FhirDate>,
+    #[serde(flatten, deserialize_with = "choice::deserialize")]
+    pub deceased: Option<PatientDeceased>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub managing_organization: Option<Reference>,
+}
+
+fn patient_resource_type() -> String {
+    "Patient".to_string()
+}
+
+impl Default for Patient {
+    fn default() -> Self {
+        Self {
+            resource_type: patient_resource_type(),
+            id: None,
+            meta: None,
+            extension: Vec::new(),
+            modifier_extension: Vec::new(),
+            identifier: Vec::new(),
+            active: None,
+            gender: None,
+            birth_date: None,
+            deceased: None,
+            managing_organization: None,
+        }
+    }
+}
+
+impl Patient {
+    /// First identifier typed as a medical record number (`type.coding.code = MR`).
+    pub fn mrn(&self) -> Option<&Identifier> {
+        self.identifier.iter().find(|identifier| {
+            identifier.identifier_type.as_ref().is_some_and(|concept| {
+                concept
+                    .coding
+                    .iter()
+                    .any(|coding| coding.code.as_deref() == Some(MRN_IDENTIFIER_TYPE))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_demographics_and_mrn() {
+        let patient: Patient = serde_json::from_value(serde_json::json!({
+            "resourceType": "Patient",
+            "id": "p1",
+            "identifier": [
+                { "system": "urn:ssn", "value": "000-00-0000" },
+                {
+                    "type": { "coding": [{
+                        "system": "http://terminology.hl7.org/CodeSystem/v2-0203",
+                        "code": "MR"
+                    }] },
+                    "system": "urn:hospital:mrn",
+                    "value": "MRN-42"
+                }
+            ],
+            "gender": "female",
+            "birthDate": "1970-03",
+            "deceasedDateTime": "2024-01-02T03:04:05Z"
+        }))
+        .expect("patient decodes");
+
+        assert_eq!(
+            patient.mrn().and_then(|id| id.value.as_deref()),
+            Some("MRN-42")
+        );
+        assert_eq!(
+            patient.birth_date.as_ref().map(FhirDate::as_str),
+            Some("1970-03")
+        );
+        assert!(matches!(
+            patient.deceased,
+            Some(PatientDeceased::DateTime(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_deceased_is_an_error() {
+        let err = serde_json::from_value::<Patient>(serde_json::json!({
+            "resourceType": "Patient",
+            "deceasedBoolean": "yes"
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("deceasedBoolean"), "{err}");
+    }
+
+    #[test]
+    fn minimal_patient_keeps_wire_shape() {
+        let patient = Patient {
+            id: Some("p1".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&patient).unwrap(),
+            serde_json::json!({ "resourceType": "Patient", "id": "p1" })
+        );
+    }
+}
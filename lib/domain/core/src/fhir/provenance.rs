@@ -0,0 +1,76 @@
+//! FHIR R4 `Provenance`: which activity produced a set of target resources,
+//! who ran it and what it was derived from.
+
+use serde::{Deserialize, Serialize};
+
+use super::datatypes::{CodeableConcept, Reference};
+use super::extension::{Extension, Meta};
+use crate::value::{FhirDateTime, FhirInstant};
+
+/// `Provenance.agent`: an actor taking part in the activity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenanceAgent {
+    /// Participation type (`provenance-participant-type`, e.g. `assembler`).
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub agent_type: Option<CodeableConcept>,
+    pub who: Reference,
+}
+
+/// `Provenance.entity`: an input of the activity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenanceEntity {
+    /// `derivation`, `revision`, `quotation`, `source` or `removal`.
+    pub role: String,
+    pub what: Reference,
+}
+
+/// FHIR Provenance resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifier_extension: Vec<Extension>,
+    #[serde(default)]
+    pub target: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub occurred_date_time: Option<FhirDateTime>,
+    pub recorded: FhirInstant,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policy: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activity: Option<CodeableConcept>,
+    #[serde(default)]
+    pub agent: Vec<ProvenanceAgent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entity: Vec<ProvenanceEntity>,
+}
+
+impl Provenance {
+    /// Provenance of `target` recorded at `recorded`, with no agents yet.
+    pub fn new(target: Vec<Reference>, recorded: FhirInstant) -> Self {
+        Self {
+            resource_type: "Provenance".to_string(),
+            id: None,
+            meta: None,
+            extension: Vec::new(),
+            modifier_extension: Vec::new(),
+            target,
+            occurred_date_time: None,
+            recorded,
+            policy: Vec::new(),
+            activity: None,
+            agent: Vec::new(),
+            entity: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Typed view over Bundle entry resources.
+//!
+//! `BundleEntry::resource` stays passthrough JSON so unknown or malformed
+//! resources survive a round trip; [`Resource`] is the single place where the
+//! `resourceType` discriminator is read and the matching struct is decoded.
+//! Resource types the crate does not model land in [`Resource::Unknown`].
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::DeserializeOwned};
+use serde_json::Value;
+
+use super::extension::{DomainResource, Extension, Meta};
+use super::{
+    DiagnosticReport, Encounter, ImagingStudy, Observation, OperationOutcome, Patient, Provenance,
+    ServiceRequest,
+};
+
+/// A FHIR resource struct that can be decoded from a Bundle entry.
+pub trait FhirResource: DeserializeOwned + Serialize + Into<Resource> + 'static {
+    /// Wire value of `resourceType`.
+    const RESOURCE_TYPE: &'static str;
+
+    /// Logical id (`Resource.id`), if present.
+    fn resource_id(&self) -> Option<&str>;
+
+    /// Borrow the typed resource out of a [`Resource`] of the same type.
+    fn from_resource(resource: &Resource) -> Option<&Self>;
+
+    /// Take the typed resource out of a [`Resource`] of the same type.
+    fn from_resource_owned(resource: Resource) -> Option<Self>;
+}
+
+/// Any resource that may appear in a Bundle entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resource {
+    ServiceRequest(Box<ServiceRequest>),
+    Patient(Box<Patient>),
+    Encounter(Box<Encounter>),
+    Observation(Box<Observation>),
+    DiagnosticReport(Box<DiagnosticReport>),
+    ImagingStudy(Box<ImagingStudy>),
+    OperationOutcome(Box<OperationOutcome>),
+    Provenance(Box<Provenance>),
+    /// Resource type not modelled by this crate (or missing `resourceType`),
+    /// kept as raw JSON.
+    Unknown(Value),
+}
+
+macro_rules! typed_resources {
+    ($($variant:ident),+ $(,)?) => {
+        $(
+            impl FhirResource for $variant {
+                const RESOURCE_TYPE: &'static str = stringify!($variant);
+
+                fn resource_id(&self) -> Option<&str> {
+                    self.id.as_deref()
+                }
+
+                fn from_resource(resource: &Resource) -> Option<&Self> {
+                    match resource {
+                        Resource::$variant(inner) => Some(inner),
+                        _ => None,
+                    }
+                }
+
+                fn from_resource_owned(resource: Resource) -> Option<Self> {
+                    match resource {
+                        Resource::$variant(inner) => Some(*inner),
+                        _ => None,
+                    }
+                }
+            }
+
+            impl DomainResource for $variant {
+                fn meta(&self) -> Option<&Meta> {
+                    self.meta.as_ref()
+                }
+
+                fn extensions(&self) -> &[Extension] {
+                    &self.extension
+                }
+
+                fn modifier_extensions(&self) -> &[Extension] {
+                    &self.modifier_extension
+                }
+            }
+
+            impl From<$variant> for Resource {
+                fn from(value: $variant) -> Self {
+                    Resource::$variant(Box::new(value))
+                }
+            }
+        )+
+
+        impl Resource {
+            /// Decode raw JSON, dispatching on `resourceType`.
+            ///
+            /// Known types that fail to decode return the serde error; unknown
+            /// types never fail.
+            pub fn from_value(value: Value) -> Result<Self, serde_json::Error> {
+                match resource_type_of(&value) {
+                    $(Some(stringify!($variant)) => {
+                        serde_json::from_value(value).map(|inner| Resource::$variant(Box::new(inner)))
+                    })+
+                    _ => Ok(Resource::Unknown(value)),
+                }
+            }
+
+            /// `resourceType` of the resource (`""` when an unknown payload has none).
+            pub fn resource_type(&self) -> &str {
+                match self {
+                    $(Resource::$variant(_) => <$variant as FhirResource>::RESOURCE_TYPE,)+
+                    Resource::Unknown(value) => resource_type_of(value).unwrap_or_default(),
+                }
+            }
+
+            /// Logical id of the resource, if present.
+            pub fn id(&self) -> Option<&str> {
+                match self {
+                    $(Resource::$variant(inner) => inner.resource_id(),)+
+                    Resource::Unknown(value) => resource_id_of(value),
+                }
+            }
+
+            /// Encode back to FHIR JSON.
+            pub fn to_value(&self) -> Result<Value, serde_json::Error> {
+                match self {
+                    $(Resource::$variant(inner) => serde_json::to_value(inner),)+
+                    Resource::Unknown(value) => Ok(value.clone()),
+                }
+            }
+        }
+    };
+}
+
+typed_resources!(
+    ServiceRequest,
+    Patient,
+    Encounter,
+    Observation,
+    DiagnosticReport,
+    ImagingStudy,
+    OperationOutcome,
+    Provenance,
+);
+
+impl Resource {
+    /// Borrow the typed resource when it is a `T`.
+    pub fn as_type<T: FhirResource>(&self) -> Option<&T> {
+        T::from_resource(self)
+    }
+
+    /// `"ResourceType/id"` reference to this resource, when it has an id.
+    pub fn reference(&self) -> Option<String> {
+        self.id()
+            .map(|id| format!("{}/{}", self.resource_type(), id))
+    }
+}
+
+impl Serialize for Resource {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value()
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Resource {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Resource::from_value(value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Read `resourceType` from raw resource JSON without decoding the rest.
+pub(crate) fn resource_type_of(value: &Value) -> Option<&str> {
+    value.get("resourceType").and_then(Value::as_str)
+}
+
+/// Read `id` from raw resource JSON without decoding the rest.
+pub(crate) fn resource_id_of(value: &Value) -> Option<&str> {
+    value.get("id").and_then(Value::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_known_types_and_passes_unknown_through() {
+        let patient = Resource::from_value(serde_json::json!({
+            "resourceType": "Patient",
+            "id": "p1",
+            "gender": "female"
+        }))
+        .unwrap();
+        assert!(matches!(patient, Resource::Patient(_)));
+        assert_eq!(patient.reference().as_deref(), Some("Patient/p1"));
+        assert_eq!(
+            patient
+                .as_type::<Patient>()
+                .and_then(|p| p.gender.as_deref()),
+            Some("female")
+        );
+        assert!(patient.as_type::<Encounter>().is_none());
+
+        let raw = serde_json::json!({ "resourceType": "Specimen", "id": "s1" });
+        let specimen = Resource::from_value(raw.clone()).unwrap();
+        assert_eq!(specimen, Resource::Unknown(raw.clone()));
+        assert_eq!(specimen.resource_type(), "Specimen");
+        assert_eq!(specimen.id(), Some("s1"));
+        assert_eq!(serde_json::to_value(&specimen).unwrap(), raw);
+    }
+
+    #[test]
+    fn malformed_known_type_is_an_error() {
+        let result = Resource::from_value(serde_json::json!({
+            "resourceType": "ServiceRequest",
+            "authoredOn": "not-a-date"
+        }));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,314 @@
+//! FHIR R4 `ServiceRequest` covering the full element set.
+//!
+//! The resource feeds the staging flatten described in
+//! `docs/system-design/clinical/fhir/models/data-model-er.md`; choice elements
+//! (`quantity[x]`, `occurrence[x]`, `asNeeded[x]`) are modelled as enums that
+//! are flattened into the parent JSON object so the wire format matches FHIR.
+
+use serde::{Deserialize, Serialize};
+
+use super::choice;
+use super::datatypes::{
+    Annotation, CodeableConcept, Identifier, Period, Quantity, Range, Ratio, Reference, Timing,
+};
+use super::extension::{Extension, Meta};
+use crate::value::FhirDateTime;
+
+/// Choice type for `ServiceRequest.quantity[x]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServiceRequestQuantity {
+    #[serde(rename = "quantityQuantity")]
+    Quantity(Quantity),
+    #[serde(rename = "quantityRatio")]
+    Ratio(Ratio),
+    #[serde(rename = "quantityRange")]
+    Range(Range),
+}
+
+/// Choice type for `ServiceRequest.occurrence[x]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServiceRequestOccurrence {
+    #[serde(rename = "occurrenceDateTime")]
+    DateTime(FhirDateTime),
+    #[serde(rename = "occurrencePeriod")]
+    Period(Period),
+    #[serde(rename = "occurrenceTiming")]
+    Timing(Box<Timing>),
+}
+
+impl ServiceRequestOccurrence {
+    /// Earliest instant described by the occurrence, if any.
+    pub fn start(&self) -> Option<&FhirDateTime> {
+        match self {
+            Self::DateTime(value) => Some(value),
+            Self::Period(period) => period.start.as_ref(),
+            Self::Timing(timing) => timing.first_instant(),
+        }
+    }
+
+    /// Latest instant described by the occurrence, if any.
+    ///
+    /// A single `occurrenceDateTime` has no separate end.
+    pub fn end(&self) -> Option<&FhirDateTime> {
+        match self {
+            Self::DateTime(_) => None,
+            Self::Period(period) => period.end.as_ref(),
+            Self::Timing(timing) => timing.last_instant(),
+        }
+    }
+}
+
+/// Choice type for `ServiceRequest.asNeeded[x]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServiceRequestAsNeeded {
+    #[serde(rename = "asNeededBoolean")]
+    Boolean(bool),
+    #[serde(rename = "asNeededCodeableConcept")]
+    CodeableConcept(CodeableConcept),
+}
+
+/// FHIR R4 ServiceRequest.
+///
+/// Fields present in the original ingestion MVP keep their wire shape; the
+/// remaining R4 elements are skipped on serialization when empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceRequest {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extension: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifier_extension: Vec<Extension>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identifier: Vec<Identifier>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instantiates_canonical: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instantiates_uri: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub based_on: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replaces: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requisition: Option<Identifier>,
+
+    pub status: Option<String>,
+    pub intent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub do_not_perform: Option<bool>,
+
+    pub subject: Option<Reference>,
+    pub encounter: Option<Reference>,
+    pub requester: Option<Reference>,
+    #[serde(default)]
+    pub supporting_info: Vec<Reference>,
+
+    pub code: Option<CodeableConcept>,
+    #[serde(default)]
+    pub category: Vec<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order_detail: Vec<CodeableConcept>,
+    #[serde(flatten, deserialize_with = "choice::deserialize")]
+    pub quantity: Option<ServiceRequestQuantity>,
+    pub description: Option<String>,
+
+    #[serde(flatten, deserialize_with = "choice::deserialize")]
+    pub occurrence: Option<ServiceRequestOccurrence>,
+    #[serde(flatten, deserialize_with = "choice::deserialize")]
+    pub as_needed: Option<ServiceRequestAsNeeded>,
+    pub authored_on: Option<FhirDateTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub performer_type: Option<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub performer: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub location_code: Vec<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub location_reference: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reason_code: Vec<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reason_reference: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub insurance: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub specimen: Vec<Reference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub body_site: Vec<CodeableConcept>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub note: Vec<Annotation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patient_instruction: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relevant_history: Vec<Reference>,
+}
+
+impl Default for ServiceRequest {
+    fn default() -> Self {
+        Self {
+            resource_type: "ServiceRequest".to_string(),
+            id: None,
+            meta: None,
+            extension: Vec::new(),
+            modifier_extension: Vec::new(),
+            identifier: Vec::new(),
+            instantiates_canonical: Vec::new(),
+            instantiates_uri: Vec::new(),
+            based_on: Vec::new(),
+            replaces: Vec::new(),
+            requisition: None,
+            status: None,
+            intent: None,
+            priority: None,
+            do_not_perform: None,
+            subject: None,
+            encounter: None,
+            requester: None,
+            supporting_info: Vec::new(),
+            code: None,
+            category: Vec::new(),
+            order_detail: Vec::new(),
+            quantity: None,
+            description: None,
+            occurrence: None,
+            as_needed: None,
+            authored_on: None,
+            performer_type: None,
+            performer: Vec::new(),
+            location_code: Vec::new(),
+            location_reference: Vec::new(),
+            reason_code: Vec::new(),
+            reason_reference: Vec::new(),
+            insurance: Vec::new(),
+            specimen: Vec::new(),
+            body_site: Vec::new(),
+            note: Vec::new(),
+            patient_instruction: None,
+            relevant_history: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fhir::{AnnotationAuthor, TimingBounds};
+
+    #[test]
+    fn deserializes_full_r4_element_set() {
+        let sr: ServiceRequest = serde_json::from_value(serde_json::json!({
+            "resourceType": "ServiceRequest",
+            "id": "SR-FULL",
+            "identifier": [{ "system": "urn:placer", "value": "PL-1" }],
+            "basedOn": [{ "reference": "CarePlan/cp-1" }],
+            "replaces": [{ "reference": "ServiceRequest/SR-OLD" }],
+            "status": "active",
+            "intent": "order",
+            "priority": "urgent",
+            "subject": { "reference": "Patient/p1" },
+            "orderDetail": [{ "text": "with contrast" }],
+            "quantityQuantity": { "value": 1, "unit": "study" },
+            "occurrencePeriod": { "start": "2024-05-02", "end": "2024-05-03" },
+            "asNeededBoolean": false,
+            "performer": [{ "reference": "Practitioner/pr-1" }],
+            "reasonCode": [{
+                "coding": [{ "system": "http://snomed.info/sct", "code": "363346000" }]
+            }],
+            "reasonReference": [{ "reference": "Condition/c-1" }],
+            "specimen": [{ "reference": "Specimen/s-1" }],
+            "bodySite": [{ "text": "Whole body" }],
+            "note": [{ "authorString": "Dr. Who", "text": "Fasting 6h" }]
+        }))
+        .expect("full ServiceRequest decodes");
+
+        assert_eq!(sr.priority.as_deref(), Some("urgent"));
+        assert_eq!(sr.identifier[0].value.as_deref(), Some("PL-1"));
+        assert!(matches!(
+            sr.quantity,
+            Some(ServiceRequestQuantity::Quantity(Quantity {
+                value: Some(v),
+                ..
+            })) if v == 1.0
+        ));
+        let occurrence = sr.occurrence.as_ref().expect("occurrence present");
+        assert_eq!(
+            occurrence.start().map(FhirDateTime::as_str),
+            Some("2024-05-02")
+        );
+        assert_eq!(
+            occurrence.end().map(FhirDateTime::as_str),
+            Some("2024-05-03")
+        );
+        assert_eq!(sr.as_needed, Some(ServiceRequestAsNeeded::Boolean(false)));
+        assert_eq!(sr.reason_code.len(), 1);
+        assert_eq!(
+            sr.note[0].author,
+            Some(AnnotationAuthor::String("Dr. Who".into()))
+        );
+    }
+
+    #[test]
+    fn occurrence_choice_variants_roundtrip() {
+        for (key, value) in [
+            (
+                "occurrenceDateTime",
+                serde_json::json!("2024-05-02T09:30:00Z"),
+            ),
+            (
+                "occurrenceTiming",
+                serde_json::json!({
+                    "repeat": { "boundsPeriod": { "start": "2024-06-01" }, "count": 3 }
+                }),
+            ),
+        ] {
+            let mut raw = serde_json::json!({
+                "resourceType": "ServiceRequest",
+                "status": "active",
+                "intent": "order"
+            });
+            raw[key] = value.clone();
+            let sr: ServiceRequest = serde_json::from_value(raw).expect("decodes");
+            let encoded = serde_json::to_value(&sr).expect("encodes");
+            assert_eq!(encoded[key], value);
+        }
+
+        let sr: ServiceRequest = serde_json::from_value(serde_json::json!({
+            "resourceType": "ServiceRequest",
+            "occurrenceTiming": { "repeat": { "boundsPeriod": { "start": "2024-06-01" } } }
+        }))
+        .unwrap();
+        match sr.occurrence {
+            Some(ServiceRequestOccurrence::Timing(timing)) => {
+                let repeat = timing.repeat.expect("repeat present");
+                assert!(matches!(repeat.bounds, Some(TimingBounds::Period(_))));
+            }
+            other => panic!("unexpected occurrence {other:?}"),
+        }
+    }
+
+    #[test]
+    fn malformed_authored_on_is_rejected() {
+        let result = serde_json::from_value::<ServiceRequest>(serde_json::json!({
+            "resourceType": "ServiceRequest",
+            "authoredOn": "05/01/2024"
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn empty_optional_elements_are_not_serialized() {
+        let encoded = serde_json::to_value(ServiceRequest::default()).unwrap();
+        let object = encoded.as_object().unwrap();
+        assert!(!object.contains_key("reasonCode"));
+        assert!(!object.contains_key("occurrenceDateTime"));
+        assert!(!object.contains_key("priority"));
+    }
+}
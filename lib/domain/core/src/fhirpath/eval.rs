@@ -0,0 +1,351 @@
+//! Evaluation of parsed expressions over JSON collections.
+
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+use super::{
+    FhirPathError, ReferenceResolver,
+    parser::{Expr, Function, Op},
+};
+
+/// One item of a collection, with the type implied by how it was reached
+/// (the suffix of a choice element such as `valueQuantity`).
+#[derive(Debug, Clone)]
+struct Item {
+    value: Value,
+    choice_type: Option<String>,
+}
+
+impl Item {
+    fn new(value: Value) -> Self {
+        Self {
+            value,
+            choice_type: None,
+        }
+    }
+
+    fn boolean(value: bool) -> Vec<Self> {
+        vec![Self::new(Value::Bool(value))]
+    }
+
+    fn type_name(&self) -> Option<&str> {
+        if let Some(choice_type) = &self.choice_type {
+            return Some(choice_type);
+        }
+        match &self.value {
+            Value::Object(map) => map.get("resourceType").and_then(Value::as_str),
+            Value::Bool(_) => Some("boolean"),
+            Value::String(_) => Some("string"),
+            Value::Number(number) if number.is_f64() => Some("decimal"),
+            Value::Number(_) => Some("integer"),
+            _ => None,
+        }
+    }
+}
+
+pub(super) struct Context<'a> {
+    pub(super) resolver: &'a dyn ReferenceResolver,
+}
+
+impl Context<'_> {
+    pub(super) fn evaluate(
+        &self,
+        expr: &Expr,
+        resource: &Value,
+    ) -> Result<Vec<Value>, FhirPathError> {
+        let focus = [Item::new(resource.clone())];
+        Ok(self
+            .eval(expr, &focus)?
+            .into_iter()
+            .map(|item| item.value)
+            .collect())
+    }
+
+    fn eval(&self, expr: &Expr, focus: &[Item]) -> Result<Vec<Item>, FhirPathError> {
+        Ok(match expr {
+            Expr::Literal(value) => vec![Item::new(value.clone())],
+            Expr::Empty => Vec::new(),
+            Expr::This => focus.to_vec(),
+            Expr::Root(name) => {
+                let typed: Vec<Item> = if name.starts_with(|c: char| c.is_ascii_uppercase()) {
+                    focus
+                        .iter()
+                        .filter(|item| item.type_name() == Some(name.as_str()))
+                        .cloned()
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                if typed.is_empty() {
+                    member(focus, name)
+                } else {
+                    typed
+                }
+            }
+            Expr::Member(base, name) => member(&self.eval(base, focus)?, name),
+            Expr::Call(base, function) => {
+                let input = self.eval(base, focus)?;
+                self.call(function, input)?
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left, focus)?;
+                let right = self.eval(right, focus)?;
+                binary(*op, left, right)?
+            }
+        })
+    }
+
+    fn call(&self, function: &Function, input: Vec<Item>) -> Result<Vec<Item>, FhirPathError> {
+        Ok(match function {
+            Function::Where(criteria) => self.filter(criteria, input)?,
+            Function::Exists(None) => Item::boolean(!input.is_empty()),
+            Function::Exists(Some(criteria)) => {
+                Item::boolean(!self.filter(criteria, input)?.is_empty())
+            }
+            Function::Empty => Item::boolean(input.is_empty()),
+            Function::Not => truth(&input)?
+                .map(|value| Item::boolean(!value))
+                .unwrap_or_default(),
+            Function::Count => vec![Item::new(Value::from(input.len()))],
+            Function::First => input.into_iter().take(1).collect(),
+            Function::Last => input.into_iter().last().into_iter().collect(),
+            Function::OfType(name) => input
+                .into_iter()
+                .filter(|item| {
+                    item.type_name()
+                        .is_some_and(|ty| ty.eq_ignore_ascii_case(name))
+                })
+                .collect(),
+            Function::Resolve => input
+                .iter()
+                .filter_map(|item| {
+                    let reference = match &item.value {
+                        Value::String(reference) => reference.as_str(),
+                        value => value.get("reference")?.as_str()?,
+                    };
+                    self.resolver.resolve(reference).map(Item::new)
+                })
+                .collect(),
+        })
+    }
+
+    /// Items of `input` for which `criteria`, evaluated with the item as
+    /// `$this`, is `true`.
+    fn filter(&self, criteria: &Expr, input: Vec<Item>) -> Result<Vec<Item>, FhirPathError> {
+        let mut output = Vec::new();
+        for item in input {
+            let result = self.eval(criteria, std::slice::from_ref(&item))?;
+            if truth(&result)? == Some(true) {
+                output.push(item);
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// Type suffixes a choice element `name[x]` can carry: the FHIR R4 open
+/// types (primitives capitalized, as they appear in `valueString`). `Id` is
+/// only taken for the open `value[x]` (Extension, Parameters), since names
+/// like `versionId` and `subjectId` are elements of their own.
+const CHOICE_TYPES: &[&str] = &[
+    "Base64Binary",
+    "Boolean",
+    "Canonical",
+    "Code",
+    "Date",
+    "DateTime",
+    "Decimal",
+    "Id",
+    "Instant",
+    "Integer",
+    "Markdown",
+    "Oid",
+    "PositiveInt",
+    "String",
+    "Time",
+    "UnsignedInt",
+    "Uri",
+    "Url",
+    "Uuid",
+    "Address",
+    "Age",
+    "Annotation",
+    "Attachment",
+    "CodeableConcept",
+    "Coding",
+    "ContactPoint",
+    "Count",
+    "Distance",
+    "Duration",
+    "HumanName",
+    "Identifier",
+    "Money",
+    "Period",
+    "Quantity",
+    "Range",
+    "Ratio",
+    "Reference",
+    "SampledData",
+    "Signature",
+    "Timing",
+    "ContactDetail",
+    "Contributor",
+    "DataRequirement",
+    "Expression",
+    "ParameterDefinition",
+    "RelatedArtifact",
+    "TriggerDefinition",
+    "UsageContext",
+    "Dosage",
+    "Meta",
+];
+
+/// Child `name` of every object in `input`, with arrays flattened. When the
+/// element is absent, a choice element `name[x]` (e.g. `valueQuantity` for
+/// `value`) is read instead and its type recorded; only datatype suffixes
+/// count, so `class` does not reach `classHistory`.
+fn member(input: &[Item], name: &str) -> Vec<Item> {
+    let mut output = Vec::new();
+    for item in input {
+        let Value::Object(map) = &item.value else {
+            continue;
+        };
+        if let Some(child) = map.get(name) {
+            push_children(&mut output, child, None);
+            continue;
+        }
+        for (key, child) in map {
+            if let Some(suffix) = key.strip_prefix(name)
+                && CHOICE_TYPES.contains(&suffix)
+                && (suffix != "Id" || name == "value")
+            {
+                push_children(&mut output, child, Some(suffix));
+            }
+        }
+    }
+    output
+}
+
+fn push_children(output: &mut Vec<Item>, child: &Value, choice_type: Option<&str>) {
+    let item = |value: &Value| Item {
+        value: value.clone(),
+        choice_type: choice_type.map(str::to_string),
+    };
+    match child {
+        Value::Array(values) => {
+            output.extend(values.iter().filter(|value| !value.is_null()).map(item))
+        }
+        Value::Null => {}
+        value => output.push(item(value)),
+    }
+}
+
+/// Singleton evaluation of a collection as a boolean; see
+/// [`super::as_boolean`].
+fn truth(collection: &[Item]) -> Result<Option<bool>, FhirPathError> {
+    match collection {
+        [] => Ok(None),
+        [item] => Ok(Some(item.value.as_bool().unwrap_or(true))),
+        _ => Err(FhirPathError::Eval(format!(
+            "expected a single boolean, got {} items",
+            collection.len()
+        ))),
+    }
+}
+
+fn binary(op: Op, left: Vec<Item>, right: Vec<Item>) -> Result<Vec<Item>, FhirPathError> {
+    Ok(match op {
+        Op::And | Op::Or | Op::Xor | Op::Implies => {
+            let (left, right) = (truth(&left)?, truth(&right)?);
+            let result = match op {
+                Op::And => match (left, right) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                },
+                Op::Or => match (left, right) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                },
+                Op::Xor => left.zip(right).map(|(left, right)| left != right),
+                _ => match (left, right) {
+                    (Some(false), _) | (_, Some(true)) => Some(true),
+                    (Some(true), Some(false)) => Some(false),
+                    _ => None,
+                },
+            };
+            result.map(Item::boolean).unwrap_or_default()
+        }
+        Op::Equals | Op::NotEquals => {
+            if left.is_empty() || right.is_empty() {
+                return Ok(Vec::new());
+            }
+            let equal = left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(&right)
+                    .all(|(a, b)| values_equal(&a.value, &b.value));
+            Item::boolean(equal == (op == Op::Equals))
+        }
+        Op::Union => {
+            let mut output: Vec<Item> = Vec::new();
+            for item in left.into_iter().chain(right) {
+                if !output
+                    .iter()
+                    .any(|seen| values_equal(&seen.value, &item.value))
+                {
+                    output.push(item);
+                }
+            }
+            output
+        }
+        Op::Less | Op::LessOrEqual | Op::Greater | Op::GreaterOrEqual => {
+            let (left, right) = match (left.as_slice(), right.as_slice()) {
+                ([], _) | (_, []) => return Ok(Vec::new()),
+                ([left], [right]) => (&left.value, &right.value),
+                _ => {
+                    return Err(FhirPathError::Eval(
+                        "comparison operands must be single items".to_string(),
+                    ));
+                }
+            };
+            let ordering = match (left, right) {
+                (Value::Number(a), Value::Number(b)) => a
+                    .as_f64()
+                    .partial_cmp(&b.as_f64())
+                    .unwrap_or(Ordering::Equal),
+                (Value::String(a), Value::String(b)) => a.cmp(b),
+                _ => {
+                    return Err(FhirPathError::Eval(format!(
+                        "cannot compare {left} with {right}"
+                    )));
+                }
+            };
+            Item::boolean(match op {
+                Op::Less => ordering == Ordering::Less,
+                Op::LessOrEqual => ordering != Ordering::Greater,
+                Op::Greater => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            })
+        }
+    })
+}
+
+/// FHIRPath equality: numbers compare by value (`1 = 1.0`), everything else
+/// structurally.
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, value)| b.get(key).is_some_and(|other| values_equal(value, other)))
+        }
+        _ => left == right,
+    }
+}
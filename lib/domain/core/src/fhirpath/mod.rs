@@ -0,0 +1,275 @@
+//! FHIRPath subset evaluator over FHIR JSON.
+//!
+//! Expressions are parsed once into a [`FhirPath`] and evaluated against the
+//! serde `Value` passthrough (Bundle entries, `Resource::Unknown`) or any
+//! typed resource, which is serialized to FHIR JSON first. Supported:
+//!
+//! - path navigation with array flattening, an optional leading type name
+//!   (`Patient.name.given`), `` `delimited` `` identifiers and choice elements
+//!   (`Observation.value` reads `valueQuantity`, `valueString`, …);
+//! - `'string'`, integer, decimal and boolean literals, `{}` and `$this`;
+//! - `=`, `!=`, `<`, `<=`, `>`, `>=`, the union operator `|`, and
+//!   `and` / `or` / `xor` / `implies` with FHIRPath's three-valued logic;
+//! - `where(criteria)`, `exists([criteria])`, `empty()`, `not()`, `count()`,
+//!   `first()`, `last()`, `ofType(Type)` and `resolve()`.
+//!
+//! `resolve()` looks references up through a [`ReferenceResolver`]; without
+//! one it yields nothing. `ofType` knows the type of resources (from
+//! `resourceType`), choice elements (from the element name suffix) and JSON
+//! primitives; other complex values have no known type. Type names compare
+//! case-insensitively and may carry a `FHIR.` or `System.` namespace.
+//! Unsupported syntax is a parse error rather than a silent empty result.
+
+mod eval;
+mod parser;
+
+use std::{fmt, str::FromStr};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use self::{eval::Context, parser::Expr};
+
+/// Failure to parse or evaluate a FHIRPath expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FhirPathError {
+    /// The expression is malformed or uses unsupported syntax.
+    Parse(String),
+    /// The expression is well formed but cannot be evaluated on this input
+    /// (e.g. `not()` on a collection of several items).
+    Eval(String),
+}
+
+impl fmt::Display for FhirPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(reason) => write!(f, "invalid FHIRPath expression: {reason}"),
+            Self::Eval(reason) => write!(f, "FHIRPath evaluation failed: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for FhirPathError {}
+
+/// Looks up the target of a reference for `resolve()`.
+///
+/// Implemented for closures, so callers can scope resolution to whatever
+/// holds the resources (a Bundle, a store, a fixture map).
+pub trait ReferenceResolver {
+    /// Resource named by `reference` as written (`"Patient/1"`, a `urn:uuid:`,
+    /// `"#contained"`, …); `None` when it cannot be found.
+    fn resolve(&self, reference: &str) -> Option<Value>;
+}
+
+impl<F> ReferenceResolver for F
+where
+    F: Fn(&str) -> Option<Value>,
+{
+    fn resolve(&self, reference: &str) -> Option<Value> {
+        self(reference)
+    }
+}
+
+/// Resolver that never finds anything.
+struct NoReferences;
+
+impl ReferenceResolver for NoReferences {
+    fn resolve(&self, _reference: &str) -> Option<Value> {
+        None
+    }
+}
+
+/// A parsed FHIRPath expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FhirPath {
+    source: String,
+    expr: Expr,
+}
+
+impl FhirPath {
+    pub fn parse(source: &str) -> Result<Self, FhirPathError> {
+        Ok(Self {
+            source: source.to_string(),
+            expr: parser::parse(source).map_err(FhirPathError::Parse)?,
+        })
+    }
+
+    /// The expression as written.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluate against a JSON resource; `resolve()` finds nothing.
+    pub fn evaluate(&self, resource: &Value) -> Result<Vec<Value>, FhirPathError> {
+        self.evaluate_with(resource, &NoReferences)
+    }
+
+    /// Evaluate against a JSON resource, resolving references with `resolver`.
+    pub fn evaluate_with(
+        &self,
+        resource: &Value,
+        resolver: &dyn ReferenceResolver,
+    ) -> Result<Vec<Value>, FhirPathError> {
+        let context = Context { resolver };
+        context.evaluate(&self.expr, resource)
+    }
+
+    /// Evaluate against a typed resource (any `fhir` struct or
+    /// [`crate::fhir::Resource`]) through its FHIR JSON form.
+    pub fn evaluate_resource<T: Serialize>(
+        &self,
+        resource: &T,
+    ) -> Result<Vec<Value>, FhirPathError> {
+        let value = serde_json::to_value(resource)
+            .map_err(|err| FhirPathError::Eval(format!("resource does not serialize: {err}")))?;
+        self.evaluate(&value)
+    }
+}
+
+impl FromStr for FhirPath {
+    type Err = FhirPathError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+impl fmt::Display for FhirPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Singleton evaluation of a result as a boolean: empty is unknown (`None`),
+/// a single boolean is its value, any other single item is `true`, and more
+/// than one item is an error.
+pub fn as_boolean(collection: &[Value]) -> Result<Option<bool>, FhirPathError> {
+    match collection {
+        [] => Ok(None),
+        [Value::Bool(value)] => Ok(Some(*value)),
+        [_] => Ok(Some(true)),
+        _ => Err(FhirPathError::Eval(format!(
+            "expected a single boolean, got {} items",
+            collection.len()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fhir::{Patient, Resource};
+    use serde_json::json;
+
+    fn patient() -> Value {
+        json!({
+            "resourceType": "Patient",
+            "id": "PAT-1",
+            "active": true,
+            "gender": "male",
+            "deceasedBoolean": false,
+            "name": [
+                { "use": "official", "family": "Chalmers", "given": ["Peter", "James"] },
+                { "use": "usual", "given": ["Jim"] }
+            ],
+            "managingOrganization": { "reference": "Organization/ORG-1" }
+        })
+    }
+
+    fn eval(expression: &str, resource: &Value) -> Vec<Value> {
+        FhirPath::parse(expression)
+            .expect("expression parses")
+            .evaluate(resource)
+            .expect("expression evaluates")
+    }
+
+    #[test]
+    fn navigates_filters_and_compares() {
+        let patient = patient();
+        assert_eq!(
+            eval("Patient.name.given", &patient),
+            vec![json!("Peter"), json!("James"), json!("Jim")]
+        );
+        assert_eq!(
+            eval("name.where(use = 'usual').given.first()", &patient),
+            vec![json!("Jim")]
+        );
+        assert_eq!(
+            eval("name.exists(family = 'Chalmers') and active", &patient),
+            vec![json!(true)]
+        );
+        assert_eq!(eval("name.given.count() > 2", &patient), vec![json!(true)]);
+        assert!(eval("Encounter.status", &patient).is_empty());
+    }
+
+    #[test]
+    fn resolve_uses_the_supplied_resolver() {
+        let path = FhirPath::parse("managingOrganization.resolve().ofType(Organization).name")
+            .expect("expression parses");
+        assert!(path.evaluate(&patient()).unwrap().is_empty());
+
+        let resolver = |reference: &str| {
+            (reference == "Organization/ORG-1")
+                .then(|| json!({ "resourceType": "Organization", "id": "ORG-1", "name": "Acme" }))
+        };
+        assert_eq!(
+            path.evaluate_with(&patient(), &resolver).unwrap(),
+            vec![json!("Acme")]
+        );
+    }
+
+    #[test]
+    fn typed_resources_evaluate_through_their_json_form() {
+        let typed: Patient = serde_json::from_value(patient()).expect("patient decodes");
+        let path: FhirPath = "deceased.ofType(boolean) | gender"
+            .parse()
+            .expect("expression parses");
+        assert_eq!(
+            path.evaluate_resource(&typed).unwrap(),
+            vec![json!(false), json!("male")]
+        );
+        let resource = Resource::from(typed);
+        assert_eq!(
+            path.evaluate_resource(&resource).unwrap(),
+            vec![json!(false), json!("male")]
+        );
+    }
+
+    #[test]
+    fn choice_elements_match_only_datatype_suffixes() {
+        let encounter = json!({
+            "resourceType": "Encounter",
+            "classHistory": [{ "class": { "code": "EMER" } }],
+            "meta": { "versionId": "3" }
+        });
+        assert!(eval("Encounter.class", &encounter).is_empty());
+        assert!(eval("meta.version", &encounter).is_empty());
+        assert_eq!(
+            eval("deceased.ofType(boolean)", &patient()),
+            vec![json!(false)]
+        );
+    }
+
+    #[test]
+    fn unsupported_syntax_and_ambiguous_booleans_are_errors() {
+        for bad in [
+            "name.matches('x')",
+            "name.given[0]",
+            "name =",
+            "(name",
+            "1 + 1",
+        ] {
+            assert!(
+                matches!(FhirPath::parse(bad), Err(FhirPathError::Parse(_))),
+                "{bad}"
+            );
+        }
+        let err = FhirPath::parse("name.given.not()")
+            .unwrap()
+            .evaluate(&patient())
+            .unwrap_err();
+        assert!(matches!(err, FhirPathError::Eval(_)));
+        assert_eq!(as_boolean(&[json!(1)]), Ok(Some(true)));
+        assert!(as_boolean(&[json!(true), json!(false)]).is_err());
+    }
+}
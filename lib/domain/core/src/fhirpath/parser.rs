@@ -0,0 +1,406 @@
+//! Tokenizer and recursive-descent parser for the supported FHIRPath subset.
+//!
+//! Operator precedence follows the FHIRPath grammar, loosest first:
+//! `implies`, `or`/`xor`, `and`, `=`/`!=`, `<`/`<=`/`>`/`>=`, `|`, then
+//! invocation (`.`).
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Expr {
+    Literal(Value),
+    /// `{}`.
+    Empty,
+    /// `$this`, and the implicit focus of a bare function call.
+    This,
+    /// Identifier at the start of a path: a type name matching the focus, or
+    /// a member of it.
+    Root(String),
+    Member(Box<Expr>, String),
+    Call(Box<Expr>, Function),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Function {
+    Where(Box<Expr>),
+    Exists(Option<Box<Expr>>),
+    Empty,
+    Not,
+    Count,
+    First,
+    Last,
+    OfType(String),
+    Resolve,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Op {
+    Equals,
+    NotEquals,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Union,
+    And,
+    Or,
+    Xor,
+    Implies,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    /// `` `delimited` `` identifier; never a keyword.
+    Delimited(String),
+    Str(String),
+    Number(String),
+    This,
+    Dot,
+    Comma,
+    Open,
+    Close,
+    OpenBrace,
+    CloseBrace,
+    Pipe,
+    Eq,
+    NotEq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+pub(super) fn parse(source: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    };
+    if parser.tokens.is_empty() {
+        return Err("expression is empty".to_string());
+    }
+    let expr = parser.implies()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(format!("unexpected {token:?} in '{source}'")),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn keyword(&mut self, word: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(ident)) if ident == word) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), String> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(format!("expected {what}"))
+        }
+    }
+
+    fn implies(&mut self) -> Result<Expr, String> {
+        let mut expr = self.or()?;
+        while self.keyword("implies") {
+            expr = binary(Op::Implies, expr, self.or()?);
+        }
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        loop {
+            let op = if self.keyword("or") {
+                Op::Or
+            } else if self.keyword("xor") {
+                Op::Xor
+            } else {
+                return Ok(expr);
+            };
+            expr = binary(op, expr, self.and()?);
+        }
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.equality()?;
+        while self.keyword("and") {
+            expr = binary(Op::And, expr, self.equality()?);
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr, String> {
+        let mut expr = self.comparison()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Eq) => Op::Equals,
+                Some(Token::NotEq) => Op::NotEquals,
+                _ => return Ok(expr),
+            };
+            self.pos += 1;
+            expr = binary(op, expr, self.comparison()?);
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let mut expr = self.union()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Lt) => Op::Less,
+                Some(Token::Le) => Op::LessOrEqual,
+                Some(Token::Gt) => Op::Greater,
+                Some(Token::Ge) => Op::GreaterOrEqual,
+                _ => return Ok(expr),
+            };
+            self.pos += 1;
+            expr = binary(op, expr, self.union()?);
+        }
+    }
+
+    fn union(&mut self) -> Result<Expr, String> {
+        let mut expr = self.term()?;
+        while self.eat(&Token::Pipe) {
+            expr = binary(Op::Union, expr, self.term()?);
+        }
+        Ok(expr)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut expr = match self.next() {
+            Some(Token::Str(value)) => Expr::Literal(Value::String(value)),
+            Some(Token::Number(text)) => Expr::Literal(number(&text)?),
+            Some(Token::Ident(word)) if word == "true" || word == "false" => {
+                Expr::Literal(Value::Bool(word == "true"))
+            }
+            Some(Token::Ident(name)) if is_keyword(&name) => {
+                return Err(format!("unexpected '{name}'"));
+            }
+            Some(Token::Ident(name)) | Some(Token::Delimited(name)) => {
+                self.invocation(name, None)?
+            }
+            Some(Token::This) => Expr::This,
+            Some(Token::OpenBrace) => {
+                self.expect(Token::CloseBrace, "'}' (only '{}' is supported)")?;
+                Expr::Empty
+            }
+            Some(Token::Open) => {
+                let inner = self.implies()?;
+                self.expect(Token::Close, "')'")?;
+                inner
+            }
+            Some(token) => return Err(format!("unexpected {token:?}")),
+            None => return Err("expression ends early".to_string()),
+        };
+        while self.eat(&Token::Dot) {
+            match self.next() {
+                Some(Token::Ident(name)) | Some(Token::Delimited(name)) => {
+                    expr = self.invocation(name, Some(expr))?;
+                }
+                _ => return Err("expected a name after '.'".to_string()),
+            }
+        }
+        Ok(expr)
+    }
+
+    /// `name(args)` as a function call on `base`, otherwise member access.
+    fn invocation(&mut self, name: String, base: Option<Expr>) -> Result<Expr, String> {
+        if !self.eat(&Token::Open) {
+            return Ok(match base {
+                Some(base) => Expr::Member(Box::new(base), name),
+                None => Expr::Root(name),
+            });
+        }
+        let mut args = Vec::new();
+        if !self.eat(&Token::Close) {
+            loop {
+                args.push(self.implies()?);
+                if self.eat(&Token::Close) {
+                    break;
+                }
+                self.expect(Token::Comma, "',' or ')'")?;
+            }
+        }
+        let function = function(&name, args)?;
+        Ok(Expr::Call(Box::new(base.unwrap_or(Expr::This)), function))
+    }
+}
+
+fn binary(op: Op, left: Expr, right: Expr) -> Expr {
+    Expr::Binary(op, Box::new(left), Box::new(right))
+}
+
+fn is_keyword(word: &str) -> bool {
+    matches!(word, "and" | "or" | "xor" | "implies")
+}
+
+fn function(name: &str, mut args: Vec<Expr>) -> Result<Function, String> {
+    let arity = |expected: usize, args: &[Expr]| {
+        if args.len() == expected {
+            Ok(())
+        } else {
+            Err(format!("'{name}()' takes {expected} argument(s)"))
+        }
+    };
+    Ok(match name {
+        "where" => {
+            arity(1, &args)?;
+            Function::Where(Box::new(args.remove(0)))
+        }
+        "exists" if args.len() <= 1 => Function::Exists(args.pop().map(Box::new)),
+        "exists" => return Err("'exists()' takes at most one argument".to_string()),
+        "ofType" => {
+            arity(1, &args)?;
+            match args.remove(0) {
+                Expr::Root(name) => Function::OfType(name),
+                Expr::Member(base, name) => match *base {
+                    Expr::Root(namespace) if namespace == "FHIR" || namespace == "System" => {
+                        Function::OfType(name)
+                    }
+                    _ => return Err("'ofType()' expects a type name".to_string()),
+                },
+                _ => return Err("'ofType()' expects a type name".to_string()),
+            }
+        }
+        "empty" | "not" | "count" | "first" | "last" | "resolve" => {
+            arity(0, &args)?;
+            match name {
+                "empty" => Function::Empty,
+                "not" => Function::Not,
+                "count" => Function::Count,
+                "first" => Function::First,
+                "last" => Function::Last,
+                _ => Function::Resolve,
+            }
+        }
+        _ => return Err(format!("unsupported function '{name}()'")),
+    })
+}
+
+fn number(text: &str) -> Result<Value, String> {
+    let invalid = || format!("invalid number '{text}'");
+    if text.contains('.') {
+        let value: f64 = text.parse().map_err(|_| invalid())?;
+        serde_json::Number::from_f64(value)
+            .map(Value::Number)
+            .ok_or_else(invalid)
+    } else {
+        text.parse::<i64>().map(Value::from).map_err(|_| invalid())
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, ch)) = chars.next() {
+        let token = match ch {
+            c if c.is_whitespace() => continue,
+            '.' => Token::Dot,
+            ',' => Token::Comma,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '{' => Token::OpenBrace,
+            '}' => Token::CloseBrace,
+            '|' => Token::Pipe,
+            '=' => Token::Eq,
+            '!' if chars.next_if(|(_, next)| *next == '=').is_some() => Token::NotEq,
+            '<' if chars.next_if(|(_, next)| *next == '=').is_some() => Token::Le,
+            '<' => Token::Lt,
+            '>' if chars.next_if(|(_, next)| *next == '=').is_some() => Token::Ge,
+            '>' => Token::Gt,
+            '\'' => Token::Str(quoted(&mut chars, '\'')?),
+            '`' => Token::Delimited(quoted(&mut chars, '`')?),
+            '$' => {
+                let word = word(source, start + 1, start + 1, &mut chars);
+                if word != "this" {
+                    return Err(format!("unsupported variable '${word}'"));
+                }
+                Token::This
+            }
+            c if c.is_ascii_digit() => {
+                let mut end = start + 1;
+                while let Some((index, _)) = chars.next_if(|(_, next)| next.is_ascii_digit()) {
+                    end = index + 1;
+                }
+                // A '.' only belongs to the number when a digit follows it;
+                // otherwise it is an invocation on the integer literal.
+                let mut lookahead = chars.clone();
+                if let (Some((_, '.')), Some((_, digit))) = (lookahead.next(), lookahead.next())
+                    && digit.is_ascii_digit()
+                {
+                    chars.next();
+                    while let Some((index, _)) = chars.next_if(|(_, next)| next.is_ascii_digit()) {
+                        end = index + 1;
+                    }
+                }
+                Token::Number(source[start..end].to_string())
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                Token::Ident(word(source, start, start + c.len_utf8(), &mut chars))
+            }
+            other => return Err(format!("unexpected character '{other}'")),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::CharIndices<'a>>;
+
+/// `source[start..end]` extended by the identifier characters that follow.
+fn word(source: &str, start: usize, mut end: usize, chars: &mut Chars<'_>) -> String {
+    while let Some((index, next)) =
+        chars.next_if(|(_, next)| next.is_alphanumeric() || *next == '_')
+    {
+        end = index + next.len_utf8();
+    }
+    source[start..end].to_string()
+}
+
+/// Body of a quoted string or delimited identifier, with `\` escapes.
+fn quoted(chars: &mut Chars<'_>, delimiter: char) -> Result<String, String> {
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some((_, '\\')) => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, 'r')) => value.push('\r'),
+                Some((_, escaped)) => value.push(escaped),
+                None => return Err("unterminated escape".to_string()),
+            },
+            Some((_, c)) if c == delimiter => return Ok(value),
+            Some((_, c)) => value.push(c),
+            None => return Err(format!("unterminated {delimiter}…{delimiter}")),
+        }
+    }
+}
@@ -5,8 +5,10 @@
 
 pub mod encounter;
 pub mod fhir;
+pub mod fhirpath;
 pub mod mapping;
 pub mod order;
 pub mod patient;
+pub mod provenance;
 pub mod staging;
 pub mod value;
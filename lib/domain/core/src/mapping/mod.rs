@@ -9,6 +9,7 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::provenance::SourceProvenance;
 use crate::staging::StgSrCodeExploded;
 
 /// Atomic code extracted from staging and ready for mapping.
@@ -43,6 +44,7 @@ impl From<StgSrCodeExploded> for CodeElement {
             system,
             code,
             display,
+            ..
         } = value;
 
         let id = format!(
@@ -106,6 +108,9 @@ pub struct MappingResult {
     pub reason: Option<String>,
     pub license_tier: Option<String>,
     pub source_kind: Option<String>,
+    /// Provenance of the staged coding this result was mapped from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<SourceProvenance>,
 }
 
 /// Strategies used by the mapping engine. Keeps provenance readable.
@@ -189,6 +194,7 @@ mod tests {
             system: Some("http://loinc.org".into()),
             code: Some("24606-6".into()),
             display: Some("FDG uptake PET".into()),
+            provenance: None,
         };
 
         let element: CodeElement = staging.clone().into();
@@ -7,7 +7,9 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::value::{EncounterId, PatientId, ServiceRequestId};
+use crate::value::{
+    ClinicalCode, EncounterId, FhirDateTime, PatientId, ResourceReference, ServiceRequestId,
+};
 
 #[cfg(feature = "dummy")]
 use fake::Dummy;
@@ -43,6 +45,148 @@ pub enum ServiceRequestIntent {
     FillerOrder,
 }
 
+impl ServiceRequestStatus {
+    /// FHIR `request-status` code for this status.
+    pub fn as_fhir_code(&self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Active => "active",
+            Self::OnHold => "on-hold",
+            Self::Completed => "completed",
+            Self::Cancelled => "cancelled",
+            Self::Revoked => "revoked",
+            Self::EnteredInError => "entered-in-error",
+        }
+    }
+
+    /// Parse a FHIR `request-status` code, tolerating case and `_` separators.
+    pub fn from_fhir_code(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "draft" => Some(Self::Draft),
+            "active" => Some(Self::Active),
+            "on-hold" | "on_hold" => Some(Self::OnHold),
+            "completed" => Some(Self::Completed),
+            "cancelled" | "canceled" => Some(Self::Cancelled),
+            "revoked" => Some(Self::Revoked),
+            "entered-in-error" | "entered_in_error" => Some(Self::EnteredInError),
+            _ => None,
+        }
+    }
+
+    /// Statuses with no outgoing transitions other than `entered-in-error`.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            Self::Completed | Self::Cancelled | Self::Revoked | Self::EnteredInError
+        )
+    }
+
+    /// Whether the lifecycle in `state-servicerequest.md` permits moving to `next`.
+    ///
+    /// Staying in the same status is always allowed (re-delivery of an unchanged order).
+    pub fn can_transition_to(&self, next: ServiceRequestStatus) -> bool {
+        use ServiceRequestStatus::*;
+
+        if *self == next {
+            return true;
+        }
+        matches!(
+            (self, next),
+            (Draft, Active | Cancelled | EnteredInError)
+                | (
+                    Active,
+                    OnHold | Completed | Cancelled | Revoked | EnteredInError
+                )
+                | (OnHold, Active)
+                | (Completed | Cancelled, EnteredInError)
+        )
+    }
+}
+
+impl ServiceRequestIntent {
+    /// True for the actionable `order` family (`order`, `original-order`, ...).
+    pub fn is_order(&self) -> bool {
+        matches!(
+            self,
+            Self::Order | Self::OriginalOrder | Self::ReflexOrder | Self::FillerOrder
+        )
+    }
+}
+
+/// Reasons a lifecycle change is rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleError {
+    /// The state diagram has no edge between the two statuses.
+    IllegalTransition {
+        from: ServiceRequestStatus,
+        to: ServiceRequestStatus,
+    },
+    /// The status cannot be held by a request with this intent.
+    IncompatibleIntent {
+        status: ServiceRequestStatus,
+        intent: ServiceRequestIntent,
+    },
+}
+
+impl std::fmt::Display for LifecycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LifecycleError::IllegalTransition { from, to } => write!(
+                f,
+                "illegal ServiceRequest transition {} -> {}",
+                from.as_fhir_code(),
+                to.as_fhir_code()
+            ),
+            LifecycleError::IncompatibleIntent { status, intent } => write!(
+                f,
+                "ServiceRequest status {} is not valid for intent {intent:?}",
+                status.as_fhir_code()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LifecycleError {}
+
+/// Check that a status/intent pair is coherent.
+///
+/// Only actionable orders can be completed; any intent may be a draft.
+pub fn check_status_intent(
+    status: ServiceRequestStatus,
+    intent: ServiceRequestIntent,
+) -> Result<(), LifecycleError> {
+    if status == ServiceRequestStatus::Completed && !intent.is_order() {
+        Err(LifecycleError::IncompatibleIntent { status, intent })
+    } else {
+        Ok(())
+    }
+}
+
+/// Check a move from `from` to `next` for a request with `intent`: the state
+/// diagram must have the edge and `next` must suit the intent. Staying in the
+/// same status is always allowed.
+pub fn check_transition(
+    from: ServiceRequestStatus,
+    next: ServiceRequestStatus,
+    intent: ServiceRequestIntent,
+) -> Result<(), LifecycleError> {
+    if from == next {
+        return Ok(());
+    }
+    if !from.can_transition_to(next) {
+        return Err(LifecycleError::IllegalTransition { from, to: next });
+    }
+    check_status_intent(next, intent)
+}
+
+/// One accepted status change recorded on the aggregate.
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub from: ServiceRequestStatus,
+    pub to: ServiceRequestStatus,
+}
+
 /// Core "order" aggregate in DFPS, similar to a FHIR ServiceRequest.
 ///
 /// # Examples
@@ -64,6 +208,29 @@ pub enum ServiceRequestIntent {
 /// );
 /// assert_eq!(sr.status, ServiceRequestStatus::Active);
 /// ```
+///
+/// Status changes go through the lifecycle state machine:
+///
+/// ```
+/// use dfps_core::order::{LifecycleError, ServiceRequest, ServiceRequestStatus};
+/// use dfps_core::value::{PatientId, ServiceRequestId};
+///
+/// let mut sr = ServiceRequest::new_active_order(
+///     ServiceRequestId::new("SR-123"),
+///     PatientId::new("PAT-1"),
+///     None,
+///     "PET/CT staging order",
+/// );
+/// sr.transition_to(ServiceRequestStatus::Completed).unwrap();
+/// assert_eq!(
+///     sr.transition_to(ServiceRequestStatus::Active),
+///     Err(LifecycleError::IllegalTransition {
+///         from: ServiceRequestStatus::Completed,
+///         to: ServiceRequestStatus::Active,
+///     })
+/// );
+/// assert_eq!(sr.status_history.len(), 1);
+/// ```
 #[cfg_attr(feature = "dummy", derive(Dummy))]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServiceRequest {
@@ -76,7 +243,38 @@ pub struct ServiceRequest {
 
     /// A human-readable label or code display.
     pub description: String,
-    // Future: coding(s), categories, reason codes, supportingInfo, etc.
+
+    /// Codes describing what is being requested (`ServiceRequest.code`).
+    #[serde(default)]
+    pub codes: Vec<ClinicalCode>,
+    /// Classification codes (`ServiceRequest.category`).
+    #[serde(default)]
+    pub categories: Vec<ClinicalCode>,
+    /// Coded reasons for the order (`ServiceRequest.reasonCode`).
+    #[serde(default)]
+    pub reason_codes: Vec<ClinicalCode>,
+    /// Resources justifying the order (`ServiceRequest.reasonReference`).
+    #[serde(default)]
+    pub reason_references: Vec<ResourceReference>,
+    /// Who placed the order (`ServiceRequest.requester`).
+    #[serde(default)]
+    pub requester: Option<ResourceReference>,
+    /// When the order was signed (`ServiceRequest.authoredOn`).
+    #[serde(default)]
+    pub authored_on: Option<FhirDateTime>,
+    /// Requested performance window (`ServiceRequest.occurrence[x]`).
+    #[serde(default)]
+    pub occurrence_start: Option<FhirDateTime>,
+    /// End of the window; `None` when the occurrence is a single instant.
+    #[serde(default)]
+    pub occurrence_end: Option<FhirDateTime>,
+    /// Extra clinical context for the order (`ServiceRequest.supportingInfo`).
+    #[serde(default)]
+    pub supporting_info: Vec<ResourceReference>,
+
+    /// Status changes accepted through [`ServiceRequest::transition_to`], oldest first.
+    #[serde(default)]
+    pub status_history: Vec<StatusTransition>,
 }
 
 impl ServiceRequest {
@@ -98,6 +296,16 @@ impl ServiceRequest {
             status,
             intent,
             description: description.into(),
+            codes: Vec::new(),
+            categories: Vec::new(),
+            reason_codes: Vec::new(),
+            reason_references: Vec::new(),
+            requester: None,
+            authored_on: None,
+            occurrence_start: None,
+            occurrence_end: None,
+            supporting_info: Vec::new(),
+            status_history: Vec::new(),
         }
     }
 
@@ -118,9 +326,241 @@ impl ServiceRequest {
         )
     }
 
-    /// Simple status transition helper.
+    /// Builder-style status setter; bypasses the lifecycle checks and history.
+    ///
+    /// Use [`ServiceRequest::transition_to`] for changes to an existing order.
     pub fn with_status(mut self, status: ServiceRequestStatus) -> Self {
         self.status = status;
         self
     }
+
+    /// Move to `next` if the lifecycle and the current intent allow it.
+    ///
+    /// Accepted changes are appended to `status_history`; a same-status call is
+    /// a no-op.
+    pub fn transition_to(&mut self, next: ServiceRequestStatus) -> Result<(), LifecycleError> {
+        check_transition(self.status, next, self.intent)?;
+        if self.status != next {
+            self.status_history.push(StatusTransition {
+                from: self.status,
+                to: next,
+            });
+            self.status = next;
+        }
+        Ok(())
+    }
+
+    /// Replace this order with a newer delivery of the same order.
+    ///
+    /// The revision's status must be reachable from the current one; the
+    /// accumulated history is carried over. On error `self` is left untouched.
+    pub fn apply_revision(&mut self, revision: ServiceRequest) -> Result<(), LifecycleError> {
+        let mut current = self.clone();
+        current.intent = revision.intent;
+        current.transition_to(revision.status)?;
+        *self = ServiceRequest {
+            status_history: current.status_history,
+            ..revision
+        };
+        Ok(())
+    }
+
+    /// Attach the requested procedure codes.
+    pub fn with_codes(mut self, codes: Vec<ClinicalCode>) -> Self {
+        self.codes = codes;
+        self
+    }
+
+    /// Attach category codes.
+    pub fn with_categories(mut self, categories: Vec<ClinicalCode>) -> Self {
+        self.categories = categories;
+        self
+    }
+
+    /// Attach coded reasons and supporting reason references.
+    pub fn with_reasons(
+        mut self,
+        reason_codes: Vec<ClinicalCode>,
+        reason_references: Vec<ResourceReference>,
+    ) -> Self {
+        self.reason_codes = reason_codes;
+        self.reason_references = reason_references;
+        self
+    }
+
+    /// Set who placed the order.
+    pub fn with_requester(mut self, requester: Option<ResourceReference>) -> Self {
+        self.requester = requester;
+        self
+    }
+
+    /// Set when the order was signed.
+    pub fn with_authored_on(mut self, authored_on: Option<FhirDateTime>) -> Self {
+        self.authored_on = authored_on;
+        self
+    }
+
+    /// Set the requested performance window; `end` is `None` for a single instant.
+    pub fn with_occurrence(
+        mut self,
+        start: Option<FhirDateTime>,
+        end: Option<FhirDateTime>,
+    ) -> Self {
+        self.occurrence_start = start;
+        self.occurrence_end = end;
+        self
+    }
+
+    /// Attach references to supporting clinical information.
+    pub fn with_supporting_info(mut self, supporting_info: Vec<ResourceReference>) -> Self {
+        self.supporting_info = supporting_info;
+        self
+    }
+
+    /// True when any order code matches the given system + code pair.
+    pub fn has_code(&self, system: &str, code: &str) -> bool {
+        self.codes.iter().any(|candidate| {
+            candidate.system.as_deref() == Some(system) && candidate.code.as_deref() == Some(code)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(status: ServiceRequestStatus, intent: ServiceRequestIntent) -> ServiceRequest {
+        ServiceRequest::new(
+            ServiceRequestId::new("SR-1"),
+            PatientId::new("PAT-1"),
+            None,
+            status,
+            intent,
+            "test order",
+        )
+    }
+
+    #[test]
+    fn lifecycle_follows_state_diagram() {
+        use ServiceRequestStatus::*;
+
+        assert!(Draft.can_transition_to(Active));
+        assert!(Active.can_transition_to(OnHold));
+        assert!(OnHold.can_transition_to(Active));
+        assert!(Completed.can_transition_to(EnteredInError));
+        assert!(!Completed.can_transition_to(Active));
+        assert!(!Cancelled.can_transition_to(Draft));
+        assert!(!Revoked.can_transition_to(Completed));
+        assert!(!OnHold.can_transition_to(Cancelled));
+        assert!(!Revoked.can_transition_to(EnteredInError));
+        for next in [Draft, Active, OnHold, Completed, Cancelled, Revoked] {
+            assert!(!EnteredInError.can_transition_to(next));
+        }
+    }
+
+    #[test]
+    fn transition_records_history_and_rejects_illegal_moves() {
+        let mut sr = order(ServiceRequestStatus::Draft, ServiceRequestIntent::Plan);
+        sr.transition_to(ServiceRequestStatus::Active).unwrap();
+        sr.transition_to(ServiceRequestStatus::Active).unwrap();
+        sr.transition_to(ServiceRequestStatus::EnteredInError)
+            .unwrap();
+
+        let err = sr.transition_to(ServiceRequestStatus::Active).unwrap_err();
+        assert_eq!(
+            err,
+            LifecycleError::IllegalTransition {
+                from: ServiceRequestStatus::EnteredInError,
+                to: ServiceRequestStatus::Active,
+            }
+        );
+        assert_eq!(sr.status, ServiceRequestStatus::EnteredInError);
+        assert_eq!(
+            sr.status_history,
+            vec![
+                StatusTransition {
+                    from: ServiceRequestStatus::Draft,
+                    to: ServiceRequestStatus::Active,
+                },
+                StatusTransition {
+                    from: ServiceRequestStatus::Active,
+                    to: ServiceRequestStatus::EnteredInError,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn status_intent_combinations_are_checked() {
+        assert!(
+            check_status_intent(ServiceRequestStatus::Draft, ServiceRequestIntent::Order).is_ok()
+        );
+        assert!(
+            check_status_intent(ServiceRequestStatus::Completed, ServiceRequestIntent::Plan)
+                .is_err()
+        );
+        assert!(
+            check_status_intent(
+                ServiceRequestStatus::Completed,
+                ServiceRequestIntent::FillerOrder
+            )
+            .is_ok()
+        );
+
+        let mut proposal = order(ServiceRequestStatus::Active, ServiceRequestIntent::Proposal);
+        assert_eq!(
+            proposal.transition_to(ServiceRequestStatus::Completed),
+            Err(LifecycleError::IncompatibleIntent {
+                status: ServiceRequestStatus::Completed,
+                intent: ServiceRequestIntent::Proposal,
+            })
+        );
+        assert!(proposal.status_history.is_empty());
+
+        let mut draft = order(ServiceRequestStatus::Draft, ServiceRequestIntent::Order);
+        draft
+            .apply_revision(order(
+                ServiceRequestStatus::Draft,
+                ServiceRequestIntent::Order,
+            ))
+            .unwrap();
+        draft.transition_to(ServiceRequestStatus::Active).unwrap();
+        assert_eq!(draft.status_history.len(), 1);
+    }
+
+    #[test]
+    fn revision_keeps_history_and_leaves_self_on_error() {
+        let mut sr = order(ServiceRequestStatus::Active, ServiceRequestIntent::Order);
+        let completed = order(ServiceRequestStatus::Completed, ServiceRequestIntent::Order)
+            .with_authored_on(Some("2024-05-01".parse().unwrap()));
+        sr.apply_revision(completed).unwrap();
+        assert_eq!(
+            sr.authored_on.as_ref().map(FhirDateTime::as_str),
+            Some("2024-05-01")
+        );
+        assert_eq!(sr.status_history.len(), 1);
+
+        let reopened = order(ServiceRequestStatus::Active, ServiceRequestIntent::Order);
+        assert!(sr.apply_revision(reopened).is_err());
+        assert_eq!(sr.status, ServiceRequestStatus::Completed);
+    }
+
+    #[test]
+    fn fhir_codes_roundtrip() {
+        for status in [
+            ServiceRequestStatus::Draft,
+            ServiceRequestStatus::Active,
+            ServiceRequestStatus::OnHold,
+            ServiceRequestStatus::Completed,
+            ServiceRequestStatus::Cancelled,
+            ServiceRequestStatus::Revoked,
+            ServiceRequestStatus::EnteredInError,
+        ] {
+            assert_eq!(
+                ServiceRequestStatus::from_fhir_code(status.as_fhir_code()),
+                Some(status)
+            );
+        }
+        assert_eq!(ServiceRequestStatus::from_fhir_code("bogus"), None);
+    }
 }
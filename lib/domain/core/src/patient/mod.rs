@@ -6,23 +6,102 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::value::PatientId;
+use crate::value::{BusinessIdentifier, FhirDate, FhirDateTime, PatientId};
 
 #[cfg(feature = "dummy")]
 use fake::Dummy;
 
-/// Minimal patient entity.
-///
-/// Intentionally small for now; expand with demographics as needed.
+/// Administrative gender, per FHIR `administrative-gender`.
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdministrativeGender {
+    Male,
+    Female,
+    Other,
+    Unknown,
+}
+
+impl AdministrativeGender {
+    pub fn as_fhir_code(&self) -> &'static str {
+        match self {
+            Self::Male => "male",
+            Self::Female => "female",
+            Self::Other => "other",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Parse a FHIR `administrative-gender` code (case-insensitive).
+    pub fn from_fhir_code(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "male" => Some(Self::Male),
+            "female" => Some(Self::Female),
+            "other" => Some(Self::Other),
+            "unknown" => Some(Self::Unknown),
+            _ => None,
+        }
+    }
+}
+
+/// Patient entity with the identifiers and demographics used for cohorts.
 #[cfg_attr(feature = "dummy", derive(Dummy))]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Patient {
     pub id: PatientId,
-    // Future: MRN, name, birth date, etc.
+    /// Medical record number, when the source marks one.
+    #[serde(default)]
+    pub mrn: Option<BusinessIdentifier>,
+    #[serde(default)]
+    pub identifiers: Vec<BusinessIdentifier>,
+    #[serde(default)]
+    pub gender: Option<AdministrativeGender>,
+    #[serde(default)]
+    pub birth_date: Option<FhirDate>,
+    /// `None` when the source says nothing about vital status.
+    #[serde(default)]
+    pub deceased: Option<bool>,
+    #[serde(default)]
+    pub deceased_at: Option<FhirDateTime>,
 }
 
 impl Patient {
     pub fn new(id: PatientId) -> Self {
-        Self { id }
+        Self {
+            id,
+            mrn: None,
+            identifiers: Vec::new(),
+            gender: None,
+            birth_date: None,
+            deceased: None,
+            deceased_at: None,
+        }
+    }
+
+    pub fn with_mrn(mut self, mrn: Option<BusinessIdentifier>) -> Self {
+        self.mrn = mrn;
+        self
+    }
+
+    pub fn with_identifiers(mut self, identifiers: Vec<BusinessIdentifier>) -> Self {
+        self.identifiers = identifiers;
+        self
+    }
+
+    pub fn with_gender(mut self, gender: Option<AdministrativeGender>) -> Self {
+        self.gender = gender;
+        self
+    }
+
+    pub fn with_birth_date(mut self, birth_date: Option<FhirDate>) -> Self {
+        self.birth_date = birth_date;
+        self
+    }
+
+    /// Record vital status; a date of death implies `deceased = Some(true)`.
+    pub fn with_deceased(mut self, deceased: Option<bool>, at: Option<FhirDateTime>) -> Self {
+        self.deceased = if at.is_some() { Some(true) } else { deceased };
+        self.deceased_at = at;
+        self
     }
 }
@@ -0,0 +1,161 @@
+//! Lineage of staged and mapped rows back to the input they were read from.
+//!
+//! Ingestion stamps a [`SourceProvenance`] on every `StgServiceRequestFlat`
+//! and `StgSrCodeExploded` row; mapping copies it onto the `MappingResult`
+//! and the datamart onto `FactServiceRequest`. [`SourceProvenance::to_fhir`]
+//! renders it as a FHIR `Provenance` for consumers that want resources.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fhir::{self, CodeableConcept, Coding, ProvenanceAgent, ProvenanceEntity, Reference};
+use crate::value::FhirInstant;
+
+#[cfg(feature = "dummy")]
+use fake::Dummy;
+
+/// `Provenance.agent.type` system.
+pub const PARTICIPANT_TYPE_SYSTEM: &str =
+    "http://terminology.hl7.org/CodeSystem/provenance-participant-type";
+
+/// Where a row came from and which pipeline run produced it.
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceProvenance {
+    /// Input file path or URI (`stdin` when piped).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_uri: Option<String>,
+    /// Byte offset in the input where the entry starts (streamed input).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub byte_offset: Option<u64>,
+    /// 1-based input line where the entry, CSV row or HL7 message starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+    /// Position of the Bundle (or HL7 message) in the input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle_index: Option<usize>,
+    /// `Bundle.id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle_id: Option<String>,
+    /// Position of the entry in its Bundle.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_index: Option<usize>,
+    /// `Bundle.entry.fullUrl`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_full_url: Option<String>,
+    pub run_id: String,
+    pub ingested_at: FhirInstant,
+}
+
+impl SourceProvenance {
+    /// Record for `run_id` with no source position yet.
+    pub fn new(run_id: impl Into<String>, ingested_at: FhirInstant) -> Self {
+        Self {
+            source_uri: None,
+            byte_offset: None,
+            line: None,
+            bundle_index: None,
+            bundle_id: None,
+            entry_index: None,
+            entry_full_url: None,
+            run_id: run_id.into(),
+            ingested_at,
+        }
+    }
+
+    /// FHIR `Provenance` of `target`: recorded at `ingested_at`, assembled by
+    /// the run, with the source entry as its `source` entity.
+    pub fn to_fhir(&self, target: Reference) -> fhir::Provenance {
+        let mut provenance = fhir::Provenance::new(vec![target], self.ingested_at.clone());
+        provenance.agent.push(ProvenanceAgent {
+            agent_type: Some(CodeableConcept {
+                coding: vec![Coding {
+                    system: Some(PARTICIPANT_TYPE_SYSTEM.to_string()),
+                    code: Some("assembler".to_string()),
+                    display: Some("Assembler".to_string()),
+                }],
+                text: None,
+            }),
+            who: Reference {
+                reference: None,
+                display: Some(format!("dfps pipeline run {}", self.run_id)),
+            },
+        });
+        provenance.entity.push(ProvenanceEntity {
+            role: "source".to_string(),
+            what: Reference {
+                reference: self.entry_full_url.clone(),
+                display: Some(self.to_string()),
+            },
+        });
+        provenance
+    }
+}
+
+impl fmt::Display for SourceProvenance {
+    /// Source position, e.g. `orders.ndjson bundle 0 (b-1) entry 2 line 3 byte 120`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![self.source_uri.clone().unwrap_or_else(|| "input".into())];
+        if let Some(index) = self.bundle_index {
+            parts.push(format!("bundle {index}"));
+        }
+        if let Some(id) = &self.bundle_id {
+            parts.push(format!("({id})"));
+        }
+        if let Some(index) = self.entry_index {
+            parts.push(format!("entry {index}"));
+        }
+        if let Some(line) = self.line {
+            parts.push(format!("line {line}"));
+        }
+        if let Some(offset) = self.byte_offset {
+            parts.push(format!("byte {offset}"));
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fhir::Resource;
+
+    #[test]
+    fn renders_as_fhir_provenance() {
+        let provenance = SourceProvenance {
+            source_uri: Some("orders.ndjson".into()),
+            byte_offset: Some(120),
+            line: Some(3),
+            bundle_index: Some(0),
+            bundle_id: Some("b-1".into()),
+            entry_index: Some(2),
+            entry_full_url: Some("urn:uuid:sr-1".into()),
+            ..SourceProvenance::new("run-1", "2024-05-01T12:00:00Z".parse().unwrap())
+        };
+        assert_eq!(
+            provenance.to_string(),
+            "orders.ndjson bundle 0 (b-1) entry 2 line 3 byte 120"
+        );
+
+        let resource = provenance.to_fhir(Reference {
+            reference: Some("ServiceRequest/SR-1".into()),
+            display: None,
+        });
+        let value = serde_json::to_value(&resource).unwrap();
+        assert_eq!(value["resourceType"], "Provenance");
+        assert_eq!(value["recorded"], "2024-05-01T12:00:00Z");
+        assert_eq!(value["target"][0]["reference"], "ServiceRequest/SR-1");
+        assert_eq!(value["entity"][0]["what"]["reference"], "urn:uuid:sr-1");
+        assert_eq!(value["agent"][0]["type"]["coding"][0]["code"], "assembler");
+        let decoded = Resource::from_value(value).unwrap();
+        assert_eq!(decoded.as_type::<fhir::Provenance>(), Some(&resource));
+
+        let row: SourceProvenance = serde_json::from_value(serde_json::json!({
+            "run_id": "run-1",
+            "ingested_at": "2024-05-01T12:00:00Z"
+        }))
+        .unwrap();
+        assert_eq!(row.to_string(), "input");
+    }
+}
@@ -6,14 +6,23 @@
 //! interactions in `docs/system-design/fhir/behavior/sequence-servicerequest.md`.
 //! They capture flattened ServiceRequest fields before NCIt/UMLS enrichment.
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::provenance::SourceProvenance;
+use crate::value::{FhirDate, FhirDateTime, FhirInstant, FhirPeriod};
+
 #[cfg(feature = "dummy")]
 use fake::Dummy;
 
 /// Flattened ServiceRequest row (`stg_servicerequest_flat`).
+///
+/// Repeating R4 elements are carried as token lists: codings render as
+/// `system|code` (or the concept text when uncoded), identifiers as
+/// `system|value`, and references as their raw `reference` string.
 #[cfg_attr(feature = "dummy", derive(Dummy))]
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StgServiceRequestFlat {
     pub sr_id: String,
     pub patient_id: String,
@@ -21,7 +30,40 @@ pub struct StgServiceRequestFlat {
     pub status: String,
     pub intent: String,
     pub description: String,
-    pub ordered_at: Option<String>,
+    pub ordered_at: Option<FhirDateTime>,
+    #[serde(default)]
+    pub priority: Option<String>,
+    #[serde(default)]
+    pub occurrence_start: Option<FhirDateTime>,
+    #[serde(default)]
+    pub occurrence_end: Option<FhirDateTime>,
+    #[serde(default)]
+    pub reason_codes: Vec<String>,
+    #[serde(default)]
+    pub reason_references: Vec<String>,
+    #[serde(default)]
+    pub body_sites: Vec<String>,
+    #[serde(default)]
+    pub performers: Vec<String>,
+    #[serde(default)]
+    pub specimens: Vec<String>,
+    #[serde(default)]
+    pub notes: Vec<String>,
+    #[serde(default)]
+    pub identifiers: Vec<String>,
+    #[serde(default)]
+    pub based_on: Vec<String>,
+    #[serde(default)]
+    pub replaces: Vec<String>,
+    #[serde(default)]
+    pub order_details: Vec<String>,
+    /// Site extensions projected into extra columns, keyed by column name
+    /// (see `dfps_ingestion::ExtensionProjection`).
+    #[serde(default)]
+    pub extensions: BTreeMap<String, String>,
+    /// Input position and pipeline run this row was staged from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<SourceProvenance>,
 }
 
 /// Exploded coding row (`stg_sr_code_exploded`) linking back to ServiceRequest.
@@ -32,4 +74,158 @@ pub struct StgSrCodeExploded {
     pub system: Option<String>,
     pub code: Option<String>,
     pub display: Option<String>,
+    /// Provenance of the ServiceRequest row this coding was exploded from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<SourceProvenance>,
+}
+
+/// Flattened Patient row (`stg_patient_flat`).
+///
+/// Identifiers use the same `system|value` token form as
+/// [`StgServiceRequestFlat::identifiers`]; `gender` is the lowercase FHIR code.
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StgPatientFlat {
+    pub patient_id: String,
+    pub mrn: Option<String>,
+    #[serde(default)]
+    pub identifiers: Vec<String>,
+    pub gender: Option<String>,
+    pub birth_date: Option<FhirDate>,
+    pub deceased: Option<bool>,
+    pub deceased_at: Option<FhirDateTime>,
+}
+
+/// Flattened Encounter row (`stg_encounter_flat`).
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StgEncounterFlat {
+    pub encounter_id: String,
+    pub patient_id: Option<String>,
+    pub status: Option<String>,
+    /// `system|code` token for `Encounter.class`.
+    pub class_code: Option<String>,
+    #[serde(default)]
+    pub encounter_types: Vec<String>,
+    pub period_start: Option<FhirDateTime>,
+    pub period_end: Option<FhirDateTime>,
+    pub service_provider: Option<String>,
+    #[serde(default)]
+    pub identifiers: Vec<String>,
+}
+
+impl StgEncounterFlat {
+    /// Rebuild the encounter period from the flattened bounds.
+    pub fn period(&self) -> Option<FhirPeriod> {
+        (self.period_start.is_some() || self.period_end.is_some()).then(|| FhirPeriod {
+            start: self.period_start.clone(),
+            end: self.period_end.clone(),
+        })
+    }
+}
+
+/// Flattened Observation row (`stg_observation_flat`).
+///
+/// `sr_ids` holds the ServiceRequest ids named in `basedOn`, closing the
+/// order-to-result loop; `value` is rendered as text (`7.4 g/mL`,
+/// `system|code`, ...).
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StgObservationFlat {
+    pub observation_id: String,
+    pub patient_id: Option<String>,
+    pub encounter_id: Option<String>,
+    #[serde(default)]
+    pub sr_ids: Vec<String>,
+    pub status: Option<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub effective_start: Option<FhirDateTime>,
+    pub effective_end: Option<FhirDateTime>,
+    pub issued: Option<FhirInstant>,
+    pub value: Option<String>,
+    #[serde(default)]
+    pub interpretations: Vec<String>,
+}
+
+/// Flattened DiagnosticReport row (`stg_diagnostic_report_flat`).
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StgDiagnosticReportFlat {
+    pub report_id: String,
+    pub patient_id: Option<String>,
+    pub encounter_id: Option<String>,
+    #[serde(default)]
+    pub sr_ids: Vec<String>,
+    pub status: Option<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub effective_start: Option<FhirDateTime>,
+    pub effective_end: Option<FhirDateTime>,
+    pub issued: Option<FhirInstant>,
+    /// Observation references listed in `result`.
+    #[serde(default)]
+    pub results: Vec<String>,
+    #[serde(default)]
+    pub imaging_studies: Vec<String>,
+    pub conclusion: Option<String>,
+    #[serde(default)]
+    pub conclusion_codes: Vec<String>,
+}
+
+/// Flattened ImagingStudy row (`stg_imaging_study_flat`).
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StgImagingStudyFlat {
+    pub study_id: String,
+    pub patient_id: Option<String>,
+    pub encounter_id: Option<String>,
+    #[serde(default)]
+    pub sr_ids: Vec<String>,
+    pub status: Option<String>,
+    /// `system|code` tokens for study-level modalities (e.g. DICOM `PT`, `CT`).
+    #[serde(default)]
+    pub modalities: Vec<String>,
+    pub started: Option<FhirDateTime>,
+    pub number_of_series: Option<u32>,
+    pub number_of_instances: Option<u32>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub series_uids: Vec<String>,
+}
+
+/// Exploded coding row for result resources (`stg_result_code_exploded`).
+///
+/// Observation/DiagnosticReport `code` and ImagingStudy `procedureCode`
+/// codings, keyed by the result and carrying the originating order ids.
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StgResultCodeExploded {
+    pub resource_type: String,
+    pub resource_id: String,
+    #[serde(default)]
+    pub sr_ids: Vec<String>,
+    pub system: Option<String>,
+    pub code: Option<String>,
+    pub display: Option<String>,
+}
+
+impl StgResultCodeExploded {
+    /// `ResourceType/id` key of the result this coding belongs to.
+    pub fn result_key(&self) -> String {
+        format!("{}/{}", self.resource_type, self.resource_id)
+    }
+
+    /// View the coding as an order-style code row keyed by [`Self::result_key`],
+    /// so it can flow through the ServiceRequest mapping path without colliding
+    /// with the order's own code elements.
+    pub fn to_code_row(&self) -> StgSrCodeExploded {
+        StgSrCodeExploded {
+            sr_id: self.result_key(),
+            system: self.system.clone(),
+            code: self.code.clone(),
+            display: self.display.clone(),
+            provenance: None,
+        }
+    }
 }
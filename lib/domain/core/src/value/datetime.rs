@@ -0,0 +1,779 @@
+//! FHIR temporal primitives (`date`, `dateTime`, `instant`) and `Period`.
+//!
+//! Values keep the exact lexical form they were parsed from, so partial
+//! precision (`2024`, `2024-05`) and offsets (`+02:00` vs `Z`) round-trip
+//! unchanged, while [`FhirDateTime::to_utc`] gives a comparable point in time.
+//! Partial dates carry no offset and are read as UTC.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[cfg(feature = "dummy")]
+use fake::{Dummy, Faker, rand::Rng};
+
+/// How much of a date/time value was supplied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateTimePrecision {
+    Year,
+    Month,
+    Day,
+    /// Full time of day (`hh:mm:ss`, optionally with fractional seconds).
+    Second,
+}
+
+/// Error raised when text does not match the FHIR temporal grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateTimeParseError {
+    pub input: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for DateTimeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid FHIR date/time '{}': {}",
+            self.input, self.reason
+        )
+    }
+}
+
+impl std::error::Error for DateTimeParseError {}
+
+/// Point on the UTC timeline (seconds + nanoseconds since the Unix epoch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UtcTimestamp {
+    pub seconds: i64,
+    pub nanos: u32,
+}
+
+impl UtcTimestamp {
+    /// Current time from the system clock.
+    pub fn now() -> Self {
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            seconds: elapsed.as_secs() as i64,
+            nanos: elapsed.subsec_nanos(),
+        }
+    }
+
+    /// Calendar date of this instant in UTC.
+    pub fn date(&self) -> FhirDate {
+        let (year, month, day) = civil_from_days(self.seconds.div_euclid(86_400));
+        FhirDate::from_parts(year as u16, Some(month), Some(day))
+    }
+}
+
+impl fmt::Display for UtcTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = civil_from_days(self.seconds.div_euclid(86_400));
+        let secs = self.seconds.rem_euclid(86_400);
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+            secs / 3600,
+            (secs % 3600) / 60,
+            secs % 60
+        )?;
+        if self.nanos > 0 {
+            let fraction = format!("{:09}", self.nanos);
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        f.write_str("Z")
+    }
+}
+
+/// FHIR `date`: `YYYY`, `YYYY-MM` or `YYYY-MM-DD`, without a time zone.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FhirDate {
+    text: String,
+    year: u16,
+    month: Option<u8>,
+    day: Option<u8>,
+}
+
+impl FhirDate {
+    pub fn parse(input: &str) -> Result<Self, DateTimeParseError> {
+        let mut cursor = Cursor::new(input);
+        let (year, month, day) = cursor.date()?;
+        cursor.finish()?;
+        Ok(Self {
+            text: input.to_string(),
+            year,
+            month,
+            day,
+        })
+    }
+
+    fn from_parts(year: u16, month: Option<u8>, day: Option<u8>) -> Self {
+        let mut text = format!("{year:04}");
+        if let Some(month) = month {
+            text.push_str(&format!("-{month:02}"));
+            if let Some(day) = day {
+                text.push_str(&format!("-{day:02}"));
+            }
+        }
+        Self {
+            text,
+            year,
+            month,
+            day,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> Option<u8> {
+        self.month
+    }
+
+    pub fn day(&self) -> Option<u8> {
+        self.day
+    }
+
+    pub fn precision(&self) -> DateTimePrecision {
+        match (self.month, self.day) {
+            (None, _) => DateTimePrecision::Year,
+            (Some(_), None) => DateTimePrecision::Month,
+            (Some(_), Some(_)) => DateTimePrecision::Day,
+        }
+    }
+
+    /// Start of the date interval, read as UTC midnight.
+    pub fn to_utc(&self) -> UtcTimestamp {
+        UtcTimestamp {
+            seconds: days_from_civil(
+                i64::from(self.year),
+                self.month.unwrap_or(1),
+                self.day.unwrap_or(1),
+            ) * 86_400,
+            nanos: 0,
+        }
+    }
+
+    /// The date `days` calendar days later (earlier when negative), keeping
+    /// any text after the date (time and zone). Year and year-month values
+    /// name no day and are returned unchanged, as are shifts leaving years
+    /// `0000`-`9999`.
+    pub fn shift_days(&self, days: i64) -> Self {
+        let (Some(month), Some(day)) = (self.month, self.day) else {
+            return self.clone();
+        };
+        let (year, month, day) =
+            civil_from_days(days_from_civil(i64::from(self.year), month, day) + days);
+        if !(0..=9999).contains(&year) {
+            return self.clone();
+        }
+        Self {
+            text: format!("{year:04}-{month:02}-{day:02}{}", &self.text[10..]),
+            year: year as u16,
+            month: Some(month),
+            day: Some(day),
+        }
+    }
+}
+
+/// Time-of-day portion of a full-precision `dateTime`/`instant`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct TimeOfDay {
+    hour: u8,
+    minute: u8,
+    second: u8,
+    nanos: u32,
+    /// Offset from UTC in minutes (`Z` is 0).
+    offset_minutes: i16,
+}
+
+/// FHIR `dateTime`: a partial date or a full timestamp with a time zone.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FhirDateTime {
+    date: FhirDate,
+    time: Option<TimeOfDay>,
+}
+
+impl FhirDateTime {
+    pub fn parse(input: &str) -> Result<Self, DateTimeParseError> {
+        let mut cursor = Cursor::new(input);
+        let (year, month, day) = cursor.date()?;
+        let time = if cursor.eat(b'T') {
+            if day.is_none() {
+                return Err(cursor.error("a time requires a full date"));
+            }
+            Some(cursor.time()?)
+        } else {
+            None
+        };
+        cursor.finish()?;
+        Ok(Self {
+            date: FhirDate {
+                text: input.to_string(),
+                year,
+                month,
+                day,
+            },
+            time,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.date.text
+    }
+
+    pub fn precision(&self) -> DateTimePrecision {
+        match self.time {
+            Some(_) => DateTimePrecision::Second,
+            None => self.date.precision(),
+        }
+    }
+
+    /// Offset from UTC in minutes, when a time (and therefore a zone) is present.
+    pub fn offset_minutes(&self) -> Option<i16> {
+        self.time.map(|time| time.offset_minutes)
+    }
+
+    /// Start of the interval described by this value, normalized to UTC.
+    pub fn to_utc(&self) -> UtcTimestamp {
+        let midnight = self.date.to_utc();
+        match self.time {
+            None => midnight,
+            Some(time) => UtcTimestamp {
+                seconds: midnight.seconds
+                    + i64::from(time.hour) * 3600
+                    + i64::from(time.minute) * 60
+                    + i64::from(time.second)
+                    - i64::from(time.offset_minutes) * 60,
+                nanos: time.nanos,
+            },
+        }
+    }
+
+    /// Exclusive end of the interval (e.g. `2024-05` ends at `2024-06-01T00:00:00Z`).
+    pub fn utc_end(&self) -> UtcTimestamp {
+        let start = self.to_utc();
+        let FhirDate {
+            year, month, day, ..
+        } = &self.date;
+        let seconds = match (self.time, month, day) {
+            (Some(_), _, _) => return next_instant(start),
+            (None, Some(_), Some(_)) => start.seconds + 86_400,
+            (None, Some(month), None) => {
+                let (year, month) = if *month == 12 {
+                    (i64::from(*year) + 1, 1)
+                } else {
+                    (i64::from(*year), month + 1)
+                };
+                days_from_civil(year, month, 1) * 86_400
+            }
+            (None, None, _) => days_from_civil(i64::from(*year) + 1, 1, 1) * 86_400,
+        };
+        UtcTimestamp { seconds, nanos: 0 }
+    }
+
+    /// Calendar date in UTC, when the value is precise to at least a day.
+    pub fn utc_date(&self) -> Option<FhirDate> {
+        (self.precision() >= DateTimePrecision::Day).then(|| self.to_utc().date())
+    }
+
+    /// See [`FhirDate::shift_days`]; the time of day and zone are kept.
+    pub fn shift_days(&self, days: i64) -> Self {
+        Self {
+            date: self.date.shift_days(days),
+            time: self.time,
+        }
+    }
+}
+
+impl Ord for FhirDateTime {
+    /// Orders by UTC start, then by precision (coarser first), then lexically,
+    /// so sorting is total and deterministic across mixed precisions and zones.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_utc()
+            .cmp(&other.to_utc())
+            .then_with(|| self.precision().cmp(&other.precision()))
+            .then_with(|| self.as_str().cmp(other.as_str()))
+    }
+}
+
+impl PartialOrd for FhirDateTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<FhirDate> for FhirDateTime {
+    fn from(date: FhirDate) -> Self {
+        Self { date, time: None }
+    }
+}
+
+/// FHIR `instant`: a full timestamp with seconds and a time zone.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FhirInstant(FhirDateTime);
+
+impl FhirInstant {
+    pub fn parse(input: &str) -> Result<Self, DateTimeParseError> {
+        let value = FhirDateTime::parse(input)?;
+        if value.time.is_none() {
+            return Err(DateTimeParseError {
+                input: input.to_string(),
+                reason: "an instant requires a time and zone",
+            });
+        }
+        Ok(Self(value))
+    }
+
+    /// Current time in UTC (`Z`), from the system clock.
+    pub fn now() -> Self {
+        Self::parse(&UtcTimestamp::now().to_string()).expect("the clock is within 0000-9999")
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn to_utc(&self) -> UtcTimestamp {
+        self.0.to_utc()
+    }
+
+    pub fn as_date_time(&self) -> &FhirDateTime {
+        &self.0
+    }
+}
+
+impl From<FhirInstant> for FhirDateTime {
+    fn from(value: FhirInstant) -> Self {
+        value.0
+    }
+}
+
+/// FHIR `Period` with inclusive `start`/`end` boundaries.
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FhirPeriod {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<FhirDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<FhirDateTime>,
+}
+
+impl FhirPeriod {
+    /// False when both bounds are present and `end` finishes before `start` begins.
+    pub fn is_well_ordered(&self) -> bool {
+        match (&self.start, &self.end) {
+            (Some(start), Some(end)) => end.utc_end() > start.to_utc(),
+            _ => true,
+        }
+    }
+
+    /// Whether `value` overlaps the period; open bounds are unbounded.
+    pub fn contains(&self, value: &FhirDateTime) -> bool {
+        let after_start = self
+            .start
+            .as_ref()
+            .is_none_or(|start| value.utc_end() > start.to_utc());
+        let before_end = self
+            .end
+            .as_ref()
+            .is_none_or(|end| value.to_utc() < end.utc_end());
+        after_start && before_end
+    }
+}
+
+macro_rules! string_value {
+    ($ty:ty) => {
+        impl FromStr for $ty {
+            type Err = DateTimeParseError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::parse(s)
+            }
+        }
+
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let raw = String::deserialize(deserializer)?;
+                Self::parse(&raw).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+string_value!(FhirDate);
+string_value!(FhirDateTime);
+string_value!(FhirInstant);
+
+#[cfg(feature = "dummy")]
+impl Dummy<Faker> for FhirDate {
+    fn dummy_with_rng<R: Rng + ?Sized>(_: &Faker, rng: &mut R) -> Self {
+        Self::from_parts(
+            rng.random_range(1930..=2024),
+            Some(rng.random_range(1..=12)),
+            Some(rng.random_range(1..=28)),
+        )
+    }
+}
+
+#[cfg(feature = "dummy")]
+impl Dummy<Faker> for FhirDateTime {
+    fn dummy_with_rng<R: Rng + ?Sized>(_: &Faker, rng: &mut R) -> Self {
+        let text = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            rng.random_range(2000..=2030),
+            rng.random_range(1..=12),
+            rng.random_range(1..=28),
+            rng.random_range(0..24),
+            rng.random_range(0..60),
+            rng.random_range(0..60),
+        );
+        Self::parse(&text).expect("generated dateTime is valid")
+    }
+}
+
+#[cfg(feature = "dummy")]
+impl Dummy<Faker> for FhirInstant {
+    fn dummy_with_rng<R: Rng + ?Sized>(config: &Faker, rng: &mut R) -> Self {
+        // Generated dateTimes always carry a time and `Z`, so they are instants.
+        Self(FhirDateTime::dummy_with_rng(config, rng))
+    }
+}
+
+struct Cursor<'a> {
+    input: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            bytes: input.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn error(&self, reason: &'static str) -> DateTimeParseError {
+        DateTimeParseError {
+            input: self.input.to_string(),
+            reason,
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.bytes.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, byte: u8, reason: &'static str) -> Result<(), DateTimeParseError> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(self.error(reason))
+        }
+    }
+
+    fn digits(&mut self, count: usize, reason: &'static str) -> Result<u32, DateTimeParseError> {
+        let end = self.pos + count;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .filter(|slice| slice.iter().all(u8::is_ascii_digit))
+            .ok_or_else(|| self.error(reason))?;
+        self.pos = end;
+        Ok(slice
+            .iter()
+            .fold(0, |acc, digit| acc * 10 + u32::from(digit - b'0')))
+    }
+
+    fn date(&mut self) -> Result<(u16, Option<u8>, Option<u8>), DateTimeParseError> {
+        let year = self.digits(4, "expected a four-digit year")?;
+        if year == 0 {
+            return Err(self.error("year 0000 is not allowed"));
+        }
+        if !self.eat(b'-') {
+            return Ok((year as u16, None, None));
+        }
+        let month = self.digits(2, "expected a two-digit month")?;
+        if !(1..=12).contains(&month) {
+            return Err(self.error("month out of range"));
+        }
+        if !self.eat(b'-') {
+            return Ok((year as u16, Some(month as u8), None));
+        }
+        let day = self.digits(2, "expected a two-digit day")?;
+        if day == 0 || day > u32::from(days_in_month(year as u16, month as u8)) {
+            return Err(self.error("day out of range"));
+        }
+        Ok((year as u16, Some(month as u8), Some(day as u8)))
+    }
+
+    fn time(&mut self) -> Result<TimeOfDay, DateTimeParseError> {
+        let hour = self.digits(2, "expected a two-digit hour")?;
+        self.expect(b':', "expected ':' after hour")?;
+        let minute = self.digits(2, "expected two-digit minutes")?;
+        self.expect(b':', "expected ':' after minutes")?;
+        let second = self.digits(2, "expected two-digit seconds")?;
+        if hour > 23 || minute > 59 || second > 60 {
+            return Err(self.error("time out of range"));
+        }
+
+        let mut nanos = 0u32;
+        if self.eat(b'.') {
+            let start = self.pos;
+            while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+                self.pos += 1;
+            }
+            let fraction = &self.input[start..self.pos];
+            if fraction.is_empty() {
+                return Err(self.error("expected fractional seconds after '.'"));
+            }
+            let padded = format!("{:0<9}", &fraction[..fraction.len().min(9)]);
+            nanos = padded.parse().map_err(|_| self.error("bad fraction"))?;
+        }
+
+        let offset_minutes = if self.eat(b'Z') {
+            0
+        } else {
+            let sign = match self.bytes.get(self.pos) {
+                Some(b'+') => 1,
+                Some(b'-') => -1,
+                _ => return Err(self.error("a time requires a zone (Z or +hh:mm)")),
+            };
+            self.pos += 1;
+            let hours = self.digits(2, "expected two-digit zone hours")?;
+            self.expect(b':', "expected ':' in zone offset")?;
+            let minutes = self.digits(2, "expected two-digit zone minutes")?;
+            if minutes > 59 || hours > 14 || (hours == 14 && minutes > 0) {
+                return Err(self.error("zone offset out of range"));
+            }
+            sign * (hours * 60 + minutes) as i16
+        };
+
+        Ok(TimeOfDay {
+            hour: hour as u8,
+            minute: minute as u8,
+            second: second as u8,
+            nanos,
+            offset_minutes,
+        })
+    }
+
+    fn finish(&self) -> Result<(), DateTimeParseError> {
+        if self.pos == self.bytes.len() {
+            Ok(())
+        } else {
+            Err(self.error("unexpected trailing characters"))
+        }
+    }
+}
+
+fn next_instant(value: UtcTimestamp) -> UtcTimestamp {
+    if value.nanos == 999_999_999 {
+        UtcTimestamp {
+            seconds: value.seconds + 1,
+            nanos: 0,
+        }
+    } else {
+        UtcTimestamp {
+            seconds: value.seconds,
+            nanos: value.nanos + 1,
+        }
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_precision_roundtrips_losslessly() {
+        for text in [
+            "2024",
+            "2024-05",
+            "2024-05-01",
+            "2024-05-01T12:00:00Z",
+            "2024-05-01T12:00:00.120+02:00",
+            "2024-05-01T23:59:60-05:30",
+        ] {
+            let value: FhirDateTime = text.parse().expect(text);
+            assert_eq!(value.to_string(), text);
+            let json = serde_json::to_value(&value).unwrap();
+            assert_eq!(json, serde_json::json!(text));
+            assert_eq!(serde_json::from_value::<FhirDateTime>(json).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn rejects_values_outside_the_grammar() {
+        for text in [
+            "24-05-01",
+            "2024-13",
+            "2023-02-29",
+            "2024-05-01T12:00",
+            "2024-05-01T12:00:00",
+            "2024-05T12:00:00Z",
+            "2024-05-01T12:00:00+15:00",
+            "2024-05-01 ",
+        ] {
+            assert!(FhirDateTime::parse(text).is_err(), "{text} should fail");
+        }
+        assert!(FhirDate::parse("2024-05-01T12:00:00Z").is_err());
+        assert!(FhirInstant::parse("2024-05-01").is_err());
+        assert!(FhirInstant::parse("2024-05-01T00:00:00.5Z").is_ok());
+    }
+
+    #[test]
+    fn converts_offsets_to_utc() {
+        let local: FhirDateTime = "2024-05-01T01:30:00+02:00".parse().unwrap();
+        assert_eq!(local.to_utc().to_string(), "2024-04-30T23:30:00Z");
+        assert_eq!(local.utc_date().unwrap().as_str(), "2024-04-30");
+        assert_eq!(local.offset_minutes(), Some(120));
+
+        let fractional: FhirDateTime = "2024-05-01T00:00:00.25Z".parse().unwrap();
+        assert_eq!(fractional.to_utc().nanos, 250_000_000);
+        assert_eq!(fractional.to_utc().to_string(), "2024-05-01T00:00:00.25Z");
+
+        let month: FhirDateTime = "2024-12".parse().unwrap();
+        assert_eq!(month.to_utc().to_string(), "2024-12-01T00:00:00Z");
+        assert_eq!(month.utc_end().to_string(), "2025-01-01T00:00:00Z");
+        assert_eq!(month.utc_date(), None);
+    }
+
+    #[test]
+    fn ordering_uses_utc_then_precision() {
+        let mut values: Vec<FhirDateTime> = [
+            "2024-05-01T12:00:00Z",
+            "2024-05-01T13:00:00+02:00",
+            "2024-05",
+            "2024-05-01",
+            "2023",
+        ]
+        .iter()
+        .map(|text| text.parse().unwrap())
+        .collect();
+        values.sort();
+        let sorted: Vec<&str> = values.iter().map(FhirDateTime::as_str).collect();
+        assert_eq!(
+            sorted,
+            vec![
+                "2023",
+                "2024-05",
+                "2024-05-01",
+                "2024-05-01T13:00:00+02:00",
+                "2024-05-01T12:00:00Z",
+            ]
+        );
+    }
+
+    #[test]
+    fn period_bounds_and_containment() {
+        let period: FhirPeriod = serde_json::from_value(
+            serde_json::json!({ "start": "2024-05-02", "end": "2024-05-03" }),
+        )
+        .unwrap();
+        assert!(period.is_well_ordered());
+        assert!(period.contains(&"2024-05-03T23:00:00Z".parse().unwrap()));
+        assert!(!period.contains(&"2024-05-04".parse().unwrap()));
+
+        let inverted = FhirPeriod {
+            start: Some("2024-05-03".parse().unwrap()),
+            end: Some("2024-05-02".parse().unwrap()),
+        };
+        assert!(!inverted.is_well_ordered());
+    }
+
+    #[test]
+    fn shifting_days_keeps_time_zone_and_partial_dates() {
+        let value: FhirDateTime = "2024-02-28T23:30:00.5+02:00".parse().unwrap();
+        let shifted = value.shift_days(2);
+        assert_eq!(shifted.as_str(), "2024-03-01T23:30:00.5+02:00");
+        assert_eq!(shifted, "2024-03-01T23:30:00.5+02:00".parse().unwrap());
+        assert_eq!(
+            FhirDate::parse("2025-01-03")
+                .unwrap()
+                .shift_days(-3)
+                .as_str(),
+            "2024-12-31"
+        );
+        let month: FhirDateTime = "2024-05".parse().unwrap();
+        assert_eq!(month.shift_days(40), month);
+    }
+
+    #[test]
+    fn civil_day_conversions_agree() {
+        for days in [-719_468, -1, 0, 19_844, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! FHIR `decimal`.
+//!
+//! The value is kept as decimal text rather than a binary float, so it reads
+//! and prints the digits it was given. JSON numbers reach it through
+//! `serde_json`, which decodes integers exactly and fractions as correctly
+//! rounded `f64` (the `float_roundtrip` feature); those keep up to 15
+//! significant digits, without trailing zeros. Decimals sent as strings keep
+//! their exact text.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Error raised when text does not match the FHIR decimal grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecimalParseError {
+    pub input: String,
+}
+
+impl fmt::Display for DecimalParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid FHIR decimal '{}'", self.input)
+    }
+}
+
+impl std::error::Error for DecimalParseError {}
+
+/// FHIR `decimal`: `-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FhirDecimal(String);
+
+impl FhirDecimal {
+    pub fn parse(input: &str) -> Result<Self, DecimalParseError> {
+        if is_decimal(input) {
+            Ok(Self(input.to_string()))
+        } else {
+            Err(DecimalParseError {
+                input: input.to_string(),
+            })
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Nearest `f64`, for arithmetic.
+    pub fn to_f64(&self) -> f64 {
+        self.0.parse().unwrap_or_default()
+    }
+}
+
+fn is_decimal(input: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let unsigned = input.strip_prefix('-').unwrap_or(input);
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (unsigned, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+    digits(integer)
+        && (integer == "0" || !integer.starts_with('0'))
+        && fraction.is_none_or(digits)
+        && exponent
+            .is_none_or(|exponent| digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent)))
+}
+
+impl FromStr for FhirDecimal {
+    type Err = DecimalParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for FhirDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<i64> for FhirDecimal {
+    fn from(value: i64) -> Self {
+        Self(value.to_string())
+    }
+}
+
+/// Written as a JSON number: integers exactly, anything else as its nearest
+/// `f64`.
+impl Serialize for FhirDecimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0.parse::<i64>() {
+            Ok(integer) => serializer.serialize_i64(integer),
+            Err(_) => serializer.serialize_f64(self.to_f64()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FhirDecimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DecimalVisitor;
+
+        impl Visitor<'_> for DecimalVisitor {
+            type Value = FhirDecimal;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a decimal number")
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<FhirDecimal, E> {
+                Ok(FhirDecimal(value.to_string()))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<FhirDecimal, E> {
+                Ok(FhirDecimal(value.to_string()))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<FhirDecimal, E> {
+                if !value.is_finite() {
+                    return Err(E::custom(format!("invalid FHIR decimal '{value}'")));
+                }
+                // `Display` gives the shortest text that reads back as `value`.
+                FhirDecimal::parse(&value.to_string()).map_err(E::custom)
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<FhirDecimal, E> {
+                FhirDecimal::parse(value).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keeps_the_digits_it_was_given() {
+        let decode = |value| serde_json::from_value::<FhirDecimal>(value).unwrap();
+        assert_eq!(decode(json!(0.1)).as_str(), "0.1");
+        assert_eq!(decode(json!(123456789.12345)).as_str(), "123456789.12345");
+        assert_eq!(
+            decode(json!(9007199254740993_i64)).as_str(),
+            "9007199254740993"
+        );
+        assert_eq!(decode(json!("1.10")).as_str(), "1.10");
+        assert_eq!(
+            serde_json::from_str::<FhirDecimal>("2.675")
+                .unwrap()
+                .as_str(),
+            "2.675"
+        );
+        assert_eq!(
+            serde_json::to_value(FhirDecimal::from(9007199254740993)).unwrap(),
+            json!(9007199254740993_i64)
+        );
+    }
+
+    #[test]
+    fn rejects_text_outside_the_grammar() {
+        for text in ["", "01", "1.", ".5", "1e", "+1", "1,5", "NaN"] {
+            assert!(FhirDecimal::parse(text).is_err(), "{text} should fail");
+        }
+        for text in ["0", "-0.50", "1e10", "6.02E+23"] {
+            assert!(FhirDecimal::parse(text).is_ok(), "{text} should parse");
+        }
+        assert!(serde_json::from_value::<FhirDecimal>(json!(true)).is_err());
+    }
+}
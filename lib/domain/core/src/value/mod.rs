@@ -1,11 +1,23 @@
-//! Strongly typed value objects (IDs) for the DFPS domain model.
+//! Strongly typed value objects (IDs, codes, FHIR temporal values) for the
+//! DFPS domain model.
 //!
 //! These types enforce invariants discussed in
 //! `docs/system-design/fhir/models/data-model-er.md` and provide the anchors
 //! for ServiceRequest/Encounter relationships.
 
+mod datetime;
+mod decimal;
+
+pub use datetime::{
+    DateTimeParseError, DateTimePrecision, FhirDate, FhirDateTime, FhirInstant, FhirPeriod,
+    UtcTimestamp,
+};
+pub use decimal::{DecimalParseError, FhirDecimal};
+
 use serde::{Deserialize, Serialize};
 
+use crate::fhir;
+
 #[cfg(feature = "dummy")]
 use fake::Dummy;
 
@@ -25,6 +37,31 @@ pub struct EncounterId(pub String);
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ServiceRequestId(pub String);
 
+/// Code drawn from a terminology (`system` + `code` + optional display).
+///
+/// Domain-side counterpart of FHIR `Coding`, used for order codes, categories
+/// and reasons.
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ClinicalCode {
+    pub system: Option<String>,
+    pub code: Option<String>,
+    pub display: Option<String>,
+}
+
+/// Business identifier (`system` namespace + `value`), e.g. an MRN.
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BusinessIdentifier {
+    pub system: Option<String>,
+    pub value: String,
+}
+
+/// Literal reference to another resource (e.g. `Practitioner/123`).
+#[cfg_attr(feature = "dummy", derive(Dummy))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ResourceReference(pub String);
+
 impl PatientId {
     pub fn new(id: impl Into<String>) -> Self {
         Self(id.into())
@@ -42,3 +79,45 @@ impl ServiceRequestId {
         Self(id.into())
     }
 }
+
+impl ClinicalCode {
+    pub fn new(system: Option<String>, code: Option<String>, display: Option<String>) -> Self {
+        Self {
+            system,
+            code,
+            display,
+        }
+    }
+}
+
+impl From<&fhir::Coding> for ClinicalCode {
+    fn from(value: &fhir::Coding) -> Self {
+        Self::new(
+            value.system.clone(),
+            value.code.clone(),
+            value.display.clone(),
+        )
+    }
+}
+
+impl BusinessIdentifier {
+    pub fn new(system: Option<String>, value: impl Into<String>) -> Self {
+        Self {
+            system,
+            value: value.into(),
+        }
+    }
+}
+
+impl ResourceReference {
+    pub fn new(reference: impl Into<String>) -> Self {
+        Self(reference.into())
+    }
+
+    /// Resource type segment of a `Type/id` reference, if present.
+    pub fn resource_type(&self) -> Option<&str> {
+        let mut segments = self.0.rsplit('/');
+        segments.next()?;
+        segments.next().filter(|segment| !segment.is_empty())
+    }
+}
@@ -7,7 +7,6 @@ use dfps_core::{
     order::{ServiceRequestIntent, ServiceRequestStatus},
 };
 use rand::{Rng, SeedableRng, rng, rngs::StdRng, seq::IndexedRandom};
-use serde_json::to_value;
 
 #[derive(Debug, Clone)]
 struct ProcedureCoding {
@@ -88,9 +87,38 @@ pub fn fake_fhir_patient_with_seed(seed: u64) -> fhir::Patient {
 
 fn fake_fhir_patient_with_rng<R: Rng + ?Sized>(rng: &mut R) -> fhir::Patient {
     let id = fake_patient_id_with_rng(rng).0;
+    let gender = ["male", "female", "other", "unknown"]
+        .choose(rng)
+        .copied()
+        .unwrap_or("unknown");
+    let birth_date = format!(
+        "{:04}-{:02}-{:02}",
+        rng.random_range(1930..=2010),
+        rng.random_range(1..=12),
+        rng.random_range(1..=28)
+    );
     fhir::Patient {
-        resource_type: "Patient".into(),
+        identifier: vec![fhir::Identifier {
+            identifier_type: Some(fhir::CodeableConcept {
+                coding: vec![fhir::Coding {
+                    system: Some("http://terminology.hl7.org/CodeSystem/v2-0203".into()),
+                    code: Some(fhir::MRN_IDENTIFIER_TYPE.into()),
+                    display: Some("Medical record number".into()),
+                }],
+                text: None,
+            }),
+            system: Some("urn:dfps:mrn".into()),
+            value: Some(format!("MRN-{id}")),
+            ..Default::default()
+        }],
         id: Some(id),
+        gender: Some(gender.into()),
+        birth_date: Some(
+            birth_date
+                .parse()
+                .expect("generated birthDate is a valid date"),
+        ),
+        ..Default::default()
     }
 }
 
@@ -105,13 +133,31 @@ pub fn fake_fhir_encounter_for_with_seed(seed: u64, patient: &fhir::Patient) ->
 }
 
 fn fake_fhir_encounter_for_with_rng<R: Rng + ?Sized>(
-    _patient: &fhir::Patient,
+    patient: &fhir::Patient,
     rng: &mut R,
 ) -> fhir::Encounter {
     let id = fake_encounter_id_with_rng(rng).0;
     fhir::Encounter {
-        resource_type: "Encounter".into(),
         id: Some(id),
+        status: Some("finished".into()),
+        class: Some(fhir::Coding {
+            system: Some("http://terminology.hl7.org/CodeSystem/v3-ActCode".into()),
+            code: Some("AMB".into()),
+            display: Some("ambulatory".into()),
+        }),
+        subject: patient.id.as_ref().map(|id| fhir::Reference {
+            reference: Some(format!("Patient/{id}")),
+            display: None,
+        }),
+        period: Some(fhir::Period {
+            start: Some(
+                "2024-05-01T08:00:00Z"
+                    .parse()
+                    .expect("static period start is valid"),
+            ),
+            end: None,
+        }),
+        ..Default::default()
     }
 }
 
@@ -147,7 +193,6 @@ fn fake_fhir_servicerequest_with_rng<R: Rng + ?Sized>(
         .unwrap_or_else(|| "PET/CT procedure".to_string());
 
     fhir::ServiceRequest {
-        resource_type: "ServiceRequest".into(),
         id: Some(id),
         status: Some(status_to_fhir(status).into()),
         intent: Some(intent_to_fhir(intent).into()),
@@ -172,7 +217,13 @@ fn fake_fhir_servicerequest_with_rng<R: Rng + ?Sized>(
             text: None,
         }],
         description: Some("PET/CT order from fake data".into()),
-        authored_on: Some("2024-05-01T12:00:00Z".into()),
+        authored_on: Some(
+            "2024-05-01T12:00:00Z"
+                .parse()
+                .expect("static authoredOn is a valid dateTime"),
+        ),
+        priority: Some("routine".into()),
+        ..Default::default()
     }
 }
 
@@ -192,14 +243,15 @@ fn fake_fhir_bundle_scenario_with_rng<R: Rng + ?Sized>(rng: &mut R) -> FhirBundl
     let service_request = fake_fhir_servicerequest_with_rng(&patient, Some(&encounter), rng);
 
     let entries = vec![
-        to_entry(&patient),
-        to_entry(&encounter),
-        to_entry(&service_request),
+        to_entry(patient.clone()),
+        to_entry(encounter.clone()),
+        to_entry(service_request.clone()),
     ];
 
     FhirBundleScenario {
         bundle: fhir::Bundle {
             resource_type: "Bundle".into(),
+            id: None,
             bundle_type: Some("collection".into()),
             entry: entries,
         },
@@ -209,13 +261,9 @@ fn fake_fhir_bundle_scenario_with_rng<R: Rng + ?Sized>(rng: &mut R) -> FhirBundl
     }
 }
 
-fn to_entry(resource: &impl serde::Serialize) -> fhir::BundleEntry {
-    fhir::BundleEntry {
-        full_url: None,
-        resource: Some(
-            to_value(resource).expect("fake FHIR resources should serialize to JSON value"),
-        ),
-    }
+fn to_entry(resource: impl Into<fhir::Resource>) -> fhir::BundleEntry {
+    fhir::BundleEntry::from_resource(resource)
+        .expect("fake FHIR resources should serialize to JSON value")
 }
 
 fn sample_procedure_codings<R: Rng + ?Sized>(rng: &mut R) -> Vec<fhir::Coding> {
@@ -0,0 +1,374 @@
+//! FHIR Bulk Data (`$export`) ingestion from a local directory.
+//!
+//! An export is a manifest plus NDJSON files holding one resource per line,
+//! one or more files per resource type. [`BulkExport`] reads it offline:
+//! Patient and Encounter files are indexed up front, then ServiceRequests are
+//! streamed in batches and each batch is wrapped in a `collection` Bundle
+//! together with the Patients and Encounters it references, so staging,
+//! validation and mapping run on it unchanged. Other resource types listed in
+//! the manifest are not joined (see [`BulkExport::unjoined_types`]).
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use dfps_core::fhir::{self, BundleType};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    reference::ParsedReference,
+    stream::{BundleStreamReader, StreamEvent},
+    transforms::IngestionError,
+};
+
+/// Resource types joined into the Bundles built from an export.
+const JOINED_TYPES: [&str; 3] = ["ServiceRequest", "Patient", "Encounter"];
+
+/// Bulk Data export manifest (the `$export` status response body).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkExportManifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<String>,
+    #[serde(default)]
+    pub requires_access_token: bool,
+    #[serde(default)]
+    pub output: Vec<BulkExportFile>,
+    /// OperationOutcome files describing resources the server could not export.
+    #[serde(default)]
+    pub error: Vec<BulkExportFile>,
+}
+
+/// One NDJSON file listed in a manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkExportFile {
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+}
+
+impl BulkExportFile {
+    /// Where the file lives under `root`: relative URLs are joined as-is,
+    /// absolute URLs by their last path segment (exports are downloaded flat).
+    pub fn local_path(&self, root: &Path) -> PathBuf {
+        let url = self.url.trim();
+        if !url.contains("://") {
+            return root.join(url);
+        }
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        root.join(path.rsplit('/').next().unwrap_or_default())
+    }
+}
+
+/// A Bulk Data export on disk.
+#[derive(Debug, Clone)]
+pub struct BulkExport {
+    root: PathBuf,
+    manifest: BulkExportManifest,
+}
+
+impl BulkExport {
+    /// Manifest file name looked up when [`Self::open`] is given a directory.
+    pub const MANIFEST_FILE: &'static str = "manifest.json";
+
+    /// Open an export from its directory or its manifest file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, IngestionError> {
+        let path = path.as_ref();
+        let manifest_path = if path.is_dir() {
+            path.join(Self::MANIFEST_FILE)
+        } else {
+            path.to_path_buf()
+        };
+        let file = File::open(&manifest_path)
+            .map_err(|err| export_error(&manifest_path, format!("cannot open manifest: {err}")))?;
+        let manifest: BulkExportManifest = serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| export_error(&manifest_path, format!("invalid manifest: {err}")))?;
+        let root = manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Ok(Self { root, manifest })
+    }
+
+    pub fn manifest(&self) -> &BulkExportManifest {
+        &self.manifest
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resource types in the manifest that are not joined into Bundles.
+    pub fn unjoined_types(&self) -> Vec<&str> {
+        let mut types: Vec<&str> = self
+            .manifest
+            .output
+            .iter()
+            .map(|file| file.resource_type.as_str())
+            .filter(|ty| !JOINED_TYPES.contains(ty))
+            .collect();
+        types.sort_unstable();
+        types.dedup();
+        types
+    }
+
+    /// Stream every resource of `resource_type` across its NDJSON files.
+    pub fn resources(&self, resource_type: &str) -> NdjsonResources {
+        NdjsonResources {
+            files: self
+                .manifest
+                .output
+                .iter()
+                .filter(|file| file.resource_type == resource_type)
+                .map(|file| (file.local_path(&self.root), file.resource_type.clone()))
+                .collect(),
+            current: None,
+        }
+    }
+
+    /// Bundles of up to `batch_size` ServiceRequests, each with the Patients
+    /// and Encounters its orders reference.
+    ///
+    /// Patients and Encounters are read into memory first; ServiceRequests
+    /// are streamed.
+    pub fn service_request_bundles(
+        &self,
+        batch_size: usize,
+    ) -> Result<BulkBundles, IngestionError> {
+        let mut context = HashMap::new();
+        for resource_type in ["Patient", "Encounter"] {
+            for resource in self.resources(resource_type) {
+                let resource = resource?;
+                if let Some(id) = resource.get("id").and_then(Value::as_str) {
+                    context
+                        .entry((resource_type.to_string(), id.to_string()))
+                        .or_insert(resource);
+                }
+            }
+        }
+        Ok(BulkBundles {
+            orders: self.resources("ServiceRequest"),
+            context,
+            batch_size: batch_size.max(1),
+        })
+    }
+}
+
+/// Resources read line by line from NDJSON export files.
+pub struct NdjsonResources {
+    files: VecDeque<(PathBuf, String)>,
+    current: Option<(PathBuf, String, BundleStreamReader<BufReader<File>>)>,
+}
+
+impl Iterator for NdjsonResources {
+    type Item = Result<Value, IngestionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some((path, resource_type, reader)) = self.current.as_mut() else {
+                let (path, resource_type) = self.files.pop_front()?;
+                match File::open(&path) {
+                    Ok(file) => {
+                        let reader = BundleStreamReader::new(BufReader::new(file));
+                        self.current = Some((path, resource_type, reader));
+                        continue;
+                    }
+                    Err(err) => {
+                        return Some(Err(export_error(&path, format!("cannot open: {err}"))));
+                    }
+                }
+            };
+            let Some(event) = reader.next() else {
+                self.current = None;
+                continue;
+            };
+            let record = match event {
+                Ok(StreamEvent::BundleStart(header)) if !header.implicit => {
+                    let err = export_error(
+                        path,
+                        format!(
+                            "record {} is a Bundle; expected one resource per line",
+                            header.index
+                        ),
+                    );
+                    self.current = None;
+                    return Some(Err(err));
+                }
+                Ok(StreamEvent::Entry { bundle, entry, .. }) => (bundle, entry.resource),
+                Ok(_) => continue,
+                Err(err) => {
+                    self.current = None;
+                    return Some(Err(err.into()));
+                }
+            };
+            let (index, Some(resource)) = record else {
+                continue;
+            };
+            let found = resource.get("resourceType").and_then(Value::as_str);
+            if found != Some(resource_type.as_str()) {
+                return Some(Err(export_error(
+                    path,
+                    format!(
+                        "record {index} is a {}, expected {resource_type}",
+                        found.unwrap_or("resource without resourceType")
+                    ),
+                )));
+            }
+            return Some(Ok(resource));
+        }
+    }
+}
+
+/// Iterator over the Bundles built by [`BulkExport::service_request_bundles`].
+pub struct BulkBundles {
+    orders: NdjsonResources,
+    context: HashMap<(String, String), Value>,
+    batch_size: usize,
+}
+
+impl BulkBundles {
+    fn bundle_for(&self, orders: Vec<Value>) -> fhir::Bundle {
+        let mut wanted = BTreeMap::new();
+        for order in &orders {
+            self.collect_references(order, &mut wanted);
+        }
+        // Encounters point at their Patient too.
+        let encounters: Vec<_> = wanted
+            .keys()
+            .filter(|(ty, _)| ty == "Encounter")
+            .filter_map(|key| self.context.get(key))
+            .collect();
+        let mut patients = BTreeMap::new();
+        for encounter in encounters {
+            self.collect_references(encounter, &mut patients);
+        }
+        for (key, base) in patients {
+            wanted.entry(key).or_insert(base);
+        }
+
+        let mut entry: Vec<fhir::BundleEntry> = ["Patient", "Encounter"]
+            .into_iter()
+            .flat_map(|resource_type| {
+                wanted
+                    .iter()
+                    .filter(move |((ty, _), _)| ty == resource_type)
+            })
+            .map(|((ty, id), base)| fhir::BundleEntry {
+                full_url: base.as_ref().map(|base| format!("{base}/{ty}/{id}")),
+                resource: self.context.get(&(ty.clone(), id.clone())).cloned(),
+                ..Default::default()
+            })
+            .collect();
+        entry.extend(orders.into_iter().map(|order| fhir::BundleEntry {
+            resource: Some(order),
+            ..Default::default()
+        }));
+        fhir::Bundle {
+            resource_type: "Bundle".into(),
+            id: None,
+            bundle_type: Some(BundleType::Collection.as_fhir_code().into()),
+            entry,
+        }
+    }
+
+    /// Add the indexed resources referenced from `value`, keyed by
+    /// `(type, id)` with the reference's base URL when it had one.
+    fn collect_references(
+        &self,
+        value: &Value,
+        wanted: &mut BTreeMap<(String, String), Option<String>>,
+    ) {
+        match value {
+            Value::Object(map) => {
+                if let Some(ParsedReference::Path {
+                    base,
+                    resource_type: Some(ty),
+                    id,
+                    ..
+                }) = map
+                    .get("reference")
+                    .and_then(Value::as_str)
+                    .and_then(ParsedReference::parse)
+                {
+                    let key = (ty.to_string(), id.to_string());
+                    if self.context.contains_key(&key) {
+                        wanted.entry(key).or_insert(base.map(str::to_string));
+                    }
+                }
+                for child in map.values() {
+                    self.collect_references(child, wanted);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.collect_references(item, wanted);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Iterator for BulkBundles {
+    type Item = Result<fhir::Bundle, IngestionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut orders = Vec::with_capacity(self.batch_size);
+        while orders.len() < self.batch_size {
+            match self.orders.next() {
+                Some(Ok(order)) => orders.push(order),
+                Some(Err(err)) => return Some(Err(err)),
+                None => break,
+            }
+        }
+        (!orders.is_empty()).then(|| Ok(self.bundle_for(orders)))
+    }
+}
+
+fn export_error(path: &Path, reason: String) -> IngestionError {
+    IngestionError::InvalidExport(format!("{}: {reason}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_files_resolve_under_the_export_root() {
+        let manifest: BulkExportManifest = serde_json::from_value(serde_json::json!({
+            "transactionTime": "2024-05-01T00:00:00Z",
+            "request": "https://fhir.example.org/r4/$export?_type=Patient,ServiceRequest",
+            "requiresAccessToken": true,
+            "output": [
+                { "type": "Patient", "url": "https://fhir.example.org/files/Patient.ndjson?sig=abc", "count": 2 },
+                { "type": "ServiceRequest", "url": "orders/ServiceRequest-1.ndjson" },
+                { "type": "Observation", "url": "Observation.ndjson" }
+            ],
+            "error": []
+        }))
+        .unwrap();
+        let root = Path::new("/exports/run-1");
+
+        assert_eq!(
+            manifest.output[0].local_path(root),
+            root.join("Patient.ndjson")
+        );
+        assert_eq!(
+            manifest.output[1].local_path(root),
+            root.join("orders/ServiceRequest-1.ndjson")
+        );
+        let export = BulkExport {
+            root: root.to_path_buf(),
+            manifest,
+        };
+        assert_eq!(export.unjoined_types(), vec!["Observation"]);
+    }
+}
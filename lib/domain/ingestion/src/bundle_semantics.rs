@@ -0,0 +1,852 @@
+//! `Bundle.type`-aware entry processing.
+//!
+//! Runs before staging and validation and decides, per entry, whether it is
+//! ingested, kept only as reference context, skipped or rejected:
+//!
+//! - `transaction` / `batch`: `entry.request` drives the outcome. `POST`
+//!   creates (honouring `ifNoneExist` against every resource the Bundle
+//!   creates or updates, wherever its entry sits; references to a create that
+//!   matched are pointed at the matched resource), `PUT Type/id` updates,
+//!   `DELETE` and reads are acknowledged but not staged, `PATCH` is rejected.
+//!   A rejected entry aborts a transaction and is dropped from a batch.
+//!   Transaction entries are processed as FHIR orders them (`DELETE`, `POST`,
+//!   `PUT`/`PATCH`, then reads), so the entry a failed transaction reports is
+//!   the first to fail in that order; results keep the input order.
+//! - `searchset`: `search.mode = include` entries are kept so references to them
+//!   resolve, but they do not produce order/result rows; `outcome` entries are
+//!   skipped.
+//! - `document` / `message`: the first entry must be a `Composition` /
+//!   `MessageHeader`.
+//!
+//! Every entry gets a `transaction-response` style [`EntryResult`].
+
+use dfps_core::fhir::{
+    self, BundleEntryResponse, BundleType, HttpVerb, IssueSeverity, IssueType,
+    OperationOutcomeIssue,
+};
+use serde_json::Value;
+
+use std::collections::HashMap;
+
+use crate::{
+    reference::{ParsedReference, strip_history},
+    transforms::IngestionError,
+};
+
+/// What happened to a Bundle entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryOutcome {
+    /// Staged like any other resource.
+    Ingested,
+    /// Kept so references resolve, but not staged as a primary row.
+    Context,
+    /// Acknowledged without staging anything.
+    Skipped,
+    /// Rejected; the response carries an OperationOutcome.
+    Failed,
+}
+
+/// Per-entry processing result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryResult {
+    pub index: usize,
+    pub full_url: Option<String>,
+    pub outcome: EntryOutcome,
+    pub response: BundleEntryResponse,
+}
+
+impl EntryResult {
+    /// Diagnostic text for failed/skipped entries.
+    pub fn reason(&self) -> Option<&str> {
+        self.response
+            .outcome
+            .as_ref()?
+            .pointer("/issue/0/diagnostics")?
+            .as_str()
+    }
+}
+
+/// A Bundle reduced to the entries ingestion should see, plus per-entry results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessedBundle {
+    pub bundle_type: Option<BundleType>,
+    /// Ingested and context entries, in their original order.
+    pub bundle: fhir::Bundle,
+    pub entries: Vec<EntryResult>,
+}
+
+impl ProcessedBundle {
+    /// `transaction-response` (or `batch-response`) Bundle with one
+    /// `entry.response` per input entry.
+    pub fn response_bundle(&self) -> fhir::Bundle {
+        let response_type = match self.bundle_type {
+            Some(BundleType::Batch) => BundleType::BatchResponse,
+            _ => BundleType::TransactionResponse,
+        };
+        fhir::Bundle {
+            resource_type: "Bundle".into(),
+            id: None,
+            bundle_type: Some(response_type.as_fhir_code().into()),
+            entry: self
+                .entries
+                .iter()
+                .map(|result| fhir::BundleEntry {
+                    full_url: result.full_url.clone(),
+                    response: Some(result.response.clone()),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    /// Index in the input Bundle of each entry kept in [`Self::bundle`].
+    pub fn source_indices(&self) -> Vec<usize> {
+        self.entries
+            .iter()
+            .filter(|result| {
+                matches!(
+                    result.outcome,
+                    EntryOutcome::Ingested | EntryOutcome::Context
+                )
+            })
+            .map(|result| result.index)
+            .collect()
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &EntryResult> {
+        self.entries
+            .iter()
+            .filter(|result| result.outcome == EntryOutcome::Failed)
+    }
+}
+
+/// Apply `Bundle.type` semantics to `bundle`.
+///
+/// Errors when a document/message Bundle lacks its required first entry or a
+/// transaction entry is rejected; batch failures are reported per entry.
+pub fn process_bundle(bundle: &fhir::Bundle) -> Result<ProcessedBundle, IngestionError> {
+    process_entries(bundle, true)
+}
+
+/// [`process_bundle`] for a run of entries cut from a larger Bundle; the
+/// document/message first-entry rule only applies when `opens_bundle`.
+pub(crate) fn process_entries(
+    bundle: &fhir::Bundle,
+    opens_bundle: bool,
+) -> Result<ProcessedBundle, IngestionError> {
+    let bundle_type = bundle.kind();
+
+    if opens_bundle
+        && let Some(kind) = bundle_type
+        && let Some(required) = kind.required_first_resource()
+        && bundle
+            .entry
+            .first()
+            .and_then(fhir::BundleEntry::resource_type)
+            != Some(required)
+    {
+        return Err(IngestionError::InvalidBundle {
+            bundle_type: kind.as_fhir_code().into(),
+            reason: format!("the first entry must be a {required}"),
+        });
+    }
+
+    let requests = bundle_type.is_some_and(|kind| kind.requires_request());
+    // Conditional creates wait until every other entry is decided, so
+    // `ifNoneExist` sees what the whole Bundle creates or updates.
+    let mut decisions: Vec<Option<Decision>> = bundle
+        .entry
+        .iter()
+        .map(|entry| match bundle_type {
+            _ if requests && is_conditional_create(entry) => None,
+            Some(kind) if kind.requires_request() => Some(request_decision(entry, &[])),
+            Some(BundleType::Searchset) => Some(searchset_decision(entry)),
+            _ => Some(plain_decision(entry)),
+        })
+        .collect();
+    let mut existing: Vec<fhir::BundleEntry> = bundle
+        .entry
+        .iter()
+        .zip(&decisions)
+        .filter_map(|(entry, decision)| kept_entry(entry, decision.as_ref()?))
+        .collect();
+
+    let mut order: Vec<usize> = (0..bundle.entry.len()).collect();
+    if bundle_type == Some(BundleType::Transaction) {
+        order.sort_by_key(|&index| transaction_step(&bundle.entry[index]));
+    }
+    for index in order {
+        let entry = &bundle.entry[index];
+        let decision = match decisions[index].take() {
+            Some(decision) => decision,
+            None => {
+                let decision = request_decision(entry, &existing);
+                existing.extend(kept_entry(entry, &decision));
+                decision
+            }
+        };
+        if decision.outcome == EntryOutcome::Failed && bundle_type == Some(BundleType::Transaction)
+        {
+            return Err(IngestionError::TransactionFailed {
+                entry: index,
+                reason: decision.reason.unwrap_or_default(),
+            });
+        }
+        decisions[index] = Some(decision);
+    }
+
+    let mut kept: Vec<fhir::BundleEntry> = Vec::new();
+    let mut entries = Vec::with_capacity(bundle.entry.len());
+    let mut aliases = HashMap::new();
+
+    for ((index, entry), decision) in bundle.entry.iter().enumerate().zip(decisions) {
+        let decision = decision.expect("every entry is decided");
+        kept.extend(kept_entry(entry, &decision));
+        if let Some(target) = &decision.alias {
+            for alias in [
+                entry.full_url.as_deref().map(strip_history),
+                location_of_id(entry).as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                aliases.insert(alias.to_string(), target.clone());
+            }
+        }
+        entries.push(EntryResult {
+            index,
+            full_url: entry.full_url.clone(),
+            outcome: decision.outcome,
+            response: BundleEntryResponse {
+                status: decision.status.into(),
+                location: decision.location,
+                outcome: decision
+                    .reason
+                    .as_deref()
+                    .map(|reason| operation_outcome(decision.outcome, decision.issue_type, reason)),
+                ..Default::default()
+            },
+        });
+    }
+
+    if !aliases.is_empty() {
+        for resource in kept.iter_mut().filter_map(|entry| entry.resource.as_mut()) {
+            redirect_references(resource, &aliases);
+        }
+    }
+
+    Ok(ProcessedBundle {
+        bundle_type,
+        bundle: fhir::Bundle {
+            resource_type: bundle.resource_type.clone(),
+            id: bundle.id.clone(),
+            bundle_type: bundle.bundle_type.clone(),
+            entry: kept,
+        },
+        entries,
+    })
+}
+
+/// The entry as staging sees it, when `decision` keeps it.
+fn kept_entry(entry: &fhir::BundleEntry, decision: &Decision) -> Option<fhir::BundleEntry> {
+    if !matches!(
+        decision.outcome,
+        EntryOutcome::Ingested | EntryOutcome::Context
+    ) {
+        return None;
+    }
+    let mut entry = entry.clone();
+    if let (Some(resource), Some(id)) = (entry.resource.as_mut(), decision.assign_id.clone()) {
+        resource["id"] = Value::String(id);
+    }
+    Some(entry)
+}
+
+fn is_conditional_create(entry: &fhir::BundleEntry) -> bool {
+    entry.request.as_ref().is_some_and(|request| {
+        request.verb() == Some(HttpVerb::Post) && request.if_none_exist.is_some()
+    })
+}
+
+/// Position of the entry's interaction in FHIR transaction processing:
+/// deletes, then creates, then updates/patches, then reads. Entries without
+/// a usable request come first, as they fail the transaction anyway.
+fn transaction_step(entry: &fhir::BundleEntry) -> u8 {
+    match entry
+        .request
+        .as_ref()
+        .and_then(fhir::BundleEntryRequest::verb)
+    {
+        None | Some(HttpVerb::Delete) => 0,
+        Some(HttpVerb::Post) => 1,
+        Some(HttpVerb::Put | HttpVerb::Patch) => 2,
+        Some(HttpVerb::Get | HttpVerb::Head) => 3,
+    }
+}
+
+struct Decision {
+    outcome: EntryOutcome,
+    status: &'static str,
+    location: Option<String>,
+    reason: Option<String>,
+    issue_type: IssueType,
+    /// Id taken from a `PUT Type/id` url for a resource that lacks one.
+    assign_id: Option<String>,
+    /// Reference that stands in for this entry's `fullUrl` and `Type/id`.
+    alias: Option<String>,
+}
+
+impl Decision {
+    fn new(outcome: EntryOutcome, status: &'static str) -> Self {
+        Self {
+            outcome,
+            status,
+            location: None,
+            reason: None,
+            issue_type: IssueType::Processing,
+            assign_id: None,
+            alias: None,
+        }
+    }
+
+    fn located(mut self, location: Option<String>) -> Self {
+        self.location = location;
+        self
+    }
+
+    fn because(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    fn failed(status: &'static str, reason: impl Into<String>) -> Self {
+        Self::new(EntryOutcome::Failed, status).because(reason)
+    }
+}
+
+fn plain_decision(entry: &fhir::BundleEntry) -> Decision {
+    match &entry.resource {
+        Some(_) => Decision::new(EntryOutcome::Ingested, "200 OK").located(location_of(entry)),
+        None => Decision::new(EntryOutcome::Skipped, "204 No Content")
+            .because("entry carries no resource"),
+    }
+}
+
+fn searchset_decision(entry: &fhir::BundleEntry) -> Decision {
+    match entry
+        .search
+        .as_ref()
+        .and_then(|search| search.mode.as_deref())
+    {
+        Some("include") => {
+            Decision::new(EntryOutcome::Context, "200 OK").located(location_of(entry))
+        }
+        Some("outcome") => Decision::new(EntryOutcome::Skipped, "200 OK")
+            .because("search outcome entries are not ingested"),
+        _ => plain_decision(entry),
+    }
+}
+
+/// `existing` are the resources a conditional create is matched against.
+fn request_decision(entry: &fhir::BundleEntry, existing: &[fhir::BundleEntry]) -> Decision {
+    let Some(request) = &entry.request else {
+        return Decision::failed(
+            "400 Bad Request",
+            "entry.request is required in transaction and batch bundles",
+        );
+    };
+    let url = request.url.as_deref().unwrap_or_default().trim();
+    let Some(verb) = request.verb() else {
+        return Decision::failed(
+            "400 Bad Request",
+            format!(
+                "unsupported entry.request.method '{}'",
+                request.method.as_deref().unwrap_or_default()
+            ),
+        );
+    };
+
+    match verb {
+        HttpVerb::Get | HttpVerb::Head => Decision {
+            issue_type: IssueType::NotSupported,
+            ..Decision::new(EntryOutcome::Skipped, "204 No Content")
+                .because("read interactions are not executed during ingestion")
+        },
+        HttpVerb::Patch => Decision::failed(
+            "405 Method Not Allowed",
+            "PATCH entries cannot be applied during ingestion",
+        ),
+        HttpVerb::Delete => Decision::new(EntryOutcome::Skipped, "204 No Content")
+            .located(Some(url.to_string()))
+            .because("deletes are acknowledged but not applied to staging"),
+        HttpVerb::Post => post_decision(entry, url, request.if_none_exist.as_deref(), existing),
+        HttpVerb::Put => put_decision(entry, url),
+    }
+}
+
+fn post_decision(
+    entry: &fhir::BundleEntry,
+    url: &str,
+    if_none_exist: Option<&str>,
+    existing: &[fhir::BundleEntry],
+) -> Decision {
+    let Some(resource_type) = entry.resource_type() else {
+        return Decision::failed("400 Bad Request", "POST entry carries no resource");
+    };
+    let target = url.split('?').next().unwrap_or_default().trim_matches('/');
+    if target.rsplit('/').next() != Some(resource_type) {
+        return Decision::failed(
+            "400 Bad Request",
+            format!("POST url '{url}' does not match resourceType {resource_type}"),
+        );
+    }
+
+    if let Some(query) = if_none_exist {
+        let matches = match matching_entries(query, resource_type, existing) {
+            Ok(matches) => matches,
+            Err(reason) => return Decision::failed("400 Bad Request", reason),
+        };
+        match matches.as_slice() {
+            [] => {}
+            [existing] => {
+                return Decision {
+                    alias: existing
+                        .full_url
+                        .as_deref()
+                        .map(|url| strip_history(url).to_string())
+                        .or_else(|| location_of_id(existing)),
+                    ..Decision::new(EntryOutcome::Skipped, "200 OK")
+                        .located(location_of(existing))
+                        .because(format!(
+                            "ifNoneExist '{query}' matched an existing resource"
+                        ))
+                };
+            }
+            _ => {
+                return Decision::failed(
+                    "412 Precondition Failed",
+                    format!("ifNoneExist '{query}' matched more than one resource"),
+                );
+            }
+        }
+    }
+
+    Decision::new(EntryOutcome::Ingested, "201 Created").located(location_of(entry))
+}
+
+fn put_decision(entry: &fhir::BundleEntry, url: &str) -> Decision {
+    let Some(resource_type) = entry.resource_type() else {
+        return Decision::failed("400 Bad Request", "PUT entry carries no resource");
+    };
+    if url.contains('?') {
+        return Decision::failed(
+            "400 Bad Request",
+            "conditional update is not supported during ingestion",
+        );
+    }
+    let Some(ParsedReference::Path {
+        resource_type: Some(url_type),
+        id: url_id,
+        ..
+    }) = ParsedReference::parse(url)
+    else {
+        return Decision::failed(
+            "400 Bad Request",
+            format!("PUT url '{url}' must be of the form Type/id"),
+        );
+    };
+    if url_type != resource_type {
+        return Decision::failed(
+            "400 Bad Request",
+            format!("PUT url '{url}' does not match resourceType {resource_type}"),
+        );
+    }
+
+    let mut decision = match entry.resource_id() {
+        Some(id) if id != url_id => {
+            return Decision::failed(
+                "400 Bad Request",
+                format!("resource id '{id}' does not match PUT url '{url}'"),
+            );
+        }
+        Some(_) => Decision::new(EntryOutcome::Ingested, "200 OK"),
+        None => Decision {
+            assign_id: Some(url_id.to_string()),
+            ..Decision::new(EntryOutcome::Ingested, "200 OK")
+        },
+    };
+    decision.location = Some(format!("{resource_type}/{url_id}"));
+    decision
+}
+
+/// Entries of `resource_type` in `existing` that satisfy a conditional query.
+///
+/// Supports `_id` and `identifier` (`system|value`, `value` or `system|`).
+fn matching_entries<'a>(
+    query: &str,
+    resource_type: &str,
+    existing: &'a [fhir::BundleEntry],
+) -> Result<Vec<&'a fhir::BundleEntry>, String> {
+    let query = query
+        .strip_prefix(resource_type)
+        .and_then(|rest| rest.strip_prefix('?'))
+        .unwrap_or(query);
+    let mut params = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("malformed ifNoneExist parameter '{pair}'"))?;
+        if !matches!(name, "_id" | "identifier") {
+            return Err(format!("unsupported ifNoneExist parameter '{name}'"));
+        }
+        params.push((name, decode_query_value(value)?));
+    }
+
+    Ok(existing
+        .iter()
+        .filter(|entry| entry.resource_type() == Some(resource_type))
+        .filter(|entry| {
+            params.iter().all(|(name, value)| match *name {
+                "_id" => entry.resource_id() == Some(value.as_str()),
+                _ => has_identifier(entry, value),
+            })
+        })
+        .collect())
+}
+
+fn has_identifier(entry: &fhir::BundleEntry, token: &str) -> bool {
+    let (system, value) = match token.split_once('|') {
+        Some((system, value)) => (Some(system), value),
+        None => (None, token),
+    };
+    entry
+        .resource
+        .as_ref()
+        .and_then(|resource| resource.get("identifier"))
+        .and_then(Value::as_array)
+        .is_some_and(|identifiers| {
+            identifiers.iter().any(|identifier| {
+                let field = |key: &str| identifier.get(key).and_then(Value::as_str);
+                system.is_none_or(|system| field("system") == Some(system))
+                    && (value.is_empty() || field("value") == Some(value))
+            })
+        })
+}
+
+/// Percent-decode a query parameter value (`+` is left as is, as FHIR
+/// search does not use form encoding).
+fn decode_query_value(value: &str) -> Result<String, String> {
+    let malformed = || format!("malformed percent-encoding in ifNoneExist value '{value}'");
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte != b'%' {
+            decoded.push(byte);
+            rest = tail;
+            continue;
+        }
+        let hex = tail.get(..2).ok_or_else(malformed)?;
+        let hex = std::str::from_utf8(hex).map_err(|_| malformed())?;
+        decoded.push(u8::from_str_radix(hex, 16).map_err(|_| malformed())?);
+        rest = &tail[2..];
+    }
+    String::from_utf8(decoded).map_err(|_| malformed())
+}
+
+fn location_of(entry: &fhir::BundleEntry) -> Option<String> {
+    location_of_id(entry).or_else(|| entry.full_url.clone())
+}
+
+fn location_of_id(entry: &fhir::BundleEntry) -> Option<String> {
+    Some(format!(
+        "{}/{}",
+        entry.resource_type()?,
+        entry.resource_id()?
+    ))
+}
+
+/// Point every `reference` found in `aliases` at its replacement.
+fn redirect_references(value: &mut Value, aliases: &HashMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::String(reference)) = map.get_mut("reference")
+                && let Some(target) = aliases.get(strip_history(reference))
+            {
+                *reference = target.clone();
+            }
+            for child in map.values_mut() {
+                redirect_references(child, aliases);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redirect_references(item, aliases);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn operation_outcome(outcome: EntryOutcome, issue_type: IssueType, reason: &str) -> Value {
+    let severity = match outcome {
+        EntryOutcome::Failed => IssueSeverity::Error,
+        _ => IssueSeverity::Information,
+    };
+    let outcome = fhir::OperationOutcome::new(vec![OperationOutcomeIssue::new(
+        severity, issue_type, reason,
+    )]);
+    serde_json::to_value(outcome).expect("OperationOutcome serializes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(value: Value) -> fhir::Bundle {
+        serde_json::from_value(value).expect("bundle decodes")
+    }
+
+    fn patient_post(id: &str, mrn: &str, if_none_exist: Option<&str>) -> Value {
+        let mut request = serde_json::json!({ "method": "POST", "url": "Patient" });
+        if let Some(query) = if_none_exist {
+            request["ifNoneExist"] = Value::String(query.into());
+        }
+        serde_json::json!({
+            "fullUrl": format!("urn:uuid:{id}"),
+            "resource": {
+                "resourceType": "Patient",
+                "id": id,
+                "identifier": [{ "system": "urn:mrn", "value": mrn }]
+            },
+            "request": request
+        })
+    }
+
+    #[test]
+    fn batch_honours_requests_and_conditional_create() {
+        let processed = process_bundle(&bundle(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "batch",
+            "entry": [
+                patient_post("p1", "MRN-1", None),
+                patient_post("p1-dup", "MRN-1", Some("identifier=urn:mrn%7CMRN-1")),
+                {
+                    "resource": { "resourceType": "Encounter", "status": "finished" },
+                    "request": { "method": "PUT", "url": "Encounter/e1" }
+                },
+                { "request": { "method": "DELETE", "url": "ServiceRequest/old" } },
+                {
+                    "resource": { "resourceType": "Encounter", "id": "e2" },
+                    "request": { "method": "PUT", "url": "Encounter/other" }
+                }
+            ]
+        })))
+        .expect("batch processes");
+
+        let outcomes: Vec<_> = processed.entries.iter().map(|e| e.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                EntryOutcome::Ingested,
+                EntryOutcome::Skipped,
+                EntryOutcome::Ingested,
+                EntryOutcome::Skipped,
+                EntryOutcome::Failed,
+            ]
+        );
+        assert_eq!(processed.entries[0].response.status, "201 Created");
+        assert_eq!(
+            processed.entries[1].response.location.as_deref(),
+            Some("Patient/p1")
+        );
+        assert_eq!(processed.bundle.entry.len(), 2);
+        assert_eq!(processed.bundle.entry[1].resource_id(), Some("e1"));
+        assert!(
+            processed.entries[4]
+                .reason()
+                .unwrap()
+                .contains("does not match")
+        );
+
+        let response = processed.response_bundle();
+        assert_eq!(response.bundle_type.as_deref(), Some("batch-response"));
+        assert_eq!(response.entry.len(), 5);
+    }
+
+    #[test]
+    fn matched_conditional_creates_redirect_references_and_reads_are_skipped() {
+        let processed = process_bundle(&bundle(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "transaction",
+            "entry": [
+                patient_post("p1", "urn:oid:1.2/MRN-1", None),
+                patient_post("p1-dup", "urn:oid:1.2/MRN-1", Some("Patient?identifier=urn%3Amrn%7Curn%3Aoid%3A1.2%2FMRN-1")),
+                {
+                    "fullUrl": "urn:uuid:sr1",
+                    "resource": {
+                        "resourceType": "ServiceRequest",
+                        "id": "sr1",
+                        "subject": { "reference": "urn:uuid:p1-dup" },
+                        "performer": [{ "reference": "Patient/p1-dup" }]
+                    },
+                    "request": { "method": "POST", "url": "ServiceRequest" }
+                },
+                { "request": { "method": "GET", "url": "Patient/p1" } }
+            ]
+        })))
+        .expect("transaction processes");
+
+        assert_eq!(processed.entries[1].outcome, EntryOutcome::Skipped);
+        let sr = processed.bundle.entry[1].resource.as_ref().unwrap();
+        assert_eq!(sr["subject"]["reference"], "urn:uuid:p1");
+        assert_eq!(sr["performer"][0]["reference"], "urn:uuid:p1");
+
+        let read = &processed.entries[3];
+        assert_eq!(read.outcome, EntryOutcome::Skipped);
+        assert_eq!(read.response.status, "204 No Content");
+        let outcome = read.response.outcome.as_ref().unwrap();
+        assert_eq!(outcome["issue"][0]["code"], "not-supported");
+        assert_eq!(outcome["issue"][0]["severity"], "information");
+    }
+
+    #[test]
+    fn if_none_exist_values_are_percent_decoded() {
+        assert_eq!(
+            decode_query_value("urn%3Aoid%3A1.2%7C%C3%A9+x").as_deref(),
+            Ok("urn:oid:1.2|é+x")
+        );
+        assert!(decode_query_value("bad%2").is_err());
+        assert!(decode_query_value("bad%zz").is_err());
+        assert!(decode_query_value("bad%FF").is_err());
+    }
+
+    #[test]
+    fn transaction_rejects_on_first_failed_entry() {
+        let err = process_bundle(&bundle(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "transaction",
+            "entry": [
+                patient_post("p1", "MRN-1", None),
+                { "resource": { "resourceType": "Patient", "id": "p2" } }
+            ]
+        })))
+        .expect_err("missing request aborts the transaction");
+        assert!(matches!(
+            err,
+            IngestionError::TransactionFailed { entry: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn transaction_entries_run_deletes_then_creates_then_updates_then_reads() {
+        let err = process_bundle(&bundle(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "transaction",
+            "entry": [
+                {
+                    "resource": { "resourceType": "Encounter", "id": "e2" },
+                    "request": { "method": "PUT", "url": "Encounter/other" }
+                },
+                { "request": { "method": "GET", "url": "Patient/p1" } },
+                {
+                    "resource": { "resourceType": "Encounter", "id": "e3" },
+                    "request": { "method": "POST", "url": "Patient" }
+                },
+                { "request": { "method": "DELETE", "url": "ServiceRequest/old" } }
+            ]
+        })))
+        .expect_err("the failing create aborts the transaction");
+        assert!(matches!(
+            err,
+            IngestionError::TransactionFailed { entry: 2, .. }
+        ));
+
+        let put = |id: &str| {
+            serde_json::json!({
+                "resource": {
+                    "resourceType": "Patient",
+                    "id": id,
+                    "identifier": [{ "system": "urn:mrn", "value": "MRN-1" }]
+                },
+                "request": { "method": "PUT", "url": format!("Patient/{id}") }
+            })
+        };
+        let processed = process_bundle(&bundle(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "transaction",
+            "entry": [
+                patient_post("p1-dup", "MRN-1", Some("identifier=urn:mrn%7CMRN-1")),
+                { "request": { "method": "GET", "url": "Patient/p1" } },
+                put("p1")
+            ]
+        })))
+        .expect("transaction processes");
+        let outcomes: Vec<_> = processed.entries.iter().map(|e| e.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                EntryOutcome::Skipped,
+                EntryOutcome::Skipped,
+                EntryOutcome::Ingested
+            ]
+        );
+        assert_eq!(
+            processed.entries[0].response.location.as_deref(),
+            Some("Patient/p1")
+        );
+        assert_eq!(processed.source_indices(), [2]);
+    }
+
+    #[test]
+    fn searchset_keeps_includes_as_context() {
+        let processed = process_bundle(&bundle(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "searchset",
+            "entry": [
+                {
+                    "resource": { "resourceType": "ServiceRequest", "id": "sr1" },
+                    "search": { "mode": "match" }
+                },
+                {
+                    "resource": { "resourceType": "Patient", "id": "p1" },
+                    "search": { "mode": "include" }
+                },
+                {
+                    "resource": { "resourceType": "OperationOutcome" },
+                    "search": { "mode": "outcome" }
+                }
+            ]
+        })))
+        .unwrap();
+
+        assert_eq!(processed.entries[1].outcome, EntryOutcome::Context);
+        assert_eq!(processed.entries[2].outcome, EntryOutcome::Skipped);
+        assert_eq!(processed.bundle.entry.len(), 2);
+    }
+
+    #[test]
+    fn document_and_message_require_their_first_entry() {
+        for (kind, first) in [("document", "Composition"), ("message", "MessageHeader")] {
+            let missing = bundle(serde_json::json!({
+                "resourceType": "Bundle",
+                "type": kind,
+                "entry": [{ "resource": { "resourceType": "Patient", "id": "p1" } }]
+            }));
+            assert!(matches!(
+                process_bundle(&missing),
+                Err(IngestionError::InvalidBundle { .. })
+            ));
+
+            let present = bundle(serde_json::json!({
+                "resourceType": "Bundle",
+                "type": kind,
+                "entry": [
+                    { "resource": { "resourceType": first, "id": "head" } },
+                    { "resource": { "resourceType": "Patient", "id": "p1" } }
+                ]
+            }));
+            assert_eq!(process_bundle(&present).unwrap().bundle.entry.len(), 2);
+        }
+    }
+}
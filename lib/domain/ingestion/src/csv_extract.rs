@@ -0,0 +1,628 @@
+//! Flat CSV/TSV order extracts.
+//!
+//! A [`CsvMapping`] spec (TOML or JSON) lists `target <- source` rules, where
+//! the target is a staging column (`sr_id`, `code.system`, `reason[2].code`,
+//! ...) and the source is a header name or `const("...")`. Each row becomes a
+//! ServiceRequest in memory and goes through `validate_sr` and `sr_to_staging`,
+//! so CSV rows normalize exactly like FHIR input. Row problems are reported
+//! as [`CsvRowIssue`]s instead of failing the extract.
+
+use std::{collections::HashMap, fs, io::Read, path::Path};
+
+use dfps_core::{
+    fhir::{
+        self, Annotation, CodeableConcept, Coding, Period, Reference, ServiceRequestOccurrence,
+    },
+    value::FhirDateTime,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    stream::SourcePosition,
+    transforms::{IngestionError, RowSources, StagingRows, sr_to_staging},
+    validation::{
+        RequirementRef, ValidationIssue, ValidationMode, ValidationSeverity, validate_sr,
+    },
+};
+
+/// Targets every spec must map.
+const REQUIRED_TARGETS: [&str; 4] = ["sr_id", "patient_id", "status", "intent"];
+
+/// Where a rule reads its value from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsvSource {
+    Column(String),
+    Const(String),
+}
+
+/// Coding-valued targets (`code[n]`, `reason[n]`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConceptField {
+    System,
+    Code,
+    Display,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    SrId,
+    PatientId,
+    EncounterId,
+    Status,
+    Intent,
+    Priority,
+    Description,
+    OrderedAt,
+    OccurrenceStart,
+    OccurrenceEnd,
+    Note,
+    /// `code[n].field`, `n` starting at 1.
+    Code(usize, ConceptField),
+    Reason(usize, ConceptField),
+}
+
+impl Target {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "sr_id" => Self::SrId,
+            "patient_id" => Self::PatientId,
+            "encounter_id" => Self::EncounterId,
+            "status" => Self::Status,
+            "intent" => Self::Intent,
+            "priority" => Self::Priority,
+            "description" => Self::Description,
+            "ordered_at" => Self::OrderedAt,
+            "occurrence_start" => Self::OccurrenceStart,
+            "occurrence_end" => Self::OccurrenceEnd,
+            "note" => Self::Note,
+            _ => {
+                let (concept, field) = name.rsplit_once('.')?;
+                let field = match field {
+                    "system" => ConceptField::System,
+                    "code" => ConceptField::Code,
+                    "display" => ConceptField::Display,
+                    _ => return None,
+                };
+                let (base, index) = match concept.split_once('[') {
+                    Some((base, index)) => (base, index.strip_suffix(']')?.parse().ok()?),
+                    None => (concept, 1),
+                };
+                if index == 0 {
+                    return None;
+                }
+                match base {
+                    "code" => Self::Code(index, field),
+                    "reason" => Self::Reason(index, field),
+                    _ => return None,
+                }
+            }
+        })
+    }
+
+    fn name(&self) -> String {
+        let concept = |base: &str, index: &usize, field: &ConceptField| {
+            let field = match field {
+                ConceptField::System => "system",
+                ConceptField::Code => "code",
+                ConceptField::Display => "display",
+            };
+            format!("{base}[{index}].{field}")
+        };
+        match self {
+            Self::SrId => "sr_id".into(),
+            Self::PatientId => "patient_id".into(),
+            Self::EncounterId => "encounter_id".into(),
+            Self::Status => "status".into(),
+            Self::Intent => "intent".into(),
+            Self::Priority => "priority".into(),
+            Self::Description => "description".into(),
+            Self::OrderedAt => "ordered_at".into(),
+            Self::OccurrenceStart => "occurrence_start".into(),
+            Self::OccurrenceEnd => "occurrence_end".into(),
+            Self::Note => "note".into(),
+            Self::Code(index, field) => concept("code", index, field),
+            Self::Reason(index, field) => concept("reason", index, field),
+        }
+    }
+}
+
+/// On-disk shape of a mapping spec.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CsvMappingFile {
+    #[serde(default)]
+    delimiter: Option<String>,
+    columns: Vec<String>,
+}
+
+/// Declarative column mapping for a CSV/TSV order extract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvMapping {
+    delimiter: Option<u8>,
+    rules: Vec<(Target, CsvSource)>,
+}
+
+impl CsvMapping {
+    pub fn from_toml_str(text: &str) -> Result<Self, IngestionError> {
+        let file: CsvMappingFile = toml::from_str(text)
+            .map_err(|err| IngestionError::InvalidCsvMapping(err.message().to_string()))?;
+        Self::from_file(file)
+    }
+
+    pub fn from_json_str(text: &str) -> Result<Self, IngestionError> {
+        let file: CsvMappingFile = serde_json::from_str(text)
+            .map_err(|err| IngestionError::InvalidCsvMapping(err.to_string()))?;
+        Self::from_file(file)
+    }
+
+    /// Load a spec, choosing JSON for `.json` files and TOML otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, IngestionError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| {
+            IngestionError::InvalidCsvMapping(format!("{}: {err}", path.display()))
+        })?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json_str(&text),
+            _ => Self::from_toml_str(&text),
+        }
+    }
+
+    fn from_file(file: CsvMappingFile) -> Result<Self, IngestionError> {
+        let delimiter = file
+            .delimiter
+            .as_deref()
+            .map(|delimiter| match delimiter {
+                "tab" | "\t" => Ok(b'\t'),
+                other if other.len() == 1 && other.is_ascii() => Ok(other.as_bytes()[0]),
+                other => Err(IngestionError::InvalidCsvMapping(format!(
+                    "delimiter must be a single ASCII character or \"tab\", got '{other}'"
+                ))),
+            })
+            .transpose()?;
+        let mut rules: Vec<(Target, CsvSource)> = Vec::new();
+        for rule in &file.columns {
+            let (target, source) = parse_rule(rule)?;
+            if rules.iter().any(|(existing, _)| *existing == target) {
+                return Err(IngestionError::InvalidCsvMapping(format!(
+                    "'{}' is mapped more than once",
+                    target.name()
+                )));
+            }
+            rules.push((target, source));
+        }
+        for required in REQUIRED_TARGETS {
+            let target = Target::parse(required).expect("required targets parse");
+            if !rules.iter().any(|(existing, _)| *existing == target) {
+                return Err(IngestionError::InvalidCsvMapping(format!(
+                    "no rule for required target '{required}'"
+                )));
+            }
+        }
+        Ok(Self { delimiter, rules })
+    }
+
+    /// Field delimiter set by the spec, if any.
+    pub fn delimiter(&self) -> Option<u8> {
+        self.delimiter
+    }
+
+    /// Override the spec's delimiter (e.g. `b'\t'` for a `.tsv` input).
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = Some(delimiter);
+        self
+    }
+
+    /// Header names the spec reads from.
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().filter_map(|(_, source)| match source {
+            CsvSource::Column(column) => Some(column.as_str()),
+            CsvSource::Const(_) => None,
+        })
+    }
+}
+
+/// Parse `target <- COLUMN`, `target <- "Column name"` or
+/// `target <- const("value")`.
+fn parse_rule(rule: &str) -> Result<(Target, CsvSource), IngestionError> {
+    let invalid = |reason: &str| IngestionError::InvalidCsvMapping(format!("'{rule}': {reason}"));
+    let (target, source) = rule
+        .split_once("<-")
+        .ok_or_else(|| invalid("expected `target <- source`"))?;
+    let target = Target::parse(target.trim()).ok_or_else(|| invalid("unknown target"))?;
+    let source = source.trim();
+    let source = if let Some(value) = source
+        .strip_prefix("const(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        CsvSource::Const(
+            unquote(value.trim()).ok_or_else(|| invalid("const needs a quoted value"))?,
+        )
+    } else if source.starts_with('"') {
+        CsvSource::Column(unquote(source).ok_or_else(|| invalid("unterminated column name"))?)
+    } else if !source.is_empty() && !source.contains(char::is_whitespace) {
+        CsvSource::Column(source.to_string())
+    } else {
+        return Err(invalid("expected a column name or const(\"...\")"));
+    };
+    Ok((target, source))
+}
+
+fn unquote(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    Some(inner.replace("\\\"", "\"").replace("\\\\", "\\"))
+}
+
+/// A validation issue raised for one line of the extract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CsvRowIssue {
+    /// 1-based line in the input (the header is line 1).
+    pub line: u64,
+    pub sr_id: Option<String>,
+    #[serde(flatten)]
+    pub issue: ValidationIssue,
+}
+
+/// Rows staged from an extract plus the issues raised on the way.
+#[derive(Debug, Default)]
+pub struct CsvStaging {
+    pub rows: StagingRows,
+    /// Where the record each row of `rows` was staged from starts in the input.
+    pub positions: RowSources<SourcePosition>,
+    pub issues: Vec<CsvRowIssue>,
+    /// Data rows read, staged or not.
+    pub rows_read: usize,
+    /// Data rows that produced no staging row.
+    pub rows_rejected: usize,
+}
+
+/// Stage every row of a CSV/TSV extract with `mapping`.
+///
+/// Malformed rows, unparseable values and rows `sr_to_staging` rejects are
+/// skipped with an error issue; with [`ValidationMode::Strict`] rows carrying
+/// error-severity `validate_sr` issues are skipped too. Only I/O failures and
+/// headers missing a mapped column fail the whole extract.
+pub fn csv_to_staging<R: Read>(
+    reader: R,
+    mapping: &CsvMapping,
+    mode: ValidationMode,
+) -> Result<CsvStaging, IngestionError> {
+    let mut csv = csv::ReaderBuilder::new()
+        .delimiter(mapping.delimiter.unwrap_or(b','))
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let header: HashMap<String, usize> = csv
+        .headers()
+        .map_err(|err| IngestionError::InvalidCsv(err.to_string()))?
+        .iter()
+        .enumerate()
+        .map(|(index, name)| (name.to_string(), index))
+        .collect();
+    let missing: Vec<&str> = mapping
+        .columns()
+        .filter(|column| !header.contains_key(*column))
+        .collect();
+    if !missing.is_empty() {
+        return Err(IngestionError::InvalidCsvMapping(format!(
+            "column(s) not in the header: {}",
+            missing.join(", ")
+        )));
+    }
+
+    let mut staging = CsvStaging::default();
+    for record in csv.records() {
+        staging.rows_read += 1;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) if err.is_io_error() => {
+                return Err(IngestionError::InvalidCsv(err.to_string()));
+            }
+            Err(err) => {
+                let line = err.position().map(|pos| pos.line()).unwrap_or_default();
+                staging.rows_rejected += 1;
+                staging.issues.push(row_error(
+                    line,
+                    None,
+                    "VAL_CSV_ROW_MALFORMED",
+                    err.to_string(),
+                ));
+                continue;
+            }
+        };
+        let position = record
+            .position()
+            .map(|pos| SourcePosition {
+                byte_offset: pos.byte(),
+                line: pos.line(),
+            })
+            .unwrap_or_default();
+        let line = position.line;
+        let value = |source: &CsvSource| -> Option<String> {
+            match source {
+                CsvSource::Const(value) => Some(value.clone()),
+                CsvSource::Column(column) => record
+                    .get(header[column])
+                    .filter(|value| !value.is_empty())
+                    .map(str::to_string),
+            }
+        };
+        let values: Vec<(&Target, String)> = mapping
+            .rules
+            .iter()
+            .filter_map(|(target, source)| Some((target, value(source)?)))
+            .collect();
+        let sr_id = values
+            .iter()
+            .find(|(target, _)| **target == Target::SrId)
+            .map(|(_, value)| value.clone());
+
+        let sr = match row_to_service_request(&values) {
+            Ok(sr) => sr,
+            Err(message) => {
+                staging.rows_rejected += 1;
+                staging
+                    .issues
+                    .push(row_error(line, sr_id, "VAL_CSV_VALUE_INVALID", message));
+                continue;
+            }
+        };
+        let issues = validate_sr(&sr);
+        let has_errors = issues
+            .iter()
+            .any(|issue| issue.severity == ValidationSeverity::Error);
+        staging
+            .issues
+            .extend(issues.into_iter().map(|issue| CsvRowIssue {
+                line,
+                sr_id: sr_id.clone(),
+                issue,
+            }));
+        if has_errors && mode == ValidationMode::Strict {
+            staging.rows_rejected += 1;
+            continue;
+        }
+        match sr_to_staging(&sr) {
+            Ok((flat, codes)) => {
+                staging.positions.push(position, codes.len());
+                staging.rows.0.push(flat);
+                staging.rows.1.extend(codes);
+            }
+            Err(err) => {
+                staging.rows_rejected += 1;
+                // Failures validate_sr already explained need no second issue.
+                if !has_errors {
+                    staging.issues.push(row_error(
+                        line,
+                        sr_id,
+                        "VAL_CSV_ROW_REJECTED",
+                        err.to_string(),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(staging)
+}
+
+fn row_error(line: u64, sr_id: Option<String>, id: &str, message: String) -> CsvRowIssue {
+    CsvRowIssue {
+        line,
+        sr_id,
+        issue: ValidationIssue::new(
+            id,
+            ValidationSeverity::Error,
+            message,
+            RequirementRef::TRACE,
+        ),
+    }
+}
+
+/// Assemble the row's ServiceRequest; `Err` names the value that did not parse.
+fn row_to_service_request(values: &[(&Target, String)]) -> Result<fhir::ServiceRequest, String> {
+    let datetime = |target: &Target, value: &str| {
+        FhirDateTime::parse(value)
+            .map_err(|err| format!("{} '{value}' is not a FHIR dateTime: {err}", target.name()))
+    };
+    let mut sr = fhir::ServiceRequest::default();
+    let mut period = Period::default();
+    let mut codes: Vec<(usize, Coding)> = Vec::new();
+    let mut reasons: Vec<(usize, Coding)> = Vec::new();
+    for (target, value) in values {
+        let value = value.clone();
+        match target {
+            Target::SrId => sr.id = Some(value),
+            Target::PatientId => sr.subject = Some(reference("Patient", value)),
+            Target::EncounterId => sr.encounter = Some(reference("Encounter", value)),
+            Target::Status => sr.status = Some(value),
+            Target::Intent => sr.intent = Some(value),
+            Target::Priority => sr.priority = Some(value),
+            Target::Description => sr.description = Some(value),
+            Target::OrderedAt => sr.authored_on = Some(datetime(target, &value)?),
+            Target::OccurrenceStart => period.start = Some(datetime(target, &value)?),
+            Target::OccurrenceEnd => period.end = Some(datetime(target, &value)?),
+            Target::Note => sr.note.push(Annotation {
+                author: None,
+                time: None,
+                text: Some(value),
+            }),
+            Target::Code(index, field) => set_coding(&mut codes, *index, *field, value),
+            Target::Reason(index, field) => set_coding(&mut reasons, *index, *field, value),
+        }
+    }
+    sr.occurrence = match period {
+        Period {
+            start: None,
+            end: None,
+        } => None,
+        Period {
+            start: Some(start),
+            end: None,
+        } => Some(ServiceRequestOccurrence::DateTime(start)),
+        period => Some(ServiceRequestOccurrence::Period(period)),
+    };
+    let codings = |mut codings: Vec<(usize, Coding)>| -> Vec<Coding> {
+        codings.sort_by_key(|(index, _)| *index);
+        codings
+            .into_iter()
+            .map(|(_, coding)| coding)
+            .filter(|coding| coding.code.is_some())
+            .collect()
+    };
+    let code = codings(codes);
+    if !code.is_empty() {
+        sr.code = Some(CodeableConcept {
+            coding: code,
+            text: None,
+        });
+    }
+    sr.reason_code = codings(reasons)
+        .into_iter()
+        .map(|coding| CodeableConcept {
+            coding: vec![coding],
+            text: None,
+        })
+        .collect();
+    Ok(sr)
+}
+
+fn set_coding(
+    codings: &mut Vec<(usize, Coding)>,
+    index: usize,
+    field: ConceptField,
+    value: String,
+) {
+    let position = match codings.iter().position(|(existing, _)| *existing == index) {
+        Some(position) => position,
+        None => {
+            codings.push((
+                index,
+                Coding {
+                    system: None,
+                    code: None,
+                    display: None,
+                },
+            ));
+            codings.len() - 1
+        }
+    };
+    let coding = &mut codings[position].1;
+    match field {
+        ConceptField::System => coding.system = Some(value),
+        ConceptField::Code => coding.code = Some(value),
+        ConceptField::Display => coding.display = Some(value),
+    }
+}
+
+fn reference(resource_type: &str, id: String) -> Reference {
+    Reference {
+        reference: Some(format!("{resource_type}/{id}")),
+        display: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r#"
+        columns = [
+            "sr_id <- ORDER_ID",
+            "patient_id <- MRN",
+            "encounter_id <- VISIT",
+            "status <- STATUS",
+            'intent <- const("order")',
+            "ordered_at <- ORDERED",
+            'code.system <- const("http://www.ama-assn.org/go/cpt")',
+            "code.code <- CPT",
+            'code.display <- "Order Description"',
+            'code[2].system <- const("http://snomed.info/sct")',
+            "code[2].code <- SNOMED",
+        ]
+    "#;
+
+    #[test]
+    fn rows_become_staging_rows_and_row_issues() {
+        let mapping = CsvMapping::from_toml_str(SPEC).unwrap();
+        let extract = "\
+ORDER_ID,MRN,VISIT,STATUS,ORDERED,CPT,Order Description,SNOMED
+SR-1,PAT-1,ENC-1,Active,2024-05-01T08:30:00Z,78815,PET/CT,
+SR-2,,ENC-1,active,2024-05-01,71046,Chest X-ray,399208008
+SR-3,PAT-3,,active,yesterday,71046,Chest X-ray,
+SR-4,PAT-4
+";
+        let staging =
+            csv_to_staging(extract.as_bytes(), &mapping, ValidationMode::Lenient).unwrap();
+
+        assert_eq!(staging.rows_read, 4);
+        assert_eq!(staging.rows_rejected, 3);
+        let (flats, codes) = &staging.rows;
+        assert_eq!(flats.len(), 1);
+        assert_eq!(flats[0].sr_id, "SR-1");
+        assert_eq!(flats[0].status, "active");
+        assert_eq!(flats[0].encounter_id.as_deref(), Some("ENC-1"));
+        assert_eq!(flats[0].description, "PET/CT");
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].code.as_deref(), Some("78815"));
+        assert_eq!(
+            staging.positions.flats,
+            vec![SourcePosition {
+                byte_offset: 63,
+                line: 2
+            }]
+        );
+
+        let issues: Vec<_> = staging
+            .issues
+            .iter()
+            .map(|issue| (issue.line, issue.sr_id.as_deref(), issue.issue.id.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (3, Some("SR-2"), "VAL_SR_SUBJECT_MISSING"),
+                (4, Some("SR-3"), "VAL_CSV_VALUE_INVALID"),
+                (5, None, "VAL_CSV_ROW_MALFORMED"),
+            ]
+        );
+    }
+
+    #[test]
+    fn specs_load_from_json_and_reject_bad_rules() {
+        let json = r#"{
+            "delimiter": "tab",
+            "columns": [
+                "sr_id <- ORDER_ID", "patient_id <- MRN",
+                "status <- const(\"active\")", "intent <- const(\"order\")",
+                "reason.code <- DX", "reason.system <- const(\"http://hl7.org/fhir/sid/icd-10-cm\")"
+            ]
+        }"#;
+        let mapping = CsvMapping::from_json_str(json).unwrap();
+        assert_eq!(mapping.delimiter(), Some(b'\t'));
+        let staging = csv_to_staging(
+            "ORDER_ID\tMRN\tDX\nSR-9\tPAT-9\tC34.90\n".as_bytes(),
+            &mapping,
+            ValidationMode::Strict,
+        )
+        .unwrap();
+        assert!(staging.issues.is_empty());
+        assert_eq!(
+            staging.rows.0[0].reason_codes,
+            vec!["http://hl7.org/fhir/sid/icd-10-cm|C34.90"]
+        );
+
+        for spec in [
+            r#"columns = ["sr_id <- ORDER_ID"]"#,
+            r#"columns = ["sr_id = ORDER_ID", "patient_id <- MRN", "status <- S", "intent <- I"]"#,
+            r#"columns = ["sr_id <- A", "sr_id <- B", "patient_id <- MRN", "status <- S", "intent <- I"]"#,
+            r#"columns = ["bogus <- A", "sr_id <- B", "patient_id <- MRN", "status <- S", "intent <- I"]"#,
+        ] {
+            assert!(matches!(
+                CsvMapping::from_toml_str(spec),
+                Err(IngestionError::InvalidCsvMapping(_))
+            ));
+        }
+
+        let err =
+            csv_to_staging("ORDER_ID\n".as_bytes(), &mapping, ValidationMode::Lenient).unwrap_err();
+        assert_eq!(err.code(), "invalid_csv_mapping");
+    }
+}
@@ -0,0 +1,1096 @@
+//! De-identification of Bundles and staging rows before they leave the site.
+//!
+//! A [`Deidentifier`] rewrites resources in place so staging rows, logs and
+//! datamart dims carry no source identifiers:
+//!
+//! - Patient, Encounter and ServiceRequest ids become keyed HMAC-SHA256
+//!   pseudonyms, and every reference to them (`subject`, `encounter`,
+//!   `basedOn`, `fullUrl`, `request.url`, ...) is rewritten to match, so the
+//!   same source id always maps to the same pseudonym under one key.
+//!   `identifier` values and conditional `identifier=`/`_id=` queries are
+//!   pseudonymized the same way.
+//! - Dates and date-times precise to a day are shifted by a per-patient
+//!   offset of 1 to `max_shift_days` days (either direction), derived from the
+//!   key and the patient id, so intervals within one patient's record are
+//!   preserved. Year and year-month values are kept. Resources with no
+//!   resolvable patient (`Patient.id`, `subject` or `patient`) are shifted by
+//!   one offset derived from the key alone.
+//! - Free text in `description`, `note`, Reference `display` and Extension
+//!   `valueString`/`valueMarkdown` is stripped or scrubbed with regular
+//!   expressions, narrative `text` is dropped, and Patient `name`, `telecom`,
+//!   `address`, `photo` and `contact` are removed, as are Extension
+//!   `valueHumanName`, `valueAddress` and `valueContactPoint`.
+//! - `contained` resources are treated like the resource holding them, and
+//!   `#id` references follow their pseudonymized ids.
+//!
+//! Processed resources are labelled with the `PSEUDED` security label. An
+//! inbound label is not trusted: every resource is processed, so callers must
+//! de-identify a resource once. The config names no key; it comes from
+//! [`Deidentifier::KEY_ENV_VAR`]:
+//!
+//! ```toml
+//! max_shift_days = 180
+//! text = "scrub"                    # strip (default) | scrub
+//!
+//! [[scrub]]
+//! pattern = "\\b\\d{3}-\\d{3}-\\d{4}\\b"
+//! replacement = "[PHONE]"
+//! ```
+
+use std::{collections::HashMap, env, fmt, fs, path::Path};
+
+use dfps_core::{fhir, value::FhirDateTime};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use sha2::Sha256;
+
+use crate::{
+    csv_extract::CsvRowIssue,
+    reference::ParsedReference,
+    transforms::{IngestionError, StagingRows},
+};
+
+/// Resource types whose ids, and references to them, are pseudonymized.
+pub const PSEUDONYMIZED_TYPES: [&str; 3] = ["Patient", "Encounter", "ServiceRequest"];
+
+/// System of the `PSEUDED` security label set on processed resources.
+pub const SECURITY_LABEL_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/v3-ObservationValue";
+
+const PSEUDED: &str = "PSEUDED";
+const PATIENT_DEMOGRAPHICS: [&str; 5] = ["name", "telecom", "address", "photo", "contact"];
+/// Extension values that hold free text, and those that hold demographics.
+const EXTENSION_TEXT: [&str; 2] = ["valueString", "valueMarkdown"];
+const EXTENSION_DEMOGRAPHICS: [&str; 3] = ["valueHumanName", "valueAddress", "valueContactPoint"];
+/// Keys whose string values are never dates, even when they parse as one.
+const NON_DATE_KEYS: [&str; 7] = [
+    "id",
+    "reference",
+    "url",
+    "system",
+    "code",
+    "version",
+    "fullUrl",
+];
+/// Replacement for a stripped staging description, which cannot be empty.
+const REDACTED: &str = "[redacted]";
+/// Hex characters kept from each HMAC (128 bits).
+const PSEUDONYM_LEN: usize = 32;
+
+/// How free text in `description` and `note` is handled.
+#[derive(Debug, Clone)]
+pub enum TextPolicy {
+    /// Drop the elements.
+    Strip,
+    /// Keep the text with every match of each rule replaced, in order.
+    Scrub(Vec<ScrubRule>),
+}
+
+/// One regex substitution applied by [`TextPolicy::Scrub`].
+#[derive(Debug, Clone)]
+pub struct ScrubRule {
+    pattern: Regex,
+    replacement: String,
+}
+
+impl ScrubRule {
+    /// `replacement` may use `$1`-style group references.
+    pub fn new(pattern: &str, replacement: impl Into<String>) -> Result<Self, IngestionError> {
+        let pattern = Regex::new(pattern).map_err(|err| {
+            IngestionError::InvalidDeidConfig(format!("scrub pattern '{pattern}': {err}"))
+        })?;
+        Ok(Self {
+            pattern,
+            replacement: replacement.into(),
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+}
+
+/// Settings of a [`Deidentifier`], loaded from a TOML or JSON file.
+#[derive(Debug, Clone)]
+pub struct DeidConfig {
+    /// Largest date shift in days; `0` disables date shifting.
+    pub max_shift_days: u32,
+    pub text: TextPolicy,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default = "default_max_shift_days")]
+    max_shift_days: u32,
+    #[serde(default)]
+    text: TextMode,
+    #[serde(default)]
+    scrub: Vec<ScrubSpec>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum TextMode {
+    #[default]
+    Strip,
+    Scrub,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScrubSpec {
+    pattern: String,
+    #[serde(default = "default_replacement")]
+    replacement: String,
+}
+
+fn default_max_shift_days() -> u32 {
+    DeidConfig::DEFAULT_MAX_SHIFT_DAYS
+}
+
+fn default_replacement() -> String {
+    REDACTED.to_string()
+}
+
+impl Default for DeidConfig {
+    fn default() -> Self {
+        Self {
+            max_shift_days: Self::DEFAULT_MAX_SHIFT_DAYS,
+            text: TextPolicy::Strip,
+        }
+    }
+}
+
+impl DeidConfig {
+    pub const DEFAULT_MAX_SHIFT_DAYS: u32 = 365;
+    /// Upper bound on `max_shift_days` (ten years).
+    pub const MAX_SHIFT_DAYS_LIMIT: u32 = 3650;
+
+    pub fn from_toml_str(text: &str) -> Result<Self, IngestionError> {
+        let file: ConfigFile = toml::from_str(text)
+            .map_err(|err| IngestionError::InvalidDeidConfig(err.message().to_string()))?;
+        Self::from_file(file)
+    }
+
+    pub fn from_json_str(text: &str) -> Result<Self, IngestionError> {
+        let file: ConfigFile = serde_json::from_str(text)
+            .map_err(|err| IngestionError::InvalidDeidConfig(err.to_string()))?;
+        Self::from_file(file)
+    }
+
+    /// Load a config file, choosing JSON for `.json` files and TOML otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, IngestionError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| {
+            IngestionError::InvalidDeidConfig(format!("{}: {err}", path.display()))
+        })?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json_str(&text),
+            _ => Self::from_toml_str(&text),
+        }
+    }
+
+    fn from_file(file: ConfigFile) -> Result<Self, IngestionError> {
+        if file.max_shift_days > Self::MAX_SHIFT_DAYS_LIMIT {
+            return Err(IngestionError::InvalidDeidConfig(format!(
+                "max_shift_days must be at most {}",
+                Self::MAX_SHIFT_DAYS_LIMIT
+            )));
+        }
+        let text = match file.text {
+            TextMode::Strip if file.scrub.is_empty() => TextPolicy::Strip,
+            TextMode::Strip => {
+                return Err(IngestionError::InvalidDeidConfig(
+                    "[[scrub]] rules need text = \"scrub\"".to_string(),
+                ));
+            }
+            TextMode::Scrub if file.scrub.is_empty() => {
+                return Err(IngestionError::InvalidDeidConfig(
+                    "text = \"scrub\" needs at least one [[scrub]] rule".to_string(),
+                ));
+            }
+            TextMode::Scrub => TextPolicy::Scrub(
+                file.scrub
+                    .iter()
+                    .map(|spec| ScrubRule::new(&spec.pattern, spec.replacement.clone()))
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+        Ok(Self {
+            max_shift_days: file.max_shift_days,
+            text,
+        })
+    }
+}
+
+/// Keyed pseudonymization, date shifting and free-text removal.
+#[derive(Clone)]
+pub struct Deidentifier {
+    key: Vec<u8>,
+    config: DeidConfig,
+}
+
+impl fmt::Debug for Deidentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Deidentifier")
+            .field("key", &"<redacted>")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl Deidentifier {
+    /// Path of a config file applied by [`Self::from_env`].
+    pub const ENV_VAR: &'static str = "DFPS_DEID_CONFIG";
+    /// Environment variable holding the pseudonymization key.
+    pub const KEY_ENV_VAR: &'static str = "DFPS_DEID_KEY";
+    pub const MIN_KEY_BYTES: usize = 16;
+
+    pub fn new(key: impl AsRef<[u8]>, config: DeidConfig) -> Result<Self, IngestionError> {
+        let key = key.as_ref();
+        if key.len() < Self::MIN_KEY_BYTES {
+            return Err(IngestionError::InvalidDeidConfig(format!(
+                "key must be at least {} bytes",
+                Self::MIN_KEY_BYTES
+            )));
+        }
+        Ok(Self {
+            key: key.to_vec(),
+            config,
+        })
+    }
+
+    /// Load the config at `path`, keyed by [`Self::KEY_ENV_VAR`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, IngestionError> {
+        let config = DeidConfig::load(path)?;
+        let key = env::var(Self::KEY_ENV_VAR).map_err(|_| {
+            IngestionError::InvalidDeidConfig(format!("{} is not set", Self::KEY_ENV_VAR))
+        })?;
+        Self::new(key.trim(), config)
+    }
+
+    /// Load the config named by [`Self::ENV_VAR`]; unset means no
+    /// de-identification.
+    pub fn from_env() -> Result<Option<Self>, IngestionError> {
+        match env::var(Self::ENV_VAR) {
+            Ok(path) if !path.trim().is_empty() => Self::load(path.trim()).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn config(&self) -> &DeidConfig {
+        &self.config
+    }
+
+    /// Pseudonym of `resource_type/id`: 32 hex characters, a valid FHIR id.
+    pub fn pseudonym(&self, resource_type: &str, id: &str) -> String {
+        self.digest_hex(&format!("{resource_type}/{id}"))
+    }
+
+    /// Days added to every date of `patient_id`'s resources; never zero
+    /// unless `max_shift_days` is.
+    pub fn shift_days(&self, patient_id: &str) -> i64 {
+        self.shift_for(&format!("date-shift|Patient/{patient_id}"))
+    }
+
+    /// Days added to the dates of resources with no resolvable patient.
+    pub fn unlinked_shift_days(&self) -> i64 {
+        self.shift_for("date-shift|unlinked")
+    }
+
+    fn shift_for(&self, message: &str) -> i64 {
+        let max = u64::from(self.config.max_shift_days);
+        if max == 0 {
+            return 0;
+        }
+        let digest = self.digest(message);
+        let bits = u64::from_be_bytes(digest[..8].try_into().expect("8-byte prefix"));
+        let days = (1 + (bits >> 1) % max) as i64;
+        if bits & 1 == 0 { days } else { -days }
+    }
+
+    /// De-identify every entry of `bundle`, including `fullUrl` and
+    /// `request`, resolving `urn:uuid` patient references through the
+    /// Bundle's Patient entries.
+    pub fn deidentify_bundle(&self, bundle: &mut fhir::Bundle) {
+        let patients: HashMap<String, String> = bundle
+            .entry
+            .iter()
+            .filter(|entry| entry.resource_type() == Some("Patient"))
+            .filter_map(|entry| Some((entry.full_url.clone()?, entry.resource_id()?.to_string())))
+            .collect();
+        for entry in &mut bundle.entry {
+            let resource_type = entry.resource_type().map(str::to_string);
+            if let Some(resource) = &mut entry.resource {
+                let patient = patient_of(resource, &patients);
+                self.deidentify_with(resource, patient.as_deref());
+            }
+            if let Some(full_url) = &mut entry.full_url {
+                self.pseudonymize_reference(full_url);
+            }
+            if let Some(request) = &mut entry.request {
+                if let Some(url) = &mut request.url {
+                    match url.split_once('?') {
+                        Some((path, query)) => {
+                            *url = format!("{path}?{}", self.rewrite_query(query, path));
+                        }
+                        None => self.pseudonymize_reference(url),
+                    }
+                }
+                if let Some(query) = &mut request.if_none_exist {
+                    let resource_type = resource_type.as_deref().unwrap_or_default();
+                    *query = self.rewrite_query(query, resource_type);
+                }
+            }
+        }
+    }
+
+    /// De-identify one resource; only `Patient.id`, `subject` and `patient`
+    /// literal references name the patient whose date shift applies.
+    pub fn deidentify_resource(&self, resource: &mut Value) {
+        let patient = patient_of(resource, &HashMap::new());
+        self.deidentify_with(resource, patient.as_deref());
+    }
+
+    /// De-identify rows staged without a Bundle (e.g. from a CSV extract).
+    pub fn deidentify_staging(&self, rows: &mut StagingRows) {
+        let (flats, exploded) = rows;
+        for flat in flats {
+            let shift = self.shift_days(&flat.patient_id);
+            flat.sr_id = self.pseudonym("ServiceRequest", &flat.sr_id);
+            flat.patient_id = self.pseudonym("Patient", &flat.patient_id);
+            if let Some(encounter_id) = &mut flat.encounter_id {
+                *encounter_id = self.pseudonym("Encounter", encounter_id);
+            }
+            for date in [
+                &mut flat.ordered_at,
+                &mut flat.occurrence_start,
+                &mut flat.occurrence_end,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                *date = date.shift_days(shift);
+            }
+            for reference in flat
+                .based_on
+                .iter_mut()
+                .chain(&mut flat.replaces)
+                .chain(&mut flat.reason_references)
+            {
+                self.pseudonymize_reference(reference);
+            }
+            for value in flat.extensions.values_mut() {
+                *value = self.extension_column(value, shift);
+            }
+            for token in &mut flat.identifiers {
+                *token = match token.split_once('|') {
+                    Some((system, value)) => {
+                        format!("{system}|{}", self.identifier_pseudonym(value))
+                    }
+                    None => self.identifier_pseudonym(token),
+                };
+            }
+            match &self.config.text {
+                TextPolicy::Strip => {
+                    flat.description = REDACTED.to_string();
+                    flat.notes.clear();
+                }
+                TextPolicy::Scrub(rules) => {
+                    flat.description = scrub(rules, &flat.description);
+                    for note in &mut flat.notes {
+                        *note = scrub(rules, note);
+                    }
+                }
+            }
+        }
+        for code in exploded {
+            code.sr_id = self.pseudonym("ServiceRequest", &code.sr_id);
+        }
+    }
+
+    /// Pseudonymize the order id of a CSV row issue and strip or scrub its
+    /// message, which may quote row values.
+    pub fn deidentify_csv_issue(&self, row: &mut CsvRowIssue) {
+        if let Some(sr_id) = &mut row.sr_id {
+            *sr_id = self.pseudonym("ServiceRequest", sr_id);
+        }
+        row.issue.message = self.redact(&row.issue.message);
+    }
+
+    /// A projected extension column. Its type is not recorded, so each
+    /// `,`-separated value that is not a pseudonymized reference or a day-precise
+    /// date (alone or as a `start/end` period) is handled as free text.
+    fn extension_column(&self, column: &str, shift: i64) -> String {
+        let shift_date = |text: &str| shift_date_text(text, shift);
+        column
+            .split(',')
+            .map(|value| {
+                let mut reference = value.to_string();
+                self.pseudonymize_reference(&mut reference);
+                if reference != value {
+                    return reference;
+                }
+                if let Some(shifted) = shift_date(value) {
+                    return shifted;
+                }
+                if let Some((start, end)) = value.split_once('/')
+                    && (!start.is_empty() || !end.is_empty())
+                    && [start, end]
+                        .iter()
+                        .all(|side| side.is_empty() || shift_date(side).is_some())
+                {
+                    let side = |text: &str| shift_date(text).unwrap_or_default();
+                    return format!("{}/{}", side(start), side(end));
+                }
+                self.redact(value)
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// `text` under the configured [`TextPolicy`].
+    fn redact(&self, text: &str) -> String {
+        match &self.config.text {
+            TextPolicy::Strip => REDACTED.to_string(),
+            TextPolicy::Scrub(rules) => scrub(rules, text),
+        }
+    }
+
+    fn deidentify_with(&self, resource: &mut Value, patient: Option<&str>) {
+        let shift = match patient {
+            Some(patient) => self.shift_days(patient),
+            None => self.unlinked_shift_days(),
+        };
+        let Some(object) = resource.as_object_mut() else {
+            return;
+        };
+        let resource_type = object
+            .get("resourceType")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        self.strip_text(object, &resource_type);
+        let local_ids = self.deidentify_contained(object);
+
+        self.walk(object, shift);
+        if !local_ids.is_empty() {
+            rewrite_local_references(object, &local_ids);
+        }
+        if PSEUDONYMIZED_TYPES.contains(&resource_type.as_str())
+            && let Some(Value::String(id)) = object.get_mut("id")
+        {
+            *id = self.pseudonym(&resource_type, id);
+        }
+        label(object);
+    }
+
+    /// Drop narrative, Patient demographics and the `description`/`note` free
+    /// text of one resource.
+    fn strip_text(&self, object: &mut Map<String, Value>, resource_type: &str) {
+        object.remove("text");
+        if resource_type == "Patient" {
+            for key in PATIENT_DEMOGRAPHICS {
+                object.remove(key);
+            }
+        }
+        match &self.config.text {
+            TextPolicy::Strip => {
+                object.remove("description");
+                object.remove("note");
+            }
+            TextPolicy::Scrub(rules) => {
+                if let Some(Value::String(text)) = object.get_mut("description") {
+                    *text = scrub(rules, text);
+                }
+                for note in object
+                    .get_mut("note")
+                    .and_then(Value::as_array_mut)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_object_mut)
+                {
+                    note.remove("authorString");
+                    if let Some(Value::String(text)) = note.get_mut("text") {
+                        *text = scrub(rules, text);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Strip the `contained` resources of `object` and pseudonymize their ids.
+    /// Returns the rewritten `#id` references; the caller's walk handles the
+    /// rest of their content.
+    fn deidentify_contained(&self, object: &mut Map<String, Value>) -> HashMap<String, String> {
+        let mut local_ids = HashMap::new();
+        for contained in object
+            .get_mut("contained")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_object_mut)
+        {
+            let resource_type = contained
+                .get("resourceType")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            self.strip_text(contained, &resource_type);
+            if PSEUDONYMIZED_TYPES.contains(&resource_type.as_str())
+                && let Some(Value::String(id)) = contained.get_mut("id")
+            {
+                let pseudonym = self.pseudonym(&resource_type, id);
+                local_ids.insert(format!("#{id}"), format!("#{pseudonym}"));
+                *id = pseudonym;
+            }
+        }
+        local_ids
+    }
+
+    /// Rewrite references, identifiers, Reference displays and dates below
+    /// `object`.
+    fn walk(&self, object: &mut Map<String, Value>, shift: i64) {
+        // A `display` beside `code`/`system` names a concept; elsewhere it
+        // names a person or other referenced resource.
+        if !object.contains_key("code")
+            && !object.contains_key("system")
+            && let Some(Value::String(display)) = object.get_mut("display")
+        {
+            *display = self.redact(display);
+        }
+        if object.contains_key("url") {
+            for key in EXTENSION_DEMOGRAPHICS {
+                object.remove(key);
+            }
+            for key in EXTENSION_TEXT {
+                if let Some(Value::String(text)) = object.get_mut(key) {
+                    *text = self.redact(text);
+                }
+            }
+        }
+        for (key, value) in object.iter_mut() {
+            match (key.as_str(), &mut *value) {
+                ("reference", Value::String(reference)) => self.pseudonymize_reference(reference),
+                ("identifier", Value::Array(identifiers)) => {
+                    for identifier in identifiers.iter_mut().filter_map(Value::as_object_mut) {
+                        self.pseudonymize_identifier(identifier);
+                    }
+                }
+                ("identifier" | "valueIdentifier", Value::Object(identifier)) => {
+                    self.pseudonymize_identifier(identifier)
+                }
+                _ => {}
+            }
+            self.walk_value(key, value, shift);
+        }
+    }
+
+    fn walk_value(&self, key: &str, value: &mut Value, shift: i64) {
+        match value {
+            Value::Object(object) => self.walk(object, shift),
+            Value::Array(items) => {
+                for item in items {
+                    self.walk_value(key, item, shift);
+                }
+            }
+            Value::String(text) if shift != 0 && !NON_DATE_KEYS.contains(&key) => {
+                if let Some(shifted) = shift_date_text(text, shift) {
+                    *text = shifted;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn pseudonymize_identifier(&self, identifier: &mut Map<String, Value>) {
+        if let Some(Value::String(value)) = identifier.get_mut("value") {
+            *value = self.identifier_pseudonym(value);
+        }
+    }
+
+    fn identifier_pseudonym(&self, value: &str) -> String {
+        self.digest_hex(&format!("identifier|{value}"))
+    }
+
+    /// Replace the id of a `[base/]Type/id[/_history/v]` reference to a
+    /// pseudonymized type; other references are left alone.
+    pub fn pseudonymize_reference(&self, reference: &mut String) {
+        let Some(ParsedReference::Path {
+            base,
+            resource_type: Some(resource_type),
+            id,
+            version,
+        }) = ParsedReference::parse(reference)
+        else {
+            return;
+        };
+        if !PSEUDONYMIZED_TYPES.contains(&resource_type) {
+            return;
+        }
+        let mut rewritten = base.map(|base| format!("{base}/")).unwrap_or_default();
+        rewritten.push_str(resource_type);
+        rewritten.push('/');
+        rewritten.push_str(&self.pseudonym(resource_type, id));
+        if let Some(version) = version {
+            rewritten.push_str("/_history/");
+            rewritten.push_str(version);
+        }
+        *reference = rewritten;
+    }
+
+    /// Pseudonymize `identifier=` values and `_id=` ids in a conditional
+    /// query against `resource_type` (which may end a longer URL).
+    fn rewrite_query(&self, query: &str, resource_type: &str) -> String {
+        let resource_type = resource_type.rsplit('/').next().unwrap_or_default();
+        query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some(("identifier", token)) => {
+                    let token = token.replace("%7C", "|").replace("%7c", "|");
+                    match token.split_once('|') {
+                        Some((_, "")) => format!("identifier={token}"),
+                        Some((system, value)) => {
+                            format!("identifier={system}|{}", self.identifier_pseudonym(value))
+                        }
+                        None => format!("identifier={}", self.identifier_pseudonym(&token)),
+                    }
+                }
+                Some(("_id", id)) if PSEUDONYMIZED_TYPES.contains(&resource_type) => {
+                    format!("_id={}", self.pseudonym(resource_type, id))
+                }
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    fn digest(&self, message: &str) -> [u8; 32] {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(message.as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    fn digest_hex(&self, message: &str) -> String {
+        let mut hex: String = self
+            .digest(message)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        hex.truncate(PSEUDONYM_LEN);
+        hex
+    }
+}
+
+/// Source id of the patient a resource belongs to.
+fn patient_of(resource: &Value, patients: &HashMap<String, String>) -> Option<String> {
+    if resource.get("resourceType").and_then(Value::as_str) == Some("Patient") {
+        return resource
+            .get("id")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+    }
+    ["subject", "patient"].iter().find_map(|key| {
+        let reference = resource.get(key)?.get("reference")?.as_str()?;
+        match ParsedReference::parse(reference)? {
+            ParsedReference::Path {
+                resource_type: Some("Patient"),
+                id,
+                ..
+            } => Some(id.to_string()),
+            ParsedReference::Urn { urn } => patients.get(urn).cloned(),
+            _ => None,
+        }
+    })
+}
+
+/// Replace every `reference` below `object` found in `local_ids`.
+fn rewrite_local_references(object: &mut Map<String, Value>, local_ids: &HashMap<String, String>) {
+    for (key, value) in object.iter_mut() {
+        match value {
+            Value::String(reference) if key == "reference" => {
+                if let Some(rewritten) = local_ids.get(reference.as_str()) {
+                    *reference = rewritten.clone();
+                }
+            }
+            Value::Object(child) => rewrite_local_references(child, local_ids),
+            Value::Array(items) => {
+                for child in items.iter_mut().filter_map(Value::as_object_mut) {
+                    rewrite_local_references(child, local_ids);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `text` shifted by `days` when it is a FHIR date or dateTime precise to a
+/// day.
+fn shift_date_text(text: &str, days: i64) -> Option<String> {
+    let bytes = text.as_bytes();
+    if bytes.len() < 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+    let value = FhirDateTime::parse(text).ok()?;
+    Some(value.shift_days(days).as_str().to_string())
+}
+
+fn scrub(rules: &[ScrubRule], text: &str) -> String {
+    rules.iter().fold(text.to_string(), |text, rule| {
+        rule.pattern
+            .replace_all(&text, rule.replacement.as_str())
+            .into_owned()
+    })
+}
+
+fn label(object: &mut Map<String, Value>) {
+    let meta = object
+        .entry("meta")
+        .or_insert_with(|| Value::Object(Map::new()));
+    let Some(meta) = meta.as_object_mut() else {
+        return;
+    };
+    let security = meta
+        .entry("security")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if let Some(labels) = security.as_array_mut()
+        && !labels.iter().any(|label| {
+            label.get("system").and_then(Value::as_str) == Some(SECURITY_LABEL_SYSTEM)
+                && label.get("code").and_then(Value::as_str) == Some(PSEUDED)
+        })
+    {
+        labels.push(json!({
+            "system": SECURITY_LABEL_SYSTEM,
+            "code": PSEUDED,
+            "display": "pseudonymized"
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "test-key-0123456789abcdef";
+
+    fn deid(config: DeidConfig) -> Deidentifier {
+        Deidentifier::new(KEY, config).unwrap()
+    }
+
+    fn bundle() -> fhir::Bundle {
+        serde_json::from_value(json!({
+            "resourceType": "Bundle",
+            "type": "transaction",
+            "entry": [
+                {
+                    "fullUrl": "urn:uuid:patient-1",
+                    "resource": {
+                        "resourceType": "Patient",
+                        "id": "P-1",
+                        "identifier": [{ "system": "urn:mrn", "value": "MRN-1" }],
+                        "name": [{ "family": "Doe" }],
+                        "birthDate": "1970-01-31"
+                    },
+                    "request": {
+                        "method": "POST",
+                        "url": "Patient",
+                        "ifNoneExist": "identifier=urn:mrn%7CMRN-1"
+                    }
+                },
+                {
+                    "fullUrl": "https://ehr.example/fhir/ServiceRequest/SR-1",
+                    "resource": {
+                        "resourceType": "ServiceRequest",
+                        "id": "SR-1",
+                        "status": "active",
+                        "intent": "order",
+                        "subject": { "reference": "urn:uuid:patient-1", "display": "Jane Doe" },
+                        "encounter": { "reference": "Encounter/E-1" },
+                        "code": { "coding": [{
+                            "system": "http://loinc.org", "code": "24627-2", "display": "Chest CT"
+                        }] },
+                        "basedOn": [{ "reference": "ServiceRequest/SR-0/_history/2" }],
+                        "authoredOn": "2024-05-01T10:00:00+02:00",
+                        "occurrencePeriod": { "start": "2024-05", "end": "2024-05-20" },
+                        "description": "PET for Jane Doe, call 555-123-4567",
+                        "note": [{ "authorString": "Dr Smith", "text": "Call 555-123-4567" }]
+                    },
+                    "request": { "method": "PUT", "url": "ServiceRequest/SR-1" }
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn pseudonymizes_ids_and_references_consistently() {
+        let deid = deid(DeidConfig::default());
+        let mut bundle = bundle();
+        deid.deidentify_bundle(&mut bundle);
+
+        let patient = bundle.entry[0].resource.as_ref().unwrap();
+        let order = bundle.entry[1].resource.as_ref().unwrap();
+        let patient_id = deid.pseudonym("Patient", "P-1");
+        assert_eq!(patient["id"], patient_id.as_str());
+        assert_eq!(patient_id.len(), 32);
+        assert!(patient.get("name").is_none());
+        assert_ne!(patient["identifier"][0]["value"], "MRN-1");
+        assert_eq!(
+            bundle.entry[0].request.as_ref().unwrap().if_none_exist,
+            Some(format!(
+                "identifier=urn:mrn|{}",
+                patient["identifier"][0]["value"].as_str().unwrap()
+            ))
+        );
+
+        let sr_id = deid.pseudonym("ServiceRequest", "SR-1");
+        assert_eq!(order["id"], sr_id.as_str());
+        assert_eq!(order["subject"]["reference"], "urn:uuid:patient-1");
+        assert_eq!(
+            order["encounter"]["reference"],
+            format!("Encounter/{}", deid.pseudonym("Encounter", "E-1"))
+        );
+        assert_eq!(
+            order["basedOn"][0]["reference"],
+            format!(
+                "ServiceRequest/{}/_history/2",
+                deid.pseudonym("ServiceRequest", "SR-0")
+            )
+        );
+        assert_eq!(
+            bundle.entry[1].full_url.as_deref(),
+            Some(format!("https://ehr.example/fhir/ServiceRequest/{sr_id}").as_str())
+        );
+        assert_eq!(
+            bundle.entry[1].request.as_ref().unwrap().url.as_deref(),
+            Some(format!("ServiceRequest/{sr_id}").as_str())
+        );
+        assert!(order.get("description").is_none() && order.get("note").is_none());
+        assert_eq!(order["subject"]["display"], "[redacted]");
+        assert_eq!(order["code"]["coding"][0]["display"], "Chest CT");
+        assert_eq!(order["meta"]["security"][0]["code"], "PSEUDED");
+
+        let other = Deidentifier::new("another-key-0123456789", DeidConfig::default()).unwrap();
+        assert_ne!(other.pseudonym("Patient", "P-1"), patient_id);
+    }
+
+    #[test]
+    fn shifts_dates_per_patient_and_keeps_partial_dates() {
+        let deid = deid(DeidConfig {
+            max_shift_days: 30,
+            ..DeidConfig::default()
+        });
+        let shift = deid.shift_days("P-1");
+        assert!(shift != 0 && shift.abs() <= 30);
+        assert_eq!(deid.shift_days("P-1"), shift);
+
+        let mut bundle = bundle();
+        deid.deidentify_bundle(&mut bundle);
+        let patient = bundle.entry[0].resource.as_ref().unwrap();
+        let order = bundle.entry[1].resource.as_ref().unwrap();
+        let shifted = |text: &str| {
+            FhirDateTime::parse(text)
+                .unwrap()
+                .shift_days(shift)
+                .as_str()
+                .to_string()
+        };
+        assert_eq!(patient["birthDate"], shifted("1970-01-31"));
+        assert_eq!(order["authoredOn"], shifted("2024-05-01T10:00:00+02:00"));
+        assert_eq!(order["occurrencePeriod"]["start"], "2024-05");
+        assert_eq!(order["occurrencePeriod"]["end"], shifted("2024-05-20"));
+
+        // Resources with no resolvable patient share the key's fallback shift.
+        let unlinked = deid.unlinked_shift_days();
+        assert!(unlinked != 0 && unlinked.abs() <= 30);
+        let mut orphan = json!({ "resourceType": "Practitioner", "birthDate": "1980-02-02" });
+        deid.deidentify_resource(&mut orphan);
+        assert_eq!(
+            orphan["birthDate"],
+            FhirDateTime::parse("1980-02-02")
+                .unwrap()
+                .shift_days(unlinked)
+                .as_str()
+        );
+    }
+
+    #[test]
+    fn inbound_pseudonymized_labels_are_not_trusted() {
+        let deid = deid(DeidConfig::default());
+        let mut bundle = bundle();
+        let spoofed = json!([{ "system": SECURITY_LABEL_SYSTEM, "code": "PSEUDED" }]);
+        for entry in &mut bundle.entry {
+            entry.resource.as_mut().unwrap()["meta"] = json!({ "security": spoofed });
+        }
+        deid.deidentify_bundle(&mut bundle);
+
+        let patient = bundle.entry[0].resource.as_ref().unwrap();
+        let order = bundle.entry[1].resource.as_ref().unwrap();
+        assert_eq!(patient["id"], deid.pseudonym("Patient", "P-1").as_str());
+        assert!(patient.get("name").is_none());
+        assert_ne!(patient["birthDate"], "1970-01-31");
+        assert_eq!(
+            order["id"],
+            deid.pseudonym("ServiceRequest", "SR-1").as_str()
+        );
+        assert!(order.get("description").is_none());
+        assert_eq!(order["subject"]["display"], "[redacted]");
+        assert_eq!(order["meta"]["security"].as_array().unwrap().len(), 1);
+
+        let mut resource = json!({
+            "resourceType": "Patient",
+            "id": "P-2",
+            "meta": { "security": spoofed },
+            "name": [{ "family": "Roe" }]
+        });
+        deid.deidentify_resource(&mut resource);
+        assert_eq!(resource["id"], deid.pseudonym("Patient", "P-2").as_str());
+        assert!(resource.get("name").is_none());
+    }
+
+    #[test]
+    fn scrubs_free_text_and_staging_rows() {
+        let config = DeidConfig::from_toml_str(
+            r#"
+            text = "scrub"
+            [[scrub]]
+            pattern = "\\d{3}-\\d{3}-\\d{4}"
+            replacement = "[PHONE]"
+            [[scrub]]
+            pattern = "Jane Doe"
+            "#,
+        )
+        .unwrap();
+        let deid = deid(config);
+        let mut bundle = bundle();
+        deid.deidentify_bundle(&mut bundle);
+        let order = bundle.entry[1].resource.as_ref().unwrap();
+        assert_eq!(order["description"], "PET for [redacted], call [PHONE]");
+        assert_eq!(order["note"][0], json!({ "text": "Call [PHONE]" }));
+
+        let mut rows: StagingRows = (
+            vec![dfps_core::staging::StgServiceRequestFlat {
+                sr_id: "SR-1".into(),
+                patient_id: "P-1".into(),
+                description: "Jane Doe".into(),
+                identifiers: vec!["urn:placer|A-1".into()],
+                ..Default::default()
+            }],
+            vec![dfps_core::staging::StgSrCodeExploded {
+                sr_id: "SR-1".into(),
+                system: None,
+                code: None,
+                display: None,
+                provenance: None,
+            }],
+        );
+        deid.deidentify_staging(&mut rows);
+        assert_eq!(rows.0[0].sr_id, deid.pseudonym("ServiceRequest", "SR-1"));
+        assert_eq!(rows.1[0].sr_id, rows.0[0].sr_id);
+        assert_eq!(rows.0[0].description, "[redacted]");
+        assert!(rows.0[0].identifiers[0].starts_with("urn:placer|"));
+
+        let mut issue = CsvRowIssue {
+            line: 3,
+            sr_id: Some("SR-1".into()),
+            issue: crate::validation::ValidationIssue::new(
+                "CSV_INVALID_DATE",
+                crate::validation::ValidationSeverity::Error,
+                "ordered_at 'Jane Doe 555-123-4567' is not a date",
+                crate::validation::RequirementRef::STATUS,
+            ),
+        };
+        deid.deidentify_csv_issue(&mut issue);
+        assert_eq!(issue.sr_id, Some(deid.pseudonym("ServiceRequest", "SR-1")));
+        assert_eq!(
+            issue.issue.message,
+            "ordered_at '[redacted] [PHONE]' is not a date"
+        );
+    }
+
+    #[test]
+    fn contained_resources_and_extension_values_are_deidentified() {
+        let deid = deid(DeidConfig::default());
+        let mut order = json!({
+            "resourceType": "ServiceRequest",
+            "id": "SR-9",
+            "contained": [{
+                "resourceType": "Patient",
+                "id": "p",
+                "text": { "status": "generated", "div": "<div>John Smith</div>" },
+                "name": [{ "family": "Smith", "given": ["John"] }],
+                "address": [{ "line": ["1 Main St"] }],
+                "telecom": [{ "system": "phone", "value": "555-123-4567" }],
+                "identifier": [{ "system": "urn:mrn", "value": "MRN-9" }]
+            }],
+            "subject": { "reference": "#p" },
+            "extension": [
+                { "url": "https://dfps.example/ext/contact", "valueString": "call John" },
+                { "url": "https://dfps.example/ext/mrn", "valueIdentifier": { "value": "MRN-9" } },
+                { "url": "https://dfps.example/ext/kin", "valueHumanName": { "family": "Smith" } }
+            ]
+        });
+        deid.deidentify_resource(&mut order);
+
+        let patient = &order["contained"][0];
+        let id = deid.pseudonym("Patient", "p");
+        assert_eq!(patient["id"], id.as_str());
+        assert_eq!(order["subject"]["reference"], format!("#{id}"));
+        for key in ["text", "name", "address", "telecom"] {
+            assert!(patient.get(key).is_none(), "{key} kept");
+        }
+        assert_ne!(patient["identifier"][0]["value"], "MRN-9");
+        assert!(!order.to_string().contains("Smith") && !order.to_string().contains("MRN-9"));
+        assert_eq!(order["extension"][0]["valueString"], "[redacted]");
+
+        let mut rows: StagingRows = (
+            vec![dfps_core::staging::StgServiceRequestFlat {
+                sr_id: "SR-9".into(),
+                patient_id: "P-9".into(),
+                extensions: [
+                    ("contact", "call John"),
+                    ("visit", "2024-05-01,2024-05-02/"),
+                    ("ordering", "Patient/P-9"),
+                ]
+                .into_iter()
+                .map(|(column, value)| (column.to_string(), value.to_string()))
+                .collect(),
+                ..Default::default()
+            }],
+            Vec::new(),
+        );
+        deid.deidentify_staging(&mut rows);
+        let shift = deid.shift_days("P-9");
+        let day = |text: &str| shift_date_text(text, shift).unwrap();
+        let extensions = &rows.0[0].extensions;
+        assert_eq!(extensions["contact"], "[redacted]");
+        assert_eq!(
+            extensions["visit"],
+            format!("{},{}/", day("2024-05-01"), day("2024-05-02"))
+        );
+        assert_eq!(
+            extensions["ordering"],
+            format!("Patient/{}", deid.pseudonym("Patient", "P-9"))
+        );
+    }
+
+    #[test]
+    fn rejects_short_keys_and_inconsistent_configs() {
+        assert!(Deidentifier::new("short", DeidConfig::default()).is_err());
+        for text in [
+            "text = \"scrub\"",
+            "[[scrub]]\npattern = \"x\"",
+            "max_shift_days = 100000",
+            "text = \"scrub\"\n[[scrub]]\npattern = \"(\"",
+            "salt = \"x\"",
+        ] {
+            let err = DeidConfig::from_toml_str(text).unwrap_err();
+            assert_eq!(err.code(), "invalid_deid_config", "{text}");
+        }
+        assert!(format!("{:?}", deid(DeidConfig::default())).contains("<redacted>"));
+    }
+}
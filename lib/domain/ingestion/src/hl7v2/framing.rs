@@ -0,0 +1,288 @@
+//! Splitting a byte stream into HL7 v2 messages.
+//!
+//! Two layouts are accepted, detected from the first non-whitespace byte:
+//! MLLP frames (`0x0B` message `0x1C 0x0D`) and plain files where segments
+//! sit on their own lines and every `MSH` segment starts a new message.
+//! Batch envelope segments (`FHS`, `BHS`, `BTS`, `FTS`) are skipped.
+//!
+//! Messages are split as bytes and then decoded in the character set MSH-18
+//! declares: UTF-8 when it is empty or `UNICODE UTF-8`, `ASCII`, or `8859/1`
+//! (Latin-1). Other character sets, and bytes that are invalid in the
+//! declared one, fail that message instead of being replaced.
+
+use std::{collections::VecDeque, io::BufRead};
+
+use super::Hl7Error;
+
+/// MLLP start-of-block byte.
+pub const MLLP_START: u8 = 0x0b;
+/// MLLP end-of-block byte, followed by a carriage return.
+pub const MLLP_END: u8 = 0x1c;
+
+const BATCH_SEGMENTS: [&str; 4] = ["FHS", "BHS", "BTS", "FTS"];
+
+/// Reads raw HL7 v2 messages (segments joined with `\r`) from a reader.
+pub struct MessageReader<R> {
+    reader: R,
+    /// `None` until the first non-whitespace byte has been seen.
+    mllp: Option<bool>,
+    /// Segments of the message being assembled (newline layout).
+    current: Vec<Vec<u8>>,
+    ready: VecDeque<Vec<u8>>,
+    done: bool,
+}
+
+impl<R: BufRead> MessageReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            mllp: None,
+            current: Vec::new(),
+            ready: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    fn detect(&mut self) -> Result<Option<bool>, Hl7Error> {
+        loop {
+            let buf = self.reader.fill_buf().map_err(io_error)?;
+            let Some(&first) = buf.first() else {
+                return Ok(None);
+            };
+            if first.is_ascii_whitespace() {
+                self.reader.consume(1);
+                continue;
+            }
+            return Ok(Some(first == MLLP_START));
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Vec<u8>>, Hl7Error> {
+        let mut skipped = Vec::new();
+        self.reader
+            .read_until(MLLP_START, &mut skipped)
+            .map_err(io_error)?;
+        let started = skipped.last() == Some(&MLLP_START);
+        if started {
+            skipped.pop();
+        }
+        if !skipped.iter().all(u8::is_ascii_whitespace) {
+            return Err(Hl7Error::Framing("data outside an MLLP frame".to_string()));
+        }
+        if !started {
+            return Ok(None);
+        }
+        let mut frame = Vec::new();
+        self.reader
+            .read_until(MLLP_END, &mut frame)
+            .map_err(io_error)?;
+        if frame.pop() != Some(MLLP_END) {
+            return Err(Hl7Error::Framing(
+                "MLLP frame is missing its end block".to_string(),
+            ));
+        }
+        let segments: Vec<&[u8]> = frame
+            .split(|&byte| byte == b'\r' || byte == b'\n')
+            .filter(|segment| !segment.trim_ascii().is_empty() && !is_batch_segment(segment))
+            .collect();
+        Ok(Some(segments.join(&b'\r')))
+    }
+
+    /// Read lines until at least one message is complete (newline layout).
+    fn fill_ready(&mut self) -> Result<(), Hl7Error> {
+        let mut line = Vec::new();
+        while self.ready.is_empty() {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line).map_err(io_error)? == 0 {
+                self.flush();
+                return Ok(());
+            }
+            for segment in line.split(|&byte| byte == b'\r' || byte == b'\n') {
+                if segment.trim_ascii().is_empty() {
+                    // A blank line also ends a message.
+                    if line.trim_ascii().is_empty() {
+                        self.flush();
+                    }
+                    continue;
+                }
+                if is_batch_segment(segment) {
+                    continue;
+                }
+                if segment.starts_with(b"MSH") {
+                    self.flush();
+                }
+                self.current.push(segment.to_vec());
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        if !self.current.is_empty() {
+            self.ready.push_back(self.current.join(&b'\r'));
+            self.current.clear();
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for MessageReader<R> {
+    type Item = Result<String, Hl7Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mllp = match self.mllp {
+            Some(mllp) => mllp,
+            None => match self.detect() {
+                Ok(Some(mllp)) => *self.mllp.insert(mllp),
+                Ok(None) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            },
+        };
+        let next = if mllp {
+            self.next_frame()
+        } else {
+            self.fill_ready().map(|()| self.ready.pop_front())
+        };
+        match next {
+            // A message that does not decode leaves the framing intact, so
+            // reading carries on with the next one.
+            Ok(Some(message)) => Some(decode(message)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+fn is_batch_segment(segment: &[u8]) -> bool {
+    BATCH_SEGMENTS
+        .iter()
+        .any(|id| segment.trim_ascii_start().starts_with(id.as_bytes()))
+}
+
+/// Decode a message in the character set declared by its MSH-18.
+fn decode(message: Vec<u8>) -> Result<String, Hl7Error> {
+    let header = message
+        .split(|&byte| byte == b'\r')
+        .next()
+        .unwrap_or_default();
+    match charset(header).as_deref() {
+        None | Some("" | "UNICODE UTF-8") => String::from_utf8(message)
+            .map_err(|err| Hl7Error::Encoding(format!("message is not valid UTF-8: {err}"))),
+        Some("ASCII") if !message.is_ascii() => Err(Hl7Error::Encoding(
+            "non-ASCII byte in a message declared ASCII".to_string(),
+        )),
+        Some("ASCII" | "8859/1") => Ok(message.iter().map(|&byte| char::from(byte)).collect()),
+        Some(other) => Err(Hl7Error::Encoding(format!(
+            "unsupported character set '{other}'"
+        ))),
+    }
+}
+
+/// First repetition of MSH-18, read from the raw header.
+fn charset(header: &[u8]) -> Option<String> {
+    let (&separator, rest) = header.strip_prefix(b"MSH")?.split_first()?;
+    // MSH-2 lists the component, repetition, escape and subcomponent
+    // characters, in that order.
+    let repetition = rest.get(1).copied();
+    let field = rest.split(|&byte| byte == separator).nth(16)?;
+    let first = field.split(|&byte| Some(byte) == repetition).next()?;
+    let name: String = first.iter().map(|&byte| char::from(byte)).collect();
+    Some(name.trim().to_ascii_uppercase())
+}
+
+fn io_error(err: std::io::Error) -> Hl7Error {
+    Hl7Error::Framing(format!("failed to read input: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(input: &[u8]) -> Vec<Result<String, Hl7Error>> {
+        MessageReader::new(input).collect()
+    }
+
+    #[test]
+    fn splits_mllp_frames() {
+        let input = b"\x0bMSH|^~\\&|A\rPID|1\r\x1c\r\n\x0bMSH|^~\\&|B\rOBR|1\x1c\r";
+        let messages: Vec<_> = read(input).into_iter().map(Result::unwrap).collect();
+        assert_eq!(messages, vec!["MSH|^~\\&|A\rPID|1", "MSH|^~\\&|B\rOBR|1"]);
+
+        let unterminated = read(b"\x0bMSH|^~\\&|A\rPID|1");
+        assert!(matches!(unterminated[..], [Err(Hl7Error::Framing(_))]));
+    }
+
+    #[test]
+    fn splits_newline_files_on_each_msh() {
+        let input = b"BHS|^~\\&|LAB\nMSH|^~\\&|A\r\nPID|1\n\nMSH|^~\\&|B\rPID|2\rOBR|1\nMSH|^~\\&|C\nBTS|3\n";
+        let messages: Vec<_> = read(input).into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "MSH|^~\\&|A\rPID|1",
+                "MSH|^~\\&|B\rPID|2\rOBR|1",
+                "MSH|^~\\&|C"
+            ]
+        );
+        assert!(read(b"  \n").is_empty());
+    }
+
+    #[test]
+    fn decodes_the_character_set_declared_in_msh_18() {
+        let header =
+            |charset: &str| format!("MSH|^~\\&|A|||||||1|P|2.5.1||||||{charset}\rPID|1||M||");
+        let message = |charset: &str, name: &[u8]| {
+            let mut bytes = header(charset).into_bytes();
+            bytes.extend_from_slice(name);
+            bytes.push(b'\n');
+            read(&bytes).remove(0)
+        };
+
+        assert!(
+            message("", "Müller".as_bytes())
+                .unwrap()
+                .ends_with("|Müller")
+        );
+        assert!(
+            message("8859/1", b"M\xfcller")
+                .unwrap()
+                .ends_with("|Müller")
+        );
+        assert!(
+            message("UNICODE UTF-8~8859/1", "Müller".as_bytes())
+                .unwrap()
+                .ends_with("|Müller")
+        );
+        for (charset, name) in [
+            ("", &b"M\xfcller"[..]),
+            ("ASCII", "Müller".as_bytes()),
+            ("ISO IR87", b"Muller"),
+        ] {
+            assert!(
+                matches!(message(charset, name), Err(Hl7Error::Encoding(_))),
+                "{charset:?} should fail"
+            );
+        }
+
+        // The next message still reads after one that does not decode.
+        let mut input = b"\x0bMSH|^~\\&|A\rPID|1||M\xfc\x1c\r".to_vec();
+        input.extend_from_slice(b"\x0bMSH|^~\\&|B\x1c\r");
+        let results = read(&input);
+        assert!(matches!(results[0], Err(Hl7Error::Encoding(_))));
+        assert_eq!(results[1], Ok("MSH|^~\\&|B".to_string()));
+    }
+}
@@ -0,0 +1,843 @@
+//! ORM^O01 / OMI^O23 order messages mapped onto FHIR resources.
+//!
+//! PID becomes a Patient, PV1 an Encounter and every ORC/OBR order group a
+//! ServiceRequest; the resources are returned as a `collection` Bundle so the
+//! rest of the pipeline treats them like any other FHIR input.
+
+use super::{
+    Hl7Error,
+    message::{Message, Repetition, Segment},
+};
+use crate::transforms::IngestionError;
+use dfps_core::{
+    fhir::{
+        self, Annotation, BundleType, CodeableConcept, Coding, Identifier, PatientDeceased, Period,
+        Reference, ServiceRequestOccurrence,
+    },
+    order::ServiceRequestStatus,
+    value::{FhirDate, FhirDateTime},
+};
+use sha2::{Digest, Sha256};
+
+/// `Identifier.type` codes (HL7 v2 table 0203).
+const IDENTIFIER_TYPE_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/v2-0203";
+const ENCOUNTER_CLASS_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/v3-ActCode";
+/// Diagnostic service section IDs (HL7 v2 table 0074), used for OBR-24.
+const SERVICE_SECTION_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/v2-0074";
+
+/// Options for [`message_to_bundle_with_options`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hl7MappingOptions {
+    /// UTC offset applied to timestamps that carry a time but no offset.
+    /// FHIR requires a zone with every time, so without one such timestamps
+    /// keep only their date.
+    local_offset: Option<String>,
+}
+
+impl Hl7MappingOptions {
+    /// Assume `offset` (`Z`, `+hh:mm` or `-hh:mm`) for zone-less timestamps.
+    pub fn with_local_offset(mut self, offset: &str) -> Result<Self, Hl7Error> {
+        FhirDateTime::parse(&format!("2000-01-01T00:00:00{offset}")).map_err(|_| {
+            Hl7Error::InvalidValue {
+                field: "local offset",
+                value: offset.to_string(),
+            }
+        })?;
+        self.local_offset = Some(offset.to_string());
+        Ok(self)
+    }
+
+    pub fn local_offset(&self) -> Option<&str> {
+        self.local_offset.as_deref()
+    }
+}
+
+/// Map an order message with the default [`Hl7MappingOptions`].
+pub fn message_to_bundle(message: &Message) -> Result<fhir::Bundle, IngestionError> {
+    message_to_bundle_with_options(message, &Hl7MappingOptions::default())
+}
+
+/// Map an ORM^O01 or OMI^O23 message into a `collection` Bundle holding the
+/// Patient, the Encounter (when PV1 carries a visit number) and one
+/// ServiceRequest per order group.
+pub fn message_to_bundle_with_options(
+    message: &Message,
+    options: &Hl7MappingOptions,
+) -> Result<fhir::Bundle, IngestionError> {
+    match message.message_type() {
+        (Some("ORM"), Some("O01")) | (Some("OMI"), Some("O23")) => {}
+        (code, event) => {
+            return Err(Hl7Error::UnsupportedMessage(format!(
+                "{}^{}",
+                code.unwrap_or_default(),
+                event.unwrap_or_default()
+            ))
+            .into());
+        }
+    }
+    let mapper = Mapper { options };
+
+    let pid = message
+        .segment("PID")
+        .ok_or(Hl7Error::MissingSegment("PID"))?;
+    let patient = mapper.patient(pid)?;
+    let patient_id = patient.id.clone().unwrap_or_default();
+    let encounter = message
+        .segment("PV1")
+        .map(|pv1| mapper.encounter(pv1, &patient_id))
+        .transpose()?
+        .flatten();
+    let encounter_id = encounter
+        .as_ref()
+        .and_then(|encounter| encounter.id.clone());
+
+    let groups = order_groups(message);
+    if groups.is_empty() {
+        return Err(Hl7Error::MissingSegment("OBR").into());
+    }
+
+    let mut entry = vec![fhir::BundleEntry::from_resource(patient)?];
+    if let Some(encounter) = encounter {
+        entry.push(fhir::BundleEntry::from_resource(encounter)?);
+    }
+    for group in &groups {
+        let order = mapper.service_request(group, &patient_id, encounter_id.as_deref())?;
+        entry.push(fhir::BundleEntry::from_resource(order)?);
+    }
+    Ok(fhir::Bundle {
+        resource_type: "Bundle".into(),
+        id: None,
+        bundle_type: Some(BundleType::Collection.as_fhir_code().into()),
+        entry,
+    })
+}
+
+/// An ORC and/or OBR with the segments that belong to it.
+#[derive(Default)]
+struct OrderGroup<'a> {
+    orc: Option<&'a Segment>,
+    obr: Option<&'a Segment>,
+    tq1: Option<&'a Segment>,
+    ipc: Vec<&'a Segment>,
+    notes: Vec<&'a Segment>,
+}
+
+/// Split the message into order groups: an ORC opens a group, and an OBR
+/// opens one too unless the current group is still waiting for its OBR.
+fn order_groups(message: &Message) -> Vec<OrderGroup<'_>> {
+    let mut groups: Vec<OrderGroup<'_>> = Vec::new();
+    for segment in message.segments() {
+        match segment.id() {
+            "ORC" => groups.push(OrderGroup {
+                orc: Some(segment),
+                ..OrderGroup::default()
+            }),
+            "OBR" => match groups.last_mut() {
+                Some(group) if group.obr.is_none() => group.obr = Some(segment),
+                _ => groups.push(OrderGroup {
+                    obr: Some(segment),
+                    ..OrderGroup::default()
+                }),
+            },
+            id => {
+                let Some(group) = groups.last_mut() else {
+                    continue;
+                };
+                match id {
+                    "TQ1" if group.tq1.is_none() => group.tq1 = Some(segment),
+                    "IPC" => group.ipc.push(segment),
+                    // NTEs before the OBR annotate the patient or the ORC.
+                    "NTE" if group.obr.is_some() => group.notes.push(segment),
+                    _ => {}
+                }
+            }
+        }
+    }
+    groups
+}
+
+struct Mapper<'a> {
+    options: &'a Hl7MappingOptions,
+}
+
+impl Mapper<'_> {
+    fn patient(&self, pid: &Segment) -> Result<fhir::Patient, Hl7Error> {
+        let identifiers: Vec<&Repetition> = pid
+            .field(3)
+            .map(|field| field.repetitions().collect())
+            .unwrap_or_default();
+        let primary = identifiers
+            .iter()
+            .find(|cx| cx.component(5) == Some(fhir::MRN_IDENTIFIER_TYPE))
+            .or(identifiers.first())
+            .and_then(|cx| cx.component(1))
+            .ok_or(Hl7Error::MissingField("PID-3"))?;
+
+        let gender = match pid.value(8, 1) {
+            None => None,
+            Some("M") => Some("male"),
+            Some("F") => Some("female"),
+            Some("U") => Some("unknown"),
+            Some("A" | "N" | "O") => Some("other"),
+            Some(other) => return Err(invalid("PID-8", other)),
+        };
+        let birth_date = pid
+            .value(7, 1)
+            .map(|ts| parse_date("PID-7", ts))
+            .transpose()?;
+        let deceased = match (pid.value(30, 1), pid.value(29, 1)) {
+            (_, Some(ts)) => Some(PatientDeceased::DateTime(self.datetime("PID-29", ts)?)),
+            (Some("Y"), None) => Some(PatientDeceased::Boolean(true)),
+            (Some("N"), None) => Some(PatientDeceased::Boolean(false)),
+            (Some(other), None) => return Err(invalid("PID-30", other)),
+            (None, None) => None,
+        };
+
+        Ok(fhir::Patient {
+            id: Some(fhir_id(primary)),
+            identifier: identifiers
+                .iter()
+                .filter_map(|cx| cx_identifier(cx))
+                .collect(),
+            gender: gender.map(str::to_string),
+            birth_date,
+            deceased,
+            ..Default::default()
+        })
+    }
+
+    /// `None` when PV1-19 (visit number) is empty, since there is nothing
+    /// stable to key the Encounter on.
+    fn encounter(
+        &self,
+        pv1: &Segment,
+        patient_id: &str,
+    ) -> Result<Option<fhir::Encounter>, Hl7Error> {
+        let Some(visit) = pv1.field(19).and_then(|field| field.first()) else {
+            return Ok(None);
+        };
+        let Some(visit_number) = visit.component(1) else {
+            return Ok(None);
+        };
+        let class = pv1.value(2, 1).and_then(|class| {
+            let (code, display) = match class {
+                "I" | "B" => ("IMP", "inpatient encounter"),
+                "O" | "R" => ("AMB", "ambulatory"),
+                "E" => ("EMER", "emergency"),
+                "P" => ("PRENC", "pre-admission"),
+                _ => return None,
+            };
+            Some(Coding {
+                system: Some(ENCOUNTER_CLASS_SYSTEM.into()),
+                code: Some(code.into()),
+                display: Some(display.into()),
+            })
+        });
+        let start = pv1
+            .value(44, 1)
+            .map(|ts| self.datetime("PV1-44", ts))
+            .transpose()?;
+        let end = pv1
+            .value(45, 1)
+            .map(|ts| self.datetime("PV1-45", ts))
+            .transpose()?;
+        let status = if end.is_some() {
+            "finished"
+        } else {
+            "in-progress"
+        };
+
+        Ok(Some(fhir::Encounter {
+            id: Some(fhir_id(visit_number)),
+            identifier: cx_identifier(visit)
+                .map(|identifier| typed(identifier, "VN"))
+                .into_iter()
+                .collect(),
+            status: Some(status.into()),
+            class,
+            subject: Some(reference("Patient", patient_id)),
+            period: (start.is_some() || end.is_some()).then_some(Period { start, end }),
+            ..Default::default()
+        }))
+    }
+
+    fn service_request(
+        &self,
+        group: &OrderGroup<'_>,
+        patient_id: &str,
+        encounter_id: Option<&str>,
+    ) -> Result<fhir::ServiceRequest, Hl7Error> {
+        // ORC fields win over their OBR duplicates.
+        let either = |orc: usize, obr: usize| {
+            group
+                .orc
+                .and_then(|seg| seg.field(orc))
+                .and_then(|field| field.first())
+                .or_else(|| {
+                    group
+                        .obr
+                        .and_then(|seg| seg.field(obr))
+                        .and_then(|field| field.first())
+                })
+        };
+        let obr = |n: usize| group.obr.and_then(|seg| seg.field(n));
+
+        let placer = either(2, 2);
+        let filler = either(3, 3);
+        let order_number = placer
+            .or(filler)
+            .and_then(|ei| ei.component(1))
+            .ok_or(Hl7Error::MissingField("ORC-2/OBR-2"))?;
+
+        let mut identifier = Vec::new();
+        identifier.extend(placer.and_then(ei_identifier).map(|id| typed(id, "PLAC")));
+        identifier.extend(filler.and_then(ei_identifier).map(|id| typed(id, "FILL")));
+        identifier.extend(
+            group
+                .ipc
+                .iter()
+                .filter_map(|ipc| ipc.field(1)?.first())
+                .filter_map(ei_identifier)
+                .map(|id| typed(id, "ACSN")),
+        );
+
+        let status = self.status(group.orc)?;
+        let priority = group
+            .tq1
+            .and_then(|tq1| tq1.value(9, 1))
+            .or_else(|| either(7, 27).and_then(|tq| tq.component(6)))
+            .and_then(|priority| match priority {
+                "S" => Some("stat"),
+                "A" => Some("asap"),
+                "R" => Some("routine"),
+                "T" => Some("urgent"),
+                _ => None,
+            });
+
+        let authored_on = group
+            .orc
+            .and_then(|orc| orc.value(9, 1))
+            .map(|ts| self.datetime("ORC-9", ts))
+            .transpose()?;
+        let occurrence = self.occurrence(group, either(7, 27))?;
+
+        let requester = either(12, 16)
+            .and_then(xcn_display)
+            .map(|display| Reference {
+                reference: None,
+                display: Some(display),
+            });
+
+        let note = group
+            .notes
+            .iter()
+            .filter_map(|nte| {
+                let lines: Vec<&str> = nte
+                    .field(3)?
+                    .repetitions()
+                    .filter_map(|rep| rep.component(1))
+                    .collect();
+                (!lines.is_empty()).then(|| Annotation {
+                    author: None,
+                    time: None,
+                    text: Some(lines.join("\n")),
+                })
+            })
+            .collect();
+
+        Ok(fhir::ServiceRequest {
+            id: Some(fhir_id(order_number)),
+            identifier,
+            requisition: group
+                .orc
+                .and_then(|orc| orc.field(4)?.first())
+                .and_then(ei_identifier)
+                .map(|id| typed(id, "PGN")),
+            status: Some(status.as_fhir_code().into()),
+            intent: Some("order".into()),
+            priority: priority.map(str::to_string),
+            subject: Some(reference("Patient", patient_id)),
+            encounter: encounter_id.map(|id| reference("Encounter", id)),
+            requester,
+            code: obr(4).and_then(|field| field.first()).and_then(cwe_concept),
+            category: obr(24)
+                .and_then(|field| field.first())
+                .and_then(|id| id.component(1))
+                .map(|section| CodeableConcept {
+                    coding: vec![Coding {
+                        system: Some(SERVICE_SECTION_SYSTEM.into()),
+                        code: Some(section.into()),
+                        display: None,
+                    }],
+                    text: None,
+                })
+                .into_iter()
+                .collect(),
+            reason_code: obr(31)
+                .map(|field| field.repetitions().filter_map(cwe_concept).collect())
+                .unwrap_or_default(),
+            authored_on,
+            occurrence,
+            note,
+            ..Default::default()
+        })
+    }
+
+    /// ORC-5 (order status) when present, otherwise ORC-1 (order control).
+    /// A bare OBR is a new order.
+    fn status(&self, orc: Option<&Segment>) -> Result<ServiceRequestStatus, Hl7Error> {
+        use ServiceRequestStatus::*;
+
+        if let Some(status) = orc.and_then(|orc| orc.value(5, 1)) {
+            return match status {
+                "A" | "IP" | "SC" => Ok(Active),
+                "CM" => Ok(Completed),
+                "CA" => Ok(Cancelled),
+                "DC" | "RP" => Ok(Revoked),
+                "HD" => Ok(OnHold),
+                "ER" => Ok(EnteredInError),
+                other => Err(invalid("ORC-5", other)),
+            };
+        }
+        match orc.and_then(|orc| orc.value(1, 1)).unwrap_or("NW") {
+            "NW" | "OK" | "SC" | "XO" | "XX" | "RL" | "RE" | "SN" => Ok(Active),
+            "CA" | "OC" | "CR" => Ok(Cancelled),
+            "DC" | "OD" | "DR" => Ok(Revoked),
+            "HD" | "OH" => Ok(OnHold),
+            other => Err(invalid("ORC-1", other)),
+        }
+    }
+
+    /// TQ1-7/TQ1-8, then the TQ start/end (ORC-7/OBR-27 .4/.5), then OBR-6.
+    fn occurrence(
+        &self,
+        group: &OrderGroup<'_>,
+        quantity_timing: Option<&Repetition>,
+    ) -> Result<Option<ServiceRequestOccurrence>, Hl7Error> {
+        let bounds = match group.tq1 {
+            Some(tq1) => (
+                tq1.value(7, 1).map(|ts| ("TQ1-7", ts)),
+                tq1.value(8, 1).map(|ts| ("TQ1-8", ts)),
+            ),
+            None => (None, None),
+        };
+        let bounds = match (bounds, quantity_timing) {
+            ((None, None), Some(tq)) => (
+                tq.component(4).map(|ts| ("ORC-7/OBR-27", ts)),
+                tq.component(5).map(|ts| ("ORC-7/OBR-27", ts)),
+            ),
+            (bounds, _) => bounds,
+        };
+        let (start, end) = match bounds {
+            (None, None) => (
+                group
+                    .obr
+                    .and_then(|obr| obr.value(6, 1))
+                    .map(|ts| ("OBR-6", ts)),
+                None,
+            ),
+            bounds => bounds,
+        };
+        let start = start
+            .map(|(field, ts)| self.datetime(field, ts))
+            .transpose()?;
+        let end = end
+            .map(|(field, ts)| self.datetime(field, ts))
+            .transpose()?;
+        Ok(match (start, end) {
+            (None, None) => None,
+            (Some(start), None) => Some(ServiceRequestOccurrence::DateTime(start)),
+            (start, end) => Some(ServiceRequestOccurrence::Period(Period { start, end })),
+        })
+    }
+
+    /// HL7 `TS`/`DTM` (`YYYY[MM[DD[HH[MM[SS[.S+]]]]]][+/-ZZZZ]`) as a FHIR
+    /// dateTime; see [`Hl7MappingOptions`] for timestamps without an offset.
+    fn datetime(&self, field: &'static str, ts: &str) -> Result<FhirDateTime, Hl7Error> {
+        let (stamp, offset) = match ts.find(['+', '-']) {
+            Some(at) => (&ts[..at], Some(&ts[at..])),
+            None => (ts, None),
+        };
+        let (digits, fraction) = match stamp.split_once('.') {
+            Some((digits, fraction)) => (digits, Some(fraction)),
+            None => (stamp, None),
+        };
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid(field, ts));
+        }
+        let date = hl7_date(digits).ok_or_else(|| invalid(field, ts))?;
+        let time = &digits[digits.len().min(8)..];
+        let offset = match offset {
+            Some(offset) => {
+                let (sign, hhmm) = offset.split_at(1);
+                if hhmm.len() != 4 {
+                    return Err(invalid(field, ts));
+                }
+                Some(format!("{sign}{}:{}", &hhmm[..2], &hhmm[2..]))
+            }
+            None => self.options.local_offset.clone(),
+        };
+        let text = match (time.len(), offset) {
+            (0, _) | (_, None) => date,
+            (2 | 4 | 6, Some(offset)) => {
+                let part = |at: usize| time.get(at..at + 2).unwrap_or("00");
+                let fraction = fraction.map(|f| format!(".{f}")).unwrap_or_default();
+                format!(
+                    "{date}T{}:{}:{}{fraction}{offset}",
+                    part(0),
+                    part(2),
+                    part(4)
+                )
+            }
+            _ => return Err(invalid(field, ts)),
+        };
+        FhirDateTime::parse(&text).map_err(|_| invalid(field, ts))
+    }
+}
+
+/// Date part of an HL7 timestamp (`YYYY`, `YYYYMM` or `YYYYMMDD...`).
+fn hl7_date(digits: &str) -> Option<String> {
+    match digits.len() {
+        4 => Some(digits.to_string()),
+        6 => Some(format!("{}-{}", &digits[..4], &digits[4..6])),
+        len if len >= 8 => Some(format!(
+            "{}-{}-{}",
+            &digits[..4],
+            &digits[4..6],
+            &digits[6..8]
+        )),
+        _ => None,
+    }
+}
+
+fn parse_date(field: &'static str, ts: &str) -> Result<FhirDate, Hl7Error> {
+    let digits: String = ts.chars().take_while(char::is_ascii_digit).collect();
+    hl7_date(&digits)
+        .and_then(|date| FhirDate::parse(&date).ok())
+        .ok_or_else(|| invalid(field, ts))
+}
+
+fn invalid(field: &'static str, value: &str) -> Hl7Error {
+    Hl7Error::InvalidValue {
+        field,
+        value: value.to_string(),
+    }
+}
+
+/// FHIR ids allow `[A-Za-z0-9-.]{1,64}`. Other values keep their allowed
+/// characters, with `-` for the rest, and end in a hash of the original so
+/// identifiers that differ only in replaced or cut characters (`A B`, `A/B`)
+/// still get distinct ids.
+fn fhir_id(value: &str) -> String {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '.';
+    if (1..=64).contains(&value.len()) && value.chars().all(allowed) {
+        return value.to_string();
+    }
+    let digest = Sha256::digest(value.as_bytes());
+    let hash: String = digest[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    let mut id: String = value
+        .chars()
+        .map(|c| if allowed(c) { c } else { '-' })
+        .take(64 - hash.len() - 1)
+        .collect();
+    id.push('-');
+    id.push_str(&hash);
+    id
+}
+
+fn reference(resource_type: &str, id: &str) -> Reference {
+    Reference {
+        reference: Some(format!("{resource_type}/{id}")),
+        display: None,
+    }
+}
+
+/// Set `Identifier.type` to a table 0203 code.
+fn typed(mut identifier: Identifier, code: &str) -> Identifier {
+    identifier.identifier_type = Some(CodeableConcept {
+        coding: vec![Coding {
+            system: Some(IDENTIFIER_TYPE_SYSTEM.into()),
+            code: Some(code.into()),
+            display: None,
+        }],
+        text: None,
+    });
+    identifier
+}
+
+/// `urn:oid:` system for an HD/EI universal ID typed `ISO`.
+fn universal_system(id: Option<&str>, id_type: Option<&str>) -> Option<String> {
+    match (id, id_type) {
+        (Some(oid), Some("ISO")) => Some(format!("urn:oid:{oid}")),
+        (Some(uri), Some("URI")) => Some(uri.to_string()),
+        _ => None,
+    }
+}
+
+/// `CX` (patient and visit identifiers): ID, assigning authority, type code.
+fn cx_identifier(cx: &Repetition) -> Option<Identifier> {
+    let value = cx.component(1)?;
+    let mut identifier = Identifier {
+        value: Some(value.into()),
+        system: universal_system(cx.subcomponent(4, 2), cx.subcomponent(4, 3)),
+        assigner: cx.component(4).map(|namespace| {
+            Box::new(Reference {
+                reference: None,
+                display: Some(namespace.into()),
+            })
+        }),
+        ..Default::default()
+    };
+    if let Some(code) = cx.component(5) {
+        identifier = typed(identifier, code);
+    }
+    Some(identifier)
+}
+
+/// `EI` (order numbers): entity ID, namespace, universal ID and its type.
+fn ei_identifier(ei: &Repetition) -> Option<Identifier> {
+    Some(Identifier {
+        value: Some(ei.component(1)?.into()),
+        system: universal_system(ei.component(3), ei.component(4)),
+        assigner: ei.component(2).map(|namespace| {
+            Box::new(Reference {
+                reference: None,
+                display: Some(namespace.into()),
+            })
+        }),
+        ..Default::default()
+    })
+}
+
+/// `XCN` as `Family, Given`, falling back to the ID number.
+fn xcn_display(xcn: &Repetition) -> Option<String> {
+    match (xcn.component(2), xcn.component(3)) {
+        (Some(family), Some(given)) => Some(format!("{family}, {given}")),
+        (Some(name), None) | (None, Some(name)) => Some(name.to_string()),
+        (None, None) => xcn.component(1).map(str::to_string),
+    }
+}
+
+/// `CE`/`CWE`: identifier triple, alternate triple and original text (.9).
+fn cwe_concept(cwe: &Repetition) -> Option<CodeableConcept> {
+    let coding: Vec<Coding> = [(1, 2, 3), (4, 5, 6)]
+        .into_iter()
+        .filter_map(|(code, display, system)| {
+            let code = cwe.component(code)?;
+            Some(Coding {
+                system: cwe.component(system).map(coding_system),
+                code: Some(code.into()),
+                display: cwe.component(display).map(str::to_string),
+            })
+        })
+        .collect();
+    let text = cwe.component(9).or(cwe.component(2)).map(str::to_string);
+    (!coding.is_empty() || text.is_some()).then_some(CodeableConcept { coding, text })
+}
+
+/// Canonical URI for an HL7 v2 coding system name (table 0396).
+pub fn coding_system(name: &str) -> String {
+    match name.to_ascii_uppercase().as_str() {
+        "C4" | "C5" | "CPT" | "CPT4" => "http://www.ama-assn.org/go/cpt".into(),
+        "LN" | "LOINC" => "http://loinc.org".into(),
+        "SCT" | "SNOMED" | "SNOMEDCT" | "SNOMED-CT" => "http://snomed.info/sct".into(),
+        "I10" | "ICD10" => "http://hl7.org/fhir/sid/icd-10".into(),
+        "I10C" | "ICD10CM" => "http://hl7.org/fhir/sid/icd-10-cm".into(),
+        "I9C" | "ICD9CM" => "http://hl7.org/fhir/sid/icd-9-cm".into(),
+        "HCPCS" => "https://www.cms.gov/Medicare/Coding/HCPCSReleaseCodeSets".into(),
+        "NCIT" => "http://purl.obolibrary.org/obo/ncit".into(),
+        _ => format!("urn:hl7v2:coding-system:{name}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDER: &str = "MSH|^~\\&|EHR|HOSP|LIS|LAB|20240501083000-0500||ORM^O01^ORM_O01|MSG-1|P|2.5.1\r\
+        PID|1||MRN-100^^^HOSP&2.16.840.1.113883.19.5&ISO^MR||Doe^Jane||19800214|F\r\
+        PV1|1|O|CLINIC^101||||||||||||||||V-500^^^HOSP^VN|||||||||||||||||||||||||20240501080000-0500\r\
+        ORC|NW|PLC-1^EHR|FIL-9^LIS|GRP-1^EHR|||^^^20240502090000-0500^^S||20240501083000-0500|||1234^Smith^Ann\r\
+        OBR|1|PLC-1^EHR|FIL-9^LIS|71046^Chest X-ray 2 views^C4^XR2V^CXR 2V^L||||||||||||||||||||RAD|||||||R07.9^Chest pain^I10C\r\
+        NTE|1||Patient is pregnant\\.br\\shield abdomen\r\
+        ORC|CA|PLC-2^EHR\r\
+        OBR|2|PLC-2^EHR||24627-2^CT chest^LN\r";
+
+    #[test]
+    fn maps_pid_pv1_and_order_groups() {
+        let message = Message::parse(ORDER).unwrap();
+        let bundle = message_to_bundle(&message).unwrap();
+        assert_eq!(bundle.bundle_type.as_deref(), Some("collection"));
+        let resources: Vec<_> = bundle
+            .entry
+            .iter()
+            .map(|entry| entry.resource.clone().unwrap())
+            .collect();
+        assert_eq!(resources.len(), 4);
+
+        let patient: fhir::Patient = serde_json::from_value(resources[0].clone()).unwrap();
+        assert_eq!(patient.id.as_deref(), Some("MRN-100"));
+        assert_eq!(patient.gender.as_deref(), Some("female"));
+        assert_eq!(patient.birth_date.as_ref().unwrap().as_str(), "1980-02-14");
+        let mrn = patient.mrn().unwrap();
+        assert_eq!(
+            mrn.system.as_deref(),
+            Some("urn:oid:2.16.840.1.113883.19.5")
+        );
+
+        let encounter: fhir::Encounter = serde_json::from_value(resources[1].clone()).unwrap();
+        assert_eq!(encounter.id.as_deref(), Some("V-500"));
+        assert_eq!(encounter.class.unwrap().code.as_deref(), Some("AMB"));
+        assert_eq!(
+            encounter.period.unwrap().start.unwrap().as_str(),
+            "2024-05-01T08:00:00-05:00"
+        );
+
+        let order: fhir::ServiceRequest = serde_json::from_value(resources[2].clone()).unwrap();
+        assert_eq!(order.id.as_deref(), Some("PLC-1"));
+        assert_eq!(order.status.as_deref(), Some("active"));
+        assert_eq!(order.intent.as_deref(), Some("order"));
+        assert_eq!(order.priority.as_deref(), Some("stat"));
+        assert_eq!(
+            order.subject.unwrap().reference.as_deref(),
+            Some("Patient/MRN-100")
+        );
+        assert_eq!(
+            order.encounter.unwrap().reference.as_deref(),
+            Some("Encounter/V-500")
+        );
+        let code = order.code.unwrap();
+        let systems: Vec<_> = code
+            .coding
+            .iter()
+            .map(|coding| (coding.system.as_deref().unwrap(), coding.code.as_deref()))
+            .collect();
+        assert_eq!(
+            systems,
+            vec![
+                ("http://www.ama-assn.org/go/cpt", Some("71046")),
+                ("urn:hl7v2:coding-system:L", Some("XR2V"))
+            ]
+        );
+        assert_eq!(code.text.as_deref(), Some("Chest X-ray 2 views"));
+        assert_eq!(order.category[0].coding[0].code.as_deref(), Some("RAD"));
+        assert_eq!(
+            order.reason_code[0].coding[0].system.as_deref(),
+            Some("http://hl7.org/fhir/sid/icd-10-cm")
+        );
+        assert_eq!(
+            order.authored_on.unwrap().as_str(),
+            "2024-05-01T08:30:00-05:00"
+        );
+        assert!(matches!(
+            order.occurrence,
+            Some(ServiceRequestOccurrence::DateTime(ref at)) if at.as_str() == "2024-05-02T09:00:00-05:00"
+        ));
+        assert_eq!(
+            order.requester.unwrap().display.as_deref(),
+            Some("Smith, Ann")
+        );
+        assert_eq!(
+            order.note[0].text.as_deref(),
+            Some("Patient is pregnant\nshield abdomen")
+        );
+        let types: Vec<_> = order
+            .identifier
+            .iter()
+            .map(|id| {
+                id.identifier_type.as_ref().unwrap().coding[0]
+                    .code
+                    .as_deref()
+            })
+            .collect();
+        assert_eq!(types, vec![Some("PLAC"), Some("FILL")]);
+        assert_eq!(order.requisition.unwrap().value.as_deref(), Some("GRP-1"));
+
+        let cancelled: fhir::ServiceRequest = serde_json::from_value(resources[3].clone()).unwrap();
+        assert_eq!(cancelled.status.as_deref(), Some("cancelled"));
+        assert_eq!(
+            cancelled.code.unwrap().coding[0].system.as_deref(),
+            Some("http://loinc.org")
+        );
+    }
+
+    #[test]
+    fn zoneless_timestamps_keep_their_date_unless_an_offset_is_configured() {
+        let message = Message::parse(
+            "MSH|^~\\&|EHR|HOSP|||20240501||OMI^O23|MSG-2|P|2.5.1\r\
+             PID|1||MRN-7\r\
+             ORC|NW|PLC-7|||||||202405010830\r\
+             OBR|1|PLC-7||71046^Chest X-ray^C4\r\
+             IPC|ACC-1^RIS|PROC-1\r",
+        )
+        .unwrap();
+        let authored = |bundle: &fhir::Bundle| {
+            bundle.entry[1].resource.as_ref().unwrap()["authoredOn"].clone()
+        };
+
+        let bundle = message_to_bundle(&message).unwrap();
+        assert_eq!(authored(&bundle), "2024-05-01");
+        assert_eq!(
+            bundle.entry[1].resource.as_ref().unwrap()["identifier"][1]["type"]["coding"][0]["code"],
+            "ACSN"
+        );
+
+        let options = Hl7MappingOptions::default()
+            .with_local_offset("+02:00")
+            .unwrap();
+        let bundle = message_to_bundle_with_options(&message, &options).unwrap();
+        assert_eq!(authored(&bundle), "2024-05-01T08:30:00+02:00");
+
+        assert!(
+            Hl7MappingOptions::default()
+                .with_local_offset("CET")
+                .is_err()
+        );
+    }
+
+    fn hl7_error(message: &Message) -> Hl7Error {
+        match message_to_bundle(message) {
+            Err(IngestionError::Hl7(err)) => err,
+            other => panic!("expected an HL7 error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_other_message_types_and_bad_values() {
+        let unsupported = Message::parse("MSH|^~\\&|A||||||ORU^R01|1|P|2.5.1\rPID|1||M1").unwrap();
+        assert_eq!(
+            hl7_error(&unsupported),
+            Hl7Error::UnsupportedMessage("ORU^R01".into())
+        );
+
+        let bad_status =
+            Message::parse("MSH|^~\\&|A||||||ORM^O01|1|P|2.3\rPID|1||M1\rORC|ZZ|P1\rOBR|1|P1")
+                .unwrap();
+        assert_eq!(
+            hl7_error(&bad_status),
+            Hl7Error::InvalidValue {
+                field: "ORC-1",
+                value: "ZZ".into()
+            }
+        );
+
+        let no_orders = Message::parse("MSH|^~\\&|A||||||ORM^O01|1|P|2.3\rPID|1||M1").unwrap();
+        assert_eq!(hl7_error(&no_orders), Hl7Error::MissingSegment("OBR"));
+    }
+
+    #[test]
+    fn sanitized_ids_stay_distinct() {
+        assert_eq!(fhir_id("MRN-100.1"), "MRN-100.1");
+        let (space, slash) = (fhir_id("MRN 100"), fhir_id("MRN/100"));
+        assert!(space.starts_with("MRN-100-") && slash.starts_with("MRN-100-"));
+        assert_ne!(space, slash);
+
+        let long = "A".repeat(70);
+        let (a, b) = (fhir_id(&long), fhir_id(&format!("{long}B")));
+        assert!(a.len() <= 64 && b.len() <= 64);
+        assert_ne!(a, b);
+    }
+}
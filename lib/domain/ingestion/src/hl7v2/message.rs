@@ -0,0 +1,342 @@
+//! ER7 ("pipe and hat") parsing of a single HL7 v2 message.
+
+use super::Hl7Error;
+
+/// Encoding characters declared in MSH-1 and MSH-2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delimiters {
+    pub field: char,
+    pub component: char,
+    pub repetition: char,
+    pub escape: Option<char>,
+    pub subcomponent: char,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Self {
+            field: '|',
+            component: '^',
+            repetition: '~',
+            escape: Some('\\'),
+            subcomponent: '&',
+        }
+    }
+}
+
+impl Delimiters {
+    /// Read the delimiters from the start of an `MSH` segment.
+    ///
+    /// MSH-2 may carry a fifth (truncation) character in v2.7+; it is ignored.
+    fn from_header(segment: &str) -> Result<Self, Hl7Error> {
+        let mut chars = segment.strip_prefix("MSH").unwrap_or_default().chars();
+        let field = chars
+            .next()
+            .ok_or_else(|| Hl7Error::InvalidDelimiters(segment.to_string()))?;
+        let encoding: Vec<char> = chars.take_while(|c| *c != field).collect();
+        let [component, repetition, rest @ ..] = encoding.as_slice() else {
+            return Err(Hl7Error::InvalidDelimiters(segment.to_string()));
+        };
+        let (escape, subcomponent) = match rest {
+            [escape, subcomponent, ..] => (Some(*escape), *subcomponent),
+            // Three encoding characters: no escape character defined.
+            [subcomponent] => (None, *subcomponent),
+            [] => return Err(Hl7Error::InvalidDelimiters(segment.to_string())),
+        };
+        let all = [field, *component, *repetition, subcomponent];
+        let distinct = all
+            .iter()
+            .enumerate()
+            .all(|(i, c)| !all[..i].contains(c) && Some(*c) != escape);
+        if !distinct || field.is_alphanumeric() {
+            return Err(Hl7Error::InvalidDelimiters(segment.to_string()));
+        }
+        Ok(Self {
+            field,
+            component: *component,
+            repetition: *repetition,
+            escape,
+            subcomponent,
+        })
+    }
+
+    /// Decode escape sequences (`\F\`, `\S\`, `\T\`, `\R\`, `\E\`, `\Xhh..\`,
+    /// `\.br\`). Formatting sequences such as `\H\`/`\N\` are dropped and
+    /// unknown sequences are kept verbatim.
+    pub fn unescape(&self, raw: &str) -> String {
+        let Some(escape) = self.escape.filter(|escape| raw.contains(*escape)) else {
+            return raw.to_string();
+        };
+        let mut out = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(start) = rest.find(escape) {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + escape.len_utf8()..];
+            let Some(end) = after.find(escape) else {
+                // Unterminated sequence: keep the remainder as-is.
+                out.push_str(&rest[start..]);
+                return out;
+            };
+            let sequence = &after[..end];
+            match sequence {
+                "F" => out.push(self.field),
+                "S" => out.push(self.component),
+                "T" => out.push(self.subcomponent),
+                "R" => out.push(self.repetition),
+                "E" => out.push(escape),
+                ".br" => out.push('\n'),
+                "H" | "N" => {}
+                _ => match sequence.strip_prefix('X').and_then(decode_hex) {
+                    Some(decoded) => out.push_str(&decoded),
+                    None => {
+                        out.push(escape);
+                        out.push_str(sequence);
+                        out.push(escape);
+                    }
+                },
+            }
+            rest = &after[end + escape.len_utf8()..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// `\Xhh..\` payload decoded as UTF-8 (falling back to Latin-1 bytes).
+fn decode_hex(hex: &str) -> Option<String> {
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) => err.into_bytes().into_iter().map(char::from).collect(),
+    })
+}
+
+/// One repetition of a field: components made of subcomponents, unescaped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Repetition {
+    components: Vec<Vec<String>>,
+}
+
+impl Repetition {
+    /// Component `n` (1-based), first subcomponent. Empty values and the
+    /// explicit null `""` read as `None`.
+    pub fn component(&self, n: usize) -> Option<&str> {
+        self.subcomponent(n, 1)
+    }
+
+    /// Subcomponent `s` of component `n`, both 1-based.
+    pub fn subcomponent(&self, n: usize, s: usize) -> Option<&str> {
+        let value = self
+            .components
+            .get(n.checked_sub(1)?)?
+            .get(s.checked_sub(1)?)?
+            .as_str();
+        (!value.is_empty() && value != "\"\"").then_some(value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.iter().flatten().all(String::is_empty)
+    }
+}
+
+/// A field and its repetitions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Field {
+    repetitions: Vec<Repetition>,
+}
+
+impl Field {
+    /// Non-empty repetitions.
+    pub fn repetitions(&self) -> impl Iterator<Item = &Repetition> {
+        self.repetitions.iter().filter(|rep| !rep.is_empty())
+    }
+
+    pub fn first(&self) -> Option<&Repetition> {
+        self.repetitions().next()
+    }
+}
+
+/// A segment (`PID`, `OBR`, ...) with its fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    id: String,
+    /// `fields[0]` is field 1.
+    fields: Vec<Field>,
+}
+
+impl Segment {
+    fn parse(line: &str, delimiters: &Delimiters) -> Self {
+        let mut parts = line.split(delimiters.field);
+        let id = parts.next().unwrap_or_default().to_string();
+        let mut fields = Vec::new();
+        if id == "MSH" {
+            // MSH-1 is the field separator itself and MSH-2 the encoding
+            // characters; neither is split or unescaped.
+            let literal = |value: String| Field {
+                repetitions: vec![Repetition {
+                    components: vec![vec![value]],
+                }],
+            };
+            fields.push(literal(delimiters.field.to_string()));
+            fields.push(literal(parts.next().unwrap_or_default().to_string()));
+        }
+        fields.extend(parts.map(|raw| parse_field(raw, delimiters)));
+        Self { id, fields }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Field `n` using HL7 numbering (MSH-1 is the field separator).
+    pub fn field(&self, n: usize) -> Option<&Field> {
+        self.fields.get(n.checked_sub(1)?)
+    }
+
+    /// Component `component` of the first repetition of field `n`.
+    pub fn value(&self, n: usize, component: usize) -> Option<&str> {
+        self.field(n)?.first()?.component(component)
+    }
+}
+
+fn parse_field(raw: &str, delimiters: &Delimiters) -> Field {
+    Field {
+        repetitions: raw
+            .split(delimiters.repetition)
+            .map(|rep| Repetition {
+                components: rep
+                    .split(delimiters.component)
+                    .map(|component| {
+                        component
+                            .split(delimiters.subcomponent)
+                            .map(|sub| delimiters.unescape(sub))
+                            .collect()
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+/// A parsed HL7 v2 message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    delimiters: Delimiters,
+    segments: Vec<Segment>,
+}
+
+impl Message {
+    /// Parse an ER7-encoded message. Segments may end in `\r`, `\n` or `\r\n`;
+    /// the first segment must be `MSH`.
+    pub fn parse(text: &str) -> Result<Self, Hl7Error> {
+        let mut lines = text
+            .split(['\r', '\n'])
+            .map(|line| line.trim_start_matches(['\u{0b}', '\u{1c}']))
+            .filter(|line| !line.trim().is_empty());
+        let header = lines
+            .next()
+            .filter(|line| line.starts_with("MSH"))
+            .ok_or(Hl7Error::MissingHeader)?;
+        let delimiters = Delimiters::from_header(header)?;
+        let segments = std::iter::once(header)
+            .chain(lines)
+            .map(|line| Segment::parse(line, &delimiters))
+            .collect();
+        Ok(Self {
+            delimiters,
+            segments,
+        })
+    }
+
+    pub fn delimiters(&self) -> &Delimiters {
+        &self.delimiters
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// First segment named `id`.
+    pub fn segment(&self, id: &str) -> Option<&Segment> {
+        self.segments.iter().find(|segment| segment.id == id)
+    }
+
+    pub fn header(&self) -> &Segment {
+        &self.segments[0]
+    }
+
+    /// MSH-9 message code and trigger event, e.g. `("ORM", "O01")`.
+    pub fn message_type(&self) -> (Option<&str>, Option<&str>) {
+        (self.header().value(9, 1), self.header().value(9, 2))
+    }
+
+    /// MSH-10 message control ID.
+    pub fn control_id(&self) -> Option<&str> {
+        self.header().value(10, 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fields_components_repetitions_and_escapes() {
+        let message = Message::parse(
+            "MSH|^~\\&|LIS|LAB|||20240501083000-0500||ORM^O01|MSG-1|P|2.5.1\r\
+             PID|1||MRN-1^^^HOSP^MR~SSN-9^^^SSA^SS||Doe^Jane\r\
+             NTE|1||Fasting \\T\\ hydrated\\.br\\see \\F\\ pipe, caret \\S\\, \\X41\\\\E\\\n",
+        )
+        .unwrap();
+
+        assert_eq!(message.message_type(), (Some("ORM"), Some("O01")));
+        assert_eq!(message.control_id(), Some("MSG-1"));
+        assert_eq!(message.header().value(1, 1), Some("|"));
+        assert_eq!(message.header().value(2, 1), Some("^~\\&"));
+
+        let pid = message.segment("PID").unwrap();
+        let ids: Vec<_> = pid
+            .field(3)
+            .unwrap()
+            .repetitions()
+            .map(|rep| (rep.component(1), rep.component(4), rep.component(5)))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                (Some("MRN-1"), Some("HOSP"), Some("MR")),
+                (Some("SSN-9"), Some("SSA"), Some("SS"))
+            ]
+        );
+        assert_eq!(pid.value(3, 2), None);
+
+        let note = message.segment("NTE").unwrap().value(3, 1).unwrap();
+        assert_eq!(note, "Fasting & hydrated\nsee | pipe, caret ^, A\\");
+    }
+
+    #[test]
+    fn honours_custom_delimiters_and_rejects_non_msh_input() {
+        let message = Message::parse("MSH#*@!%#A#B\nOBR#1#P-1%X*Y@Z").unwrap();
+        let obr = message.segment("OBR").unwrap();
+        let rep = obr.field(2).unwrap().first().unwrap();
+        assert_eq!(rep.subcomponent(1, 1), Some("P-1"));
+        assert_eq!(rep.subcomponent(1, 2), Some("X"));
+        assert_eq!(rep.component(2), Some("Y"));
+        assert_eq!(obr.field(2).unwrap().repetitions().count(), 2);
+
+        assert!(matches!(
+            Message::parse("PID|1"),
+            Err(Hl7Error::MissingHeader)
+        ));
+        assert!(matches!(
+            Message::parse("MSH|^^\\&|A"),
+            Err(Hl7Error::InvalidDelimiters(_))
+        ));
+    }
+}
@@ -0,0 +1,63 @@
+//! HL7 v2 order message ingestion (ORM^O01, OMI^O23).
+//!
+//! [`MessageReader`] splits MLLP-framed or newline-delimited input into
+//! messages, [`Message`] parses the ER7 encoding (fields, components,
+//! repetitions, subcomponents, escape sequences) and [`message_to_bundle`]
+//! maps PID/PV1/ORC/OBR onto Patient, Encounter and ServiceRequest so v2
+//! orders go through the same validation, staging and NCIt mapping as FHIR
+//! input.
+
+mod framing;
+mod mapper;
+mod message;
+
+use std::fmt;
+
+pub use framing::{MLLP_END, MLLP_START, MessageReader};
+pub use mapper::{
+    Hl7MappingOptions, coding_system, message_to_bundle, message_to_bundle_with_options,
+};
+pub use message::{Delimiters, Field, Message, Repetition, Segment};
+
+/// Errors raised while reading, parsing or mapping HL7 v2 messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hl7Error {
+    /// Input is not MLLP-framed or newline-delimited as expected.
+    Framing(String),
+    /// MSH-18 names an unsupported character set, or the message bytes are
+    /// invalid in the declared one.
+    Encoding(String),
+    /// The message does not start with an `MSH` segment.
+    MissingHeader,
+    /// MSH-1/MSH-2 do not declare four distinct delimiters.
+    InvalidDelimiters(String),
+    /// MSH-9 names a message other than ORM^O01 or OMI^O23.
+    UnsupportedMessage(String),
+    MissingSegment(&'static str),
+    MissingField(&'static str),
+    InvalidValue {
+        field: &'static str,
+        value: String,
+    },
+}
+
+impl fmt::Display for Hl7Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Framing(reason) => write!(f, "invalid HL7 v2 framing: {reason}"),
+            Self::Encoding(reason) => write!(f, "invalid HL7 v2 character encoding: {reason}"),
+            Self::MissingHeader => write!(f, "HL7 v2 message does not start with MSH"),
+            Self::InvalidDelimiters(header) => {
+                write!(f, "invalid HL7 v2 encoding characters in '{header}'")
+            }
+            Self::UnsupportedMessage(kind) => {
+                write!(f, "unsupported HL7 v2 message type '{kind}'")
+            }
+            Self::MissingSegment(segment) => write!(f, "missing required segment {segment}"),
+            Self::MissingField(field) => write!(f, "missing required field {field}"),
+            Self::InvalidValue { field, value } => write!(f, "invalid {field} value '{value}'"),
+        }
+    }
+}
+
+impl std::error::Error for Hl7Error {}
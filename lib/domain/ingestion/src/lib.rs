@@ -4,17 +4,62 @@
 //! The helpers here are intentionally lightweight and align with the minimal
 //! scope documented in `docs\kanban\feature\002-fhir-pipeline-mvp.md`.
 
+mod bulk;
+mod bundle_semantics;
+mod csv_extract;
+mod deid;
+pub mod hl7v2;
+mod outcome;
+mod projection;
+mod provenance;
+mod quarantine;
 mod reference;
+mod results;
+mod stream;
 mod transforms;
 pub mod validation;
+mod versioning;
+mod window;
 
-pub use reference::{reference_id, reference_id_from_str};
+pub use bulk::{BulkBundles, BulkExport, BulkExportFile, BulkExportManifest, NdjsonResources};
+pub use bundle_semantics::{EntryOutcome, EntryResult, ProcessedBundle, process_bundle};
+pub use csv_extract::{CsvMapping, CsvRowIssue, CsvSource, CsvStaging, csv_to_staging};
+pub use deid::{
+    DeidConfig, Deidentifier, PSEUDONYMIZED_TYPES, SECURITY_LABEL_SYSTEM, ScrubRule, TextPolicy,
+};
+pub use outcome::{INGESTION_ERROR_SYSTEM, REQUIREMENT_SYSTEM, VALIDATION_ISSUE_SYSTEM};
+pub use projection::{ExtensionColumn, ExtensionProjection};
+pub use provenance::ProvenanceContext;
+pub use quarantine::{
+    IngestionMode, PartialStaging, QuarantinedEntry, bundle_to_staging_partial,
+    processed_to_staging_partial,
+};
+pub use reference::{
+    BundleResolver, ParsedReference, ResolvedReference, reference_id, reference_id_from_str,
+    target_id,
+};
+pub use results::{
+    ResultStagingRows, bundle_to_result_staging, diagnostic_report_to_staging,
+    imaging_study_to_staging, observation_to_staging, processed_to_result_staging,
+};
+pub use stream::{
+    BundleHeader, BundleStreamReader, SourcePosition, StreamError, StreamEvent, StreamOptions,
+};
 pub use transforms::{
-    IngestionError, bundle_to_domain, bundle_to_domain_with_validation, bundle_to_staging,
-    bundle_to_staging_with_validation, sr_to_domain, sr_to_staging,
+    IngestionError, RowSources, StagingRows, bundle_to_domain, bundle_to_domain_with_validation,
+    bundle_to_encounter_staging, bundle_to_patient_staging, bundle_to_staging,
+    bundle_to_staging_with_projection, bundle_to_staging_with_validation, encounter_to_domain,
+    encounter_to_staging, patient_to_domain, patient_to_staging,
+    processed_to_staging_with_projection, sr_to_domain, sr_to_staging,
+    sr_to_staging_with_projection,
 };
 
 pub use validation::{
-    RequirementRef, Validated, ValidationIssue, ValidationMode, ValidationReport,
-    ValidationSeverity, validate_bundle, validate_sr,
+    Profiles, RequirementRef, StructureDefinition, Validated, ValidationIssue, ValidationMode,
+    ValidationReport, ValidationRule, ValidationRules, ValidationSeverity, ValueSetBinding,
+    validate_bundle, validate_bundle_with_rules, validate_processed_with_rules, validate_sr,
+};
+pub use versioning::{
+    ChangeKind, ChangeRecord, SrVersion, VersionLedger, bundle_sr_deletes, bundle_sr_versions,
 };
+pub use window::{BundleWindow, BundleWindows};
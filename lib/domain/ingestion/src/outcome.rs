@@ -0,0 +1,334 @@
+//! Conversions from DFPS ingestion results into FHIR `OperationOutcome`.
+//!
+//! [`ValidationReport`], [`ValidationIssue`], [`IngestionError`] and
+//! [`QuarantinedEntry`] are DFPS-specific; FHIR clients expect an
+//! OperationOutcome. Each issue keeps the generic FHIR `issue-type` in `code`,
+//! the DFPS identifier (issue id or error code, plus the requirement for
+//! validation issues) in `details`, the message in `diagnostics` and the
+//! element path in `expression`.
+
+use dfps_core::fhir::{
+    CodeableConcept, Coding, IssueSeverity, IssueType, OperationOutcome, OperationOutcomeIssue,
+};
+
+use crate::{
+    quarantine::QuarantinedEntry,
+    transforms::IngestionError,
+    validation::{ValidationIssue, ValidationReport, ValidationSeverity},
+};
+
+/// Code system of [`ValidationIssue::id`] codings in `issue.details`.
+pub const VALIDATION_ISSUE_SYSTEM: &str = "https://dfps.example/fhir/CodeSystem/validation-issue";
+/// Code system of [`crate::RequirementRef`] codings in `issue.details`.
+pub const REQUIREMENT_SYSTEM: &str = "https://dfps.example/fhir/CodeSystem/requirement";
+/// Code system of [`IngestionError::code`] codings in `issue.details`.
+pub const INGESTION_ERROR_SYSTEM: &str = "https://dfps.example/fhir/CodeSystem/ingestion-error";
+
+impl From<ValidationSeverity> for IssueSeverity {
+    fn from(severity: ValidationSeverity) -> Self {
+        match severity {
+            ValidationSeverity::Error => Self::Error,
+            ValidationSeverity::Warning => Self::Warning,
+            ValidationSeverity::Info => Self::Information,
+        }
+    }
+}
+
+impl From<&ValidationIssue> for OperationOutcomeIssue {
+    fn from(issue: &ValidationIssue) -> Self {
+        let mut outcome = OperationOutcomeIssue::new(
+            issue.severity.into(),
+            validation_issue_type(&issue.id),
+            issue.message.clone(),
+        )
+        .with_details(CodeableConcept {
+            coding: vec![
+                coding(VALIDATION_ISSUE_SYSTEM, &issue.id),
+                coding(REQUIREMENT_SYSTEM, issue.requirement_ref()),
+            ],
+            text: None,
+        });
+        if let Some(path) = &issue.path {
+            outcome = outcome.with_expression(path.clone());
+        }
+        outcome
+    }
+}
+
+/// One issue per validation issue; a clean report becomes
+/// [`OperationOutcome::all_ok`].
+impl From<&ValidationReport> for OperationOutcome {
+    fn from(report: &ValidationReport) -> Self {
+        if report.issues.is_empty() {
+            return OperationOutcome::all_ok();
+        }
+        OperationOutcome::new(report.issues.iter().map(Into::into).collect())
+    }
+}
+
+/// `ValidationFailed` expands to its issues; every other error is a single
+/// `error` issue.
+impl From<&IngestionError> for OperationOutcome {
+    fn from(err: &IngestionError) -> Self {
+        OperationOutcome::new(error_issues(err))
+    }
+}
+
+/// The entry's error and validation issues, with expressions rooted at the
+/// entry (`Bundle.entry[n].resource.subject`).
+impl From<&QuarantinedEntry> for OperationOutcome {
+    fn from(entry: &QuarantinedEntry) -> Self {
+        let mut issues = entry.error_outcome_issues();
+        issues.extend(
+            entry
+                .issues
+                .iter()
+                .map(|issue| at_entry(entry.index, issue.into())),
+        );
+        OperationOutcome::new(issues)
+    }
+}
+
+impl QuarantinedEntry {
+    /// Issues for the error that quarantined the entry alone, rooted at the
+    /// entry like the [`OperationOutcome`] conversion. For callers that report
+    /// the validation issues separately.
+    pub fn error_outcome_issues(&self) -> Vec<OperationOutcomeIssue> {
+        error_issues(&self.error)
+            .into_iter()
+            .map(|issue| at_entry(self.index, issue))
+            .collect()
+    }
+}
+
+/// Re-root resource-level expressions (`ServiceRequest.subject`) at Bundle
+/// entry `index`; an issue without one points at the entry itself.
+fn at_entry(index: usize, mut issue: OperationOutcomeIssue) -> OperationOutcomeIssue {
+    let entry = format!("Bundle.entry[{index}]");
+    if issue.expression.is_empty() {
+        issue.expression.push(entry);
+        return issue;
+    }
+    for expression in &mut issue.expression {
+        let (root, rest) = expression
+            .split_once('.')
+            .unwrap_or((expression.as_str(), ""));
+        if root != "Bundle" && root.starts_with(|c: char| c.is_ascii_uppercase()) {
+            *expression = if rest.is_empty() {
+                format!("{entry}.resource")
+            } else {
+                format!("{entry}.resource.{rest}")
+            };
+        }
+    }
+    issue
+}
+
+fn error_issues(err: &IngestionError) -> Vec<OperationOutcomeIssue> {
+    if let IngestionError::ValidationFailed(issues) = err
+        && !issues.is_empty()
+    {
+        return issues.iter().map(Into::into).collect();
+    }
+    let (code, expression) = match err {
+        IngestionError::MissingField(field) => (IssueType::Required, Some(field.to_string())),
+        IngestionError::InvalidReference(field) => (IssueType::Value, Some(field.to_string())),
+        IngestionError::InvalidCode { field, .. } => {
+            (IssueType::CodeInvalid, Some(field.to_string()))
+        }
+        IngestionError::InvalidStatus(_) | IngestionError::InvalidIntent(_) => {
+            (IssueType::CodeInvalid, None)
+        }
+        IngestionError::TransactionFailed { entry, .. } => (
+            IssueType::Processing,
+            Some(format!("Bundle.entry[{entry}]")),
+        ),
+        IngestionError::InvalidResourceType { .. }
+        | IngestionError::InvalidBundle { .. }
+        | IngestionError::InvalidExport(_) => (IssueType::Invalid, None),
+        IngestionError::Decode(_)
+        | IngestionError::Stream(_)
+        | IngestionError::Hl7(_)
+        | IngestionError::InvalidCsv(_) => (IssueType::Structure, None),
+        IngestionError::ValidationFailed(_) => (IssueType::Invariant, None),
+        IngestionError::InvalidProjection(_)
+        | IngestionError::InvalidCsvMapping(_)
+        | IngestionError::InvalidValidationRules(_)
+        | IngestionError::InvalidProfile(_)
+        | IngestionError::InvalidDeidConfig(_) => (IssueType::Processing, None),
+    };
+    let mut issue = OperationOutcomeIssue::new(IssueSeverity::Error, code, err.to_string())
+        .with_details(CodeableConcept {
+            coding: vec![coding(INGESTION_ERROR_SYSTEM, err.code())],
+            text: None,
+        });
+    if let Some(expression) = expression {
+        issue = issue.with_expression(expression);
+    }
+    vec![issue]
+}
+
+/// FHIR issue type for a DFPS validation issue id. Ids from site rules files
+/// are constraints on the resource, so anything unrecognised is `invariant`.
+fn validation_issue_type(id: &str) -> IssueType {
+    match id {
+        "VAL_SR_SUBJECT_MISSING"
+        | "VAL_SR_STATUS_MISSING"
+        | "VAL_SR_TRACE_ID_MISSING"
+        | "VAL_PROFILE_CARDINALITY" => IssueType::Required,
+        "VAL_SR_SUBJECT_INVALID"
+        | "VAL_CSV_VALUE_INVALID"
+        | "VAL_PROFILE_FIXED_VALUE"
+        | "VAL_PROFILE_PATTERN" => IssueType::Value,
+        "VAL_SR_STATUS_INVALID"
+        | "VAL_PROFILE_BINDING"
+        | "VAL_BINDING_CODE"
+        | "VAL_BINDING_SYSTEM" => IssueType::CodeInvalid,
+        "VAL_SR_SUBJECT_PATIENT_NOT_FOUND"
+        | "VAL_SR_ENCOUNTER_NOT_FOUND"
+        | "VAL_SR_REFERENCE_UNRESOLVED"
+        | "VAL_RESULT_BASED_ON_NOT_FOUND" => IssueType::NotFound,
+        "VAL_SR_STATUS_TRANSITION_INVALID" | "VAL_SR_REVISION_NOT_APPLIED" => {
+            IssueType::BusinessRule
+        }
+        "VAL_BUNDLE_SR_DECODE" | "VAL_CSV_ROW_MALFORMED" => IssueType::Structure,
+        "VAL_BUNDLE_REJECTED"
+        | "VAL_BUNDLE_ENTRY_REJECTED"
+        | "VAL_CSV_ROW_REJECTED"
+        | "VAL_RESULT_NOT_STAGED" => IssueType::Processing,
+        "VAL_PROFILE_UNKNOWN" => IssueType::NotSupported,
+        "VAL_PROFILE_MUST_SUPPORT" | "VAL_PROFILE_BINDING_UNCHECKED" | "VAL_BINDING_UNCHECKED" => {
+            IssueType::Informational
+        }
+        _ => IssueType::Invariant,
+    }
+}
+
+fn coding(system: &str, code: &str) -> Coding {
+    Coding {
+        system: Some(system.to_string()),
+        code: Some(code.to_string()),
+        display: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::RequirementRef;
+
+    #[test]
+    fn validation_issues_keep_id_requirement_and_path() {
+        let report = ValidationReport::new(vec![
+            ValidationIssue::new(
+                "VAL_SR_SUBJECT_MISSING",
+                ValidationSeverity::Error,
+                "ServiceRequest.subject is required.",
+                RequirementRef::SUBJECT,
+            )
+            .with_path("ServiceRequest.subject"),
+            ValidationIssue::new(
+                "VAL_SITE_SR_PRIORITY_MISSING",
+                ValidationSeverity::Warning,
+                "priority should be set",
+                RequirementRef::new("R_Site"),
+            ),
+        ]);
+        let outcome = OperationOutcome::from(&report);
+        assert!(outcome.has_errors());
+
+        let [subject, site] = outcome.issue.as_slice() else {
+            panic!("expected two issues: {outcome:?}");
+        };
+        assert_eq!(subject.severity, IssueSeverity::Error);
+        assert_eq!(subject.code, IssueType::Required);
+        assert_eq!(subject.expression, ["ServiceRequest.subject"]);
+        let codes: Vec<_> = subject
+            .details
+            .iter()
+            .flat_map(|details| &details.coding)
+            .filter_map(|coding| coding.code.as_deref())
+            .collect();
+        assert_eq!(codes, ["VAL_SR_SUBJECT_MISSING", "R_Subject"]);
+
+        assert_eq!(site.severity, IssueSeverity::Warning);
+        assert_eq!(site.code, IssueType::Invariant);
+        assert!(site.expression.is_empty());
+
+        assert_eq!(
+            OperationOutcome::from(&ValidationReport::default()),
+            OperationOutcome::all_ok()
+        );
+    }
+
+    #[test]
+    fn ingestion_errors_map_to_issue_types() {
+        let outcome = OperationOutcome::from(&IngestionError::MissingField("ServiceRequest.id"));
+        let issue = &outcome.issue[0];
+        assert_eq!(issue.code, IssueType::Required);
+        assert_eq!(issue.expression, ["ServiceRequest.id"]);
+        assert_eq!(
+            issue.diagnostics.as_deref(),
+            Some("missing required field: ServiceRequest.id")
+        );
+        assert_eq!(
+            issue.details.as_ref().unwrap().coding[0].code.as_deref(),
+            Some("missing_field")
+        );
+
+        let outcome = OperationOutcome::from(&IngestionError::TransactionFailed {
+            entry: 2,
+            reason: "conflict".into(),
+        });
+        assert_eq!(outcome.issue[0].code, IssueType::Processing);
+        assert_eq!(outcome.issue[0].expression, ["Bundle.entry[2]"]);
+
+        let issues = vec![ValidationIssue::new(
+            "VAL_SR_STATUS_INVALID",
+            ValidationSeverity::Error,
+            "bad status",
+            RequirementRef::STATUS,
+        )];
+        let outcome = OperationOutcome::from(&IngestionError::ValidationFailed(issues));
+        assert_eq!(outcome.issue.len(), 1);
+        assert_eq!(outcome.issue[0].code, IssueType::CodeInvalid);
+    }
+
+    #[test]
+    fn quarantined_entries_root_expressions_at_the_entry() {
+        let entry = QuarantinedEntry {
+            index: 3,
+            entry: Default::default(),
+            error: IngestionError::MissingField("ServiceRequest.subject"),
+            issues: vec![
+                ValidationIssue::new(
+                    "VAL_SR_SUBJECT_MISSING",
+                    ValidationSeverity::Error,
+                    "ServiceRequest.subject is required.",
+                    RequirementRef::SUBJECT,
+                )
+                .with_path("ServiceRequest.subject"),
+                ValidationIssue::new(
+                    "VAL_SITE_SR_PRIORITY_MISSING",
+                    ValidationSeverity::Warning,
+                    "priority should be set",
+                    RequirementRef::new("R_Site"),
+                ),
+            ],
+        };
+        let expressions: Vec<_> = OperationOutcome::from(&entry)
+            .issue
+            .into_iter()
+            .map(|issue| issue.expression)
+            .collect();
+        assert_eq!(
+            expressions,
+            [
+                vec!["Bundle.entry[3].resource.subject"],
+                vec!["Bundle.entry[3].resource.subject"],
+                vec!["Bundle.entry[3]"],
+            ]
+        );
+        assert_eq!(entry.error_outcome_issues().len(), 1);
+    }
+}
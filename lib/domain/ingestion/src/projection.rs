@@ -0,0 +1,197 @@
+//! Configurable projection of FHIR extensions into staging columns.
+//!
+//! Sites carry local data (priority overrides, protocol IDs, ...) in
+//! `extension`/`modifierExtension`. An [`ExtensionProjection`] names which
+//! extension URLs become extra columns on `StgServiceRequestFlat::extensions`.
+
+use std::{collections::BTreeMap, env};
+
+use dfps_core::{
+    fhir::{DomainResource, Extension, ExtensionValue},
+    value::FhirDateTime,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::transforms::{IngestionError, concept_tokens, token};
+
+/// One projected column: the value of the extension at `url` lands in `column`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionColumn {
+    pub column: String,
+    pub url: String,
+}
+
+/// Set of extension columns projected onto ServiceRequest staging rows.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionProjection {
+    #[serde(default)]
+    pub columns: Vec<ExtensionColumn>,
+}
+
+impl ExtensionProjection {
+    /// Environment variable holding the projection spec.
+    pub const ENV_VAR: &'static str = "DFPS_SR_EXTENSION_COLUMNS";
+
+    pub fn new(columns: Vec<ExtensionColumn>) -> Result<Self, IngestionError> {
+        let mut seen = Vec::with_capacity(columns.len());
+        for column in &columns {
+            if column.column.trim().is_empty() || column.url.trim().is_empty() {
+                return Err(IngestionError::InvalidProjection(format!(
+                    "column '{}' needs both a name and an extension url",
+                    column.column
+                )));
+            }
+            if seen.contains(&column.column.as_str()) {
+                return Err(IngestionError::InvalidProjection(format!(
+                    "duplicate column '{}'",
+                    column.column
+                )));
+            }
+            seen.push(column.column.as_str());
+        }
+        Ok(Self { columns })
+    }
+
+    /// Parse a `column=url,column=url` spec (whitespace around entries is ignored).
+    pub fn parse(spec: &str) -> Result<Self, IngestionError> {
+        let columns = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (column, url) = entry.split_once('=').ok_or_else(|| {
+                    IngestionError::InvalidProjection(format!(
+                        "expected column=url, found '{entry}'"
+                    ))
+                })?;
+                Ok(ExtensionColumn {
+                    column: column.trim().to_string(),
+                    url: url.trim().to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, IngestionError>>()?;
+        Self::new(columns)
+    }
+
+    /// Read [`Self::ENV_VAR`]; unset means no projected columns.
+    pub fn from_env() -> Result<Self, IngestionError> {
+        match env::var(Self::ENV_VAR) {
+            Ok(spec) => Self::parse(&spec),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Project the configured extensions of `resource` into column values.
+    ///
+    /// `extension` is searched before `modifierExtension`; repeated extensions
+    /// are joined with `,`. Columns whose extension is absent are omitted.
+    pub fn project(&self, resource: &impl DomainResource) -> BTreeMap<String, String> {
+        self.columns
+            .iter()
+            .filter_map(|column| {
+                let values: Vec<String> = resource
+                    .extensions()
+                    .iter()
+                    .chain(resource.modifier_extensions())
+                    .filter(|ext| ext.url == column.url)
+                    .filter_map(extension_text)
+                    .collect();
+                (!values.is_empty()).then(|| (column.column.clone(), values.join(",")))
+            })
+            .collect()
+    }
+}
+
+/// Render an extension value with the staging token conventions.
+fn extension_text(extension: &Extension) -> Option<String> {
+    let value = extension.value.as_ref()?;
+    let text = match value {
+        ExtensionValue::Boolean(value) => value.to_string(),
+        ExtensionValue::Integer(value) => value.to_string(),
+        ExtensionValue::PositiveInt(value) => value.to_string(),
+        ExtensionValue::Decimal(value) => value.to_string(),
+        ExtensionValue::Date(value) => value.as_str().to_string(),
+        ExtensionValue::DateTime(value) => value.as_str().to_string(),
+        ExtensionValue::Instant(value) => value.as_str().to_string(),
+        ExtensionValue::Coding(coding) => token(coding.system.as_deref(), coding.code.as_deref()?),
+        ExtensionValue::CodeableConcept(concept) => {
+            concept_tokens(std::slice::from_ref(concept)).join(",")
+        }
+        ExtensionValue::Identifier(identifier) => {
+            token(identifier.system.as_deref(), identifier.value.as_deref()?)
+        }
+        ExtensionValue::Reference(reference) => reference.reference.clone()?,
+        ExtensionValue::Quantity(quantity) => {
+            let amount = quantity.value?.to_string();
+            match quantity.unit.as_deref().or(quantity.code.as_deref()) {
+                Some(unit) => format!("{amount} {unit}"),
+                None => amount,
+            }
+        }
+        ExtensionValue::Period(period) => format!(
+            "{}/{}",
+            period
+                .start
+                .as_ref()
+                .map(FhirDateTime::as_str)
+                .unwrap_or_default(),
+            period
+                .end
+                .as_ref()
+                .map(FhirDateTime::as_str)
+                .unwrap_or_default()
+        ),
+        other => other.as_str()?.to_string(),
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use dfps_core::fhir::ServiceRequest;
+
+    use super::*;
+
+    #[test]
+    fn projects_configured_extensions_by_url() {
+        let projection = ExtensionProjection::parse(
+            "priority_override=https://dfps.example/ext/priority-override, \
+             protocol_id=https://dfps.example/ext/protocol-id",
+        )
+        .unwrap();
+        let sr = ServiceRequest {
+            extension: vec![
+                Extension::new(
+                    "https://dfps.example/ext/protocol-id",
+                    ExtensionValue::String("PROTO-7".into()),
+                ),
+                Extension::new(
+                    "https://dfps.example/ext/unprojected",
+                    ExtensionValue::Boolean(true),
+                ),
+            ],
+            modifier_extension: vec![Extension::new(
+                "https://dfps.example/ext/priority-override",
+                ExtensionValue::Code("stat".into()),
+            )],
+            ..Default::default()
+        };
+
+        let columns = projection.project(&sr);
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns["priority_override"], "stat");
+        assert_eq!(columns["protocol_id"], "PROTO-7");
+    }
+
+    #[test]
+    fn rejects_malformed_specs() {
+        assert!(ExtensionProjection::parse("no_equals").is_err());
+        assert!(ExtensionProjection::parse("a=https://x,a=https://y").is_err());
+        assert!(ExtensionProjection::parse("=https://x").is_err());
+        assert!(ExtensionProjection::parse("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+//! Stamping staged rows with where they were read from.
+//!
+//! A [`ProvenanceContext`] names one pipeline run (run id, ingest time and
+//! optional source URI). Staging records the position each row was read from
+//! ([`RowSources`]); rows staged from a Bundle get that entry's Bundle id,
+//! position and `fullUrl`, and callers that know more (a streamed window's
+//! byte offset and line) fill it in through the `locate` callback. Exploded
+//! code rows share the provenance of their order.
+
+use std::collections::HashMap;
+
+use dfps_core::{fhir, provenance::SourceProvenance, value::FhirInstant};
+
+use crate::{
+    csv_extract::CsvStaging,
+    transforms::{RowSources, StagingRows},
+};
+
+/// One pipeline run, applied to every row it stages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvenanceContext {
+    pub source_uri: Option<String>,
+    /// Position of the Bundle in the input, for callers that stage Bundles
+    /// one at a time.
+    pub bundle_index: Option<usize>,
+    pub run_id: String,
+    pub ingested_at: FhirInstant,
+}
+
+impl ProvenanceContext {
+    pub fn new(run_id: impl Into<String>, ingested_at: FhirInstant) -> Self {
+        Self {
+            source_uri: None,
+            bundle_index: None,
+            run_id: run_id.into(),
+            ingested_at,
+        }
+    }
+
+    /// A new run: random UUID run id, ingested now.
+    pub fn start() -> Self {
+        Self::new(uuid::Uuid::new_v4().to_string(), FhirInstant::now())
+    }
+
+    pub fn with_source_uri(mut self, source_uri: impl Into<String>) -> Self {
+        self.source_uri = Some(source_uri.into());
+        self
+    }
+
+    /// The same run, for the Bundle at `index` in the input.
+    pub fn for_bundle(&self, index: usize) -> Self {
+        Self {
+            bundle_index: Some(index),
+            ..self.clone()
+        }
+    }
+
+    /// Provenance with the run fields set and no position.
+    pub fn record(&self) -> SourceProvenance {
+        SourceProvenance {
+            source_uri: self.source_uri.clone(),
+            bundle_index: self.bundle_index,
+            ..SourceProvenance::new(self.run_id.clone(), self.ingested_at.clone())
+        }
+    }
+
+    /// Stamp `rows` staged from `bundle` with the entry each came from;
+    /// `sources` holds the entry positions in `bundle`.
+    ///
+    /// `locate` is called once per entry with its position in `bundle` and
+    /// may rewrite the position fields (e.g. to the position in the source
+    /// Bundle). Rows that already carry provenance are left alone.
+    pub fn stamp_bundle(
+        &self,
+        rows: &mut StagingRows,
+        sources: &RowSources<usize>,
+        bundle: &fhir::Bundle,
+        mut locate: impl FnMut(usize, &mut SourceProvenance),
+    ) {
+        let mut by_entry = HashMap::new();
+        stamp(rows, sources, |&index| {
+            by_entry
+                .entry(index)
+                .or_insert_with(|| {
+                    let mut provenance = SourceProvenance {
+                        bundle_id: bundle.id.clone(),
+                        entry_index: Some(index),
+                        entry_full_url: bundle
+                            .entry
+                            .get(index)
+                            .and_then(|entry| entry.full_url.clone()),
+                        ..self.record()
+                    };
+                    locate(index, &mut provenance);
+                    provenance
+                })
+                .clone()
+        });
+    }
+
+    /// Stamp the rows of a CSV extract with the line and byte offset of the
+    /// record each was staged from.
+    pub fn stamp_csv(&self, staging: &mut CsvStaging) {
+        stamp(&mut staging.rows, &staging.positions, |position| {
+            SourceProvenance {
+                byte_offset: Some(position.byte_offset),
+                line: Some(position.line),
+                ..self.record()
+            }
+        });
+    }
+}
+
+fn stamp<T>(
+    rows: &mut StagingRows,
+    sources: &RowSources<T>,
+    mut provenance: impl FnMut(&T) -> SourceProvenance,
+) {
+    let (flats, codes) = rows;
+    for (flat, source) in flats.iter_mut().zip(&sources.flats) {
+        if flat.provenance.is_none() {
+            flat.provenance = Some(provenance(source));
+        }
+    }
+    for (code, source) in codes.iter_mut().zip(&sources.codes) {
+        if code.provenance.is_none() {
+            code.provenance = Some(provenance(source));
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! Partial-success ingestion.
+//!
+//! [`bundle_to_staging_partial`] stages every order and result entry on its
+//! own: entries that fail to decode or normalize are set aside as
+//! [`QuarantinedEntry`] records (raw entry, Bundle index, error, validation
+//! issues) while the rest of the Bundle still produces rows. Bundle-level
+//! failures (`InvalidBundle`, `TransactionFailed`) still reject the Bundle,
+//! since a transaction is all-or-nothing by definition.
+
+use std::collections::HashMap;
+
+use dfps_core::fhir;
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+
+use crate::{
+    bundle_semantics::{ProcessedBundle, process_bundle},
+    projection::ExtensionProjection,
+    reference::BundleResolver,
+    results::{ResultStagingRows, stage_results},
+    transforms::{IngestionError, RowSources, StagingRows, sr_to_staging_with_projection},
+    validation::{
+        ValidationIssue, ValidationMode, ValidationReport, ValidationRules, ValidationSeverity,
+        sr_decode_issue, validate_processed_with_rules, validate_sr_entry,
+    },
+};
+
+/// How a Bundle reacts to entries that cannot be staged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IngestionMode {
+    /// The first failing entry rejects the whole Bundle.
+    #[default]
+    Atomic,
+    /// Failing entries are quarantined; the rest are staged.
+    PartialSuccess,
+}
+
+/// A Bundle entry that could not be staged.
+#[derive(Debug)]
+pub struct QuarantinedEntry {
+    /// Position of the entry in the input Bundle.
+    pub index: usize,
+    /// The entry exactly as received.
+    pub entry: fhir::BundleEntry,
+    pub error: IngestionError,
+    /// Validation issues raised for this entry.
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl QuarantinedEntry {
+    pub fn resource_type(&self) -> Option<&str> {
+        self.entry.resource_type()
+    }
+
+    pub fn resource_id(&self) -> Option<&str> {
+        self.entry.resource_id()
+    }
+}
+
+impl Serialize for QuarantinedEntry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct ErrorRecord {
+            code: &'static str,
+            message: String,
+        }
+
+        let mut state = serializer.serialize_struct("QuarantinedEntry", 6)?;
+        state.serialize_field("index", &self.index)?;
+        state.serialize_field("resource_type", &self.resource_type())?;
+        state.serialize_field("resource_id", &self.resource_id())?;
+        state.serialize_field(
+            "error",
+            &ErrorRecord {
+                code: self.error.code(),
+                message: self.error.to_string(),
+            },
+        )?;
+        state.serialize_field("issues", &self.issues)?;
+        state.serialize_field("entry", &self.entry)?;
+        state.end()
+    }
+}
+
+/// Rows staged from the healthy entries of a Bundle plus the quarantined ones.
+#[derive(Debug, Default)]
+pub struct PartialStaging {
+    pub rows: StagingRows,
+    /// Input entry position of every row in `rows`.
+    pub sources: RowSources<usize>,
+    pub results: ResultStagingRows,
+    pub quarantine: Vec<QuarantinedEntry>,
+    /// Bundle-wide validation report.
+    pub report: ValidationReport,
+}
+
+impl PartialStaging {
+    pub fn is_clean(&self) -> bool {
+        self.quarantine.is_empty()
+    }
+}
+
+/// Stage each ServiceRequest and result entry independently.
+///
+/// Entries that fail to decode or normalize are quarantined. With
+/// [`ValidationMode::Strict`], ServiceRequests carrying error-severity issues
+/// are quarantined too (as `IngestionError::ValidationFailed`) instead of
+/// rejecting the Bundle.
+pub fn bundle_to_staging_partial(
+    bundle: &fhir::Bundle,
+    mode: ValidationMode,
+    projection: &ExtensionProjection,
+) -> Result<PartialStaging, IngestionError> {
+    Ok(processed_to_staging_partial(
+        bundle,
+        &process_bundle(bundle)?,
+        mode,
+        projection,
+    ))
+}
+
+/// [`bundle_to_staging_partial`] for a Bundle already run through
+/// [`process_bundle`]; `bundle` is the input the quarantined entries are
+/// copied from.
+pub fn processed_to_staging_partial(
+    bundle: &fhir::Bundle,
+    processed: &ProcessedBundle,
+    mode: ValidationMode,
+    projection: &ExtensionProjection,
+) -> PartialStaging {
+    let sources = processed.source_indices();
+    let resolver = BundleResolver::new(&processed.bundle);
+    let mut staging = PartialStaging {
+        report: validate_processed_with_rules(processed, &ValidationRules::default()),
+        ..PartialStaging::default()
+    };
+    let quarantine = |index: usize, error: IngestionError, issues: Vec<ValidationIssue>| {
+        let index = sources[index];
+        QuarantinedEntry {
+            index,
+            entry: bundle.entry[index].clone(),
+            error,
+            issues,
+        }
+    };
+
+    let mut last_status = HashMap::new();
+    for (index, entry) in resolver.primary_resources_of::<fhir::ServiceRequest>() {
+        let sr = match entry {
+            Ok(sr) => sr,
+            Err(err) => {
+                let issues = vec![sr_decode_issue(&err)];
+                staging
+                    .quarantine
+                    .push(quarantine(index, err.into(), issues));
+                continue;
+            }
+        };
+        let issues = validate_sr_entry(&sr, &resolver, index, &mut last_status);
+        let blocking = matches!(mode, ValidationMode::Strict)
+            && issues
+                .iter()
+                .any(|issue| issue.severity == ValidationSeverity::Error);
+        let staged = if blocking {
+            Err(IngestionError::ValidationFailed(issues.clone()))
+        } else {
+            sr_to_staging_with_projection(&sr, projection)
+        };
+        match staged {
+            Ok((flat, codes)) => {
+                staging.sources.push(sources[index], codes.len());
+                staging.rows.0.push(flat);
+                staging.rows.1.extend(codes);
+            }
+            Err(error) => staging.quarantine.push(quarantine(index, error, issues)),
+        }
+    }
+
+    let quarantined = &mut staging.quarantine;
+    staging.results = stage_results(&resolver, |index, error| {
+        quarantined.push(quarantine(index, error, Vec::new()));
+    });
+
+    staging.quarantine.sort_by_key(|entry| entry.index);
+    staging
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn bundle(entries: Vec<serde_json::Value>) -> fhir::Bundle {
+        serde_json::from_value(json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": entries.into_iter().map(|resource| json!({ "resource": resource })).collect::<Vec<_>>()
+        }))
+        .unwrap()
+    }
+
+    fn order(id: &str, status: &str) -> serde_json::Value {
+        json!({
+            "resourceType": "ServiceRequest",
+            "id": id,
+            "status": status,
+            "intent": "order",
+            "subject": { "reference": "Patient/p1" }
+        })
+    }
+
+    #[test]
+    fn bad_entries_are_quarantined_and_good_ones_staged() {
+        let bundle = bundle(vec![
+            json!({ "resourceType": "Patient", "id": "p1" }),
+            order("SR-OK", "active"),
+            order("SR-BAD-STATUS", "bogus"),
+            json!({ "resourceType": "ServiceRequest", "id": "SR-BAD-DATE", "authoredOn": "soon" }),
+            json!({ "resourceType": "Observation", "status": "final" }),
+        ]);
+
+        let staging = bundle_to_staging_partial(
+            &bundle,
+            ValidationMode::Lenient,
+            &ExtensionProjection::default(),
+        )
+        .unwrap();
+
+        assert_eq!(staging.rows.0.len(), 1);
+        assert_eq!(staging.rows.0[0].sr_id, "SR-OK");
+        let quarantined: Vec<_> = staging
+            .quarantine
+            .iter()
+            .map(|entry| (entry.index, entry.error.code()))
+            .collect();
+        assert_eq!(
+            quarantined,
+            vec![(2, "invalid_status"), (3, "decode"), (4, "missing_field")]
+        );
+        let bad_status = &staging.quarantine[0];
+        assert_eq!(bad_status.resource_id(), Some("SR-BAD-STATUS"));
+        assert!(
+            bad_status
+                .issues
+                .iter()
+                .any(|issue| issue.id == "VAL_SR_STATUS_INVALID")
+        );
+        assert_eq!(
+            bad_status.entry.resource.as_ref().unwrap()["status"],
+            "bogus"
+        );
+    }
+
+    #[test]
+    fn strict_mode_quarantines_entries_with_validation_errors() {
+        let bundle = bundle(vec![
+            json!({ "resourceType": "Patient", "id": "p1" }),
+            order("SR-OK", "active"),
+            json!({
+                "resourceType": "ServiceRequest",
+                "id": "SR-ORPHAN",
+                "status": "active",
+                "intent": "order",
+                "subject": { "reference": "Patient/elsewhere" }
+            }),
+        ]);
+
+        let lenient = bundle_to_staging_partial(
+            &bundle,
+            ValidationMode::Lenient,
+            &ExtensionProjection::default(),
+        )
+        .unwrap();
+        assert!(lenient.is_clean());
+        assert_eq!(lenient.rows.0.len(), 2);
+
+        let strict = bundle_to_staging_partial(
+            &bundle,
+            ValidationMode::Strict,
+            &ExtensionProjection::default(),
+        )
+        .unwrap();
+        assert_eq!(strict.rows.0.len(), 1);
+        assert_eq!(strict.quarantine.len(), 1);
+        assert_eq!(strict.quarantine[0].index, 2);
+        assert!(matches!(
+            strict.quarantine[0].error,
+            IngestionError::ValidationFailed(_)
+        ));
+        let record = serde_json::to_value(&strict.quarantine[0]).unwrap();
+        assert_eq!(record["error"]["code"], "validation_failed");
+        assert_eq!(
+            record["issues"][0]["id"],
+            "VAL_SR_SUBJECT_PATIENT_NOT_FOUND"
+        );
+    }
+}
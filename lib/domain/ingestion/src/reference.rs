@@ -1,16 +1,99 @@
-use dfps_core::fhir;
+//! FHIR reference parsing and bundle-scoped resolution.
+//!
+//! [`ParsedReference`] splits a literal reference into its parts;
+//! [`BundleResolver`] follows the R4 rules for resolving references inside a
+//! Bundle (`fullUrl` matching, `#contained` resources, base-URL-relative and
+//! versioned references) and rewrites resolvable references to the local
+//! `"ResourceType/id"` form the staging and validation code expects. It also
+//! backs FHIRPath `resolve()` for expressions evaluated on a Bundle entry.
+
+use std::collections::HashMap;
+
+use dfps_core::{
+    fhir::{self, FhirResource, Resource},
+    fhirpath::{FhirPath, FhirPathError, ReferenceResolver},
+};
+use serde_json::Value;
+
+/// Components of a literal `Reference.reference` string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParsedReference<'a> {
+    /// `#id` pointing into the containing resource's `contained` list (`#`
+    /// alone is the container itself).
+    Contained { id: &'a str },
+    /// `urn:uuid:` / `urn:oid:` identifier, only resolvable via `fullUrl`.
+    Urn { urn: &'a str },
+    /// `[base/]Type/id[/_history/version]`.
+    Path {
+        base: Option<&'a str>,
+        resource_type: Option<&'a str>,
+        id: &'a str,
+        version: Option<&'a str>,
+    },
+}
+
+impl<'a> ParsedReference<'a> {
+    pub fn parse(reference: &'a str) -> Option<Self> {
+        let trimmed = reference.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+        if let Some(id) = trimmed.strip_prefix('#') {
+            return Some(Self::Contained { id });
+        }
+        if trimmed.starts_with("urn:") {
+            return Some(Self::Urn { urn: trimmed });
+        }
+
+        let (path, version) = match trimmed.find("/_history/") {
+            Some(pos) => (
+                &trimmed[..pos],
+                Some(trimmed[pos + "/_history/".len()..].trim_end_matches('/'))
+                    .filter(|version| !version.is_empty()),
+            ),
+            None => (trimmed, None),
+        };
+        let path = path.trim_end_matches('/');
+        let (rest, id) = match path.rfind('/') {
+            Some(pos) => (&path[..pos], &path[pos + 1..]),
+            None => ("", path),
+        };
+        if id.is_empty() {
+            return None;
+        }
+        let (base, resource_type) = match rest.rfind('/') {
+            Some(pos) => (Some(&rest[..pos]), &rest[pos + 1..]),
+            None => (None, rest),
+        };
+        let resource_type = Some(resource_type).filter(|ty| is_resource_type_name(ty));
+
+        Some(Self::Path {
+            base: base.filter(|base| !base.is_empty() && resource_type.is_some()),
+            resource_type,
+            id,
+            version,
+        })
+    }
+
+    /// Resource type named by the reference, when it carries one.
+    pub fn resource_type(&self) -> Option<&'a str> {
+        match self {
+            Self::Path { resource_type, .. } => *resource_type,
+            _ => None,
+        }
+    }
+}
 
 /// Extracts the ID component from a `"ResourceType/id"` reference string.
+///
+/// Versioned references yield the resource id, not the version; `#contained`
+/// and `urn:` references are returned whole because they have no separate id.
 pub fn reference_id_from_str(reference: &str) -> Option<&str> {
-    let trimmed = reference.trim();
-    if trimmed.is_empty() {
-        return None;
+    match ParsedReference::parse(reference)? {
+        ParsedReference::Contained { .. } => Some(reference.trim()),
+        ParsedReference::Urn { urn } => Some(urn),
+        ParsedReference::Path { id, .. } => Some(id),
     }
-
-    trimmed
-        .split('/')
-        .filter(|segment| !segment.is_empty())
-        .last()
 }
 
 /// Convenience helper to extract the ID from a FHIR `Reference`.
@@ -21,3 +104,382 @@ pub fn reference_id(reference: &fhir::Reference) -> Option<String> {
         .and_then(reference_id_from_str)
         .map(|value| value.to_string())
 }
+
+/// Id of the resource a `[base/]Type/id` reference points at.
+///
+/// Unlike [`reference_id`] this yields nothing for `#contained` and `urn:`
+/// references: once [`BundleResolver::localize`] has rewritten what it can,
+/// those are left only when the target has no id usable outside its entry,
+/// so they cannot key a staging row.
+pub fn target_id(reference: &fhir::Reference) -> Option<String> {
+    match ParsedReference::parse(reference.reference.as_deref()?)? {
+        ParsedReference::Path { id, .. } => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Target of a reference resolved inside a Bundle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedReference<'a> {
+    /// Index of the entry holding the target (the container for `#contained`).
+    pub entry_index: usize,
+    pub resource_type: &'a str,
+    pub id: Option<&'a str>,
+    /// Version requested by a `_history` reference.
+    pub version: Option<String>,
+    pub contained: bool,
+    pub resource: &'a Value,
+}
+
+impl ResolvedReference<'_> {
+    /// Local `"ResourceType/id"` form; `None` for contained targets, which have
+    /// no identity outside their container.
+    pub fn local_reference(&self) -> Option<String> {
+        if self.contained {
+            return None;
+        }
+        self.id.map(|id| format!("{}/{}", self.resource_type, id))
+    }
+}
+
+/// Resolves references between the entries of one Bundle.
+pub struct BundleResolver<'a> {
+    bundle: &'a fhir::Bundle,
+    by_full_url: HashMap<&'a str, usize>,
+    by_type_id: HashMap<(&'a str, &'a str), usize>,
+}
+
+impl<'a> BundleResolver<'a> {
+    pub fn new(bundle: &'a fhir::Bundle) -> Self {
+        let mut by_full_url = HashMap::new();
+        let mut by_type_id = HashMap::new();
+        for (index, entry) in bundle.entry.iter().enumerate() {
+            if entry.resource.is_none() {
+                continue;
+            }
+            if let Some(full_url) = entry.full_url.as_deref() {
+                by_full_url.entry(strip_history(full_url)).or_insert(index);
+            }
+            if let (Some(ty), Some(id)) = (entry.resource_type(), entry.resource_id()) {
+                by_type_id.entry((ty, id)).or_insert(index);
+            }
+        }
+        Self {
+            bundle,
+            by_full_url,
+            by_type_id,
+        }
+    }
+
+    /// Resolve `reference` as written in the entry at `from_entry`.
+    ///
+    /// Absolute URLs and URNs match `fullUrl`; relative references are first
+    /// made absolute against the containing entry's RESTful `fullUrl`, then
+    /// fall back to a `resourceType`/`id` match. Returns `None` for targets
+    /// outside the Bundle.
+    pub fn resolve(
+        &self,
+        reference: &str,
+        from_entry: Option<usize>,
+    ) -> Option<ResolvedReference<'a>> {
+        match ParsedReference::parse(reference)? {
+            ParsedReference::Contained { id } => self.resolve_contained(id, from_entry?),
+            ParsedReference::Urn { urn } => self.entry_target(*self.by_full_url.get(urn)?, None),
+            ParsedReference::Path {
+                base: Some(base),
+                resource_type: Some(ty),
+                id,
+                version,
+            } => {
+                let index = *self.by_full_url.get(format!("{base}/{ty}/{id}").as_str())?;
+                self.entry_target(index, version)
+            }
+            ParsedReference::Path {
+                base: None,
+                resource_type: Some(ty),
+                id,
+                version,
+            } => {
+                let from_base = from_entry
+                    .and_then(|index| self.bundle.entry.get(index)?.full_url.as_deref())
+                    .and_then(ParsedReference::parse)
+                    .and_then(|parsed| match parsed {
+                        ParsedReference::Path { base, .. } => base,
+                        _ => None,
+                    });
+                let index = from_base
+                    .and_then(|base| {
+                        self.by_full_url
+                            .get(format!("{base}/{ty}/{id}").as_str())
+                            .copied()
+                    })
+                    .or_else(|| self.by_type_id.get(&(ty, id)).copied())?;
+                self.entry_target(index, version)
+            }
+            ParsedReference::Path {
+                resource_type: None,
+                ..
+            } => None,
+        }
+    }
+
+    /// Decode every entry of type `T` after rewriting its resolvable
+    /// references to local form, yielding the entry index alongside.
+    pub fn resources_of<T: FhirResource>(
+        &self,
+    ) -> impl Iterator<Item = (usize, Result<T, serde_json::Error>)> + '_ {
+        self.bundle
+            .entry
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.resource_type() == Some(T::RESOURCE_TYPE))
+            .filter_map(|(index, _)| {
+                let localized = self.localize(index)?;
+                let decoded = Resource::from_value(localized).map(|resource| {
+                    T::from_resource_owned(resource)
+                        .expect("entry resourceType was checked against T::RESOURCE_TYPE")
+                });
+                Some((index, decoded))
+            })
+    }
+
+    /// Like [`Self::resources_of`], minus searchset `include` entries, which
+    /// are context for the matches rather than rows of their own.
+    pub fn primary_resources_of<T: FhirResource>(
+        &self,
+    ) -> impl Iterator<Item = (usize, Result<T, serde_json::Error>)> + '_ {
+        self.resources_of::<T>()
+            .filter(|(index, _)| !self.bundle.entry[*index].is_search_include())
+    }
+
+    /// Copy of the entry's resource with every resolvable bundle reference
+    /// rewritten to `"ResourceType/id"`. Contained and external references are
+    /// left as written.
+    pub fn localize(&self, entry_index: usize) -> Option<Value> {
+        let mut value = self.bundle.entry.get(entry_index)?.resource.clone()?;
+        self.rewrite_references(&mut value, entry_index);
+        Some(value)
+    }
+
+    /// FHIRPath reference resolver for expressions evaluated on the entry at
+    /// `entry_index`: `resolve()` follows references as [`Self::resolve`]
+    /// does from that entry.
+    pub fn fhirpath_resolver(&self, entry_index: usize) -> impl ReferenceResolver + '_ {
+        move |reference: &str| {
+            self.resolve(reference, Some(entry_index))
+                .map(|target| target.resource.clone())
+        }
+    }
+
+    /// Evaluate `path` on the entry at `entry_index` with `resolve()` scoped
+    /// to this Bundle; an entry without a resource yields nothing.
+    pub fn evaluate(
+        &self,
+        path: &FhirPath,
+        entry_index: usize,
+    ) -> Result<Vec<Value>, FhirPathError> {
+        match self
+            .bundle
+            .entry
+            .get(entry_index)
+            .and_then(|entry| entry.resource.as_ref())
+        {
+            Some(resource) => path.evaluate_with(resource, &self.fhirpath_resolver(entry_index)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn rewrite_references(&self, value: &mut Value, entry_index: usize) {
+        match value {
+            Value::Object(map) => {
+                let local = map
+                    .get("reference")
+                    .and_then(Value::as_str)
+                    .and_then(|reference| self.resolve(reference, Some(entry_index)))
+                    .and_then(|target| target.local_reference());
+                if let Some(local) = local {
+                    map.insert("reference".into(), Value::String(local));
+                }
+                for child in map.values_mut() {
+                    self.rewrite_references(child, entry_index);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.rewrite_references(item, entry_index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn resolve_contained(&self, id: &str, from_entry: usize) -> Option<ResolvedReference<'a>> {
+        let container = self.bundle.entry.get(from_entry)?.resource.as_ref()?;
+        if id.is_empty() {
+            return self.entry_target(from_entry, None);
+        }
+        let resource = container
+            .get("contained")?
+            .as_array()?
+            .iter()
+            .find(|candidate| candidate.get("id").and_then(Value::as_str) == Some(id))?;
+        Some(ResolvedReference {
+            entry_index: from_entry,
+            resource_type: resource.get("resourceType")?.as_str()?,
+            id: resource.get("id").and_then(Value::as_str),
+            version: None,
+            contained: true,
+            resource,
+        })
+    }
+
+    fn entry_target(&self, index: usize, version: Option<&str>) -> Option<ResolvedReference<'a>> {
+        let entry = self.bundle.entry.get(index)?;
+        Some(ResolvedReference {
+            entry_index: index,
+            resource_type: entry.resource_type()?,
+            id: entry.resource_id(),
+            version: version.map(str::to_string),
+            contained: false,
+            resource: entry.resource.as_ref()?,
+        })
+    }
+}
+
+pub(crate) fn strip_history(url: &str) -> &str {
+    url.find("/_history/").map_or(url, |pos| &url[..pos])
+}
+
+/// FHIR resource type names are PascalCase ASCII words.
+fn is_resource_type_name(segment: &str) -> bool {
+    segment.starts_with(|c: char| c.is_ascii_uppercase())
+        && segment.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_reference_forms() {
+        assert_eq!(reference_id_from_str("Patient/p1"), Some("p1"));
+        assert_eq!(reference_id_from_str("Patient/p1/_history/2"), Some("p1"));
+        assert_eq!(
+            reference_id_from_str("https://fhir.example/r4/Patient/p1"),
+            Some("p1")
+        );
+        assert_eq!(reference_id_from_str("#pat"), Some("#pat"));
+        assert_eq!(reference_id_from_str("  "), None);
+        assert_eq!(
+            ParsedReference::parse("https://fhir.example/r4/Patient/p1/_history/3"),
+            Some(ParsedReference::Path {
+                base: Some("https://fhir.example/r4"),
+                resource_type: Some("Patient"),
+                id: "p1",
+                version: Some("3"),
+            })
+        );
+    }
+
+    fn transaction_bundle() -> fhir::Bundle {
+        serde_json::from_value(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "transaction",
+            "entry": [
+                {
+                    "fullUrl": "urn:uuid:61ebe359-bfdc-4613-8bf2-c5e300945f0a",
+                    "resource": { "resourceType": "Patient", "id": "p1" }
+                },
+                {
+                    "fullUrl": "https://fhir.example/r4/Encounter/e1",
+                    "resource": { "resourceType": "Encounter", "id": "e1" }
+                },
+                {
+                    "fullUrl": "https://fhir.example/r4/ServiceRequest/sr1",
+                    "resource": {
+                        "resourceType": "ServiceRequest",
+                        "id": "sr1",
+                        "status": "active",
+                        "intent": "order",
+                        "contained": [{ "resourceType": "Practitioner", "id": "doc" }],
+                        "subject": { "reference": "urn:uuid:61ebe359-bfdc-4613-8bf2-c5e300945f0a" },
+                        "encounter": { "reference": "Encounter/e1/_history/4" },
+                        "requester": { "reference": "#doc" },
+                        "performer": [{ "reference": "https://other.example/Practitioner/x" }]
+                    }
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn resolves_bundle_reference_forms() {
+        let bundle = transaction_bundle();
+        let resolver = BundleResolver::new(&bundle);
+
+        let patient = resolver
+            .resolve("urn:uuid:61ebe359-bfdc-4613-8bf2-c5e300945f0a", Some(2))
+            .expect("fullUrl match");
+        assert_eq!(patient.local_reference().as_deref(), Some("Patient/p1"));
+
+        let encounter = resolver
+            .resolve("Encounter/e1/_history/4", Some(2))
+            .expect("relative to RESTful base");
+        assert_eq!(encounter.entry_index, 1);
+        assert_eq!(encounter.version.as_deref(), Some("4"));
+
+        let doc = resolver.resolve("#doc", Some(2)).expect("contained");
+        assert!(doc.contained);
+        assert_eq!(doc.resource_type, "Practitioner");
+        assert!(resolver.resolve("#doc", None).is_none());
+
+        assert!(
+            resolver
+                .resolve("https://other.example/Practitioner/x", Some(2))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn localized_resources_use_local_references() {
+        let bundle = transaction_bundle();
+        let resolver = BundleResolver::new(&bundle);
+        let (index, sr) = resolver
+            .resources_of::<fhir::ServiceRequest>()
+            .next()
+            .expect("one ServiceRequest");
+        let sr = sr.unwrap();
+
+        assert_eq!(index, 2);
+        assert_eq!(sr.subject.unwrap().reference.as_deref(), Some("Patient/p1"));
+        assert_eq!(
+            sr.encounter.unwrap().reference.as_deref(),
+            Some("Encounter/e1")
+        );
+        assert_eq!(sr.requester.unwrap().reference.as_deref(), Some("#doc"));
+        assert_eq!(
+            sr.performer[0].reference.as_deref(),
+            Some("https://other.example/Practitioner/x")
+        );
+    }
+
+    #[test]
+    fn fhirpath_resolve_follows_bundle_references() {
+        let bundle = transaction_bundle();
+        let resolver = BundleResolver::new(&bundle);
+        let path = FhirPath::parse(
+            "(subject | encounter | requester | performer).resolve().ofType(Patient).id \
+             | requester.resolve().resourceType | performer.resolve().exists()",
+        )
+        .unwrap();
+        assert_eq!(
+            resolver.evaluate(&path, 2).unwrap(),
+            vec![
+                Value::from("p1"),
+                Value::from("Practitioner"),
+                Value::from(false)
+            ]
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! Result resources (Observation, DiagnosticReport, ImagingStudy) -> staging.
+//!
+//! Each result is flattened into its own landing row and its codes are
+//! exploded into [`StgResultCodeExploded`]. `basedOn` references to
+//! ServiceRequests become `sr_ids`, which is how the mapping engine and the
+//! datamart follow an order through to its results.
+
+use dfps_core::{
+    fhir,
+    staging::{
+        StgDiagnosticReportFlat, StgImagingStudyFlat, StgObservationFlat, StgResultCodeExploded,
+    },
+};
+
+use crate::{
+    bundle_semantics::{ProcessedBundle, process_bundle},
+    reference::{self, BundleResolver},
+    transforms::{IngestionError, concept_tokens, ensure_resource_type, reference_tokens, token},
+};
+
+/// Staging rows for every result resource in a Bundle.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResultStagingRows {
+    pub observations: Vec<StgObservationFlat>,
+    pub diagnostic_reports: Vec<StgDiagnosticReportFlat>,
+    pub imaging_studies: Vec<StgImagingStudyFlat>,
+    pub codes: Vec<StgResultCodeExploded>,
+}
+
+/// Flatten an Observation into its staging row plus exploded `code` codings.
+pub fn observation_to_staging(
+    observation: &fhir::Observation,
+) -> Result<(StgObservationFlat, Vec<StgResultCodeExploded>), IngestionError> {
+    ensure_resource_type(&observation.resource_type, "Observation")?;
+    let observation_id = observation
+        .id
+        .clone()
+        .ok_or(IngestionError::MissingField("Observation.id"))?;
+    let sr_ids = service_request_ids(&observation.based_on);
+    let effective = observation.effective.as_ref();
+
+    let flat = StgObservationFlat {
+        observation_id: observation_id.clone(),
+        patient_id: subject_id(
+            observation.subject.as_ref(),
+            "Observation.subject.reference",
+        )?,
+        encounter_id: subject_id(
+            observation.encounter.as_ref(),
+            "Observation.encounter.reference",
+        )?,
+        sr_ids: sr_ids.clone(),
+        status: lowercase(observation.status.as_deref()),
+        categories: concept_tokens(&observation.category),
+        effective_start: effective.and_then(fhir::Effective::start).cloned(),
+        effective_end: effective.and_then(fhir::Effective::end).cloned(),
+        issued: observation.issued.clone(),
+        value: observation.value.as_ref().map(render_value),
+        interpretations: concept_tokens(&observation.interpretation),
+    };
+    let codes = explode(
+        "Observation",
+        &observation_id,
+        &sr_ids,
+        observation.code.iter(),
+    );
+    Ok((flat, codes))
+}
+
+/// Flatten a DiagnosticReport into its staging row plus exploded `code` codings.
+pub fn diagnostic_report_to_staging(
+    report: &fhir::DiagnosticReport,
+) -> Result<(StgDiagnosticReportFlat, Vec<StgResultCodeExploded>), IngestionError> {
+    ensure_resource_type(&report.resource_type, "DiagnosticReport")?;
+    let report_id = report
+        .id
+        .clone()
+        .ok_or(IngestionError::MissingField("DiagnosticReport.id"))?;
+    let sr_ids = service_request_ids(&report.based_on);
+    let effective = report.effective.as_ref();
+
+    let flat = StgDiagnosticReportFlat {
+        report_id: report_id.clone(),
+        patient_id: subject_id(
+            report.subject.as_ref(),
+            "DiagnosticReport.subject.reference",
+        )?,
+        encounter_id: subject_id(
+            report.encounter.as_ref(),
+            "DiagnosticReport.encounter.reference",
+        )?,
+        sr_ids: sr_ids.clone(),
+        status: lowercase(report.status.as_deref()),
+        categories: concept_tokens(&report.category),
+        effective_start: effective.and_then(fhir::Effective::start).cloned(),
+        effective_end: effective.and_then(fhir::Effective::end).cloned(),
+        issued: report.issued.clone(),
+        results: reference_tokens(&report.result),
+        imaging_studies: reference_tokens(&report.imaging_study),
+        conclusion: report.conclusion.clone(),
+        conclusion_codes: concept_tokens(&report.conclusion_code),
+    };
+    let codes = explode("DiagnosticReport", &report_id, &sr_ids, report.code.iter());
+    Ok((flat, codes))
+}
+
+/// Flatten an ImagingStudy into its staging row plus exploded `procedureCode` codings.
+pub fn imaging_study_to_staging(
+    study: &fhir::ImagingStudy,
+) -> Result<(StgImagingStudyFlat, Vec<StgResultCodeExploded>), IngestionError> {
+    ensure_resource_type(&study.resource_type, "ImagingStudy")?;
+    let study_id = study
+        .id
+        .clone()
+        .ok_or(IngestionError::MissingField("ImagingStudy.id"))?;
+    let sr_ids = service_request_ids(&study.based_on);
+
+    let flat = StgImagingStudyFlat {
+        study_id: study_id.clone(),
+        patient_id: subject_id(study.subject.as_ref(), "ImagingStudy.subject.reference")?,
+        encounter_id: subject_id(study.encounter.as_ref(), "ImagingStudy.encounter.reference")?,
+        sr_ids: sr_ids.clone(),
+        status: lowercase(study.status.as_deref()),
+        modalities: study
+            .modality
+            .iter()
+            .filter_map(|coding| {
+                coding
+                    .code
+                    .as_deref()
+                    .map(|code| token(coding.system.as_deref(), code))
+            })
+            .collect(),
+        started: study.started.clone(),
+        number_of_series: study.number_of_series,
+        number_of_instances: study.number_of_instances,
+        description: study.description.clone(),
+        series_uids: study
+            .series
+            .iter()
+            .filter_map(|series| series.uid.clone())
+            .collect(),
+    };
+    let codes = explode(
+        "ImagingStudy",
+        &study_id,
+        &sr_ids,
+        study.procedure_code.iter(),
+    );
+    Ok((flat, codes))
+}
+
+/// Flatten every result resource in the bundle; the first entry that cannot
+/// be staged fails the call.
+pub fn bundle_to_result_staging(
+    bundle: &fhir::Bundle,
+) -> Result<ResultStagingRows, IngestionError> {
+    let processed = process_bundle(bundle)?;
+    let mut failure = None;
+    let rows = stage_results(&BundleResolver::new(&processed.bundle), |_, err| {
+        failure.get_or_insert(err);
+    });
+    match failure {
+        Some(err) => Err(err),
+        None => Ok(rows),
+    }
+}
+
+/// Result rows for a Bundle already run through [`process_bundle`].
+///
+/// Entries that cannot be staged are left out rather than failing the Bundle;
+/// [`validate_processed_with_rules`](crate::validate_processed_with_rules)
+/// reports each one as `VAL_RESULT_NOT_STAGED`.
+pub fn processed_to_result_staging(processed: &ProcessedBundle) -> ResultStagingRows {
+    stage_results(&BundleResolver::new(&processed.bundle), |_, _| {})
+}
+
+/// Stage every primary result entry of `resolver`, handing the ones that fail
+/// to decode or flatten to `rejected` with their index.
+pub(crate) fn stage_results(
+    resolver: &BundleResolver<'_>,
+    mut rejected: impl FnMut(usize, IngestionError),
+) -> ResultStagingRows {
+    let mut rows = ResultStagingRows::default();
+
+    for (index, entry) in resolver.primary_resources_of::<fhir::Observation>() {
+        match entry
+            .map_err(IngestionError::from)
+            .and_then(|observation| observation_to_staging(&observation))
+        {
+            Ok((flat, codes)) => {
+                rows.observations.push(flat);
+                rows.codes.extend(codes);
+            }
+            Err(err) => rejected(index, err),
+        }
+    }
+    for (index, entry) in resolver.primary_resources_of::<fhir::DiagnosticReport>() {
+        match entry
+            .map_err(IngestionError::from)
+            .and_then(|report| diagnostic_report_to_staging(&report))
+        {
+            Ok((flat, codes)) => {
+                rows.diagnostic_reports.push(flat);
+                rows.codes.extend(codes);
+            }
+            Err(err) => rejected(index, err),
+        }
+    }
+    for (index, entry) in resolver.primary_resources_of::<fhir::ImagingStudy>() {
+        match entry
+            .map_err(IngestionError::from)
+            .and_then(|study| imaging_study_to_staging(&study))
+        {
+            Ok((flat, codes)) => {
+                rows.imaging_studies.push(flat);
+                rows.codes.extend(codes);
+            }
+            Err(err) => rejected(index, err),
+        }
+    }
+
+    rows
+}
+
+/// Ids of the ServiceRequests named in `basedOn`; other request types are skipped.
+fn service_request_ids(based_on: &[fhir::Reference]) -> Vec<String> {
+    based_on
+        .iter()
+        .filter_map(|reference| reference.reference.as_deref())
+        .filter(|reference| reference.trim_start().starts_with("ServiceRequest/"))
+        .filter_map(reference::reference_id_from_str)
+        .map(str::to_string)
+        .collect()
+}
+
+fn subject_id(
+    reference: Option<&fhir::Reference>,
+    field: &'static str,
+) -> Result<Option<String>, IngestionError> {
+    reference
+        .map(|reference| {
+            reference::target_id(reference).ok_or(IngestionError::InvalidReference(field))
+        })
+        .transpose()
+}
+
+fn lowercase(value: Option<&str>) -> Option<String> {
+    value.map(str::to_ascii_lowercase)
+}
+
+fn explode<'a>(
+    resource_type: &str,
+    resource_id: &str,
+    sr_ids: &[String],
+    concepts: impl Iterator<Item = &'a fhir::CodeableConcept>,
+) -> Vec<StgResultCodeExploded> {
+    concepts
+        .flat_map(|concept| concept.coding.iter())
+        .map(|coding| StgResultCodeExploded {
+            resource_type: resource_type.to_string(),
+            resource_id: resource_id.to_string(),
+            sr_ids: sr_ids.to_vec(),
+            system: coding.system.clone(),
+            code: coding.code.clone(),
+            display: coding.display.clone(),
+        })
+        .collect()
+}
+
+fn render_value(value: &fhir::ObservationValue) -> String {
+    match value {
+        fhir::ObservationValue::Quantity(quantity) => render_quantity(quantity),
+        fhir::ObservationValue::CodeableConcept(concept) => {
+            concept_tokens(std::slice::from_ref(concept)).join(",")
+        }
+        fhir::ObservationValue::String(text) => text.clone(),
+        fhir::ObservationValue::Boolean(flag) => flag.to_string(),
+        fhir::ObservationValue::Integer(number) => number.to_string(),
+        fhir::ObservationValue::Range(range) => format!(
+            "{}..{}",
+            range.low.as_ref().map(render_quantity).unwrap_or_default(),
+            range.high.as_ref().map(render_quantity).unwrap_or_default()
+        ),
+        fhir::ObservationValue::Ratio(ratio) => format!(
+            "{}/{}",
+            ratio
+                .numerator
+                .as_ref()
+                .map(render_quantity)
+                .unwrap_or_default(),
+            ratio
+                .denominator
+                .as_ref()
+                .map(render_quantity)
+                .unwrap_or_default()
+        ),
+        fhir::ObservationValue::DateTime(value) => value.to_string(),
+        fhir::ObservationValue::Period(period) => format!(
+            "{}/{}",
+            period
+                .start
+                .as_ref()
+                .map(|v| v.as_str())
+                .unwrap_or_default(),
+            period.end.as_ref().map(|v| v.as_str()).unwrap_or_default()
+        ),
+    }
+}
+
+fn render_quantity(quantity: &fhir::Quantity) -> String {
+    let mut rendered = String::new();
+    if let Some(comparator) = &quantity.comparator {
+        rendered.push_str(comparator);
+    }
+    if let Some(value) = quantity.value {
+        rendered.push_str(&value.to_string());
+    }
+    if let Some(unit) = quantity.unit.as_ref().or(quantity.code.as_ref()) {
+        rendered.push(' ');
+        rendered.push_str(unit);
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pet_ct_bundle() -> fhir::Bundle {
+        serde_json::from_value(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                { "resource": {
+                    "resourceType": "Observation",
+                    "id": "obs-suv",
+                    "basedOn": [
+                        { "reference": "ServiceRequest/SR-1" },
+                        { "reference": "CarePlan/cp-1" }
+                    ],
+                    "status": "FINAL",
+                    "code": { "coding": [
+                        { "system": "http://loinc.org", "code": "81555-5", "display": "SUVmax" }
+                    ] },
+                    "subject": { "reference": "Patient/p1" },
+                    "effectiveDateTime": "2024-05-02T10:00:00Z",
+                    "valueQuantity": { "value": 7.4, "unit": "g/mL" }
+                } },
+                { "resource": {
+                    "resourceType": "DiagnosticReport",
+                    "id": "dr-1",
+                    "basedOn": [{ "reference": "ServiceRequest/SR-1" }],
+                    "status": "final",
+                    "code": { "coding": [
+                        { "system": "http://loinc.org", "code": "44136-0", "display": "PET-CT report" }
+                    ] },
+                    "subject": { "reference": "Patient/p1" },
+                    "issued": "2024-05-02T12:00:00Z",
+                    "result": [{ "reference": "Observation/obs-suv" }],
+                    "imagingStudy": [{ "reference": "ImagingStudy/is-1" }],
+                    "conclusion": "FDG-avid lesion"
+                } },
+                { "resource": {
+                    "resourceType": "ImagingStudy",
+                    "id": "is-1",
+                    "status": "available",
+                    "basedOn": [{ "reference": "ServiceRequest/SR-1" }],
+                    "modality": [
+                        { "system": "http://dicom.nema.org/resources/ontology/DCM", "code": "PT" },
+                        { "system": "http://dicom.nema.org/resources/ontology/DCM", "code": "CT" }
+                    ],
+                    "subject": { "reference": "Patient/p1" },
+                    "started": "2024-05-02T09:00:00Z",
+                    "numberOfSeries": 2,
+                    "procedureCode": [{ "coding": [
+                        { "system": "http://snomed.info/sct", "code": "450436003" }
+                    ] }],
+                    "series": [{ "uid": "1.2.3", "modality": { "code": "PT" } }]
+                } }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn results_link_back_to_service_request() {
+        let rows = bundle_to_result_staging(&pet_ct_bundle()).expect("result staging");
+
+        assert_eq!(rows.observations.len(), 1);
+        let observation = &rows.observations[0];
+        assert_eq!(observation.sr_ids, vec!["SR-1".to_string()]);
+        assert_eq!(observation.status.as_deref(), Some("final"));
+        assert_eq!(observation.value.as_deref(), Some("7.4 g/mL"));
+
+        let report = &rows.diagnostic_reports[0];
+        assert_eq!(report.results, vec!["Observation/obs-suv".to_string()]);
+        assert_eq!(report.sr_ids, vec!["SR-1".to_string()]);
+
+        let study = &rows.imaging_studies[0];
+        assert_eq!(study.modalities.len(), 2);
+        assert_eq!(study.series_uids, vec!["1.2.3".to_string()]);
+
+        assert_eq!(rows.codes.len(), 3);
+        assert!(rows.codes.iter().all(|code| code.sr_ids == ["SR-1"]));
+        assert_eq!(rows.codes[0].result_key(), "Observation/obs-suv");
+    }
+
+    #[test]
+    fn result_without_id_errors() {
+        let observation = fhir::Observation::default();
+        assert!(matches!(
+            observation_to_staging(&observation),
+            Err(IngestionError::MissingField("Observation.id"))
+        ));
+    }
+}
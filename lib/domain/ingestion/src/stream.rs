@@ -0,0 +1,742 @@
+//! Streaming Bundle reader.
+//!
+//! [`BundleStreamReader`] pulls Bundle entries one at a time from any
+//! [`BufRead`] without materialising the Bundle: only the entry being decoded
+//! is buffered, so memory does not grow with the number of entries. The input
+//! may hold a single Bundle, Bundles separated by whitespace/newlines
+//! (NDJSON), a top-level JSON array of Bundles, or bare resources (each read as
+//! a one-entry Bundle, as `$export` NDJSON files are).
+//!
+//! Bundle-level members are decoded as they are met, so `Bundle.type` must
+//! precede `entry` for it to be known when [`StreamEvent::BundleStart`] is
+//! emitted; [`StreamEvent::BundleEnd`] carries every member seen.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, BufRead},
+};
+
+use dfps_core::fhir;
+use serde_json::{Map, Value};
+
+/// Limits applied while streaming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamOptions {
+    /// Largest single JSON value accepted (one entry, or one Bundle member).
+    pub max_entry_bytes: usize,
+    /// Entries staged together by [`crate::BundleWindows`].
+    pub window_entries: usize,
+    /// Earlier entries [`crate::BundleWindows`] keeps for resolving references
+    /// across windows.
+    pub index_entries: usize,
+}
+
+impl StreamOptions {
+    pub const DEFAULT_MAX_ENTRY_BYTES: usize = 64 * 1024 * 1024;
+    pub const DEFAULT_WINDOW_ENTRIES: usize = 1_000;
+    pub const DEFAULT_INDEX_ENTRIES: usize = 100_000;
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            max_entry_bytes: Self::DEFAULT_MAX_ENTRY_BYTES,
+            window_entries: Self::DEFAULT_WINDOW_ENTRIES,
+            index_entries: Self::DEFAULT_INDEX_ENTRIES,
+        }
+    }
+}
+
+/// Where a value starts in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SourcePosition {
+    pub byte_offset: u64,
+    /// 1-based line number.
+    pub line: u64,
+}
+
+/// Bundle-level data known when a Bundle starts or ends.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BundleHeader {
+    /// Position of the Bundle in the input.
+    pub index: usize,
+    /// `Bundle.id`.
+    pub id: Option<String>,
+    /// `Bundle.type`.
+    pub bundle_type: Option<String>,
+    /// The input value was a bare resource, wrapped as a one-entry Bundle.
+    pub implicit: bool,
+}
+
+/// One step of a streamed input.
+#[derive(Debug)]
+pub enum StreamEvent {
+    BundleStart(BundleHeader),
+    Entry {
+        /// [`BundleHeader::index`] of the enclosing Bundle.
+        bundle: usize,
+        /// Position of the entry in its Bundle.
+        index: usize,
+        /// Where the entry (or the bare resource) starts in the input.
+        position: SourcePosition,
+        entry: Box<fhir::BundleEntry>,
+    },
+    BundleEnd {
+        header: BundleHeader,
+        entries: usize,
+    },
+}
+
+/// Failure while streaming; the reader stops after the first one.
+#[derive(Debug)]
+pub enum StreamError {
+    Io(io::Error),
+    /// Malformed JSON, or a top-level value that is neither a Bundle nor a resource.
+    Syntax {
+        offset: u64,
+        message: String,
+    },
+    /// An entry is well-formed JSON but not a valid `Bundle.entry`.
+    Decode {
+        offset: u64,
+        source: serde_json::Error,
+    },
+    /// A value exceeds [`StreamOptions::max_entry_bytes`].
+    EntryTooLarge {
+        offset: u64,
+        limit: usize,
+    },
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read input: {err}"),
+            Self::Syntax { offset, message } => {
+                write!(f, "malformed input at byte {offset}: {message}")
+            }
+            Self::Decode { offset, source } => {
+                write!(f, "invalid bundle entry at byte {offset}: {source}")
+            }
+            Self::EntryTooLarge { offset, limit } => {
+                write!(f, "value at byte {offset} exceeds the {limit} byte limit")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Decode { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StreamError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+enum State {
+    /// Between top-level values.
+    TopLevel,
+    /// Inside a top-level object, before the next member.
+    Members {
+        first: bool,
+    },
+    /// Inside the `entry` array of the current Bundle.
+    Entries {
+        first: bool,
+    },
+    Done,
+}
+
+/// Top-level object being read.
+struct OpenObject {
+    index: usize,
+    position: SourcePosition,
+    /// Members other than `entry`.
+    members: Map<String, Value>,
+    /// `entry` was streamed, so this is a Bundle.
+    streamed: bool,
+    entries: usize,
+}
+
+impl OpenObject {
+    fn header(&self, implicit: bool) -> BundleHeader {
+        BundleHeader {
+            index: self.index,
+            id: self
+                .members
+                .get("id")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            bundle_type: self
+                .members
+                .get("type")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            implicit,
+        }
+    }
+
+    fn resource_type(&self) -> Option<&str> {
+        self.members.get("resourceType").and_then(Value::as_str)
+    }
+}
+
+/// Iterator over the [`StreamEvent`]s of a reader.
+pub struct BundleStreamReader<R> {
+    scanner: Scanner<R>,
+    max_value_bytes: usize,
+    buf: Vec<u8>,
+    state: State,
+    in_array: bool,
+    /// A top-level array element was read and a `,` or `]` must follow.
+    needs_separator: bool,
+    bundles: usize,
+    open: Option<OpenObject>,
+    pending: VecDeque<StreamEvent>,
+}
+
+impl<R: BufRead> BundleStreamReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, &StreamOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: &StreamOptions) -> Self {
+        Self {
+            scanner: Scanner {
+                reader,
+                offset: 0,
+                line: 1,
+            },
+            max_value_bytes: options.max_entry_bytes,
+            buf: Vec::new(),
+            state: State::TopLevel,
+            in_array: false,
+            needs_separator: false,
+            bundles: 0,
+            open: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Bytes consumed so far.
+    pub fn offset(&self) -> u64 {
+        self.scanner.offset
+    }
+
+    fn step(&mut self) -> Result<Option<StreamEvent>, StreamError> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            match self.state {
+                State::Done => return Ok(None),
+                State::TopLevel => {
+                    if !self.top_level()? {
+                        self.state = State::Done;
+                    }
+                }
+                State::Members { first } => {
+                    if let Some(event) = self.member(first)? {
+                        return Ok(Some(event));
+                    }
+                }
+                State::Entries { first } => {
+                    if let Some(event) = self.entry(first)? {
+                        return Ok(Some(event));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Advance to the next top-level object; `false` at end of input.
+    fn top_level(&mut self) -> Result<bool, StreamError> {
+        loop {
+            let Some(byte) = self.scanner.skip_whitespace()? else {
+                if self.in_array {
+                    return Err(self.scanner.syntax("unterminated top-level array"));
+                }
+                return Ok(false);
+            };
+            match byte {
+                b'[' if !self.in_array => {
+                    self.scanner.bump();
+                    self.in_array = true;
+                    self.needs_separator = false;
+                }
+                b']' if self.in_array => {
+                    self.scanner.bump();
+                    self.in_array = false;
+                    self.needs_separator = false;
+                }
+                b',' if self.in_array && self.needs_separator => {
+                    self.scanner.bump();
+                    self.needs_separator = false;
+                }
+                b'{' if !self.needs_separator => {
+                    let position = self.scanner.position();
+                    self.scanner.bump();
+                    self.open = Some(OpenObject {
+                        index: self.bundles,
+                        position,
+                        members: Map::new(),
+                        streamed: false,
+                        entries: 0,
+                    });
+                    self.state = State::Members { first: true };
+                    return Ok(true);
+                }
+                other => {
+                    return Err(self.scanner.syntax(format!(
+                        "expected a Bundle or resource object, found '{}'",
+                        char::from(other)
+                    )));
+                }
+            }
+        }
+    }
+
+    fn member(&mut self, first: bool) -> Result<Option<StreamEvent>, StreamError> {
+        match self.scanner.skip_whitespace()? {
+            Some(b'}') => {
+                self.scanner.bump();
+                self.close_object()?;
+                return Ok(None);
+            }
+            Some(b',') if !first => self.scanner.bump(),
+            _ if first => {}
+            _ => return Err(self.scanner.syntax("expected ',' or '}' between members")),
+        }
+
+        let offset = self.scanner.offset;
+        self.scanner
+            .read_value(&mut self.buf, self.max_value_bytes)?;
+        let key: String = serde_json::from_slice(&self.buf).map_err(|_| StreamError::Syntax {
+            offset,
+            message: "expected a member name".into(),
+        })?;
+        self.scanner.expect(b':')?;
+
+        let open = self
+            .open
+            .as_mut()
+            .expect("members are read inside an object");
+        if key == "entry" && open.resource_type().is_none_or(|ty| ty == "Bundle") {
+            self.scanner.expect(b'[')?;
+            self.state = State::Entries { first: true };
+            if !open.streamed {
+                open.streamed = true;
+                return Ok(Some(StreamEvent::BundleStart(open.header(false))));
+            }
+            return Ok(None);
+        }
+
+        let offset = self.scanner.offset;
+        self.scanner
+            .read_value(&mut self.buf, self.max_value_bytes)?;
+        let value: Value =
+            serde_json::from_slice(&self.buf).map_err(|err| StreamError::Syntax {
+                offset,
+                message: err.to_string(),
+            })?;
+        open.members.insert(key, value);
+        self.state = State::Members { first: false };
+        Ok(None)
+    }
+
+    fn entry(&mut self, first: bool) -> Result<Option<StreamEvent>, StreamError> {
+        match self.scanner.skip_whitespace()? {
+            Some(b']') => {
+                self.scanner.bump();
+                self.state = State::Members { first: false };
+                return Ok(None);
+            }
+            Some(b',') if !first => self.scanner.bump(),
+            _ if first => {}
+            _ => return Err(self.scanner.syntax("expected ',' or ']' between entries")),
+        }
+
+        self.scanner.skip_whitespace()?;
+        let position = self.scanner.position();
+        let offset = position.byte_offset;
+        self.scanner
+            .read_value(&mut self.buf, self.max_value_bytes)?;
+        let entry: Box<fhir::BundleEntry> = serde_json::from_slice(&self.buf)
+            .map_err(|source| StreamError::Decode { offset, source })?;
+        let open = self
+            .open
+            .as_mut()
+            .expect("entries are read inside an object");
+        let index = open.entries;
+        open.entries += 1;
+        self.state = State::Entries { first: false };
+        Ok(Some(StreamEvent::Entry {
+            bundle: open.index,
+            index,
+            position,
+            entry,
+        }))
+    }
+
+    /// Queue the events for a finished top-level object.
+    fn close_object(&mut self) -> Result<(), StreamError> {
+        let open = self.open.take().expect("an object is open");
+        if open.streamed || open.resource_type() == Some("Bundle") {
+            if !open.streamed {
+                self.pending
+                    .push_back(StreamEvent::BundleStart(open.header(false)));
+            }
+            self.pending.push_back(StreamEvent::BundleEnd {
+                header: open.header(false),
+                entries: open.entries,
+            });
+        } else if open.resource_type().is_some() {
+            let header = BundleHeader {
+                index: open.index,
+                id: None,
+                bundle_type: None,
+                implicit: true,
+            };
+            self.pending
+                .push_back(StreamEvent::BundleStart(header.clone()));
+            self.pending.push_back(StreamEvent::Entry {
+                bundle: open.index,
+                index: 0,
+                position: open.position,
+                entry: Box::new(fhir::BundleEntry {
+                    resource: Some(Value::Object(open.members)),
+                    ..Default::default()
+                }),
+            });
+            self.pending
+                .push_back(StreamEvent::BundleEnd { header, entries: 1 });
+        } else {
+            return Err(self.scanner.syntax("top-level object has no resourceType"));
+        }
+        self.bundles += 1;
+        self.needs_separator = self.in_array;
+        self.state = State::TopLevel;
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for BundleStreamReader<R> {
+    type Item = Result<StreamEvent, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.step() {
+            Ok(event) => event.map(Ok),
+            Err(err) => {
+                self.state = State::Done;
+                self.pending.clear();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Byte-level JSON tokenizer that copies out one value at a time.
+struct Scanner<R> {
+    reader: R,
+    offset: u64,
+    line: u64,
+}
+
+impl<R: BufRead> Scanner<R> {
+    fn bump(&mut self) {
+        self.consume(1);
+    }
+
+    fn consume(&mut self, count: usize) {
+        self.reader.consume(count);
+        self.offset += count as u64;
+    }
+
+    fn position(&self) -> SourcePosition {
+        SourcePosition {
+            byte_offset: self.offset,
+            line: self.line,
+        }
+    }
+
+    fn syntax(&self, message: impl Into<String>) -> StreamError {
+        StreamError::Syntax {
+            offset: self.offset,
+            message: message.into(),
+        }
+    }
+
+    /// Skip whitespace and peek at the next byte.
+    fn skip_whitespace(&mut self) -> Result<Option<u8>, StreamError> {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            let skipped = buf
+                .iter()
+                .take_while(|byte| byte.is_ascii_whitespace())
+                .count();
+            let next = buf.get(skipped).copied();
+            let lines = newlines(&buf[..skipped]);
+            self.consume(skipped);
+            self.line += lines;
+            if next.is_some() {
+                return Ok(next);
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), StreamError> {
+        match self.skip_whitespace()? {
+            Some(byte) if byte == expected => {
+                self.bump();
+                Ok(())
+            }
+            _ => Err(self.syntax(format!("expected '{}'", char::from(expected)))),
+        }
+    }
+
+    /// Copy the next complete JSON value into `out` without parsing it.
+    fn read_value(&mut self, out: &mut Vec<u8>, limit: usize) -> Result<(), StreamError> {
+        out.clear();
+        let start = self.offset;
+        let first = self
+            .skip_whitespace()?
+            .ok_or_else(|| self.syntax("unexpected end of input"))?;
+        let nested = matches!(first, b'{' | b'[' | b'"');
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                if nested {
+                    return Err(self.syntax("unexpected end of input"));
+                }
+                break;
+            }
+            let mut end = None;
+            for (pos, &byte) in buf.iter().enumerate() {
+                if !nested {
+                    if matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace() {
+                        end = Some(pos);
+                        break;
+                    }
+                    continue;
+                }
+                if in_string {
+                    if escaped {
+                        escaped = false;
+                    } else if byte == b'\\' {
+                        escaped = true;
+                    } else if byte == b'"' {
+                        in_string = false;
+                        if depth == 0 {
+                            end = Some(pos + 1);
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                match byte {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth = depth.checked_sub(1).ok_or_else(|| StreamError::Syntax {
+                            offset: start,
+                            message: "unbalanced brackets".into(),
+                        })?;
+                        if depth == 0 {
+                            end = Some(pos + 1);
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let take = end.unwrap_or(buf.len());
+            if out.len() + take > limit {
+                return Err(StreamError::EntryTooLarge {
+                    offset: start,
+                    limit,
+                });
+            }
+            out.extend_from_slice(&buf[..take]);
+            let lines = newlines(&buf[..take]);
+            self.consume(take);
+            self.line += lines;
+            if end.is_some() {
+                break;
+            }
+        }
+        if out.is_empty() {
+            return Err(self.syntax("expected a JSON value"));
+        }
+        Ok(())
+    }
+}
+
+fn newlines(bytes: &[u8]) -> u64 {
+    bytes.iter().filter(|&&byte| byte == b'\n').count() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+
+    fn events(input: &str) -> Vec<StreamEvent> {
+        BundleStreamReader::new(input.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn entry_ids(events: &[StreamEvent]) -> Vec<(usize, usize, String)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::Entry {
+                    bundle,
+                    index,
+                    entry,
+                    ..
+                } => Some((*bundle, *index, entry.resource_id()?.to_string())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn streams_entries_from_ndjson_arrays_and_bare_resources() {
+        let input = r#"
+            {"resourceType":"Bundle","type":"collection","entry":[
+                {"resource":{"resourceType":"Patient","id":"p1","name":[{"text":"a \"}]{ b"}]}},
+                {"resource":{"resourceType":"ServiceRequest","id":"sr1"}}
+            ],"id":"b1"}
+            [{"resourceType":"Bundle","entry":[]},{"resourceType":"Patient","id":"p2"}]
+            {"resourceType":"Bundle","type":"batch"}
+        "#;
+        let events = events(input);
+
+        assert_eq!(
+            entry_ids(&events),
+            vec![
+                (0, 0, "p1".to_string()),
+                (0, 1, "sr1".to_string()),
+                (2, 0, "p2".to_string()),
+            ]
+        );
+        let ends: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::BundleEnd { header, entries } => Some((
+                    header.index,
+                    header.bundle_type.clone(),
+                    header.implicit,
+                    *entries,
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            ends,
+            vec![
+                (0, Some("collection".into()), false, 2),
+                (1, None, false, 0),
+                (2, None, true, 1),
+                (3, Some("batch".into()), false, 0),
+            ]
+        );
+
+        let positions: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::Entry { position, .. } => Some(*position),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            positions.iter().map(|at| at.line).collect::<Vec<_>>(),
+            vec![3, 4, 6]
+        );
+        assert!(input[positions[1].byte_offset as usize..].starts_with(r#"{"resource""#));
+        assert!(
+            input[positions[2].byte_offset as usize..].starts_with(r#"{"resourceType":"Patient""#)
+        );
+        let ids: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::BundleEnd { header, .. } => Some(header.id.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, vec![Some("b1".into()), None, None, None]);
+    }
+
+    #[test]
+    fn reads_entries_across_small_buffer_boundaries() {
+        let entries: Vec<_> = (0..50)
+            .map(|i| format!(r#"{{"resource":{{"resourceType":"Patient","id":"p{i}"}}}}"#))
+            .collect();
+        let input = format!(
+            r#"{{"resourceType":"Bundle","type":"collection","entry":[{}]}}"#,
+            entries.join(",")
+        );
+        let reader = BufReader::with_capacity(7, input.as_bytes());
+        let events: Vec<_> = BundleStreamReader::new(reader)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let ids = entry_ids(&events);
+        assert_eq!(ids.len(), 50);
+        assert_eq!(ids[49].2, "p49");
+    }
+
+    #[test]
+    fn reports_oversized_and_malformed_input() {
+        let options = StreamOptions {
+            max_entry_bytes: 64,
+            ..StreamOptions::default()
+        };
+        let input = format!(
+            r#"{{"resourceType":"Bundle","entry":[{{"resource":{{"resourceType":"Patient","id":"{}"}}}}]}}"#,
+            "x".repeat(100)
+        );
+        let mut reader = BundleStreamReader::with_options(input.as_bytes(), &options);
+        assert!(matches!(
+            reader.next(),
+            Some(Ok(StreamEvent::BundleStart(_)))
+        ));
+        assert!(matches!(
+            reader.next(),
+            Some(Err(StreamError::EntryTooLarge { limit: 64, .. }))
+        ));
+        assert!(reader.next().is_none());
+
+        let truncated: Vec<_> =
+            BundleStreamReader::new(r#"{"resourceType":"Bundle","entry":[{"resource":"#.as_bytes())
+                .collect();
+        assert!(matches!(
+            truncated.last(),
+            Some(Err(StreamError::Syntax { .. }))
+        ));
+
+        let not_a_resource: Vec<_> = BundleStreamReader::new(r#"{"id":"x"}"#.as_bytes()).collect();
+        assert!(matches!(
+            not_a_resource.last(),
+            Some(Err(StreamError::Syntax { .. }))
+        ));
+    }
+}
@@ -1,14 +1,26 @@
 use dfps_core::{
+    encounter::{self, EncounterStatus},
     fhir,
     order::{self, ServiceRequestIntent, ServiceRequestStatus},
-    staging::{StgServiceRequestFlat, StgSrCodeExploded},
-    value::{EncounterId, PatientId, ServiceRequestId},
+    patient::{self, AdministrativeGender},
+    staging::{StgEncounterFlat, StgPatientFlat, StgServiceRequestFlat, StgSrCodeExploded},
+    value::{
+        BusinessIdentifier, ClinicalCode, EncounterId, FhirDateTime, PatientId, ResourceReference,
+        ServiceRequestId,
+    },
 };
 use serde_json::Error as SerdeError;
 
 use crate::{
-    reference,
-    validation::{Validated, ValidationIssue, ValidationMode, validate_bundle},
+    bundle_semantics::{ProcessedBundle, process_bundle},
+    hl7v2::Hl7Error,
+    projection::ExtensionProjection,
+    reference::{self, BundleResolver},
+    stream::StreamError,
+    validation::{
+        RequirementRef, Validated, ValidationIssue, ValidationMode, ValidationRules,
+        ValidationSeverity, validate_processed_with_rules,
+    },
 };
 
 /// Errors surfaced while normalizing raw FHIR payloads.
@@ -22,8 +34,41 @@ pub enum IngestionError {
     },
     InvalidStatus(String),
     InvalidIntent(String),
+    /// A coded element outside its FHIR value set (e.g. `Patient.gender`).
+    InvalidCode {
+        field: &'static str,
+        value: String,
+    },
     Decode(SerdeError),
     ValidationFailed(Vec<ValidationIssue>),
+    /// The Bundle as a whole violates its `Bundle.type` rules.
+    InvalidBundle {
+        bundle_type: String,
+        reason: String,
+    },
+    /// A transaction entry was rejected, so the whole transaction is.
+    TransactionFailed {
+        entry: usize,
+        reason: String,
+    },
+    /// An extension projection spec is malformed.
+    InvalidProjection(String),
+    /// A streamed input could not be read.
+    Stream(StreamError),
+    /// A Bulk Data export manifest or one of its files is unusable.
+    InvalidExport(String),
+    /// An HL7 v2 message could not be read or mapped.
+    Hl7(Hl7Error),
+    /// A CSV column-mapping spec is malformed or does not fit the extract.
+    InvalidCsvMapping(String),
+    /// A CSV extract could not be read.
+    InvalidCsv(String),
+    /// A validation rules file is malformed.
+    InvalidValidationRules(String),
+    /// A StructureDefinition/ValueSet profile file cannot be used.
+    InvalidProfile(String),
+    /// A de-identification config or key is unusable.
+    InvalidDeidConfig(String),
 }
 
 impl std::fmt::Display for IngestionError {
@@ -36,10 +81,60 @@ impl std::fmt::Display for IngestionError {
             }
             Self::InvalidStatus(value) => write!(f, "invalid status value '{value}'"),
             Self::InvalidIntent(value) => write!(f, "invalid intent value '{value}'"),
+            Self::InvalidCode { field, value } => write!(f, "invalid {field} value '{value}'"),
             Self::Decode(err) => write!(f, "failed to decode resource: {err}"),
             Self::ValidationFailed(issues) => {
                 write!(f, "validation failed with {} issue(s)", issues.len())
             }
+            Self::InvalidBundle {
+                bundle_type,
+                reason,
+            } => write!(f, "invalid {bundle_type} bundle: {reason}"),
+            Self::TransactionFailed { entry, reason } => {
+                write!(f, "transaction rejected at entry {entry}: {reason}")
+            }
+            Self::InvalidProjection(reason) => {
+                write!(f, "invalid extension projection: {reason}")
+            }
+            Self::Stream(err) => write!(f, "{err}"),
+            Self::InvalidExport(reason) => write!(f, "invalid bulk export: {reason}"),
+            Self::Hl7(err) => write!(f, "{err}"),
+            Self::InvalidCsvMapping(reason) => write!(f, "invalid CSV mapping: {reason}"),
+            Self::InvalidCsv(reason) => write!(f, "failed to read CSV extract: {reason}"),
+            Self::InvalidValidationRules(reason) => {
+                write!(f, "invalid validation rules: {reason}")
+            }
+            Self::InvalidProfile(reason) => write!(f, "invalid profile: {reason}"),
+            Self::InvalidDeidConfig(reason) => {
+                write!(f, "invalid de-identification config: {reason}")
+            }
+        }
+    }
+}
+
+impl IngestionError {
+    /// Stable machine-readable code for the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingField(_) => "missing_field",
+            Self::InvalidReference(_) => "invalid_reference",
+            Self::InvalidResourceType { .. } => "invalid_resource_type",
+            Self::InvalidStatus(_) => "invalid_status",
+            Self::InvalidIntent(_) => "invalid_intent",
+            Self::InvalidCode { .. } => "invalid_code",
+            Self::Decode(_) => "decode",
+            Self::ValidationFailed(_) => "validation_failed",
+            Self::InvalidBundle { .. } => "invalid_bundle",
+            Self::TransactionFailed { .. } => "transaction_failed",
+            Self::InvalidProjection(_) => "invalid_projection",
+            Self::Stream(_) => "stream",
+            Self::InvalidExport(_) => "invalid_export",
+            Self::Hl7(_) => "hl7v2",
+            Self::InvalidCsvMapping(_) => "invalid_csv_mapping",
+            Self::InvalidCsv(_) => "invalid_csv",
+            Self::InvalidValidationRules(_) => "invalid_validation_rules",
+            Self::InvalidProfile(_) => "invalid_profile",
+            Self::InvalidDeidConfig(_) => "invalid_deid_config",
         }
     }
 }
@@ -52,9 +147,51 @@ impl From<SerdeError> for IngestionError {
     }
 }
 
+impl From<StreamError> for IngestionError {
+    fn from(value: StreamError) -> Self {
+        Self::Stream(value)
+    }
+}
+
+impl From<Hl7Error> for IngestionError {
+    fn from(value: Hl7Error) -> Self {
+        Self::Hl7(value)
+    }
+}
+
+/// Staging row collections produced from a Bundle (flat rows + exploded codings).
+pub type StagingRows = (Vec<StgServiceRequestFlat>, Vec<StgSrCodeExploded>);
+
+/// Where each staged row was read from, parallel to [`StagingRows`]:
+/// `flats[i]` is the source of `rows.0[i]` and `codes[j]` of `rows.1[j]`
+/// (a Bundle entry position, or a record position in an extract).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RowSources<T> {
+    pub flats: Vec<T>,
+    pub codes: Vec<T>,
+}
+
+impl<T: Clone> RowSources<T> {
+    /// Record one staged order, and its `codes` exploded rows, read from `source`.
+    pub(crate) fn push(&mut self, source: T, codes: usize) {
+        self.codes
+            .extend(std::iter::repeat_n(source.clone(), codes));
+        self.flats.push(source);
+    }
+}
+
 /// Convert a FHIR ServiceRequest into staging rows (flat + exploded coding rows).
 pub fn sr_to_staging(
     sr: &fhir::ServiceRequest,
+) -> Result<(StgServiceRequestFlat, Vec<StgSrCodeExploded>), IngestionError> {
+    sr_to_staging_with_projection(sr, &ExtensionProjection::default())
+}
+
+/// [`sr_to_staging`], projecting the configured extensions into
+/// `StgServiceRequestFlat::extensions`.
+pub fn sr_to_staging_with_projection(
+    sr: &fhir::ServiceRequest,
+    projection: &ExtensionProjection,
 ) -> Result<(StgServiceRequestFlat, Vec<StgSrCodeExploded>), IngestionError> {
     ensure_resource_type(&sr.resource_type, "ServiceRequest")?;
     let sr_id = sr
@@ -66,12 +203,12 @@ pub fn sr_to_staging(
         .subject
         .as_ref()
         .ok_or(IngestionError::MissingField("ServiceRequest.subject"))?;
-    let patient_id = reference::reference_id(patient_id).ok_or(
-        IngestionError::InvalidReference("ServiceRequest.subject.reference"),
-    )?;
+    let patient_id = reference::target_id(patient_id).ok_or(IngestionError::InvalidReference(
+        "ServiceRequest.subject.reference",
+    ))?;
 
     let encounter_id = match sr.encounter.as_ref() {
-        Some(reference) => Some(reference::reference_id(reference).ok_or(
+        Some(reference) => Some(reference::target_id(reference).ok_or(
             IngestionError::InvalidReference("ServiceRequest.encounter.reference"),
         )?),
         None => None,
@@ -89,6 +226,33 @@ pub fn sr_to_staging(
         intent,
         description,
         ordered_at: sr.authored_on.clone(),
+        priority: sr.priority.as_ref().map(|value| value.to_ascii_lowercase()),
+        occurrence_start: sr
+            .occurrence
+            .as_ref()
+            .and_then(|occurrence| occurrence.start())
+            .cloned(),
+        occurrence_end: sr
+            .occurrence
+            .as_ref()
+            .and_then(|occurrence| occurrence.end())
+            .cloned(),
+        reason_codes: concept_tokens(&sr.reason_code),
+        reason_references: reference_tokens(&sr.reason_reference),
+        body_sites: concept_tokens(&sr.body_site),
+        performers: reference_tokens(&sr.performer),
+        specimens: reference_tokens(&sr.specimen),
+        notes: sr
+            .note
+            .iter()
+            .filter_map(|note| note.text.clone())
+            .collect(),
+        identifiers: identifier_tokens(&sr.identifier),
+        based_on: reference_tokens(&sr.based_on),
+        replaces: reference_tokens(&sr.replaces),
+        order_details: concept_tokens(&sr.order_detail),
+        extensions: projection.project(sr),
+        provenance: None,
     };
 
     let exploded = sr
@@ -102,6 +266,7 @@ pub fn sr_to_staging(
             system: coding.system,
             code: coding.code,
             display: coding.display,
+            provenance: None,
         })
         .collect();
 
@@ -119,12 +284,12 @@ pub fn sr_to_domain(sr: &fhir::ServiceRequest) -> Result<order::ServiceRequest,
         .subject
         .as_ref()
         .ok_or(IngestionError::MissingField("ServiceRequest.subject"))?;
-    let patient_id = reference::reference_id(patient_reference).ok_or(
+    let patient_id = reference::target_id(patient_reference).ok_or(
         IngestionError::InvalidReference("ServiceRequest.subject.reference"),
     )?;
 
     let encounter_id = match sr.encounter.as_ref() {
-        Some(reference) => Some(EncounterId(reference::reference_id(reference).ok_or(
+        Some(reference) => Some(EncounterId(reference::target_id(reference).ok_or(
             IngestionError::InvalidReference("ServiceRequest.encounter.reference"),
         )?)),
         None => None,
@@ -133,6 +298,7 @@ pub fn sr_to_domain(sr: &fhir::ServiceRequest) -> Result<order::ServiceRequest,
     let (_, status) = parse_status(sr.status.as_deref())?;
     let (_, intent) = parse_intent(sr.intent.as_deref(), status)?;
     let description = description_from_sr(sr);
+    let occurrence = sr.occurrence.as_ref();
 
     Ok(order::ServiceRequest::new(
         ServiceRequestId(sr_id.to_string()),
@@ -141,13 +307,29 @@ pub fn sr_to_domain(sr: &fhir::ServiceRequest) -> Result<order::ServiceRequest,
         status,
         intent,
         description,
-    ))
+    )
+    .with_codes(concept_codes(sr.code.iter()))
+    .with_categories(concept_codes(sr.category.iter()))
+    .with_reasons(
+        concept_codes(sr.reason_code.iter()),
+        domain_references(&sr.reason_reference),
+    )
+    .with_requester(
+        sr.requester
+            .as_ref()
+            .and_then(|reference| reference.reference.clone())
+            .map(ResourceReference),
+    )
+    .with_authored_on(sr.authored_on.clone())
+    .with_occurrence(
+        occurrence.and_then(|o| o.start()).cloned(),
+        occurrence.and_then(|o| o.end()).cloned(),
+    )
+    .with_supporting_info(domain_references(&sr.supporting_info)))
 }
 
 /// Convert a bundle into staging row collections.
-pub fn bundle_to_staging(
-    bundle: &fhir::Bundle,
-) -> Result<(Vec<StgServiceRequestFlat>, Vec<StgSrCodeExploded>), IngestionError> {
+pub fn bundle_to_staging(bundle: &fhir::Bundle) -> Result<StagingRows, IngestionError> {
     bundle_to_staging_with_validation(bundle, ValidationMode::default())
         .map(|validated| validated.value)
 }
@@ -156,29 +338,191 @@ pub fn bundle_to_staging(
 pub fn bundle_to_staging_with_validation(
     bundle: &fhir::Bundle,
     mode: ValidationMode,
-) -> Result<Validated<(Vec<StgServiceRequestFlat>, Vec<StgSrCodeExploded>)>, IngestionError> {
-    let report = validate_bundle(bundle);
+) -> Result<Validated<StagingRows>, IngestionError> {
+    bundle_to_staging_with_projection(bundle, mode, &ExtensionProjection::default())
+}
+
+/// [`bundle_to_staging_with_validation`] with extension columns projected
+/// onto every ServiceRequest row.
+pub fn bundle_to_staging_with_projection(
+    bundle: &fhir::Bundle,
+    mode: ValidationMode,
+    projection: &ExtensionProjection,
+) -> Result<Validated<StagingRows>, IngestionError> {
+    let staged = processed_to_staging_with_projection(&process_bundle(bundle)?, mode, projection)?;
+    Ok(Validated::new(staged.value.0, staged.report))
+}
+
+/// [`bundle_to_staging_with_projection`] for a Bundle already run through
+/// [`process_bundle`], with the input entry position of every row.
+pub fn processed_to_staging_with_projection(
+    processed: &ProcessedBundle,
+    mode: ValidationMode,
+    projection: &ExtensionProjection,
+) -> Result<Validated<(StagingRows, RowSources<usize>)>, IngestionError> {
+    let report = validate_processed_with_rules(processed, &ValidationRules::default());
     if matches!(mode, ValidationMode::Strict) && report.has_errors() {
         return Err(IngestionError::ValidationFailed(report.issues.clone()));
     }
-    let (flats, exploded) = bundle_to_staging_inner(bundle)?;
-    Ok(Validated::new((flats, exploded), report))
+    let entries = processed.source_indices();
+    let mut rows = StagingRows::default();
+    let mut sources = RowSources::default();
+    for (index, entry) in
+        BundleResolver::new(&processed.bundle).primary_resources_of::<fhir::ServiceRequest>()
+    {
+        let (flat, codes) = sr_to_staging_with_projection(&entry?, projection)?;
+        sources.push(entries[index], codes.len());
+        rows.0.push(flat);
+        rows.1.extend(codes);
+    }
+    Ok(Validated::new((rows, sources), report))
+}
+
+/// Flatten a FHIR Patient into a `stg_patient_flat` row.
+pub fn patient_to_staging(patient: &fhir::Patient) -> Result<StgPatientFlat, IngestionError> {
+    ensure_resource_type(&patient.resource_type, "Patient")?;
+    let patient_id = patient
+        .id
+        .clone()
+        .ok_or(IngestionError::MissingField("Patient.id"))?;
+    let gender = parse_gender(patient.gender.as_deref())?;
+    let (deceased, deceased_at) = deceased_from_patient(patient);
+
+    Ok(StgPatientFlat {
+        patient_id,
+        mrn: patient
+            .mrn()
+            .and_then(|identifier| identifier.value.clone()),
+        identifiers: identifier_tokens(&patient.identifier),
+        gender: gender.map(|gender| gender.as_fhir_code().to_string()),
+        birth_date: patient.birth_date.clone(),
+        deceased,
+        deceased_at,
+    })
+}
+
+/// Convert a FHIR Patient into the domain aggregate.
+pub fn patient_to_domain(patient: &fhir::Patient) -> Result<patient::Patient, IngestionError> {
+    ensure_resource_type(&patient.resource_type, "Patient")?;
+    let patient_id = patient
+        .id
+        .as_deref()
+        .ok_or(IngestionError::MissingField("Patient.id"))?;
+    let (deceased, deceased_at) = deceased_from_patient(patient);
+
+    Ok(patient::Patient::new(PatientId(patient_id.to_string()))
+        .with_mrn(patient.mrn().and_then(business_identifier))
+        .with_identifiers(
+            patient
+                .identifier
+                .iter()
+                .filter_map(business_identifier)
+                .collect(),
+        )
+        .with_gender(parse_gender(patient.gender.as_deref())?)
+        .with_birth_date(patient.birth_date.clone())
+        .with_deceased(deceased, deceased_at))
+}
+
+/// Flatten a FHIR Encounter into a `stg_encounter_flat` row.
+pub fn encounter_to_staging(
+    encounter: &fhir::Encounter,
+) -> Result<StgEncounterFlat, IngestionError> {
+    ensure_resource_type(&encounter.resource_type, "Encounter")?;
+    let encounter_id = encounter
+        .id
+        .clone()
+        .ok_or(IngestionError::MissingField("Encounter.id"))?;
+    let patient_id = match encounter.subject.as_ref() {
+        Some(reference) => Some(reference::target_id(reference).ok_or(
+            IngestionError::InvalidReference("Encounter.subject.reference"),
+        )?),
+        None => None,
+    };
+    let status = parse_encounter_status(encounter.status.as_deref())?;
+    let period = encounter.period.as_ref();
+
+    Ok(StgEncounterFlat {
+        encounter_id,
+        patient_id,
+        status: status.map(|status| status.as_fhir_code().to_string()),
+        class_code: encounter.class.as_ref().and_then(|class| {
+            class
+                .code
+                .as_deref()
+                .map(|code| token(class.system.as_deref(), code))
+        }),
+        encounter_types: concept_tokens(&encounter.encounter_type),
+        period_start: period.and_then(|period| period.start.clone()),
+        period_end: period.and_then(|period| period.end.clone()),
+        service_provider: encounter
+            .service_provider
+            .as_ref()
+            .and_then(|reference| reference.reference.clone()),
+        identifiers: identifier_tokens(&encounter.identifier),
+    })
 }
 
-fn bundle_to_staging_inner(
+/// Convert a FHIR Encounter into the domain entity; the subject is required.
+pub fn encounter_to_domain(
+    encounter: &fhir::Encounter,
+) -> Result<encounter::Encounter, IngestionError> {
+    ensure_resource_type(&encounter.resource_type, "Encounter")?;
+    let encounter_id = encounter
+        .id
+        .as_deref()
+        .ok_or(IngestionError::MissingField("Encounter.id"))?;
+    let subject = encounter
+        .subject
+        .as_ref()
+        .ok_or(IngestionError::MissingField("Encounter.subject"))?;
+    let patient_id = reference::target_id(subject).ok_or(IngestionError::InvalidReference(
+        "Encounter.subject.reference",
+    ))?;
+
+    Ok(
+        encounter::Encounter::new(EncounterId(encounter_id.to_string()), PatientId(patient_id))
+            .with_identifiers(
+                encounter
+                    .identifier
+                    .iter()
+                    .filter_map(business_identifier)
+                    .collect(),
+            )
+            .with_status(parse_encounter_status(encounter.status.as_deref())?)
+            .with_class(encounter.class.as_ref().map(ClinicalCode::from))
+            .with_types(concept_codes(encounter.encounter_type.iter()))
+            .with_period(encounter.period.clone())
+            .with_service_provider(
+                encounter
+                    .service_provider
+                    .as_ref()
+                    .and_then(|reference| reference.reference.clone())
+                    .map(ResourceReference),
+            ),
+    )
+}
+
+/// Flatten every Patient in the bundle into staging rows.
+pub fn bundle_to_patient_staging(
     bundle: &fhir::Bundle,
-) -> Result<(Vec<StgServiceRequestFlat>, Vec<StgSrCodeExploded>), IngestionError> {
-    let mut flats = Vec::new();
-    let mut exploded = Vec::new();
-
-    for entry in bundle.iter_servicerequests() {
-        let sr = entry?;
-        let (flat, codes) = sr_to_staging(&sr)?;
-        flats.push(flat);
-        exploded.extend(codes);
-    }
+) -> Result<Vec<StgPatientFlat>, IngestionError> {
+    let processed = process_bundle(bundle)?;
+    BundleResolver::new(&processed.bundle)
+        .resources_of::<fhir::Patient>()
+        .map(|(_, entry)| patient_to_staging(&entry?))
+        .collect()
+}
 
-    Ok((flats, exploded))
+/// Flatten every Encounter in the bundle into staging rows.
+pub fn bundle_to_encounter_staging(
+    bundle: &fhir::Bundle,
+) -> Result<Vec<StgEncounterFlat>, IngestionError> {
+    let processed = process_bundle(bundle)?;
+    BundleResolver::new(&processed.bundle)
+        .resources_of::<fhir::Encounter>()
+        .map(|(_, entry)| encounter_to_staging(&entry?))
+        .collect()
 }
 
 /// Convert a bundle into domain ServiceRequest aggregates.
@@ -194,21 +538,47 @@ pub fn bundle_to_domain_with_validation(
     bundle: &fhir::Bundle,
     mode: ValidationMode,
 ) -> Result<Validated<Vec<order::ServiceRequest>>, IngestionError> {
-    let report = validate_bundle(bundle);
+    let processed = process_bundle(bundle)?;
+    let mut report = validate_processed_with_rules(&processed, &ValidationRules::default());
     if matches!(mode, ValidationMode::Strict) && report.has_errors() {
         return Err(IngestionError::ValidationFailed(report.issues.clone()));
     }
-    let output = bundle_to_domain_inner(bundle)?;
+    let output = bundle_to_domain_inner(&processed, &mut report.issues)?;
     Ok(Validated::new(output, report))
 }
 
+/// Repeated deliveries of an order fold into one aggregate. A delivery the
+/// lifecycle rejects is not applied: the earlier state is kept and the
+/// delivery is reported as `VAL_SR_REVISION_NOT_APPLIED`.
 fn bundle_to_domain_inner(
-    bundle: &fhir::Bundle,
+    processed: &ProcessedBundle,
+    issues: &mut Vec<ValidationIssue>,
 ) -> Result<Vec<order::ServiceRequest>, IngestionError> {
-    let mut output = Vec::new();
-    for entry in bundle.iter_servicerequests() {
-        let sr = entry?;
-        output.push(sr_to_domain(&sr)?);
+    let sources = processed.source_indices();
+    let mut output: Vec<order::ServiceRequest> = Vec::new();
+    for (index, entry) in
+        BundleResolver::new(&processed.bundle).primary_resources_of::<fhir::ServiceRequest>()
+    {
+        let sr = sr_to_domain(&entry?)?;
+        let Some(existing) = output.iter_mut().find(|existing| existing.id == sr.id) else {
+            output.push(sr);
+            continue;
+        };
+        if let Err(err) = existing.apply_revision(sr) {
+            issues.push(
+                ValidationIssue::new(
+                    "VAL_SR_REVISION_NOT_APPLIED",
+                    ValidationSeverity::Warning,
+                    format!(
+                        "ServiceRequest/{} delivery was not applied ({err}); the order stays {}.",
+                        existing.id.0,
+                        existing.status.as_fhir_code()
+                    ),
+                    RequirementRef::STATUS,
+                )
+                .with_path(format!("Bundle.entry[{}]", sources[index])),
+            );
+        }
     }
     Ok(output)
 }
@@ -225,23 +595,134 @@ fn description_from_sr(sr: &fhir::ServiceRequest) -> String {
         .unwrap_or_else(|| "unspecified service request".to_string())
 }
 
+/// Render codeable concepts as `system|code` tokens, falling back to the
+/// concept text when a concept carries no codings.
+pub(crate) fn concept_tokens(concepts: &[fhir::CodeableConcept]) -> Vec<String> {
+    concepts
+        .iter()
+        .flat_map(|concept| {
+            let coded: Vec<String> = concept
+                .coding
+                .iter()
+                .filter_map(|coding| {
+                    coding
+                        .code
+                        .as_deref()
+                        .map(|code| token(coding.system.as_deref(), code))
+                })
+                .collect();
+            if coded.is_empty() {
+                concept.text.clone().into_iter().collect()
+            } else {
+                coded
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn reference_tokens(references: &[fhir::Reference]) -> Vec<String> {
+    references
+        .iter()
+        .filter_map(|reference| reference.reference.clone())
+        .collect()
+}
+
+/// Domain codes for a set of concepts; text-only concepts keep their text as display.
+fn concept_codes<'a>(
+    concepts: impl Iterator<Item = &'a fhir::CodeableConcept>,
+) -> Vec<ClinicalCode> {
+    concepts
+        .flat_map(|concept| {
+            if concept.coding.is_empty() {
+                concept
+                    .text
+                    .clone()
+                    .map(|text| ClinicalCode::new(None, None, Some(text)))
+                    .into_iter()
+                    .collect()
+            } else {
+                concept
+                    .coding
+                    .iter()
+                    .map(ClinicalCode::from)
+                    .collect::<Vec<_>>()
+            }
+        })
+        .collect()
+}
+
+fn identifier_tokens(identifiers: &[fhir::Identifier]) -> Vec<String> {
+    identifiers
+        .iter()
+        .filter_map(|identifier| {
+            identifier
+                .value
+                .as_deref()
+                .map(|value| token(identifier.system.as_deref(), value))
+        })
+        .collect()
+}
+
+fn business_identifier(identifier: &fhir::Identifier) -> Option<BusinessIdentifier> {
+    identifier
+        .value
+        .as_ref()
+        .map(|value| BusinessIdentifier::new(identifier.system.clone(), value.clone()))
+}
+
+fn deceased_from_patient(patient: &fhir::Patient) -> (Option<bool>, Option<FhirDateTime>) {
+    match &patient.deceased {
+        Some(fhir::PatientDeceased::Boolean(flag)) => (Some(*flag), None),
+        Some(fhir::PatientDeceased::DateTime(at)) => (Some(true), Some(at.clone())),
+        None => (None, None),
+    }
+}
+
+fn parse_gender(value: Option<&str>) -> Result<Option<AdministrativeGender>, IngestionError> {
+    value
+        .map(|raw| {
+            AdministrativeGender::from_fhir_code(raw).ok_or_else(|| IngestionError::InvalidCode {
+                field: "Patient.gender",
+                value: raw.to_string(),
+            })
+        })
+        .transpose()
+}
+
+fn parse_encounter_status(value: Option<&str>) -> Result<Option<EncounterStatus>, IngestionError> {
+    value
+        .map(|raw| {
+            EncounterStatus::from_fhir_code(raw).ok_or_else(|| IngestionError::InvalidCode {
+                field: "Encounter.status",
+                value: raw.to_string(),
+            })
+        })
+        .transpose()
+}
+
+fn domain_references(references: &[fhir::Reference]) -> Vec<ResourceReference> {
+    reference_tokens(references)
+        .into_iter()
+        .map(ResourceReference)
+        .collect()
+}
+
+pub(crate) fn token(system: Option<&str>, value: &str) -> String {
+    match system {
+        Some(system) => format!("{system}|{value}"),
+        None => value.to_string(),
+    }
+}
+
 fn parse_status(value: Option<&str>) -> Result<(String, ServiceRequestStatus), IngestionError> {
     let raw = value.ok_or(IngestionError::MissingField("ServiceRequest.status"))?;
     let normalized = raw.to_ascii_lowercase();
-    let status = match normalized.as_str() {
-        "draft" => ServiceRequestStatus::Draft,
-        "on-hold" | "on_hold" => ServiceRequestStatus::OnHold,
-        "completed" => ServiceRequestStatus::Completed,
-        "cancelled" | "canceled" => ServiceRequestStatus::Cancelled,
-        "revoked" => ServiceRequestStatus::Revoked,
-        "entered-in-error" | "entered_in_error" => ServiceRequestStatus::EnteredInError,
-        "active" => ServiceRequestStatus::Active,
-        other => return Err(IngestionError::InvalidStatus(other.to_string())),
-    };
+    let status = ServiceRequestStatus::from_fhir_code(&normalized)
+        .ok_or_else(|| IngestionError::InvalidStatus(normalized.clone()))?;
     Ok((normalized, status))
 }
 
-fn parse_intent(
+pub(crate) fn parse_intent(
     value: Option<&str>,
     status: ServiceRequestStatus,
 ) -> Result<(String, ServiceRequestIntent), IngestionError> {
@@ -270,7 +751,10 @@ fn parse_intent(
     Ok((normalized, coerced))
 }
 
-fn ensure_resource_type(actual: &str, expected: &'static str) -> Result<(), IngestionError> {
+pub(crate) fn ensure_resource_type(
+    actual: &str,
+    expected: &'static str,
+) -> Result<(), IngestionError> {
     if actual != expected {
         return Err(IngestionError::InvalidResourceType {
             expected,
@@ -284,10 +768,126 @@ fn ensure_resource_type(actual: &str, expected: &'static str) -> Result<(), Inge
 mod tests {
     use super::*;
 
+    #[test]
+    fn domain_order_carries_codes_reasons_and_timing() {
+        let sr = fhir::ServiceRequest {
+            id: Some("sr-domain".into()),
+            status: Some("active".into()),
+            intent: Some("order".into()),
+            subject: Some(fhir::Reference {
+                reference: Some("Patient/p1".into()),
+                display: None,
+            }),
+            requester: Some(fhir::Reference {
+                reference: Some("Practitioner/pr-1".into()),
+                display: None,
+            }),
+            supporting_info: vec![fhir::Reference {
+                reference: Some("Observation/obs-1".into()),
+                display: None,
+            }],
+            code: Some(fhir::CodeableConcept {
+                coding: vec![fhir::Coding {
+                    system: Some("http://snomed.info/sct".into()),
+                    code: Some("363679005".into()),
+                    display: Some("Imaging".into()),
+                }],
+                text: None,
+            }),
+            category: vec![fhir::CodeableConcept {
+                coding: vec![],
+                text: Some("Imaging".into()),
+            }],
+            reason_reference: vec![fhir::Reference {
+                reference: Some("Condition/c-1".into()),
+                display: None,
+            }],
+            occurrence: Some(fhir::ServiceRequestOccurrence::Period(fhir::Period {
+                start: Some("2024-05-02".parse().unwrap()),
+                end: Some("2024-05-03".parse().unwrap()),
+            })),
+            authored_on: Some("2024-05-01T12:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+
+        let order = sr_to_domain(&sr).expect("maps to domain");
+        assert!(order.has_code("http://snomed.info/sct", "363679005"));
+        assert_eq!(order.categories[0].display.as_deref(), Some("Imaging"));
+        assert_eq!(order.categories[0].code, None);
+        assert_eq!(
+            order.reason_references,
+            vec![ResourceReference::new("Condition/c-1")]
+        );
+        assert_eq!(
+            order.requester.as_ref().and_then(|r| r.resource_type()),
+            Some("Practitioner")
+        );
+        assert_eq!(
+            order.authored_on.as_ref().map(FhirDateTime::as_str),
+            Some("2024-05-01T12:00:00Z")
+        );
+        assert_eq!(
+            order.occurrence_start.as_ref().map(FhirDateTime::as_str),
+            Some("2024-05-02")
+        );
+        assert_eq!(
+            order.occurrence_end.as_ref().map(FhirDateTime::as_str),
+            Some("2024-05-03")
+        );
+        assert_eq!(order.supporting_info.len(), 1);
+    }
+
+    #[test]
+    fn repeated_orders_fold_into_one_aggregate_with_history() {
+        let delivery = |status: &str| fhir::BundleEntry {
+            full_url: None,
+            resource: Some(serde_json::json!({
+                "resourceType": "ServiceRequest",
+                "id": "SR-1",
+                "status": status,
+                "intent": "order",
+                "subject": { "reference": "Patient/p1" }
+            })),
+            ..Default::default()
+        };
+        let bundle = fhir::Bundle {
+            resource_type: "Bundle".into(),
+            id: None,
+            bundle_type: Some("collection".into()),
+            entry: vec![
+                delivery("active"),
+                delivery("on-hold"),
+                delivery("completed"),
+            ],
+        };
+
+        let validated = bundle_to_domain_with_validation(&bundle, ValidationMode::Lenient)
+            .expect("domain conversion");
+        let orders = validated.value;
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].status, ServiceRequestStatus::OnHold);
+        assert_eq!(orders[0].status_history.len(), 1);
+        let skipped: Vec<_> = validated
+            .report
+            .issues
+            .iter()
+            .filter(|issue| issue.id == "VAL_SR_REVISION_NOT_APPLIED")
+            .collect();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].path.as_deref(), Some("Bundle.entry[2]"));
+        assert!(skipped[0].message.contains("on-hold -> completed"));
+
+        let strict = bundle_to_domain_with_validation(&bundle, ValidationMode::Strict);
+        assert!(matches!(
+            strict,
+            Err(IngestionError::ValidationFailed(issues))
+                if issues.iter().any(|i| i.id == "VAL_SR_STATUS_TRANSITION_INVALID")
+        ));
+    }
+
     #[test]
     fn description_prefers_sr_field() {
         let sr = fhir::ServiceRequest {
-            resource_type: "ServiceRequest".into(),
             id: Some("sr".into()),
             status: Some("active".into()),
             intent: Some("order".into()),
@@ -306,9 +906,9 @@ mod tests {
                 }],
                 text: Some("PET CT".into()),
             }),
-            category: vec![],
             description: Some("Preferred".into()),
-            authored_on: Some("2024-05-01T12:00:00Z".into()),
+            authored_on: Some("2024-05-01T12:00:00Z".parse().unwrap()),
+            ..Default::default()
         };
 
         assert_eq!(description_from_sr(&sr), "Preferred");
@@ -344,6 +944,143 @@ mod tests {
         matches!(err, IngestionError::InvalidIntent(value) if value == "weird");
     }
 
+    #[test]
+    fn patient_and_encounter_flatten_to_staging_rows() {
+        let bundle: fhir::Bundle = serde_json::from_value(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                { "resource": {
+                    "resourceType": "Patient",
+                    "id": "p1",
+                    "identifier": [{
+                        "type": { "coding": [{ "code": "MR" }] },
+                        "system": "urn:mrn",
+                        "value": "MRN-1"
+                    }],
+                    "gender": "Female",
+                    "birthDate": "1980-02-29",
+                    "deceasedBoolean": false
+                } },
+                { "resource": {
+                    "resourceType": "Encounter",
+                    "id": "e1",
+                    "status": "in-progress",
+                    "class": { "system": "http://terminology.hl7.org/CodeSystem/v3-ActCode", "code": "AMB" },
+                    "subject": { "reference": "Patient/p1" },
+                    "period": { "start": "2024-05-01T08:00:00Z" },
+                    "serviceProvider": { "reference": "Organization/o1" }
+                } }
+            ]
+        }))
+        .unwrap();
+
+        let patients = bundle_to_patient_staging(&bundle).expect("patient rows");
+        assert_eq!(patients.len(), 1);
+        assert_eq!(patients[0].mrn.as_deref(), Some("MRN-1"));
+        assert_eq!(patients[0].identifiers, vec!["urn:mrn|MRN-1".to_string()]);
+        assert_eq!(patients[0].gender.as_deref(), Some("female"));
+        assert_eq!(patients[0].deceased, Some(false));
+
+        let encounters = bundle_to_encounter_staging(&bundle).expect("encounter rows");
+        assert_eq!(encounters[0].patient_id.as_deref(), Some("p1"));
+        assert_eq!(
+            encounters[0].class_code.as_deref(),
+            Some("http://terminology.hl7.org/CodeSystem/v3-ActCode|AMB")
+        );
+        assert_eq!(encounters[0].status.as_deref(), Some("in-progress"));
+        assert_eq!(
+            encounters[0].service_provider.as_deref(),
+            Some("Organization/o1")
+        );
+
+        let patient = patient_to_domain(&bundle.iter_patients().next().unwrap().unwrap()).unwrap();
+        assert_eq!(patient.gender, Some(AdministrativeGender::Female));
+        assert_eq!(patient.mrn.map(|mrn| mrn.value).as_deref(), Some("MRN-1"));
+
+        let encounter =
+            encounter_to_domain(&bundle.iter_encounters().next().unwrap().unwrap()).unwrap();
+        assert_eq!(encounter.patient_id, PatientId::new("p1"));
+        assert_eq!(encounter.status, Some(EncounterStatus::InProgress));
+    }
+
+    #[test]
+    fn patient_with_unknown_gender_code_errors() {
+        let patient = fhir::Patient {
+            id: Some("p1".into()),
+            gender: Some("f".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            patient_to_staging(&patient),
+            Err(IngestionError::InvalidCode {
+                field: "Patient.gender",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn encounter_domain_requires_subject() {
+        let encounter = fhir::Encounter {
+            id: Some("e1".into()),
+            ..Default::default()
+        };
+        assert!(encounter_to_staging(&encounter).is_ok());
+        assert!(matches!(
+            encounter_to_domain(&encounter),
+            Err(IngestionError::MissingField("Encounter.subject"))
+        ));
+    }
+
+    #[test]
+    fn staging_carries_r4_clinical_fields() {
+        let sr: fhir::ServiceRequest = serde_json::from_value(serde_json::json!({
+            "resourceType": "ServiceRequest",
+            "id": "SR-R4",
+            "identifier": [{ "system": "urn:placer", "value": "PL-9" }],
+            "status": "active",
+            "intent": "order",
+            "priority": "STAT",
+            "subject": { "reference": "Patient/p1" },
+            "occurrenceDateTime": "2024-05-03T08:00:00Z",
+            "reasonCode": [
+                { "coding": [{ "system": "http://snomed.info/sct", "code": "363346000" }] },
+                { "text": "staging" }
+            ],
+            "reasonReference": [{ "reference": "Condition/c-1" }],
+            "bodySite": [{ "coding": [{ "system": "http://snomed.info/sct", "code": "38266002" }] }],
+            "performer": [{ "reference": "Organization/rad" }],
+            "specimen": [{ "reference": "Specimen/s-1" }],
+            "note": [{ "text": "Fasting 6h" }],
+            "basedOn": [{ "reference": "CarePlan/cp-1" }],
+            "replaces": [{ "reference": "ServiceRequest/SR-OLD" }],
+            "orderDetail": [{ "text": "with contrast" }]
+        }))
+        .unwrap();
+
+        let (flat, _) = sr_to_staging(&sr).expect("staging conversion");
+        assert_eq!(flat.priority.as_deref(), Some("stat"));
+        assert_eq!(
+            flat.occurrence_start.as_ref().map(FhirDateTime::as_str),
+            Some("2024-05-03T08:00:00Z")
+        );
+        assert_eq!(flat.occurrence_end, None);
+        assert_eq!(
+            flat.reason_codes,
+            vec!["http://snomed.info/sct|363346000", "staging"]
+        );
+        assert_eq!(flat.reason_references, vec!["Condition/c-1"]);
+        assert_eq!(flat.body_sites, vec!["http://snomed.info/sct|38266002"]);
+        assert_eq!(flat.performers, vec!["Organization/rad"]);
+        assert_eq!(flat.specimens, vec!["Specimen/s-1"]);
+        assert_eq!(flat.notes, vec!["Fasting 6h"]);
+        assert_eq!(flat.identifiers, vec!["urn:placer|PL-9"]);
+        assert_eq!(flat.based_on, vec!["CarePlan/cp-1"]);
+        assert_eq!(flat.replaces, vec!["ServiceRequest/SR-OLD"]);
+        assert_eq!(flat.order_details, vec!["with contrast"]);
+    }
+
     #[test]
     fn strict_validation_blocks_bundle_to_staging() {
         let bundle = bundle_missing_patient_resource();
@@ -366,7 +1103,6 @@ mod tests {
 
     fn minimal_sr() -> fhir::ServiceRequest {
         fhir::ServiceRequest {
-            resource_type: "ServiceRequest".into(),
             id: Some("sr-1".into()),
             status: Some("active".into()),
             intent: Some("order".into()),
@@ -374,19 +1110,15 @@ mod tests {
                 reference: Some("Patient/p1".into()),
                 display: None,
             }),
-            encounter: None,
-            requester: None,
-            supporting_info: vec![],
-            code: None,
-            category: vec![],
-            description: None,
-            authored_on: Some("2024-05-01T12:00:00Z".into()),
+            authored_on: Some("2024-05-01T12:00:00Z".parse().unwrap()),
+            ..Default::default()
         }
     }
 
     fn bundle_missing_patient_resource() -> fhir::Bundle {
         fhir::Bundle {
             resource_type: "Bundle".into(),
+            id: None,
             bundle_type: Some("collection".into()),
             entry: vec![fhir::BundleEntry {
                 full_url: None,
@@ -397,6 +1129,7 @@ mod tests {
                     "intent": "order",
                     "subject": { "reference": "Patient/PAT-404" }
                 })),
+                ..Default::default()
             }],
         }
     }
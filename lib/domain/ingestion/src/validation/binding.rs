@@ -0,0 +1,370 @@
+//! ValueSet bindings for coded elements, configured per deployment.
+//!
+//! A [`ValueSetBinding`] ties an element path (`ServiceRequest.code`,
+//! `ServiceRequest.category`) to a ValueSet. Bindings are declared next to the
+//! site rules (see [`super::ValidationRules`]):
+//!
+//! ```toml
+//! [[bindings]]
+//! path = "ServiceRequest.code"
+//! value_set = "http://terminology.dfps/ValueSet/pet-imaging-procedures"
+//! strength = "required"            # required | extensible | preferred
+//! ```
+//!
+//! The ValueSet is looked up among those loaded with the profiles first, then
+//! in the active `dfps_terminology` store (both evaluate compose rules,
+//! filters over loaded CodeSystems and expansions, so individual codes are
+//! checked), then in the `dfps_terminology` registry, whose ValueSets admit
+//! every code of their `include_systems`. An element instance conforms when
+//! one of its codings is admitted; otherwise the issue is `VAL_BINDING_SYSTEM`
+//! when none of its systems is in the ValueSet and `VAL_BINDING_CODE` when a
+//! system is but the code is not. The strength sets the severity: `required`
+//! is an error, `extensible` a warning and `preferred` info.
+
+use std::sync::OnceLock;
+
+use dfps_terminology::{TerminologyStore, ValueSetDefinition, valueset};
+use serde_json::Value;
+
+use super::{
+    BindingStrength, Profiles, RequirementRef, ValidationIssue, ValidationSeverity,
+    profile::{ValueSetCodes, codings, instances},
+};
+use crate::transforms::IngestionError;
+
+/// One configured binding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueSetBinding {
+    /// Element path rooted at the resource type (`ServiceRequest.category`).
+    pub path: String,
+    /// Canonical URL of the ValueSet.
+    pub value_set: String,
+    /// `required`, `extensible` or `preferred`.
+    pub strength: BindingStrength,
+}
+
+impl ValueSetBinding {
+    /// Fails with [`IngestionError::InvalidValidationRules`] when `path` does
+    /// not start with a resource type or `strength` is `example`, which
+    /// imposes nothing to check.
+    pub fn new(
+        path: impl Into<String>,
+        value_set: impl Into<String>,
+        strength: BindingStrength,
+    ) -> Result<Self, IngestionError> {
+        let binding = Self {
+            path: path.into(),
+            value_set: value_set.into(),
+            strength,
+        };
+        let invalid = |reason: &str| {
+            IngestionError::InvalidValidationRules(format!("binding '{}': {reason}", binding.path))
+        };
+        if !binding.path.contains('.')
+            || !binding.path.starts_with(|c: char| c.is_ascii_uppercase())
+        {
+            return Err(invalid("path must be Type.element"));
+        }
+        if binding.value_set.trim().is_empty() {
+            return Err(invalid("value_set must not be empty"));
+        }
+        if strength == BindingStrength::Example {
+            return Err(invalid("example bindings are not checked"));
+        }
+        Ok(binding)
+    }
+
+    /// Resource type the binding applies to.
+    pub fn resource_type(&self) -> &str {
+        self.path.split('.').next().unwrap_or_default()
+    }
+
+    /// Issues for every instance of the bound element in `resource` that has
+    /// no coding from the ValueSet. ValueSets loaded with `profiles` take
+    /// precedence over the terminology store and registry.
+    pub fn check(&self, resource: &Value, profiles: &Profiles) -> Vec<ValidationIssue> {
+        if resource.get("resourceType").and_then(Value::as_str) != Some(self.resource_type()) {
+            return Vec::new();
+        }
+        let elements = instances(resource, &self.path);
+        if elements.is_empty() {
+            return Vec::new();
+        }
+
+        let registry = registry_value_sets();
+        let codes = match profiles.value_set(&self.value_set) {
+            Some(codes) => codes,
+            None if registry.value_set(&self.value_set).is_some() => {
+                ValueSetCodes::new(registry, &self.value_set)
+            }
+            None => {
+                return vec![
+                    ValidationIssue::new(
+                        "VAL_BINDING_UNCHECKED",
+                        ValidationSeverity::Info,
+                        format!(
+                            "ValueSet {} for {} is not loaded; binding not checked",
+                            self.value_set, self.path
+                        ),
+                        RequirementRef::BINDING,
+                    )
+                    .with_path(self.path.clone()),
+                ];
+            }
+        };
+
+        let severity = match self.strength {
+            BindingStrength::Required => ValidationSeverity::Error,
+            BindingStrength::Extensible => ValidationSeverity::Warning,
+            BindingStrength::Preferred | BindingStrength::Example => ValidationSeverity::Info,
+        };
+        let strength = match self.strength {
+            BindingStrength::Required => "required",
+            BindingStrength::Extensible => "extensible",
+            BindingStrength::Preferred => "preferred",
+            BindingStrength::Example => "example",
+        };
+        let mut issues = Vec::new();
+        for (at, value) in elements {
+            let codings = codings(value);
+            if codings
+                .iter()
+                .any(|(system, code)| codes.admits(*system, code))
+            {
+                continue;
+            }
+            let known_system = codings
+                .iter()
+                .filter_map(|(system, _)| *system)
+                .find(|system| codes.includes_system(system));
+            let (id, message) = match known_system {
+                Some(system) => (
+                    "VAL_BINDING_CODE",
+                    format!(
+                        "{at} has no {system} code from {strength} ValueSet {}",
+                        self.value_set
+                    ),
+                ),
+                None => (
+                    "VAL_BINDING_SYSTEM",
+                    format!(
+                        "{at} has no coding in a system of {strength} ValueSet {} (found {})",
+                        self.value_set,
+                        found_systems(&codings)
+                    ),
+                ),
+            };
+            issues.push(
+                ValidationIssue::new(id, severity, message, RequirementRef::BINDING).with_path(at),
+            );
+        }
+        issues
+    }
+}
+
+fn found_systems(codings: &[(Option<&str>, &str)]) -> String {
+    let systems: Vec<&str> = codings
+        .iter()
+        .map(|(system, _)| system.unwrap_or("no system"))
+        .collect();
+    if systems.is_empty() {
+        "no codings".to_string()
+    } else {
+        systems.join(", ")
+    }
+}
+
+/// The registry ValueSets as a store, built once and shared by every check.
+fn registry_value_sets() -> &'static TerminologyStore {
+    static REGISTRY: OnceLock<TerminologyStore> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut store = TerminologyStore::new();
+        for meta in valueset::list_value_sets() {
+            // Registry entries are static; one that does not convert stays
+            // unloaded and its bindings are reported as unchecked.
+            let _ = store.insert_value_set(ValueSetDefinition::from(meta));
+        }
+        store
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PET: &str = "http://terminology.dfps/ValueSet/pet-imaging-procedures";
+
+    fn order(code: Value, category: Value) -> Value {
+        json!({
+            "resourceType": "ServiceRequest",
+            "id": "SR-1",
+            "status": "active",
+            "intent": "order",
+            "subject": { "reference": "Patient/P-1" },
+            "code": code,
+            "category": category
+        })
+    }
+
+    fn ids(issues: &[ValidationIssue]) -> Vec<(&str, ValidationSeverity, Option<&str>)> {
+        issues
+            .iter()
+            .map(|issue| (issue.id.as_str(), issue.severity, issue.path.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn registry_value_sets_check_systems_with_strength_severity() {
+        let resource = order(
+            json!({ "coding": [{ "system": "http://loinc.org", "code": "24627-2" }] }),
+            json!([{ "coding": [{ "system": "http://snomed.info/sct", "code": "363679005" }] }]),
+        );
+        let code =
+            ValueSetBinding::new("ServiceRequest.code", PET, BindingStrength::Required).unwrap();
+        let issues = code.check(&resource, &Profiles::new());
+        assert_eq!(
+            ids(&issues),
+            [(
+                "VAL_BINDING_SYSTEM",
+                ValidationSeverity::Error,
+                Some("ServiceRequest.code")
+            )]
+        );
+        assert_eq!(issues[0].requirement, RequirementRef::BINDING);
+
+        let category =
+            ValueSetBinding::new("ServiceRequest.category", PET, BindingStrength::Extensible)
+                .unwrap();
+        assert!(category.check(&resource, &Profiles::new()).is_empty());
+
+        let preferred = ValueSetBinding::new(
+            "ServiceRequest.code",
+            "http://terminology.dfps/ValueSet/imaging-ordering",
+            BindingStrength::Preferred,
+        )
+        .unwrap();
+        assert!(preferred.check(&resource, &Profiles::new()).is_empty());
+    }
+
+    #[test]
+    fn loaded_value_sets_check_codes() {
+        let profiles = Profiles::from_json_str(
+            &json!({
+                "resourceType": "ValueSet",
+                "url": "https://dfps.example/fhir/ValueSet/pet-orders",
+                "compose": { "include": [{
+                    "system": "http://www.ama-assn.org/go/cpt",
+                    "concept": [{ "code": "78815" }]
+                }]}
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let binding = ValueSetBinding::new(
+            "ServiceRequest.code",
+            "https://dfps.example/fhir/ValueSet/pet-orders|1.0",
+            BindingStrength::Extensible,
+        )
+        .unwrap();
+        let cpt = |code: &str| {
+            order(
+                json!({ "coding": [{ "system": "http://www.ama-assn.org/go/cpt", "code": code }] }),
+                json!([]),
+            )
+        };
+        assert!(binding.check(&cpt("78815"), &profiles).is_empty());
+        assert_eq!(
+            ids(&binding.check(&cpt("70450"), &profiles)),
+            [(
+                "VAL_BINDING_CODE",
+                ValidationSeverity::Warning,
+                Some("ServiceRequest.code")
+            )]
+        );
+    }
+
+    #[test]
+    fn loaded_code_systems_drive_value_set_filters() {
+        let profiles = Profiles::from_json_str(
+            &json!({
+                "resourceType": "Bundle",
+                "entry": [
+                    { "resource": {
+                        "resourceType": "CodeSystem",
+                        "url": "https://dfps.example/fhir/CodeSystem/orders",
+                        "content": "complete",
+                        "concept": [{
+                            "code": "IMG",
+                            "concept": [{ "code": "PET" }, { "code": "CT" }]
+                        }, { "code": "LAB" }]
+                    }},
+                    { "resource": {
+                        "resourceType": "ValueSet",
+                        "url": "https://dfps.example/fhir/ValueSet/imaging",
+                        "compose": {
+                            "include": [{
+                                "system": "https://dfps.example/fhir/CodeSystem/orders",
+                                "filter": [{ "property": "concept", "op": "descendent-of", "value": "IMG" }]
+                            }],
+                            "exclude": [{
+                                "system": "https://dfps.example/fhir/CodeSystem/orders",
+                                "concept": [{ "code": "CT" }]
+                            }]
+                        }
+                    }}
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let binding = ValueSetBinding::new(
+            "ServiceRequest.code",
+            "https://dfps.example/fhir/ValueSet/imaging",
+            BindingStrength::Required,
+        )
+        .unwrap();
+        let coded = |code: &str| {
+            order(
+                json!({ "coding": [{ "system": "https://dfps.example/fhir/CodeSystem/orders", "code": code }] }),
+                json!([]),
+            )
+        };
+        assert!(binding.check(&coded("PET"), &profiles).is_empty());
+        for code in ["CT", "IMG", "LAB"] {
+            assert_eq!(
+                ids(&binding.check(&coded(code), &profiles)),
+                [(
+                    "VAL_BINDING_CODE",
+                    ValidationSeverity::Error,
+                    Some("ServiceRequest.code")
+                )],
+                "{code}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_value_sets_and_bad_specs() {
+        let binding = ValueSetBinding::new(
+            "ServiceRequest.category",
+            "https://dfps.example/fhir/ValueSet/missing",
+            BindingStrength::Required,
+        )
+        .unwrap();
+        let resource = order(json!({ "text": "PET" }), json!([{ "text": "Imaging" }]));
+        assert_eq!(
+            ids(&binding.check(&resource, &Profiles::new())),
+            [(
+                "VAL_BINDING_UNCHECKED",
+                ValidationSeverity::Info,
+                Some("ServiceRequest.category")
+            )]
+        );
+
+        assert!(ValueSetBinding::new("code", PET, BindingStrength::Required).is_err());
+        assert!(
+            ValueSetBinding::new("ServiceRequest.code", PET, BindingStrength::Example).is_err()
+        );
+    }
+}
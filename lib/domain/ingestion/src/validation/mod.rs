@@ -0,0 +1,909 @@
+//! Validation primitives for FHIR ServiceRequest ingestion requirements.
+//!
+//! Each [`RequirementRef`] corresponds to an ID defined in
+//! `docs/system-design/clinical/fhir/requirements/ingestion-requirements.md`.
+
+mod binding;
+mod profile;
+mod rules;
+
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+use dfps_core::{
+    fhir,
+    order::{self, LifecycleError, ServiceRequestStatus},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bundle_semantics::{EntryResult, ProcessedBundle, process_bundle},
+    reference::{BundleResolver, ParsedReference, reference_id_from_str},
+    results::stage_results,
+    transforms::{IngestionError, parse_intent},
+};
+
+pub use binding::ValueSetBinding;
+pub use profile::{
+    BindingStrength, ElementBinding, ElementDefinition, Profiles, StructureDefinition,
+};
+pub use rules::{ValidationRule, ValidationRules};
+
+/// Requirement identifier such as `R_Subject`.
+///
+/// The built-in constants mirror the ingestion requirements doc; rules files
+/// (see [`ValidationRules`]) name their own, so the set is open-ended.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RequirementRef(Cow<'static, str>);
+
+impl RequirementRef {
+    /// Every ServiceRequest references a Patient.
+    pub const SUBJECT: Self = Self(Cow::Borrowed("R_Subject"));
+    /// Status values are acceptable/normalizable.
+    pub const STATUS: Self = Self(Cow::Borrowed("R_Status"));
+    /// Provenance/trace identifiers are present.
+    pub const TRACE: Self = Self(Cow::Borrowed("R_Trace"));
+    /// Resources conform to the configured implementation-guide profiles.
+    pub const PROFILE: Self = Self(Cow::Borrowed("R_Profile"));
+    /// Coded elements draw on the ValueSets configured for them.
+    pub const BINDING: Self = Self(Cow::Borrowed("R_Binding"));
+
+    pub fn new(code: impl Into<String>) -> Self {
+        Self(Cow::Owned(code.into()))
+    }
+
+    /// Return the canonical string code used in documentation.
+    pub fn as_code(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequirementRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Severity of a validation issue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// Describes a requirement-linked validation issue discovered during ingestion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    /// Stable issue identifier (e.g., `VAL_SR_SUBJECT_MISSING`).
+    pub id: String,
+    pub severity: ValidationSeverity,
+    pub message: String,
+    pub requirement: RequirementRef,
+    /// Element the issue is about, with array indices
+    /// (`ServiceRequest.code.coding[0].system`), when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+impl ValidationIssue {
+    /// Convenience constructor for building a requirement-linked issue.
+    pub fn new(
+        id: impl Into<String>,
+        severity: ValidationSeverity,
+        message: impl Into<String>,
+        requirement: RequirementRef,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            severity,
+            message: message.into(),
+            requirement,
+            path: None,
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Return the canonical requirement code (e.g., `R_Subject`).
+    pub fn requirement_ref(&self) -> &str {
+        self.requirement.as_code()
+    }
+}
+
+/// Validate a FHIR ServiceRequest against ingestion requirements.
+pub fn validate_sr(sr: &fhir::ServiceRequest) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    validate_subject(sr, &mut issues);
+    validate_status(sr, &mut issues);
+    validate_traceability(sr, &mut issues);
+
+    issues
+}
+
+/// Aggregated validation mode for bundle ingestion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationMode {
+    Strict,
+    #[default]
+    Lenient,
+}
+
+/// Aggregated report returned by `validate_bundle`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn new(issues: Vec<ValidationIssue>) -> Self {
+        Self { issues }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| issue.severity == ValidationSeverity::Error)
+    }
+}
+
+/// Output wrapper for functions that combine ingestion + validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validated<T> {
+    pub value: T,
+    pub report: ValidationReport,
+}
+
+impl<T> Validated<T> {
+    pub fn new(value: T, report: ValidationReport) -> Self {
+        Self { value, report }
+    }
+}
+
+/// Validate an entire FHIR Bundle by walking ServiceRequests and referenced resources.
+pub fn validate_bundle(bundle: &fhir::Bundle) -> ValidationReport {
+    validate_bundle_with_rules(bundle, &ValidationRules::default())
+}
+
+/// [`validate_bundle`] plus `rules`, evaluated against every ingested entry
+/// (searchset `include` entries excluded) with its references localized and
+/// `resolve()` scoped to the Bundle.
+pub fn validate_bundle_with_rules(
+    bundle: &fhir::Bundle,
+    rules: &ValidationRules,
+) -> ValidationReport {
+    match process_bundle(bundle) {
+        Ok(processed) => validate_processed_with_rules(&processed, rules),
+        Err(err) => ValidationReport::new(vec![ValidationIssue::new(
+            "VAL_BUNDLE_REJECTED",
+            ValidationSeverity::Error,
+            format!("Bundle cannot be ingested: {err}"),
+            RequirementRef::TRACE,
+        )]),
+    }
+}
+
+/// [`validate_bundle_with_rules`] for a Bundle already run through
+/// [`process_bundle`].
+pub fn validate_processed_with_rules(
+    processed: &ProcessedBundle,
+    rules: &ValidationRules,
+) -> ValidationReport {
+    let mut issues: Vec<_> = processed.failed().map(rejected_entry_issue).collect();
+
+    let resolver = BundleResolver::new(&processed.bundle);
+    let mut last_status = HashMap::new();
+
+    for (index, entry) in resolver.primary_resources_of::<fhir::ServiceRequest>() {
+        match entry {
+            Ok(sr) => issues.extend(validate_sr_entry(&sr, &resolver, index, &mut last_status)),
+            Err(err) => issues.push(sr_decode_issue(&err)),
+        }
+    }
+
+    validate_result_links(&resolver, &mut issues);
+    let sources = processed.source_indices();
+    stage_results(&resolver, |index, err| {
+        issues.push(result_not_staged_issue(sources[index], &err));
+    });
+
+    if !rules.is_empty() {
+        for (index, entry) in processed.bundle.entry.iter().enumerate() {
+            if !entry.is_search_include()
+                && let Some(resource) = resolver.localize(index)
+            {
+                issues.extend(rules.check_with(&resource, &resolver.fhirpath_resolver(index)));
+            }
+        }
+    }
+
+    ValidationReport::new(issues)
+}
+
+/// Issues for one ServiceRequest entry: its own fields, its links within the
+/// Bundle, and its status against earlier deliveries of the same order.
+pub(crate) fn validate_sr_entry(
+    sr: &fhir::ServiceRequest,
+    resolver: &BundleResolver<'_>,
+    entry_index: usize,
+    last_status: &mut HashMap<String, ServiceRequestStatus>,
+) -> Vec<ValidationIssue> {
+    let mut issues = validate_sr(sr);
+    validate_bundle_relationships(sr, resolver, entry_index, &mut issues);
+    validate_status_sequence(sr, last_status, &mut issues);
+    issues
+}
+
+pub(crate) fn rejected_entry_issue(failed: &EntryResult) -> ValidationIssue {
+    ValidationIssue::new(
+        "VAL_BUNDLE_ENTRY_REJECTED",
+        ValidationSeverity::Warning,
+        format!(
+            "Bundle entry {} was rejected ({}): {}",
+            failed.index,
+            failed.response.status,
+            failed.reason().unwrap_or("no diagnostics")
+        ),
+        RequirementRef::TRACE,
+    )
+    .with_path(format!("Bundle.entry[{}]", failed.index))
+}
+
+fn result_not_staged_issue(index: usize, err: &IngestionError) -> ValidationIssue {
+    ValidationIssue::new(
+        "VAL_RESULT_NOT_STAGED",
+        ValidationSeverity::Error,
+        format!("Bundle entry {index} cannot be staged as a result: {err}"),
+        RequirementRef::TRACE,
+    )
+    .with_path(format!("Bundle.entry[{index}]"))
+}
+
+pub(crate) fn sr_decode_issue(err: &serde_json::Error) -> ValidationIssue {
+    ValidationIssue::new(
+        "VAL_BUNDLE_SR_DECODE",
+        ValidationSeverity::Error,
+        format!("Failed to decode ServiceRequest: {err}"),
+        RequirementRef::TRACE,
+    )
+}
+
+fn validate_subject(sr: &fhir::ServiceRequest, issues: &mut Vec<ValidationIssue>) {
+    match sr
+        .subject
+        .as_ref()
+        .and_then(|reference| reference.reference.as_deref())
+    {
+        Some(reference) if is_patient_reference(reference) => {}
+        Some(_) => issues.push(
+            ValidationIssue::new(
+                "VAL_SR_SUBJECT_INVALID",
+                ValidationSeverity::Error,
+                "ServiceRequest.subject must reference a Patient (Patient/<id>).",
+                RequirementRef::SUBJECT,
+            )
+            .with_path("ServiceRequest.subject"),
+        ),
+        None => issues.push(
+            ValidationIssue::new(
+                "VAL_SR_SUBJECT_MISSING",
+                ValidationSeverity::Error,
+                "ServiceRequest.subject is required.",
+                RequirementRef::SUBJECT,
+            )
+            .with_path("ServiceRequest.subject"),
+        ),
+    }
+}
+
+fn validate_status(sr: &fhir::ServiceRequest, issues: &mut Vec<ValidationIssue>) {
+    match sr.status.as_deref() {
+        Some(value) if is_known_status(value) => {}
+        Some(_) => issues.push(ValidationIssue::new(
+            "VAL_SR_STATUS_INVALID",
+            ValidationSeverity::Error,
+            "ServiceRequest.status must be a recognized value (draft, active, on-hold, completed, cancelled, revoked, entered-in-error).",
+            RequirementRef::STATUS,
+        )
+        .with_path("ServiceRequest.status")),
+        None => issues.push(
+            ValidationIssue::new(
+                "VAL_SR_STATUS_MISSING",
+                ValidationSeverity::Error,
+                "ServiceRequest.status is required.",
+                RequirementRef::STATUS,
+            )
+            .with_path("ServiceRequest.status"),
+        ),
+    }
+}
+
+fn validate_traceability(sr: &fhir::ServiceRequest, issues: &mut Vec<ValidationIssue>) {
+    if sr.id.as_deref().unwrap_or("").is_empty() {
+        issues.push(
+            ValidationIssue::new(
+                "VAL_SR_TRACE_ID_MISSING",
+                ValidationSeverity::Error,
+                "ServiceRequest.id is required to trace staging rows back to the Bundle.",
+                RequirementRef::TRACE,
+            )
+            .with_path("ServiceRequest.id"),
+        )
+    }
+}
+
+fn validate_bundle_relationships(
+    sr: &fhir::ServiceRequest,
+    resolver: &BundleResolver<'_>,
+    entry_index: usize,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let resolves_to = |reference: &str, resource_type: &str| {
+        resolver
+            .resolve(reference, Some(entry_index))
+            .is_some_and(|target| target.resource_type == resource_type)
+    };
+
+    if let Some(reference) = sr.subject.as_ref().and_then(|r| r.reference.as_deref())
+        && let Some(id) = reference_id_from_str(reference)
+        && !resolves_to(reference, "Patient")
+    {
+        issues.push(ValidationIssue::new(
+            "VAL_SR_SUBJECT_PATIENT_NOT_FOUND",
+            ValidationSeverity::Error,
+            format!(
+                "ServiceRequest.subject references Patient/{id}, which is not present in the Bundle."
+            ),
+            RequirementRef::SUBJECT,
+        )
+        .with_path("ServiceRequest.subject"));
+    }
+
+    if let Some(reference) = sr.encounter.as_ref().and_then(|r| r.reference.as_deref())
+        && let Some(id) = reference_id_from_str(reference)
+        && !resolves_to(reference, "Encounter")
+    {
+        issues.push(ValidationIssue::new(
+            "VAL_SR_ENCOUNTER_NOT_FOUND",
+            ValidationSeverity::Warning,
+            format!(
+                "ServiceRequest.encounter references Encounter/{id}, which is not present in the Bundle."
+            ),
+            RequirementRef::TRACE,
+        )
+        .with_path("ServiceRequest.encounter"));
+    }
+
+    // A contained target, or a `urn:` entry without an id, resolves but gives
+    // staging no id to key the order by.
+    for (field, reference, requirement) in [
+        ("subject", sr.subject.as_ref(), RequirementRef::SUBJECT),
+        ("encounter", sr.encounter.as_ref(), RequirementRef::TRACE),
+    ] {
+        let Some(reference) = reference.and_then(|r| r.reference.as_deref()) else {
+            continue;
+        };
+        if let Some(target) = resolver.resolve(reference, Some(entry_index))
+            && target.local_reference().is_none()
+        {
+            let why = if target.contained {
+                "a contained resource"
+            } else {
+                "an entry without an id"
+            };
+            issues.push(
+                ValidationIssue::new(
+                    "VAL_SR_REFERENCE_UNRESOLVED",
+                    ValidationSeverity::Error,
+                    format!(
+                        "ServiceRequest.{field} ({reference}) points at {why}, so the order cannot be keyed to its {}.",
+                        target.resource_type
+                    ),
+                    requirement,
+                )
+                .with_path(format!("ServiceRequest.{field}")),
+            );
+        }
+    }
+}
+
+/// Flag repeated deliveries of the same order that
+/// [`order::ServiceRequest::apply_revision`] would reject: a status move
+/// against the lifecycle (e.g. `completed` followed by `active`) or into a
+/// status the delivery's intent cannot hold.
+fn validate_status_sequence(
+    sr: &fhir::ServiceRequest,
+    last_status: &mut HashMap<String, ServiceRequestStatus>,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let (Some(id), Some(next)) = (
+        sr.id.as_deref().filter(|id| !id.is_empty()),
+        sr.status
+            .as_deref()
+            .and_then(ServiceRequestStatus::from_fhir_code),
+    ) else {
+        return;
+    };
+
+    let Some(&previous) = last_status.get(id) else {
+        last_status.insert(id.to_string(), next);
+        return;
+    };
+    // Domain conversion reads the intent the same way before applying the
+    // delivery; an unreadable intent is reported elsewhere, leaving only the
+    // lifecycle edge to check.
+    let rejected = match parse_intent(sr.intent.as_deref(), next) {
+        Ok((_, intent)) => order::check_transition(previous, next, intent).err(),
+        Err(_) => {
+            (!previous.can_transition_to(next)).then_some(LifecycleError::IllegalTransition {
+                from: previous,
+                to: next,
+            })
+        }
+    };
+    match rejected {
+        Some(err) => issues.push(
+            ValidationIssue::new(
+                "VAL_SR_STATUS_TRANSITION_INVALID",
+                ValidationSeverity::Error,
+                format!(
+                    "ServiceRequest/{id} moves from {} to {}, which the ServiceRequest lifecycle does not allow ({err}).",
+                    previous.as_fhir_code(),
+                    next.as_fhir_code()
+                ),
+                RequirementRef::STATUS,
+            )
+            .with_path("ServiceRequest.status"),
+        ),
+        None => {
+            last_status.insert(id.to_string(), next);
+        }
+    }
+}
+
+/// Results whose `basedOn` names a ServiceRequest missing from the Bundle cannot
+/// be linked back to their order downstream.
+fn validate_result_links(resolver: &BundleResolver<'_>, issues: &mut Vec<ValidationIssue>) {
+    let observations = resolver
+        .primary_resources_of::<fhir::Observation>()
+        .filter_map(|(index, res)| {
+            res.ok()
+                .map(|res| ("Observation", index, res.id, res.based_on))
+        });
+    let reports = resolver
+        .primary_resources_of::<fhir::DiagnosticReport>()
+        .filter_map(|(index, res)| {
+            res.ok()
+                .map(|res| ("DiagnosticReport", index, res.id, res.based_on))
+        });
+    let studies = resolver
+        .primary_resources_of::<fhir::ImagingStudy>()
+        .filter_map(|(index, res)| {
+            res.ok()
+                .map(|res| ("ImagingStudy", index, res.id, res.based_on))
+        });
+
+    for (resource_type, index, id, based_on) in observations.chain(reports).chain(studies) {
+        for reference in based_on.iter().filter_map(|r| r.reference.as_deref()) {
+            let Some(parsed) = ParsedReference::parse(reference) else {
+                continue;
+            };
+            if parsed.resource_type() == Some("ServiceRequest")
+                && resolver.resolve(reference, Some(index)).is_none()
+                && let Some(sr_id) = reference_id_from_str(reference)
+            {
+                issues.push(ValidationIssue::new(
+                    "VAL_RESULT_BASED_ON_NOT_FOUND",
+                    ValidationSeverity::Warning,
+                    format!(
+                        "{resource_type}/{} is based on ServiceRequest/{sr_id}, which is not present in the Bundle.",
+                        id.as_deref().unwrap_or("<missing id>")
+                    ),
+                    RequirementRef::TRACE,
+                )
+                .with_path(format!("{resource_type}.basedOn")));
+            }
+        }
+    }
+}
+
+fn is_patient_reference(reference: &str) -> bool {
+    reference.starts_with("Patient/")
+        && reference
+            .split('/')
+            .nth(1)
+            .map(|id| !id.is_empty())
+            .unwrap_or(false)
+}
+
+fn is_known_status(value: &str) -> bool {
+    ServiceRequestStatus::from_fhir_code(value).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfps_core::fhir;
+
+    #[test]
+    fn requirement_codes_match_docs() {
+        assert_eq!(RequirementRef::SUBJECT.as_code(), "R_Subject");
+        assert_eq!(RequirementRef::STATUS.as_code(), "R_Status");
+        assert_eq!(RequirementRef::TRACE.as_code(), "R_Trace");
+        assert_eq!(RequirementRef::BINDING.as_code(), "R_Binding");
+    }
+
+    #[test]
+    fn issue_construction_exposes_requirement_ref() {
+        let issue = ValidationIssue::new(
+            "VAL_SR_SUBJECT_MISSING",
+            ValidationSeverity::Error,
+            "ServiceRequest.subject must reference a Patient",
+            RequirementRef::SUBJECT,
+        );
+        assert_eq!(issue.requirement_ref(), "R_Subject");
+        assert_eq!(issue.severity, ValidationSeverity::Error);
+    }
+
+    #[test]
+    fn validate_sr_flags_missing_subject_and_status() {
+        let sr = fhir::ServiceRequest {
+            intent: Some("order".into()),
+            ..Default::default()
+        };
+
+        let issues = validate_sr(&sr);
+        assert!(
+            issues
+                .iter()
+                .any(|issue| issue.id == "VAL_SR_SUBJECT_MISSING")
+        );
+        assert!(
+            issues
+                .iter()
+                .any(|issue| issue.id == "VAL_SR_STATUS_MISSING")
+        );
+        assert!(
+            issues
+                .iter()
+                .any(|issue| issue.id == "VAL_SR_TRACE_ID_MISSING")
+        );
+    }
+
+    #[test]
+    fn validate_sr_accepts_valid_status_and_subject() {
+        let sr = fhir::ServiceRequest {
+            id: Some("SR-1".into()),
+            status: Some("active".into()),
+            intent: Some("order".into()),
+            subject: Some(fhir::Reference {
+                reference: Some("Patient/P1".into()),
+                display: None,
+            }),
+            ..Default::default()
+        };
+
+        let issues = validate_sr(&sr);
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn bundle_validation_aggregates_service_request_issues() {
+        let bundle = fhir::Bundle {
+            resource_type: "Bundle".into(),
+            id: None,
+            bundle_type: Some("collection".into()),
+            entry: vec![fhir::BundleEntry {
+                full_url: None,
+                resource: Some(serde_json::json!({
+                    "resourceType": "ServiceRequest",
+                    "id": "SR-2",
+                    "status": "unknown",
+                    "intent": "order",
+                    "subject": { "reference": "Observation/123" },
+                })),
+                ..Default::default()
+            }],
+        };
+
+        let report = validate_bundle(&bundle);
+        assert!(report.has_errors());
+        assert_eq!(report.issues.len(), 3);
+    }
+
+    #[test]
+    fn bundle_validation_flags_missing_patient_resource() {
+        let bundle = fhir::Bundle {
+            resource_type: "Bundle".into(),
+            id: None,
+            bundle_type: Some("collection".into()),
+            entry: vec![fhir::BundleEntry {
+                full_url: None,
+                resource: Some(serde_json::json!({
+                    "resourceType": "ServiceRequest",
+                    "id": "SR-3",
+                    "status": "active",
+                    "intent": "order",
+                    "subject": { "reference": "Patient/P-MISSING" }
+                })),
+                ..Default::default()
+            }],
+        };
+
+        let report = validate_bundle(&bundle);
+        assert!(report.has_errors());
+        assert!(
+            report
+                .issues
+                .iter()
+                .any(|issue| issue.id == "VAL_SR_SUBJECT_PATIENT_NOT_FOUND")
+        );
+    }
+
+    #[test]
+    fn bundle_validation_flags_missing_encounter_resource() {
+        let bundle = fhir::Bundle {
+            resource_type: "Bundle".into(),
+            id: None,
+            bundle_type: Some("collection".into()),
+            entry: vec![
+                fhir::BundleEntry {
+                    full_url: None,
+                    resource: Some(serde_json::json!({
+                        "resourceType": "Patient",
+                        "id": "PAT-1"
+                    })),
+                    ..Default::default()
+                },
+                fhir::BundleEntry {
+                    full_url: None,
+                    resource: Some(serde_json::json!({
+                        "resourceType": "ServiceRequest",
+                        "id": "SR-4",
+                        "status": "active",
+                        "intent": "order",
+                        "subject": { "reference": "Patient/PAT-1" },
+                        "encounter": { "reference": "Encounter/ENC-MISSING" }
+                    })),
+                    ..Default::default()
+                },
+            ],
+        };
+
+        let report = validate_bundle(&bundle);
+        assert!(
+            report
+                .issues
+                .iter()
+                .any(|issue| issue.id == "VAL_SR_ENCOUNTER_NOT_FOUND")
+        );
+    }
+
+    #[test]
+    fn miscased_resource_types_do_not_satisfy_references() {
+        let bundle: fhir::Bundle = serde_json::from_value(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                { "resource": { "resourceType": "patient", "id": "PAT-1" } },
+                { "resource": {
+                    "resourceType": "ServiceRequest",
+                    "id": "SR-7",
+                    "status": "active",
+                    "intent": "order",
+                    "subject": { "reference": "Patient/PAT-1" }
+                } }
+            ]
+        }))
+        .unwrap();
+
+        let report = validate_bundle(&bundle);
+        assert!(
+            report
+                .issues
+                .iter()
+                .any(|issue| issue.id == "VAL_SR_SUBJECT_PATIENT_NOT_FOUND")
+        );
+    }
+
+    #[test]
+    fn subjects_without_a_bundle_id_are_reported_and_not_staged() {
+        for (patient_entry, subject) in [
+            (
+                serde_json::json!({
+                    "fullUrl": "urn:uuid:2d4f1c2e-0a1b-4c3d-9e8f-001122334455",
+                    "resource": { "resourceType": "Patient" }
+                }),
+                "urn:uuid:2d4f1c2e-0a1b-4c3d-9e8f-001122334455",
+            ),
+            (serde_json::json!({}), "#pat"),
+        ] {
+            let bundle: fhir::Bundle = serde_json::from_value(serde_json::json!({
+                "resourceType": "Bundle",
+                "type": "collection",
+                "entry": [patient_entry, {
+                    "resource": {
+                        "resourceType": "ServiceRequest",
+                        "id": "SR-6",
+                        "status": "active",
+                        "intent": "order",
+                        "contained": [{ "resourceType": "Patient", "id": "pat" }],
+                        "subject": { "reference": subject }
+                    }
+                }]
+            }))
+            .unwrap();
+
+            let report = validate_bundle(&bundle);
+            let issue = report
+                .issues
+                .iter()
+                .find(|issue| issue.id == "VAL_SR_REFERENCE_UNRESOLVED")
+                .unwrap_or_else(|| panic!("{subject} is reported"));
+            assert_eq!(issue.path.as_deref(), Some("ServiceRequest.subject"));
+            assert!(
+                report
+                    .issues
+                    .iter()
+                    .all(|issue| issue.id != "VAL_SR_SUBJECT_PATIENT_NOT_FOUND")
+            );
+            assert!(matches!(
+                crate::bundle_to_staging(&bundle),
+                Err(crate::IngestionError::InvalidReference(
+                    "ServiceRequest.subject.reference"
+                ))
+            ));
+        }
+    }
+
+    #[test]
+    fn bundle_validation_flags_impossible_status_sequence() {
+        let delivery = |status: &str| fhir::BundleEntry {
+            full_url: None,
+            resource: Some(serde_json::json!({
+                "resourceType": "ServiceRequest",
+                "id": "SR-5",
+                "status": status,
+                "intent": "order",
+                "subject": { "reference": "Patient/PAT-1" }
+            })),
+            ..Default::default()
+        };
+        let bundle = fhir::Bundle {
+            resource_type: "Bundle".into(),
+            id: None,
+            bundle_type: Some("collection".into()),
+            entry: vec![
+                fhir::BundleEntry {
+                    full_url: None,
+                    resource: Some(serde_json::json!({
+                        "resourceType": "Patient",
+                        "id": "PAT-1"
+                    })),
+                    ..Default::default()
+                },
+                delivery("active"),
+                delivery("completed"),
+                delivery("active"),
+            ],
+        };
+
+        let report = validate_bundle(&bundle);
+        let transitions: Vec<_> = report
+            .issues
+            .iter()
+            .filter(|issue| issue.id == "VAL_SR_STATUS_TRANSITION_INVALID")
+            .collect();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].requirement, RequirementRef::STATUS);
+        assert!(transitions[0].message.contains("completed to active"));
+    }
+
+    #[test]
+    fn bundle_validation_follows_revision_rules() {
+        let delivery = |status: &str| fhir::BundleEntry {
+            full_url: None,
+            resource: Some(serde_json::json!({
+                "resourceType": "ServiceRequest",
+                "id": "SR-6",
+                "status": status,
+                "intent": "order",
+                "subject": { "reference": "Patient/PAT-1" }
+            })),
+            ..Default::default()
+        };
+        let bundle = fhir::Bundle {
+            resource_type: "Bundle".into(),
+            id: None,
+            bundle_type: Some("collection".into()),
+            entry: vec![
+                fhir::BundleEntry {
+                    full_url: None,
+                    resource: Some(serde_json::json!({
+                        "resourceType": "Patient",
+                        "id": "PAT-1"
+                    })),
+                    ..Default::default()
+                },
+                delivery("draft"),
+                delivery("draft"),
+                delivery("active"),
+                delivery("on-hold"),
+                delivery("cancelled"),
+            ],
+        };
+
+        let report = validate_bundle(&bundle);
+        let transitions: Vec<_> = report
+            .issues
+            .iter()
+            .filter(|issue| issue.id == "VAL_SR_STATUS_TRANSITION_INVALID")
+            .collect();
+        assert_eq!(transitions.len(), 1);
+        assert!(transitions[0].message.contains("on-hold to cancelled"));
+    }
+
+    #[test]
+    fn result_based_on_unknown_order_is_flagged() {
+        let bundle: fhir::Bundle = serde_json::from_value(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                { "resource": {
+                    "resourceType": "Observation",
+                    "id": "obs-1",
+                    "status": "final",
+                    "basedOn": [{ "reference": "ServiceRequest/SR-GONE" }]
+                } }
+            ]
+        }))
+        .unwrap();
+
+        let report = validate_bundle(&bundle);
+        let issue = report
+            .issues
+            .iter()
+            .find(|issue| issue.id == "VAL_RESULT_BASED_ON_NOT_FOUND")
+            .expect("dangling basedOn flagged");
+        assert_eq!(issue.severity, ValidationSeverity::Warning);
+        assert!(!report.has_errors());
+    }
+
+    #[test]
+    fn urn_uuid_references_resolve_through_full_url() {
+        let bundle: fhir::Bundle = serde_json::from_value(serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "transaction",
+            "entry": [
+                {
+                    "fullUrl": "urn:uuid:0b0e3c1a-5d2e-4a7e-9b3c-1f2d3e4f5a6b",
+                    "resource": { "resourceType": "Patient", "id": "p1" },
+                    "request": { "method": "POST", "url": "Patient" }
+                },
+                {
+                    "fullUrl": "urn:uuid:9c8d7e6f-1a2b-4c3d-8e9f-0a1b2c3d4e5f",
+                    "resource": {
+                        "resourceType": "ServiceRequest",
+                        "id": "sr-1",
+                        "status": "active",
+                        "intent": "order",
+                        "subject": { "reference": "urn:uuid:0b0e3c1a-5d2e-4a7e-9b3c-1f2d3e4f5a6b" }
+                    },
+                    "request": { "method": "POST", "url": "ServiceRequest" }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let report = validate_bundle(&bundle);
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+
+        let (flats, _) = crate::bundle_to_staging(&bundle).unwrap();
+        assert_eq!(flats[0].patient_id, "p1");
+    }
+}
@@ -0,0 +1,751 @@
+//! Implementation-guide profiles loaded from StructureDefinition snapshots.
+//!
+//! [`Profiles`] holds StructureDefinitions (snapshot form) and the ValueSets
+//! their bindings point at, loaded from JSON files, a directory of them, or a
+//! Bundle. A resource is checked against every loaded profile named in its
+//! `meta.profile`, or against the configured default for its type when it
+//! declares none that is loaded. Each snapshot element is checked for:
+//!
+//! - cardinality (`min`/`max`) within every instance of its parent element;
+//! - `fixed[x]` (exact match) and `pattern[x]` (the instance contains the
+//!   pattern) values;
+//! - `required` bindings, against ValueSets loaded alongside the profiles or
+//!   held by the active `dfps_terminology` store (compose rules, filters over
+//!   loaded CodeSystems, or `expansion.contains`);
+//! - `mustSupport` elements that are optional but absent (reported as info).
+//!
+//! Issues carry the element path with array indices. Slices (elements with a
+//! `sliceName` or a `:` in their id) are not evaluated, and neither are
+//! invariants (`constraint`), type profiles or non-required bindings.
+
+use std::{collections::BTreeMap, env, fs, path::Path};
+
+use dfps_terminology::{TerminologyStore, store};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{RequirementRef, ValidationIssue, ValidationSeverity};
+use crate::transforms::IngestionError;
+
+/// `ElementDefinition.binding.strength`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BindingStrength {
+    Required,
+    Extensible,
+    Preferred,
+    Example,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElementBinding {
+    pub strength: BindingStrength,
+    #[serde(default)]
+    pub value_set: Option<String>,
+}
+
+/// The parts of a snapshot `ElementDefinition` the validator checks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementDefinition {
+    pub id: String,
+    pub path: String,
+    pub min: u32,
+    /// `None` for `*`.
+    pub max: Option<u32>,
+    pub must_support: bool,
+    pub fixed: Option<Value>,
+    pub pattern: Option<Value>,
+    pub binding: Option<ElementBinding>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawElement {
+    #[serde(default)]
+    id: Option<String>,
+    path: String,
+    #[serde(default)]
+    slice_name: Option<String>,
+    #[serde(default)]
+    min: Option<u32>,
+    #[serde(default)]
+    max: Option<String>,
+    #[serde(default)]
+    must_support: Option<bool>,
+    #[serde(default)]
+    binding: Option<ElementBinding>,
+    #[serde(flatten)]
+    rest: Map<String, Value>,
+}
+
+/// A profile: the snapshot elements of one StructureDefinition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureDefinition {
+    /// Canonical URL, as matched against `meta.profile`.
+    pub url: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Constrained resource type (`StructureDefinition.type`).
+    pub resource_type: String,
+    pub elements: Vec<ElementDefinition>,
+}
+
+impl StructureDefinition {
+    pub fn from_json_str(text: &str) -> Result<Self, IngestionError> {
+        let value: Value =
+            serde_json::from_str(text).map_err(|err| invalid(format!("not JSON: {err}")))?;
+        Self::from_value(&value)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, IngestionError> {
+        if value.get("resourceType").and_then(Value::as_str) != Some("StructureDefinition") {
+            return Err(invalid("expected a StructureDefinition".to_string()));
+        }
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        let url =
+            text("url").ok_or_else(|| invalid("StructureDefinition has no url".to_string()))?;
+        let resource_type = text("type")
+            .ok_or_else(|| invalid(format!("{url}: StructureDefinition has no type")))?;
+        let raw = value
+            .pointer("/snapshot/element")
+            .and_then(Value::as_array)
+            .filter(|elements| !elements.is_empty())
+            .ok_or_else(|| invalid(format!("{url}: only snapshot profiles are supported")))?;
+
+        let mut elements = Vec::with_capacity(raw.len());
+        for element in raw {
+            let element: RawElement = serde_json::from_value(element.clone())
+                .map_err(|err| invalid(format!("{url}: {err}")))?;
+            let id = element.id.unwrap_or_else(|| element.path.clone());
+            if element.slice_name.is_some() || id.contains(':') {
+                continue;
+            }
+            let max = match element.max.as_deref() {
+                None | Some("*") => None,
+                Some(max) => Some(
+                    max.parse()
+                        .map_err(|_| invalid(format!("{url}: {id} has an invalid max '{max}'")))?,
+                ),
+            };
+            let choice = |prefix: &str| {
+                element
+                    .rest
+                    .iter()
+                    .find(|(key, _)| is_choice_of(key, prefix))
+                    .map(|(_, value)| value.clone())
+            };
+            elements.push(ElementDefinition {
+                fixed: choice("fixed"),
+                pattern: choice("pattern"),
+                id,
+                path: element.path,
+                min: element.min.unwrap_or(0),
+                max,
+                must_support: element.must_support.unwrap_or(false),
+                binding: element.binding,
+            });
+        }
+        Ok(Self {
+            name: text("name"),
+            version: text("version"),
+            url,
+            resource_type,
+            elements,
+        })
+    }
+
+    fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+}
+
+/// A ValueSet resolved for binding checks, with the store that defines it.
+pub(super) struct ValueSetCodes<'a> {
+    store: &'a TerminologyStore,
+    url: &'a str,
+}
+
+impl<'a> ValueSetCodes<'a> {
+    pub(super) fn new(store: &'a TerminologyStore, url: &'a str) -> Self {
+        Self { store, url }
+    }
+
+    /// Whether the code is admitted. Codes the store cannot decide on
+    /// (filters over code systems that are not loaded) are admitted, and a
+    /// bare `code` (no system) is checked against every included system.
+    pub(super) fn admits(&self, system: Option<&str>, code: &str) -> bool {
+        self.store.contains(self.url, system, code) != Some(false)
+    }
+
+    /// Whether some code of `system` is admitted.
+    pub(super) fn includes_system(&self, system: &str) -> bool {
+        self.store.includes_system(self.url, system)
+    }
+}
+
+/// Loaded profiles, their ValueSets and the default profile per resource type.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profiles {
+    profiles: BTreeMap<String, StructureDefinition>,
+    terminology: TerminologyStore,
+    defaults: BTreeMap<String, String>,
+}
+
+impl Profiles {
+    /// File or directory of profile JSON read by [`Self::from_env`].
+    pub const ENV_VAR: &'static str = "DFPS_PROFILES";
+    /// Comma-separated canonical URLs of default profiles, one per type.
+    pub const DEFAULTS_ENV_VAR: &'static str = "DFPS_DEFAULT_PROFILES";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Profiles from one JSON document: a StructureDefinition, a ValueSet, a
+    /// CodeSystem, or a Bundle of them.
+    pub fn from_json_str(text: &str) -> Result<Self, IngestionError> {
+        let mut profiles = Self::new();
+        profiles.add_json_str(text)?;
+        Ok(profiles)
+    }
+
+    /// Load a JSON file, or every `*.json` file of a directory. Other
+    /// resource types found there are ignored.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, IngestionError> {
+        let path = path.as_ref();
+        let read_error = |err: std::io::Error| invalid(format!("{}: {err}", path.display()));
+        let mut files = Vec::new();
+        if path.is_dir() {
+            for entry in fs::read_dir(path).map_err(read_error)? {
+                let file = entry.map_err(read_error)?.path();
+                if file.extension().is_some_and(|ext| ext == "json") {
+                    files.push(file);
+                }
+            }
+            files.sort();
+        } else {
+            files.push(path.to_path_buf());
+        }
+
+        let mut profiles = Self::new();
+        for file in files {
+            let text = fs::read_to_string(&file)
+                .map_err(|err| invalid(format!("{}: {err}", file.display())))?;
+            profiles
+                .add_json_str(&text)
+                .map_err(|err| invalid(format!("{}: {err}", file.display())))?;
+        }
+        Ok(profiles)
+    }
+
+    /// Load [`Self::ENV_VAR`] and apply [`Self::DEFAULTS_ENV_VAR`]; unset
+    /// means no profiles.
+    pub fn from_env() -> Result<Self, IngestionError> {
+        let mut profiles = match env::var(Self::ENV_VAR) {
+            Ok(path) if !path.trim().is_empty() => Self::load(path.trim())?,
+            _ => Self::new(),
+        };
+        if let Ok(defaults) = env::var(Self::DEFAULTS_ENV_VAR) {
+            for url in defaults
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+            {
+                profiles.set_default(url)?;
+            }
+        }
+        Ok(profiles)
+    }
+
+    pub fn add_json_str(&mut self, text: &str) -> Result<(), IngestionError> {
+        let value: Value =
+            serde_json::from_str(text).map_err(|err| invalid(format!("not JSON: {err}")))?;
+        self.add_value(&value)
+    }
+
+    fn add_value(&mut self, value: &Value) -> Result<(), IngestionError> {
+        match value.get("resourceType").and_then(Value::as_str) {
+            Some("StructureDefinition") => self.insert(StructureDefinition::from_value(value)?),
+            Some("ValueSet" | "CodeSystem") => self
+                .terminology
+                .add_value(value)
+                .map_err(|err| invalid(err.to_string()))?,
+            Some("Bundle") => {
+                let entries = value.get("entry").and_then(Value::as_array);
+                for resource in entries
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.get("resource"))
+                {
+                    self.add_value(resource)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, profile: StructureDefinition) {
+        self.profiles.insert(profile.url.clone(), profile);
+    }
+
+    /// Check resources of the profile's type that declare no loaded profile
+    /// against `url`.
+    pub fn set_default(&mut self, url: &str) -> Result<(), IngestionError> {
+        let profile = self
+            .profiles
+            .get(url)
+            .ok_or_else(|| invalid(format!("default profile {url} is not loaded")))?;
+        self.defaults
+            .insert(profile.resource_type.clone(), url.to_string());
+        Ok(())
+    }
+
+    pub fn with_default(mut self, url: &str) -> Result<Self, IngestionError> {
+        self.set_default(url)?;
+        Ok(self)
+    }
+
+    /// A ValueSet loaded alongside the profiles, else one of the active
+    /// terminology store ([`store::active`]).
+    pub(super) fn value_set<'a>(&'a self, url: &'a str) -> Option<ValueSetCodes<'a>> {
+        [&self.terminology, store::active()]
+            .into_iter()
+            .find(|store| store.value_set(url).is_some())
+            .map(|store| ValueSetCodes::new(store, url))
+    }
+
+    pub fn get(&self, url: &str) -> Option<&StructureDefinition> {
+        self.profiles.get(canonical_url(url))
+    }
+
+    pub fn len(&self) -> usize {
+        self.profiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+
+    /// Issues for `resource` against its declared or default profiles.
+    ///
+    /// A declared profile that is not loaded (or constrains another type) is
+    /// a warning, but only for resource types some loaded profile covers.
+    pub fn validate(&self, resource: &Value) -> Vec<ValidationIssue> {
+        let Some(resource_type) = resource.get("resourceType").and_then(Value::as_str) else {
+            return Vec::new();
+        };
+        if !self
+            .profiles
+            .values()
+            .any(|profile| profile.resource_type == resource_type)
+        {
+            return Vec::new();
+        }
+
+        let mut issues = Vec::new();
+        let mut applied = Vec::new();
+        let declared = resource
+            .pointer("/meta/profile")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str);
+        for (index, canonical) in declared.enumerate() {
+            let reason = match self.get(canonical) {
+                Some(profile) if profile.resource_type == resource_type => {
+                    applied.push(profile);
+                    continue;
+                }
+                Some(profile) => format!(
+                    "declared profile {canonical} constrains {}, not {resource_type}",
+                    profile.resource_type
+                ),
+                None => format!("declared profile {canonical} is not loaded; not checked"),
+            };
+            issues.push(profile_issue(
+                "VAL_PROFILE_UNKNOWN",
+                ValidationSeverity::Warning,
+                reason,
+                format!("{resource_type}.meta.profile[{index}]"),
+            ));
+        }
+        if applied.is_empty()
+            && let Some(profile) = self
+                .defaults
+                .get(resource_type)
+                .and_then(|url| self.profiles.get(url))
+        {
+            applied.push(profile);
+        }
+
+        for profile in applied {
+            for element in &profile.elements {
+                self.check_element(profile, element, resource, &mut issues);
+            }
+        }
+        issues
+    }
+
+    fn check_element(
+        &self,
+        profile: &StructureDefinition,
+        element: &ElementDefinition,
+        resource: &Value,
+        issues: &mut Vec<ValidationIssue>,
+    ) {
+        let Some((parent_path, name)) = element.path.rsplit_once('.') else {
+            return;
+        };
+        let label = profile.label();
+        let mut unchecked_binding = false;
+        for (parent_at, parent) in instances(resource, parent_path) {
+            let children = children(&parent_at, parent, name);
+            let count = children.len() as u32;
+            if count < element.min || element.max.is_some_and(|max| count > max) {
+                issues.push(profile_issue(
+                    "VAL_PROFILE_CARDINALITY",
+                    ValidationSeverity::Error,
+                    format!(
+                        "{} occurs {count} time(s); {label} allows {}..{}",
+                        element.path,
+                        element.min,
+                        element.max.map_or("*".to_string(), |max| max.to_string())
+                    ),
+                    format!("{parent_at}.{}", name.trim_end_matches("[x]")),
+                ));
+            }
+            if count == 0 && element.must_support && element.min == 0 {
+                issues.push(profile_issue(
+                    "VAL_PROFILE_MUST_SUPPORT",
+                    ValidationSeverity::Info,
+                    format!("must-support element {} is absent ({label})", element.path),
+                    format!("{parent_at}.{}", name.trim_end_matches("[x]")),
+                ));
+            }
+
+            for (at, value) in children {
+                if let Some(fixed) = &element.fixed
+                    && value != fixed
+                {
+                    issues.push(profile_issue(
+                        "VAL_PROFILE_FIXED_VALUE",
+                        ValidationSeverity::Error,
+                        format!("{} must be {fixed} ({label}), found {value}", element.path),
+                        at.clone(),
+                    ));
+                }
+                if let Some(pattern) = &element.pattern
+                    && !matches_pattern(value, pattern)
+                {
+                    issues.push(profile_issue(
+                        "VAL_PROFILE_PATTERN",
+                        ValidationSeverity::Error,
+                        format!("{} must match {pattern} ({label})", element.path),
+                        at.clone(),
+                    ));
+                }
+                let Some(ElementBinding {
+                    strength: BindingStrength::Required,
+                    value_set: Some(value_set),
+                }) = &element.binding
+                else {
+                    continue;
+                };
+                match self.value_set(value_set) {
+                    Some(codes) => {
+                        let codings = codings(value);
+                        if !codings
+                            .iter()
+                            .any(|(system, code)| codes.admits(*system, code))
+                        {
+                            issues.push(profile_issue(
+                                "VAL_PROFILE_BINDING",
+                                ValidationSeverity::Error,
+                                format!(
+                                    "{} has no code from required ValueSet {value_set} ({label})",
+                                    element.path
+                                ),
+                                at,
+                            ));
+                        }
+                    }
+                    None if !unchecked_binding => {
+                        unchecked_binding = true;
+                        issues.push(profile_issue(
+                            "VAL_PROFILE_BINDING_UNCHECKED",
+                            ValidationSeverity::Info,
+                            format!(
+                                "required ValueSet {value_set} for {} is not loaded; binding not checked",
+                                element.path
+                            ),
+                            at,
+                        ));
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+}
+
+fn invalid(reason: String) -> IngestionError {
+    IngestionError::InvalidProfile(reason)
+}
+
+fn profile_issue(
+    id: &str,
+    severity: ValidationSeverity,
+    message: String,
+    path: String,
+) -> ValidationIssue {
+    ValidationIssue::new(id, severity, message, RequirementRef::PROFILE).with_path(path)
+}
+
+/// Canonical URL without a `|version` suffix.
+pub(super) fn canonical_url(url: &str) -> &str {
+    url.split_once('|').map_or(url, |(url, _)| url)
+}
+
+/// `fixedCode`, `patternCodeableConcept`, … for `prefix`.
+fn is_choice_of(key: &str, prefix: &str) -> bool {
+    key.strip_prefix(prefix)
+        .is_some_and(|suffix| suffix.starts_with(|c: char| c.is_ascii_uppercase()))
+}
+
+/// Every instance of the element at `path` (`Type.a.b`), with its indexed path.
+pub(super) fn instances<'a>(resource: &'a Value, path: &str) -> Vec<(String, &'a Value)> {
+    let mut segments = path.split('.');
+    let root = segments.next().unwrap_or_default();
+    let mut current = vec![(root.to_string(), resource)];
+    for segment in segments {
+        current = current
+            .iter()
+            .flat_map(|(at, value)| children(at, value, segment))
+            .collect();
+    }
+    current
+}
+
+/// Occurrences of child `name` of `value` (arrays expanded), where `name[x]`
+/// matches any `nameType` key.
+fn children<'a>(at: &str, value: &'a Value, name: &str) -> Vec<(String, &'a Value)> {
+    let Value::Object(map) = value else {
+        return Vec::new();
+    };
+    let mut output = Vec::new();
+    let matched: Vec<(&String, &Value)> = match name.strip_suffix("[x]") {
+        Some(prefix) => map
+            .iter()
+            .filter(|(key, _)| is_choice_of(key, prefix))
+            .collect(),
+        None => map.get_key_value(name).into_iter().collect(),
+    };
+    for (key, child) in matched {
+        match child {
+            Value::Array(items) => output.extend(
+                items
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, item)| !item.is_null())
+                    .map(|(index, item)| (format!("{at}.{key}[{index}]"), item)),
+            ),
+            Value::Null => {}
+            child => output.push((format!("{at}.{key}"), child)),
+        }
+    }
+    output
+}
+
+/// Whether `value` contains everything in `pattern`: objects by key, arrays
+/// by every pattern item matching some value item, primitives by equality.
+fn matches_pattern(value: &Value, pattern: &Value) -> bool {
+    match (value, pattern) {
+        (Value::Object(value), Value::Object(pattern)) => pattern.iter().all(|(key, expected)| {
+            value
+                .get(key)
+                .is_some_and(|actual| matches_pattern(actual, expected))
+        }),
+        (Value::Array(values), Value::Array(patterns)) => patterns.iter().all(|expected| {
+            values
+                .iter()
+                .any(|actual| matches_pattern(actual, expected))
+        }),
+        _ => value == pattern,
+    }
+}
+
+/// `(system, code)` pairs of a `code`, `Coding` or `CodeableConcept` value.
+pub(super) fn codings(value: &Value) -> Vec<(Option<&str>, &str)> {
+    match value {
+        Value::String(code) => vec![(None, code.as_str())],
+        Value::Object(map) => match map.get("coding").and_then(Value::as_array) {
+            Some(codings) => codings.iter().filter_map(system_and_code).collect(),
+            None => system_and_code(value).into_iter().collect(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn system_and_code(coding: &Value) -> Option<(Option<&str>, &str)> {
+    Some((
+        coding.get("system").and_then(Value::as_str),
+        coding.get("code")?.as_str()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PROFILE: &str = "https://dfps.example/fhir/StructureDefinition/dfps-servicerequest";
+    const CODES: &str = "https://dfps.example/fhir/ValueSet/imaging-orders";
+
+    fn profiles() -> Profiles {
+        let bundle = json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                { "resource": {
+                    "resourceType": "StructureDefinition",
+                    "url": PROFILE,
+                    "name": "DfpsServiceRequest",
+                    "type": "ServiceRequest",
+                    "snapshot": { "element": [
+                        { "id": "ServiceRequest", "path": "ServiceRequest", "min": 0, "max": "*" },
+                        { "id": "ServiceRequest.intent", "path": "ServiceRequest.intent",
+                          "min": 1, "max": "1", "fixedCode": "order" },
+                        { "id": "ServiceRequest.category", "path": "ServiceRequest.category",
+                          "min": 1, "max": "*",
+                          "patternCodeableConcept": { "coding": [
+                              { "system": "http://snomed.info/sct", "code": "363679005" }
+                          ] } },
+                        { "id": "ServiceRequest.code", "path": "ServiceRequest.code",
+                          "min": 1, "max": "1", "mustSupport": true,
+                          "binding": { "strength": "required", "valueSet": format!("{CODES}|1.0.0") } },
+                        { "id": "ServiceRequest.code.coding", "path": "ServiceRequest.code.coding",
+                          "min": 0, "max": "1" },
+                        { "id": "ServiceRequest.code.coding:loinc", "path": "ServiceRequest.code.coding",
+                          "sliceName": "loinc", "min": 1, "max": "1" },
+                        { "id": "ServiceRequest.occurrence[x]", "path": "ServiceRequest.occurrence[x]",
+                          "min": 0, "max": "1", "mustSupport": true }
+                    ] }
+                } },
+                { "resource": {
+                    "resourceType": "ValueSet",
+                    "url": CODES,
+                    "compose": { "include": [{
+                        "system": "http://loinc.org",
+                        "concept": [{ "code": "24606-6" }]
+                    }] }
+                } }
+            ]
+        });
+        Profiles::from_json_str(&bundle.to_string()).expect("profiles load")
+    }
+
+    fn order(code: &str) -> Value {
+        json!({
+            "resourceType": "ServiceRequest",
+            "id": "SR-1",
+            "meta": { "profile": [PROFILE] },
+            "status": "active",
+            "intent": "order",
+            "category": [{ "coding": [
+                { "system": "http://snomed.info/sct", "code": "363679005", "display": "Imaging" }
+            ] }],
+            "code": { "coding": [{ "system": "http://loinc.org", "code": code }] },
+            "occurrenceDateTime": "2024-05-02"
+        })
+    }
+
+    fn ids(issues: &[ValidationIssue]) -> Vec<(&str, Option<&str>)> {
+        issues
+            .iter()
+            .map(|issue| (issue.id.as_str(), issue.path.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn conforming_order_has_no_issues() {
+        assert_eq!(profiles().validate(&order("24606-6")), Vec::new());
+    }
+
+    #[test]
+    fn reports_each_constraint_with_its_element_path() {
+        let mut sr = order("12345-6");
+        sr["intent"] = json!("plan");
+        sr["category"] = json!([{ "text": "imaging" }]);
+        sr["code"]["coding"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "system": "http://loinc.org", "code": "24606-6" }));
+        sr.as_object_mut().unwrap().remove("occurrenceDateTime");
+
+        assert_eq!(
+            ids(&profiles().validate(&sr)),
+            vec![
+                ("VAL_PROFILE_FIXED_VALUE", Some("ServiceRequest.intent")),
+                ("VAL_PROFILE_PATTERN", Some("ServiceRequest.category[0]")),
+                (
+                    "VAL_PROFILE_CARDINALITY",
+                    Some("ServiceRequest.code.coding")
+                ),
+                (
+                    "VAL_PROFILE_MUST_SUPPORT",
+                    Some("ServiceRequest.occurrence")
+                ),
+            ]
+        );
+
+        let mut wrong_code = order("12345-6");
+        wrong_code.as_object_mut().unwrap().remove("intent");
+        let issues = profiles().validate(&wrong_code);
+        assert_eq!(
+            ids(&issues),
+            vec![
+                ("VAL_PROFILE_CARDINALITY", Some("ServiceRequest.intent")),
+                ("VAL_PROFILE_BINDING", Some("ServiceRequest.code")),
+            ]
+        );
+        assert!(
+            issues
+                .iter()
+                .all(|issue| issue.requirement == RequirementRef::PROFILE)
+        );
+    }
+
+    #[test]
+    fn default_profile_applies_only_without_a_loaded_declaration() {
+        let mut sr = order("12345-6");
+        sr["meta"]["profile"] = json!(["http://hl7.org/fhir/us/core/StructureDefinition/x"]);
+        let issues = profiles().validate(&sr);
+        assert_eq!(
+            ids(&issues),
+            vec![(
+                "VAL_PROFILE_UNKNOWN",
+                Some("ServiceRequest.meta.profile[0]")
+            )]
+        );
+
+        let issues = profiles().with_default(PROFILE).unwrap().validate(&sr);
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[1].id, "VAL_PROFILE_BINDING");
+
+        let patient = json!({ "resourceType": "Patient", "meta": { "profile": ["x"] } });
+        assert!(profiles().validate(&patient).is_empty());
+        assert!(profiles().with_default("https://unknown.example").is_err());
+    }
+
+    #[test]
+    fn rejects_differential_only_profiles() {
+        let err = StructureDefinition::from_json_str(
+            r#"{"resourceType": "StructureDefinition", "url": "u", "type": "ServiceRequest",
+                "differential": {"element": [{"path": "ServiceRequest.code", "min": 1}]}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), "invalid_profile");
+    }
+}
@@ -0,0 +1,365 @@
+//! Declarative validation rules loaded from a TOML or JSON file.
+//!
+//! Each rule is a FHIRPath-style invariant over one resource type, plus the
+//! issue raised when it does not hold:
+//!
+//! ```toml
+//! [[rules]]
+//! id = "VAL_SITE_SR_PRIORITY_MISSING"
+//! severity = "warning"
+//! requirement = "R_SitePriority"
+//! resource = "ServiceRequest"   # default
+//! expression = "priority.exists()"
+//! message = "ServiceRequest.priority is required at this site."
+//! ```
+//!
+//! Expressions use the FHIRPath subset of [`dfps_core::fhirpath`], evaluated
+//! with the resource as focus (`resolve()` follows references within the
+//! Bundle when the rules run from `validate_bundle_with_rules`). As in FHIRPath
+//! constraints, only a `false` result is a violation; an empty result passes
+//! and any other single item counts as `true`. An expression that fails to
+//! evaluate on a resource (e.g. `not()` over several items) reports the rule's
+//! issue with the failure appended to its message.
+//!
+//! The same file may declare `[[bindings]]` of coded elements to ValueSets
+//! (see [`ValueSetBinding`]). [`ValidationRules::with_profiles`] attaches
+//! implementation-guide [`Profiles`], checked alongside the rules on every
+//! resource; their ValueSets are also used by the bindings.
+
+use std::{env, fs, path::Path};
+
+use dfps_core::fhirpath::{self, FhirPath, ReferenceResolver};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{
+    BindingStrength, Profiles, RequirementRef, ValidationIssue, ValidationSeverity, ValueSetBinding,
+};
+use crate::transforms::IngestionError;
+
+/// Rules, ValueSet bindings and profiles applied on top of the built-in
+/// checks; empty by default.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationRules {
+    rules: Vec<ValidationRule>,
+    bindings: Vec<ValueSetBinding>,
+    profiles: Profiles,
+}
+
+/// One loaded rule.
+#[derive(Debug, Clone)]
+pub struct ValidationRule {
+    pub id: String,
+    pub severity: ValidationSeverity,
+    pub requirement: RequirementRef,
+    pub resource_type: String,
+    pub message: String,
+    expression: FhirPath,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<RuleSpec>,
+    #[serde(default)]
+    bindings: Vec<BindingSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    id: String,
+    severity: ValidationSeverity,
+    requirement: String,
+    #[serde(default = "default_resource")]
+    resource: String,
+    expression: String,
+    message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BindingSpec {
+    path: String,
+    value_set: String,
+    strength: BindingStrength,
+}
+
+fn default_resource() -> String {
+    "ServiceRequest".to_string()
+}
+
+impl ValidationRules {
+    /// Path of a rules file applied by [`Self::from_env`].
+    pub const ENV_VAR: &'static str = "DFPS_VALIDATION_RULES";
+
+    pub fn from_toml_str(text: &str) -> Result<Self, IngestionError> {
+        let file: RulesFile = toml::from_str(text)
+            .map_err(|err| IngestionError::InvalidValidationRules(err.message().to_string()))?;
+        Self::from_file(file)
+    }
+
+    pub fn from_json_str(text: &str) -> Result<Self, IngestionError> {
+        let file: RulesFile = serde_json::from_str(text)
+            .map_err(|err| IngestionError::InvalidValidationRules(err.to_string()))?;
+        Self::from_file(file)
+    }
+
+    /// Load a rules file, choosing JSON for `.json` files and TOML otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, IngestionError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| {
+            IngestionError::InvalidValidationRules(format!("{}: {err}", path.display()))
+        })?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json_str(&text),
+            _ => Self::from_toml_str(&text),
+        }
+    }
+
+    /// Load the file named by [`Self::ENV_VAR`]; unset means no extra rules.
+    pub fn from_env() -> Result<Self, IngestionError> {
+        match env::var(Self::ENV_VAR) {
+            Ok(path) if !path.trim().is_empty() => Self::load(path.trim()),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    fn from_file(file: RulesFile) -> Result<Self, IngestionError> {
+        let mut rules: Vec<ValidationRule> = Vec::with_capacity(file.rules.len());
+        for spec in file.rules {
+            let invalid = |reason: String| {
+                IngestionError::InvalidValidationRules(format!("rule '{}': {reason}", spec.id))
+            };
+            if spec.id.trim().is_empty() {
+                return Err(IngestionError::InvalidValidationRules(
+                    "rule id must not be empty".to_string(),
+                ));
+            }
+            if rules.iter().any(|rule| rule.id == spec.id) {
+                return Err(invalid("id is defined more than once".to_string()));
+            }
+            if spec.requirement.trim().is_empty() {
+                return Err(invalid("requirement must not be empty".to_string()));
+            }
+            let expression =
+                FhirPath::parse(&spec.expression).map_err(|err| invalid(err.to_string()))?;
+            rules.push(ValidationRule {
+                id: spec.id,
+                severity: spec.severity,
+                requirement: RequirementRef::new(spec.requirement.trim()),
+                resource_type: spec.resource,
+                message: spec.message,
+                expression,
+            });
+        }
+        let bindings = file
+            .bindings
+            .into_iter()
+            .map(|spec| ValueSetBinding::new(spec.path, spec.value_set, spec.strength))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            rules,
+            bindings,
+            profiles: Profiles::default(),
+        })
+    }
+
+    /// Also check `binding`.
+    pub fn with_binding(mut self, binding: ValueSetBinding) -> Self {
+        self.bindings.push(binding);
+        self
+    }
+
+    /// Also check resources against `profiles`.
+    pub fn with_profiles(mut self, profiles: Profiles) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.bindings.is_empty() && self.profiles.is_empty()
+    }
+
+    pub fn rules(&self) -> &[ValidationRule] {
+        &self.rules
+    }
+
+    pub fn bindings(&self) -> &[ValueSetBinding] {
+        &self.bindings
+    }
+
+    pub fn profiles(&self) -> &Profiles {
+        &self.profiles
+    }
+
+    /// Issues for every rule targeting `resource`'s type that it violates,
+    /// then its binding and profile issues; `resolve()` finds nothing.
+    pub fn check(&self, resource: &Value) -> Vec<ValidationIssue> {
+        self.check_with(resource, &|_: &str| None)
+    }
+
+    /// [`Self::check`] with `resolve()` backed by `resolver`.
+    pub fn check_with(
+        &self,
+        resource: &Value,
+        resolver: &dyn ReferenceResolver,
+    ) -> Vec<ValidationIssue> {
+        let mut issues: Vec<ValidationIssue> = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.check_with(resource, resolver))
+            .collect();
+        for binding in &self.bindings {
+            issues.extend(binding.check(resource, &self.profiles));
+        }
+        issues.extend(self.profiles.validate(resource));
+        issues
+    }
+}
+
+impl ValidationRule {
+    /// The expression as written in the rules file.
+    pub fn expression(&self) -> &str {
+        self.expression.as_str()
+    }
+
+    /// `Some(issue)` when `resource` has the rule's type and the expression
+    /// evaluates to `false` (or cannot be evaluated).
+    pub fn check(&self, resource: &Value) -> Option<ValidationIssue> {
+        self.check_with(resource, &|_: &str| None)
+    }
+
+    /// [`Self::check`] with `resolve()` backed by `resolver`.
+    pub fn check_with(
+        &self,
+        resource: &Value,
+        resolver: &dyn ReferenceResolver,
+    ) -> Option<ValidationIssue> {
+        if resource.get("resourceType").and_then(Value::as_str)? != self.resource_type {
+            return None;
+        }
+        let message = match self
+            .expression
+            .evaluate_with(resource, resolver)
+            .and_then(|result| fhirpath::as_boolean(&result))
+        {
+            Ok(Some(false)) => self.message.clone(),
+            Ok(_) => return None,
+            Err(err) => format!("{} ({err})", self.message),
+        };
+        Some(ValidationIssue::new(
+            self.id.clone(),
+            self.severity,
+            message,
+            self.requirement.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn order() -> Value {
+        json!({
+            "resourceType": "ServiceRequest",
+            "id": "SR-1",
+            "status": "active",
+            "code": { "coding": [
+                { "system": "http://loinc.org", "code": "24606-6" },
+                { "system": "http://snomed.info/sct", "code": "82918005" }
+            ] }
+        })
+    }
+
+    #[test]
+    fn expressions_that_cannot_be_evaluated_report_the_rule() {
+        let rules = ValidationRules::from_toml_str(
+            r#"
+            [[rules]]
+            id = "VAL_SITE_SINGLE_SYSTEM"
+            severity = "error"
+            requirement = "R_SiteCoding"
+            expression = "code.coding.where(system = 'http://loinc.org').exists() and code.coding.system.not()"
+            message = "Orders carry one coding system."
+            "#,
+        )
+        .expect("rules parse");
+        let issues = rules.check(&order());
+        assert_eq!(issues.len(), 1);
+        assert!(
+            issues[0]
+                .message
+                .starts_with("Orders carry one coding system. (FHIRPath evaluation failed"),
+            "{}",
+            issues[0].message
+        );
+    }
+
+    #[test]
+    fn rules_report_only_false_invariants_on_their_resource_type() {
+        let rules = ValidationRules::from_toml_str(
+            r#"
+            [[rules]]
+            id = "VAL_SITE_SR_PRIORITY_MISSING"
+            severity = "warning"
+            requirement = "R_SitePriority"
+            expression = "priority.exists()"
+            message = "ServiceRequest.priority is required at this site."
+
+            [[rules]]
+            id = "VAL_SITE_PATIENT_GENDER"
+            severity = "error"
+            requirement = "R_SiteDemographics"
+            resource = "Patient"
+            expression = "gender.exists()"
+            message = "Patient.gender is required."
+            "#,
+        )
+        .expect("rules parse");
+
+        let issues = rules.check(&order());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].id, "VAL_SITE_SR_PRIORITY_MISSING");
+        assert_eq!(issues[0].requirement_ref(), "R_SitePriority");
+        assert_eq!(issues[0].severity, ValidationSeverity::Warning);
+
+        let mut urgent = order();
+        urgent["priority"] = json!("urgent");
+        assert!(rules.check(&urgent).is_empty());
+    }
+
+    #[test]
+    fn malformed_rules_are_rejected() {
+        let rule = |expression: &str| {
+            format!(
+                r#"{{"rules": [{{"id": "R1", "severity": "error", "requirement": "R_X",
+                    "expression": "{expression}", "message": "m"}}]}}"#
+            )
+        };
+        assert!(ValidationRules::from_json_str(&rule("status.exists()")).is_ok());
+        for bad in ["status.matches()", "status =", "(status", "status # 1"] {
+            let err = ValidationRules::from_json_str(&rule(bad)).unwrap_err();
+            assert_eq!(err.code(), "invalid_validation_rules", "{bad}");
+        }
+        let duplicate = r#"
+            [[rules]]
+            id = "R1"
+            severity = "info"
+            requirement = "R_X"
+            expression = "id.exists()"
+            message = "m"
+
+            [[rules]]
+            id = "R1"
+            severity = "info"
+            requirement = "R_X"
+            expression = "id.exists()"
+            message = "m"
+        "#;
+        assert!(ValidationRules::from_toml_str(duplicate).is_err());
+    }
+}
@@ -0,0 +1,441 @@
+//! ServiceRequest identity and version tracking for re-ingestion.
+//!
+//! Each staged order gets an [`SrVersion`]: its `sr_id` plus `meta.versionId`,
+//! `meta.lastUpdated` and a content hash of the resource without `meta`.
+//! [`VersionLedger`] remembers the last version seen per `sr_id` and turns the
+//! next submission into a [`ChangeRecord`]:
+//!
+//! - unknown `sr_id` → `created`;
+//! - when both sides carry `lastUpdated` it orders them: an older one is
+//!   `unchanged` (late, out-of-order delivery), a newer one `updated`, and a
+//!   tie falls through to the next check;
+//! - otherwise `versionId` when both sides carry one, else the content hash:
+//!   equal → `unchanged`, different → `updated`;
+//! - `updated` replaces the stored version;
+//! - a transaction/batch `DELETE ServiceRequest/<id>` → `deleted`.
+//!
+//! The ledger serializes to JSON so callers can keep it between runs.
+
+use std::{cmp::Ordering, collections::BTreeMap, fmt::Write, fs, io, path::Path};
+
+use dfps_core::{
+    fhir::{self, HttpVerb, Meta},
+    staging::{StgServiceRequestFlat, StgSrCodeExploded},
+    value::FhirInstant,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::reference::ParsedReference;
+
+const SERVICE_REQUEST: &str = "ServiceRequest";
+
+/// Identity and version of one staged ServiceRequest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SrVersion {
+    pub sr_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<FhirInstant>,
+    /// FNV-1a hash (hex) of the canonical JSON of the resource minus `meta`,
+    /// or of the staging rows when there is no resource.
+    pub content_hash: String,
+}
+
+impl SrVersion {
+    /// Version of a raw `ServiceRequest` resource; `None` for other resource
+    /// types and for resources without an id. An unparseable `meta` counts as
+    /// absent.
+    pub fn from_resource(resource: &Value) -> Option<Self> {
+        if resource.get("resourceType")?.as_str()? != SERVICE_REQUEST {
+            return None;
+        }
+        let sr_id = resource.get("id")?.as_str()?.to_string();
+        let meta = resource
+            .get("meta")
+            .and_then(|meta| serde_json::from_value::<Meta>(meta.clone()).ok())
+            .unwrap_or_default();
+        let mut content = resource.clone();
+        if let Some(object) = content.as_object_mut() {
+            object.remove("meta");
+        }
+        Some(Self {
+            sr_id,
+            version_id: meta.version_id,
+            last_updated: meta.last_updated,
+            content_hash: content_hash(&content),
+        })
+    }
+
+    /// Version of an order staged without a FHIR resource (e.g. a CSV row),
+    /// identified by the content of its staging rows alone (provenance is
+    /// not content: the same row read by another run is the same version).
+    pub fn from_staging(flat: &StgServiceRequestFlat, codes: &[&StgSrCodeExploded]) -> Self {
+        let flat = StgServiceRequestFlat {
+            provenance: None,
+            ..flat.clone()
+        };
+        let codes: Vec<_> = codes
+            .iter()
+            .map(|code| StgSrCodeExploded {
+                provenance: None,
+                ..(*code).clone()
+            })
+            .collect();
+        let content = serde_json::json!({ "flat": flat, "codes": codes });
+        Self {
+            sr_id: flat.sr_id.clone(),
+            version_id: None,
+            last_updated: None,
+            content_hash: content_hash(&content),
+        }
+    }
+
+    /// Whether `incoming` is the version already represented by `self` (or
+    /// an older one).
+    fn covers(&self, incoming: &SrVersion) -> bool {
+        if let (Some(stored), Some(next)) = (&self.last_updated, &incoming.last_updated) {
+            match next.to_utc().cmp(&stored.to_utc()) {
+                Ordering::Less => return true,
+                Ordering::Greater => return false,
+                Ordering::Equal => {}
+            }
+        }
+        if let (Some(stored), Some(next)) = (&self.version_id, &incoming.version_id) {
+            return stored == next;
+        }
+        self.content_hash == incoming.content_hash
+    }
+}
+
+/// What re-ingesting a ServiceRequest did to the stored version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Unchanged,
+    Deleted,
+}
+
+/// One ServiceRequest's outcome against a [`VersionLedger`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    pub sr_id: String,
+    pub change: ChangeKind,
+    /// Submitted version; `None` for deletes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<SrVersion>,
+    /// Version stored before this submission.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<SrVersion>,
+}
+
+/// Last ingested version of every known ServiceRequest, keyed by `sr_id`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionLedger {
+    versions: BTreeMap<String, SrVersion>,
+}
+
+impl VersionLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ledger saved by [`Self::save`]; a missing file is an empty ledger.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(io::Error::other),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+
+    pub fn get(&self, sr_id: &str) -> Option<&SrVersion> {
+        self.versions.get(sr_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.versions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+
+    /// Record a submitted version; the stored version only moves on
+    /// `created`/`updated`.
+    pub fn observe(&mut self, version: SrVersion) -> ChangeRecord {
+        let previous = self.versions.get(&version.sr_id).cloned();
+        let change = match &previous {
+            None => ChangeKind::Created,
+            Some(stored) if stored.covers(&version) => ChangeKind::Unchanged,
+            Some(_) => ChangeKind::Updated,
+        };
+        if change != ChangeKind::Unchanged {
+            self.versions.insert(version.sr_id.clone(), version.clone());
+        }
+        ChangeRecord {
+            sr_id: version.sr_id.clone(),
+            change,
+            version: Some(version),
+            previous,
+        }
+    }
+
+    /// Forget `sr_id`; `None` when it was never ingested.
+    pub fn delete(&mut self, sr_id: &str) -> Option<ChangeRecord> {
+        let previous = self.versions.remove(sr_id)?;
+        Some(ChangeRecord {
+            sr_id: sr_id.to_string(),
+            change: ChangeKind::Deleted,
+            version: None,
+            previous: Some(previous),
+        })
+    }
+}
+
+/// Versions of the ServiceRequest entries in `bundle`, in entry order.
+pub fn bundle_sr_versions(bundle: &fhir::Bundle) -> Vec<SrVersion> {
+    bundle
+        .entry
+        .iter()
+        .filter_map(|entry| SrVersion::from_resource(entry.resource.as_ref()?))
+        .collect()
+}
+
+/// Ids removed by `DELETE ServiceRequest/<id>` entries of a transaction or
+/// batch Bundle. Conditional deletes (`ServiceRequest?…`) are not resolved.
+pub fn bundle_sr_deletes(bundle: &fhir::Bundle) -> Vec<String> {
+    if !bundle.kind().is_some_and(|kind| kind.requires_request()) {
+        return Vec::new();
+    }
+    bundle
+        .entry
+        .iter()
+        .filter_map(|entry| {
+            let request = entry.request.as_ref()?;
+            if request.verb()? != HttpVerb::Delete {
+                return None;
+            }
+            let url = request.url.as_deref()?;
+            if url.contains('?') {
+                return None;
+            }
+            match ParsedReference::parse(url)? {
+                ParsedReference::Path {
+                    resource_type: Some(SERVICE_REQUEST),
+                    id,
+                    ..
+                } => Some(id.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// 64-bit FNV-1a over the canonical JSON text (see [`write_canonical`]);
+/// unlike `DefaultHasher` the result is stable across builds.
+fn content_hash(value: &Value) -> String {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut text = String::new();
+    write_canonical(&mut text, value);
+    let hash = text.bytes().fold(OFFSET, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    });
+    format!("{hash:016x}")
+}
+
+/// Compact JSON with object keys sorted, whatever order the map keeps them
+/// in, and integral floats written as integers (`1.0` → `1`), so equal
+/// content always gives the same text.
+fn write_canonical(out: &mut String, value: &Value) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            out.push('{');
+            for (position, (key, value)) in entries.into_iter().enumerate() {
+                if position > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_canonical(out, value);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (position, item) in items.iter().enumerate() {
+                if position > 0 {
+                    out.push(',');
+                }
+                write_canonical(out, item);
+            }
+            out.push(']');
+        }
+        Value::Number(number) => match number.as_f64() {
+            Some(float)
+                if number.is_f64()
+                    && float.fract() == 0.0
+                    && float.abs() < 9_007_199_254_740_992.0 =>
+            {
+                let _ = write!(out, "{}", float as i64);
+            }
+            _ => {
+                let _ = write!(out, "{number}");
+            }
+        },
+        scalar => {
+            let _ = write!(out, "{scalar}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sr(meta: Value, status: &str) -> Value {
+        json!({
+            "resourceType": "ServiceRequest",
+            "id": "SR-1",
+            "meta": meta,
+            "status": status,
+            "intent": "order",
+            "subject": { "reference": "Patient/PAT-1" }
+        })
+    }
+
+    fn version(resource: Value) -> SrVersion {
+        SrVersion::from_resource(&resource).expect("service request version")
+    }
+
+    fn changes(ledger: &mut VersionLedger, resource: Value) -> ChangeKind {
+        ledger.observe(version(resource)).change
+    }
+
+    #[test]
+    fn version_id_decides_when_present_on_both_sides() {
+        let mut ledger = VersionLedger::new();
+        assert_eq!(
+            changes(&mut ledger, sr(json!({ "versionId": "1" }), "active")),
+            ChangeKind::Created
+        );
+        assert_eq!(
+            changes(&mut ledger, sr(json!({ "versionId": "1" }), "active")),
+            ChangeKind::Unchanged
+        );
+        assert_eq!(
+            changes(&mut ledger, sr(json!({ "versionId": "2" }), "completed")),
+            ChangeKind::Updated
+        );
+        assert_eq!(ledger.get("SR-1").unwrap().version_id.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn last_updated_orders_versions_before_version_id() {
+        let mut ledger = VersionLedger::new();
+        let at =
+            |version: &str, instant: &str| json!({ "versionId": version, "lastUpdated": instant });
+        changes(&mut ledger, sr(at("7", "2024-05-02T10:00:00Z"), "active"));
+
+        // An older delivery with another versionId is late, not an update.
+        assert_eq!(
+            changes(&mut ledger, sr(at("6", "2024-05-01T10:00:00Z"), "draft")),
+            ChangeKind::Unchanged
+        );
+        assert_eq!(ledger.get("SR-1").unwrap().version_id.as_deref(), Some("7"));
+        // Same instant: versionId breaks the tie.
+        assert_eq!(
+            changes(&mut ledger, sr(at("7", "2024-05-02T10:00:00Z"), "active")),
+            ChangeKind::Unchanged
+        );
+        assert_eq!(
+            changes(
+                &mut ledger,
+                sr(at("8", "2024-05-02T10:00:00Z"), "completed")
+            ),
+            ChangeKind::Updated
+        );
+    }
+
+    #[test]
+    fn canonical_text_ignores_key_order_and_float_spelling() {
+        let mut text = String::new();
+        write_canonical(
+            &mut text,
+            &json!({ "b": [1.0, 2.5], "a": { "y": null, "x": "é" } }),
+        );
+        assert_eq!(text, r#"{"a":{"x":"é","y":null},"b":[1,2.5]}"#);
+        assert_eq!(
+            content_hash(&json!({ "value": 2.0 })),
+            content_hash(&json!({ "value": 2 }))
+        );
+    }
+
+    #[test]
+    fn stale_last_updated_is_ignored() {
+        let mut ledger = VersionLedger::new();
+        let newer = json!({ "lastUpdated": "2024-05-02T10:00:00Z" });
+        let older = json!({ "lastUpdated": "2024-05-02T11:00:00+02:00" });
+        changes(&mut ledger, sr(newer.clone(), "active"));
+
+        let record = ledger.observe(version(sr(older, "draft")));
+        assert_eq!(record.change, ChangeKind::Unchanged);
+        assert_eq!(
+            ledger.get("SR-1").unwrap().last_updated,
+            newer["lastUpdated"].as_str().map(|at| at.parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn content_hash_is_the_fallback_and_ignores_meta_and_key_order() {
+        let mut ledger = VersionLedger::new();
+        changes(&mut ledger, sr(json!({ "source": "a" }), "active"));
+        let reordered = json!({
+            "status": "active",
+            "subject": { "reference": "Patient/PAT-1" },
+            "intent": "order",
+            "id": "SR-1",
+            "resourceType": "ServiceRequest",
+            "meta": { "source": "b" }
+        });
+        assert_eq!(changes(&mut ledger, reordered), ChangeKind::Unchanged);
+        assert_eq!(
+            changes(&mut ledger, sr(json!({}), "completed")),
+            ChangeKind::Updated
+        );
+    }
+
+    #[test]
+    fn deletes_come_from_transaction_delete_entries() {
+        let bundle: fhir::Bundle = serde_json::from_value(json!({
+            "resourceType": "Bundle",
+            "type": "transaction",
+            "entry": [
+                { "request": { "method": "DELETE", "url": "ServiceRequest/SR-1" } },
+                { "request": { "method": "DELETE", "url": "Patient/PAT-1" } },
+                { "request": { "method": "DELETE", "url": "ServiceRequest?identifier=x" } }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(bundle_sr_deletes(&bundle), vec!["SR-1".to_string()]);
+
+        let mut ledger = VersionLedger::new();
+        assert!(ledger.delete("SR-1").is_none());
+        changes(&mut ledger, sr(json!({}), "active"));
+        let record = ledger.delete("SR-1").expect("known order");
+        assert_eq!(record.change, ChangeKind::Deleted);
+        assert!(ledger.is_empty());
+    }
+}
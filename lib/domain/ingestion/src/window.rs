@@ -0,0 +1,583 @@
+//! Fixed-size windows over a streamed Bundle.
+//!
+//! [`BundleWindows`] groups the entries of a [`BundleStreamReader`] into
+//! windows of at most [`StreamOptions::window_entries`]. Each window goes
+//! through `Bundle.type` processing and comes out as a `collection` Bundle, so
+//! the staging, validation and mapping code runs on it unchanged. A compact
+//! index of earlier entries (`fullUrl` and `Type/id` only) is carried along;
+//! references into earlier windows resolve against `search.mode = include`
+//! stand-ins appended to the window. The index keeps at most
+//! [`StreamOptions::index_entries`] targets and drops the least recently
+//! referenced one when full.
+//!
+//! Transactions are all-or-nothing, so a `transaction` Bundle is held and
+//! processed as one window. So is a Bundle whose `type` is not known when its
+//! entries start (`type` after `entry`), since its rules cannot be applied
+//! before the end. Neither is bounded by `window_entries`.
+//!
+//! Compared with processing the whole Bundle at once:
+//! - references to entries in later windows, or to earlier entries dropped
+//!   from the index, are treated as external;
+//! - `ifNoneExist` matching and status-sequence checks see one window only.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::BufRead,
+    mem,
+};
+
+use dfps_core::fhir::{self, BundleEntrySearch, BundleType};
+use serde_json::{Map, Value};
+
+use crate::{
+    bundle_semantics::{
+        EntryOutcome, EntryResult, ProcessedBundle, process_bundle, process_entries,
+    },
+    reference::{ParsedReference, strip_history},
+    stream::{BundleHeader, BundleStreamReader, SourcePosition, StreamEvent, StreamOptions},
+    transforms::IngestionError,
+    validation::{
+        ValidationReport, ValidationRules, rejected_entry_issue, validate_bundle_with_rules,
+        validate_processed_with_rules,
+    },
+};
+
+/// A run of consecutive entries from one Bundle, ready for staging.
+#[derive(Debug, Clone)]
+pub struct BundleWindow {
+    pub header: BundleHeader,
+    /// Position in the Bundle of the window's first entry.
+    pub first_entry: usize,
+    /// Last window of its Bundle.
+    pub last: bool,
+    /// Kept window entries followed by context stand-ins, as a `collection`.
+    pub bundle: fhir::Bundle,
+    /// Result for every window entry, indexed by position in the Bundle.
+    pub entries: Vec<EntryResult>,
+    sources: Vec<usize>,
+    /// Input position of every window entry, indexed like `entries`.
+    positions: Vec<SourcePosition>,
+}
+
+impl BundleWindow {
+    /// Position in the source Bundle of `bundle.entry[index]`; `None` for
+    /// context stand-ins.
+    pub fn source_index(&self, index: usize) -> Option<usize> {
+        self.sources.get(index).copied()
+    }
+
+    /// Where `bundle.entry[index]` starts in the input; `None` for context
+    /// stand-ins.
+    pub fn source_position(&self, index: usize) -> Option<SourcePosition> {
+        let source = self.source_index(index)?;
+        self.positions.get(source - self.first_entry).copied()
+    }
+
+    /// [`validate_bundle`](crate::validate_bundle) for the window, plus
+    /// rejected-entry warnings.
+    pub fn validate(&self) -> ValidationReport {
+        self.validate_with_rules(&ValidationRules::default())
+    }
+
+    /// [`Self::validate`] with site-specific `rules` applied as well.
+    pub fn validate_with_rules(&self, rules: &ValidationRules) -> ValidationReport {
+        match process_bundle(&self.bundle) {
+            Ok(processed) => self.validate_processed(&processed, rules),
+            Err(_) => self.report_with(validate_bundle_with_rules(&self.bundle, rules)),
+        }
+    }
+
+    /// [`Self::validate_with_rules`] given the window Bundle already run
+    /// through [`process_bundle`].
+    pub fn validate_processed(
+        &self,
+        processed: &ProcessedBundle,
+        rules: &ValidationRules,
+    ) -> ValidationReport {
+        self.report_with(validate_processed_with_rules(processed, rules))
+    }
+
+    fn report_with(&self, report: ValidationReport) -> ValidationReport {
+        let mut issues: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|result| result.outcome == EntryOutcome::Failed)
+            .map(rejected_entry_issue)
+            .collect();
+        issues.extend(report.issues);
+        ValidationReport::new(issues)
+    }
+}
+
+/// Iterator over the [`BundleWindow`]s of a reader.
+pub struct BundleWindows<R> {
+    reader: BundleStreamReader<R>,
+    window_entries: usize,
+    header: BundleHeader,
+    pending: Vec<fhir::BundleEntry>,
+    positions: Vec<SourcePosition>,
+    first_entry: usize,
+    index: ReferenceIndex,
+    index_entries: usize,
+    /// The current Bundle is processed as one window at its end.
+    whole: bool,
+    /// A transaction entry failed; the rest of the Bundle is skipped.
+    aborted: bool,
+}
+
+impl<R: BufRead> BundleWindows<R> {
+    pub fn new(reader: R, options: &StreamOptions) -> Self {
+        Self {
+            reader: BundleStreamReader::with_options(reader, options),
+            window_entries: options.window_entries.max(1),
+            header: BundleHeader::default(),
+            pending: Vec::new(),
+            positions: Vec::new(),
+            first_entry: 0,
+            index: ReferenceIndex::new(options.index_entries),
+            index_entries: options.index_entries,
+            whole: false,
+            aborted: false,
+        }
+    }
+
+    fn cut(&mut self, last: bool) -> Result<BundleWindow, IngestionError> {
+        let first_entry = self.first_entry;
+        let window = fhir::Bundle {
+            resource_type: "Bundle".into(),
+            id: self.header.id.clone(),
+            bundle_type: self.header.bundle_type.clone(),
+            entry: mem::take(&mut self.pending),
+        };
+        let positions = mem::take(&mut self.positions);
+        self.first_entry += window.entry.len();
+
+        let processed = process_entries(&window, first_entry == 0).map_err(|err| {
+            self.aborted = true;
+            match err {
+                IngestionError::TransactionFailed { entry, reason } => {
+                    IngestionError::TransactionFailed {
+                        entry: entry + first_entry,
+                        reason,
+                    }
+                }
+                other => other,
+            }
+        })?;
+        let sources = processed
+            .source_indices()
+            .into_iter()
+            .map(|index| index + first_entry)
+            .collect();
+        let mut entries = processed.entries;
+        for result in &mut entries {
+            result.index += first_entry;
+        }
+
+        let mut kept = processed.bundle.entry;
+        let context = self.index.context_for(&kept);
+        for entry in &kept {
+            self.index.record(entry);
+        }
+        kept.extend(context);
+
+        Ok(BundleWindow {
+            header: self.header.clone(),
+            first_entry,
+            last,
+            bundle: fhir::Bundle {
+                resource_type: "Bundle".into(),
+                id: self.header.id.clone(),
+                bundle_type: Some(BundleType::Collection.as_fhir_code().into()),
+                entry: kept,
+            },
+            entries,
+            sources,
+            positions,
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for BundleWindows<R> {
+    type Item = Result<BundleWindow, IngestionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let event = match self.reader.next()? {
+                Ok(event) => event,
+                Err(err) => return Some(Err(err.into())),
+            };
+            match event {
+                StreamEvent::BundleStart(header) => {
+                    self.whole = !header.implicit
+                        && header
+                            .bundle_type
+                            .as_deref()
+                            .is_none_or(|ty| ty == BundleType::Transaction.as_fhir_code());
+                    self.header = header;
+                    self.first_entry = 0;
+                    self.index = ReferenceIndex::new(self.index_entries);
+                    self.aborted = false;
+                }
+                StreamEvent::Entry {
+                    entry, position, ..
+                } if !self.aborted => {
+                    self.pending.push(*entry);
+                    self.positions.push(position);
+                    if !self.whole && self.pending.len() >= self.window_entries {
+                        return Some(self.cut(false));
+                    }
+                }
+                StreamEvent::BundleEnd { header, .. } if !self.aborted => {
+                    if self.first_entry > 0 && header.bundle_type != self.header.bundle_type {
+                        self.pending.clear();
+                        self.positions.clear();
+                        return Some(Err(IngestionError::InvalidBundle {
+                            bundle_type: header.bundle_type.unwrap_or_default(),
+                            reason: "Bundle.type changed after entries were staged".into(),
+                        }));
+                    }
+                    self.header = header;
+                    return Some(self.cut(true));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Where an earlier entry can be found, without its content.
+struct Target {
+    resource_type: String,
+    id: Option<String>,
+    full_url: Option<String>,
+}
+
+impl Target {
+    fn stand_in(&self) -> fhir::BundleEntry {
+        let mut resource = Map::new();
+        resource.insert("resourceType".into(), self.resource_type.clone().into());
+        if let Some(id) = &self.id {
+            resource.insert("id".into(), id.clone().into());
+        }
+        fhir::BundleEntry {
+            full_url: self.full_url.clone(),
+            resource: Some(Value::Object(resource)),
+            search: Some(BundleEntrySearch {
+                mode: Some("include".into()),
+                score: None,
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// `fullUrl` / `Type/id` index of the entries seen in earlier windows,
+/// holding at most `capacity` targets.
+///
+/// Targets are keyed by the tick of their last use, so the first one is the
+/// least recently recorded or referenced and goes first when the index is full.
+struct ReferenceIndex {
+    capacity: usize,
+    tick: u64,
+    targets: BTreeMap<u64, Target>,
+    by_full_url: HashMap<String, u64>,
+    by_type_id: HashMap<(String, String), u64>,
+}
+
+impl ReferenceIndex {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            targets: BTreeMap::new(),
+            by_full_url: HashMap::new(),
+            by_type_id: HashMap::new(),
+        }
+    }
+
+    fn record(&mut self, entry: &fhir::BundleEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        let Some(resource_type) = entry.resource_type() else {
+            return;
+        };
+        let full_url = entry.full_url.as_deref().map(strip_history);
+        let id = entry.resource_id();
+        // Earlier entries win a shared key, as in `BundleResolver`.
+        let known = full_url.is_none_or(|url| self.by_full_url.contains_key(url))
+            && id.is_none_or(|id| {
+                self.by_type_id
+                    .contains_key(&(resource_type.to_string(), id.to_string()))
+            });
+        if known {
+            return;
+        }
+        if self.targets.len() >= self.capacity
+            && let Some((tick, evicted)) = self.targets.pop_first()
+        {
+            self.unlink(tick, &evicted);
+        }
+        let target = Target {
+            resource_type: resource_type.into(),
+            id: id.map(str::to_string),
+            full_url: full_url.map(str::to_string),
+        };
+        self.insert(target);
+    }
+
+    fn insert(&mut self, target: Target) {
+        let tick = self.tick;
+        self.tick += 1;
+        if let Some(full_url) = &target.full_url {
+            self.by_full_url.entry(full_url.clone()).or_insert(tick);
+        }
+        if let Some(id) = &target.id {
+            self.by_type_id
+                .entry((target.resource_type.clone(), id.clone()))
+                .or_insert(tick);
+        }
+        self.targets.insert(tick, target);
+    }
+
+    fn unlink(&mut self, tick: u64, target: &Target) {
+        if let Some(full_url) = &target.full_url
+            && self.by_full_url.get(full_url) == Some(&tick)
+        {
+            self.by_full_url.remove(full_url);
+        }
+        if let Some(id) = &target.id {
+            let key = (target.resource_type.clone(), id.clone());
+            if self.by_type_id.get(&key) == Some(&tick) {
+                self.by_type_id.remove(&key);
+            }
+        }
+    }
+
+    /// Stand-ins for the earlier entries referenced from `entries`; each one
+    /// found counts as a use.
+    fn context_for(&mut self, entries: &[fhir::BundleEntry]) -> Vec<fhir::BundleEntry> {
+        if self.targets.is_empty() {
+            return Vec::new();
+        }
+        let mut wanted = BTreeSet::new();
+        for entry in entries {
+            let Some(resource) = &entry.resource else {
+                continue;
+            };
+            let base = entry
+                .full_url
+                .as_deref()
+                .and_then(ParsedReference::parse)
+                .and_then(|parsed| match parsed {
+                    ParsedReference::Path { base, .. } => base,
+                    _ => None,
+                });
+            self.collect(resource, base, &mut wanted);
+        }
+        let mut context = Vec::with_capacity(wanted.len());
+        for tick in wanted {
+            let Some(target) = self.targets.remove(&tick) else {
+                continue;
+            };
+            context.push(target.stand_in());
+            self.unlink(tick, &target);
+            self.insert(target);
+        }
+        context
+    }
+
+    fn collect(&self, value: &Value, base: Option<&str>, wanted: &mut BTreeSet<u64>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(position) = map
+                    .get("reference")
+                    .and_then(Value::as_str)
+                    .and_then(|reference| self.lookup(reference, base))
+                {
+                    wanted.insert(position);
+                }
+                for child in map.values() {
+                    self.collect(child, base, wanted);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.collect(item, base, wanted);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Mirrors `BundleResolver::resolve` against the index.
+    fn lookup(&self, reference: &str, base: Option<&str>) -> Option<u64> {
+        match ParsedReference::parse(reference)? {
+            ParsedReference::Urn { urn } => self.by_full_url.get(urn).copied(),
+            ParsedReference::Path {
+                base: Some(base),
+                resource_type: Some(ty),
+                id,
+                ..
+            } => self.by_full_url.get(&format!("{base}/{ty}/{id}")).copied(),
+            ParsedReference::Path {
+                base: None,
+                resource_type: Some(ty),
+                id,
+                ..
+            } => base
+                .and_then(|base| self.by_full_url.get(&format!("{base}/{ty}/{id}")))
+                .or_else(|| self.by_type_id.get(&(ty.to_string(), id.to_string())))
+                .copied(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::transforms::bundle_to_staging;
+
+    fn windows(bundle: Value, window_entries: usize) -> Vec<Result<BundleWindow, IngestionError>> {
+        let options = StreamOptions {
+            window_entries,
+            ..StreamOptions::default()
+        };
+        windows_with(bundle, &options)
+    }
+
+    fn windows_with(
+        bundle: Value,
+        options: &StreamOptions,
+    ) -> Vec<Result<BundleWindow, IngestionError>> {
+        let bundle: fhir::Bundle = serde_json::from_value(bundle).unwrap();
+        let raw = serde_json::to_vec(&bundle).unwrap();
+        BundleWindows::new(raw.as_slice(), options).collect()
+    }
+
+    #[test]
+    fn references_into_earlier_windows_resolve_through_stand_ins() {
+        let bundle = json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                { "fullUrl": "urn:uuid:pat-1", "resource": { "resourceType": "Patient", "id": "p1" } },
+                { "resource": { "resourceType": "Encounter", "id": "e1", "status": "finished",
+                    "subject": { "reference": "urn:uuid:pat-1" } } },
+                { "resource": { "resourceType": "ServiceRequest", "id": "SR-1", "status": "active",
+                    "intent": "order", "subject": { "reference": "urn:uuid:pat-1" },
+                    "encounter": { "reference": "Encounter/e1" } } }
+            ]
+        });
+        let windows: Vec<_> = windows(bundle, 2).into_iter().map(Result::unwrap).collect();
+
+        assert_eq!(windows.len(), 2);
+        assert!(windows[1].last);
+        assert_eq!(windows[1].first_entry, 2);
+        assert_eq!(windows[1].source_index(0), Some(2));
+        assert_eq!(windows[1].source_index(1), None);
+        assert_eq!(windows[1].entries[0].index, 2);
+        assert!(windows[1].validate().issues.is_empty());
+
+        let (flats, _) = bundle_to_staging(&windows[1].bundle).unwrap();
+        assert_eq!(flats.len(), 1);
+        assert_eq!(flats[0].patient_id, "p1");
+        assert_eq!(flats[0].encounter_id.as_deref(), Some("e1"));
+    }
+
+    #[test]
+    fn bundle_type_rules_span_windows() {
+        let document = json!({
+            "resourceType": "Bundle",
+            "type": "document",
+            "entry": [
+                { "resource": { "resourceType": "Composition", "id": "c1" } },
+                { "resource": { "resourceType": "Patient", "id": "p1" } },
+                { "resource": { "resourceType": "Patient", "id": "p2" } }
+            ]
+        });
+        assert!(windows(document, 1).iter().all(Result::is_ok));
+
+        let put = |id: &str| {
+            json!({ "resource": { "resourceType": "Patient", "id": id },
+                    "request": { "method": "PUT", "url": format!("Patient/{id}") } })
+        };
+        let transaction = |entries: Vec<Value>| json!({ "resourceType": "Bundle", "type": "transaction", "entry": entries });
+
+        let committed = windows(transaction(vec![put("p1"), put("p2"), put("p3")]), 1);
+        assert_eq!(committed.len(), 1);
+        let window = committed[0].as_ref().unwrap();
+        assert!(window.last);
+        assert_eq!(window.entries.len(), 3);
+
+        let mut patch = put("p2");
+        patch["request"]["method"] = "PATCH".into();
+        let failed = windows(transaction(vec![put("p1"), patch, put("p3")]), 1);
+        assert!(matches!(
+            failed.as_slice(),
+            [Err(IngestionError::TransactionFailed { entry: 1, .. })]
+        ));
+    }
+
+    #[test]
+    fn late_bundle_type_is_applied_to_the_whole_bundle() {
+        let raw = r#"{"resourceType":"Bundle","entry":[
+            {"resource":{"resourceType":"Patient","id":"p1"},"request":{"method":"PUT","url":"Patient/p1"}},
+            {"resource":{"resourceType":"Patient","id":"p2"},"request":{"method":"PATCH","url":"Patient/p2"}}
+        ],"type":"transaction"}"#;
+        let options = StreamOptions {
+            window_entries: 1,
+            ..StreamOptions::default()
+        };
+        let windows: Vec<_> = BundleWindows::new(raw.as_bytes(), &options).collect();
+        assert!(matches!(
+            windows.as_slice(),
+            [Err(IngestionError::TransactionFailed { entry: 1, .. })]
+        ));
+
+        let raw = raw.replace("PATCH", "PUT");
+        let windows: Vec<_> = BundleWindows::new(raw.as_bytes(), &options).collect();
+        assert_eq!(windows.len(), 1);
+        let window = windows[0].as_ref().unwrap();
+        assert_eq!(window.header.bundle_type.as_deref(), Some("transaction"));
+        assert_eq!(window.entries.len(), 2);
+    }
+
+    #[test]
+    fn reference_index_drops_the_least_recently_used_target() {
+        let bundle = json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                { "resource": { "resourceType": "Patient", "id": "p1" } },
+                { "resource": { "resourceType": "Patient", "id": "p2" } },
+                { "resource": { "resourceType": "ServiceRequest", "id": "sr1", "status": "active",
+                    "intent": "order", "subject": { "reference": "Patient/p1" } } },
+                { "resource": { "resourceType": "ServiceRequest", "id": "sr2", "status": "active",
+                    "intent": "order", "subject": { "reference": "Patient/p1" } } },
+                { "resource": { "resourceType": "ServiceRequest", "id": "sr3", "status": "active",
+                    "intent": "order", "subject": { "reference": "Patient/p2" } } }
+            ]
+        });
+        let options = StreamOptions {
+            window_entries: 1,
+            index_entries: 2,
+            ..StreamOptions::default()
+        };
+        let windows: Vec<_> = windows_with(bundle, &options)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        let context: Vec<_> = windows
+            .iter()
+            .map(|window| window.bundle.entry.len() - window.entries.len())
+            .collect();
+        // p1 stays referenced while p2 ages out.
+        assert_eq!(context, vec![0, 0, 1, 1, 0, 0]);
+    }
+}
@@ -65,7 +65,7 @@ mod tests {
         assert!(
             concepts
                 .iter()
-                .any(|(c, _)| c.ncit_id == "NCIT:C19951" && c.synonyms.len() >= 1)
+                .any(|(c, _)| c.ncit_id == "NCIT:C19951" && !c.synonyms.is_empty())
         );
     }
 
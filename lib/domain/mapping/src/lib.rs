@@ -12,7 +12,7 @@ use dfps_core::{
         CodeElement, DimNCITConcept, MappingCandidate, MappingResult, MappingSourceVersion,
         MappingState, MappingStrategy, MappingThresholds,
     },
-    staging::StgSrCodeExploded,
+    staging::{StgResultCodeExploded, StgSrCodeExploded},
 };
 use dfps_terminology::{CodeKind, EnrichedCode};
 
@@ -273,6 +273,7 @@ fn build_result_with_score(
         reason: final_reason,
         license_tier: None,
         source_kind: None,
+        provenance: None,
     }
 }
 
@@ -293,28 +294,50 @@ where
     (results, dims)
 }
 
+/// Map result codings (Observation/DiagnosticReport/ImagingStudy) through the
+/// same engine as order codes; results are keyed by `ResourceType/id`. The
+/// NCIt dimension rows are the ones [`map_staging_codes`] returns, so none are
+/// built here.
+pub fn map_result_codes<'a, I>(codes: I) -> Vec<MappingResult>
+where
+    I: IntoIterator<Item = &'a StgResultCodeExploded>,
+{
+    map_codes(codes.into_iter().map(StgResultCodeExploded::to_code_row)).0
+}
+
 pub fn map_staging_codes_with_summary<I>(
     codes: I,
 ) -> (Vec<MappingResult>, Vec<DimNCITConcept>, MappingSummary)
 where
     I: IntoIterator<Item = StgSrCodeExploded>,
 {
-    map_with_summary(codes.into_iter())
+    map_with_summary(codes)
 }
 
 fn map_with_summary<I>(codes: I) -> (Vec<MappingResult>, Vec<DimNCITConcept>, MappingSummary)
 where
     I: IntoIterator<Item = StgSrCodeExploded>,
 {
-    let concepts = load_ncit_concepts();
+    let (results, summary) = map_codes(codes);
+    (results, dim_concepts(), summary)
+}
+
+/// One dimension row per NCIt concept known to the mapping tables.
+fn dim_concepts() -> Vec<DimNCITConcept> {
     let mut seen = HashSet::new();
     let mut dim_concepts = Vec::new();
-    for (_, dim) in concepts {
+    for (_, dim) in load_ncit_concepts() {
         if seen.insert(dim.ncit_id.clone()) {
             dim_concepts.push(dim);
         }
     }
+    dim_concepts
+}
 
+fn map_codes<I>(codes: I) -> (Vec<MappingResult>, MappingSummary)
+where
+    I: IntoIterator<Item = StgSrCodeExploded>,
+{
     let xrefs = load_umls_xrefs();
     let engine = default_engine();
     let mut results = Vec::new();
@@ -323,7 +346,10 @@ where
     for staging in codes {
         let enriched = EnrichedCode::from_staging(staging.clone());
         let code_kind = enriched.code_kind();
-        let element = CodeElement::from(staging);
+        let mut element = CodeElement::from(staging);
+        if element.display.is_none() {
+            element.display = enriched.display().map(str::to_string);
+        }
         let system_value = enriched.staging.system.clone().unwrap_or_default();
         let code_value = enriched.staging.code.clone().unwrap_or_default();
         let key = (system_value.clone(), code_value.clone());
@@ -364,10 +390,11 @@ where
         };
 
         attach_license_metadata(&mut result, &enriched);
+        result.provenance = enriched.staging.provenance;
         results.push(result);
     }
 
-    (results, dim_concepts, summary)
+    (results, summary)
 }
 
 #[cfg(test)]
@@ -382,6 +409,7 @@ mod tests {
             system: Some("http://snomed.info/sct".into()),
             code: Some("123".into()),
             display: Some("PET CT staging".into()),
+            provenance: None,
         };
         let code = CodeElement::from(staging);
         let engine = MappingEngine::new(LexicalRanker, VectorRankerMock, RuleReranker);
@@ -400,18 +428,21 @@ mod tests {
                 system: Some("http://www.ama-assn.org/go/cpt".into()),
                 code: Some("78815".into()),
                 display: None,
+                provenance: None,
             },
             StgSrCodeExploded {
                 sr_id: "SR-2".into(),
                 system: Some("http://example.org/custom".into()),
                 code: Some("A1".into()),
                 display: None,
+                provenance: None,
             },
             StgSrCodeExploded {
                 sr_id: "SR-3".into(),
                 system: None,
                 code: Some("B1".into()),
                 display: None,
+                provenance: None,
             },
         ];
 
@@ -4,22 +4,82 @@
 //! `docs/system-design/ncit/behavior/sequence-servicerequest.md` by exposing a
 //! single entrypoint from Bundle -> staging -> NCIt concepts.
 
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    io::BufRead,
+};
+
 use dfps_core::{
     fhir::Bundle,
-    mapping::{DimNCITConcept, MappingResult},
+    mapping::{CodeElement, DimNCITConcept, MappingResult},
+    provenance::SourceProvenance,
     staging::{StgServiceRequestFlat, StgSrCodeExploded},
 };
-use dfps_ingestion::bundle_to_staging;
-use dfps_mapping::map_staging_codes;
+use dfps_ingestion::{
+    BundleHeader, BundleWindow, BundleWindows, EntryResult, ProcessedBundle, ResultStagingRows,
+    StagingRows, ValidationMode, bundle_sr_deletes, process_bundle, processed_to_result_staging,
+    processed_to_staging_partial, processed_to_staging_with_projection,
+    validate_processed_with_rules,
+};
+use dfps_mapping::{map_result_codes, map_staging_codes};
 use thiserror::Error;
 
+pub use dfps_ingestion::{
+    ChangeKind, ChangeRecord, Deidentifier, ExtensionProjection, IngestionMode, ProvenanceContext,
+    QuarantinedEntry, SrVersion, StreamOptions, ValidationReport, ValidationRules, VersionLedger,
+    validation::Profiles,
+};
+
 /// Aggregated pipeline output for a single Bundle ingestion/mapping run.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct PipelineOutput {
     pub flats: Vec<StgServiceRequestFlat>,
     pub exploded_codes: Vec<StgSrCodeExploded>,
     pub mapping_results: Vec<MappingResult>,
     pub dim_concepts: Vec<DimNCITConcept>,
+    /// Observation/DiagnosticReport/ImagingStudy rows linked via `basedOn`.
+    pub results: ResultStagingRows,
+    /// Mapping results for result codings, keyed by `ResourceType/id`.
+    pub result_mapping_results: Vec<MappingResult>,
+    /// `transaction-response` style outcome for every input Bundle entry.
+    pub entry_results: Vec<EntryResult>,
+    /// Entries set aside in [`IngestionMode::PartialSuccess`].
+    pub quarantine: Vec<QuarantinedEntry>,
+    /// Identity/version of each row in `flats`, consumed by [`track_versions`].
+    pub versions: Vec<SrVersion>,
+    /// Orders removed by `DELETE ServiceRequest/<id>` entries.
+    pub deleted_sr_ids: Vec<String>,
+    /// Filled by [`track_versions`]; empty otherwise.
+    pub changes: Vec<ChangeRecord>,
+}
+
+/// Pipeline output for one window of a streamed Bundle.
+#[derive(Debug)]
+pub struct MappedWindow {
+    pub header: BundleHeader,
+    /// Position in the Bundle of the window's first entry.
+    pub first_entry: usize,
+    /// Last window of its Bundle.
+    pub last: bool,
+    pub output: PipelineOutput,
+    pub report: ValidationReport,
+}
+
+/// Knobs for [`bundle_to_mapped_sr_with_options`] and [`stream_mapped_sr`].
+#[derive(Debug, Clone, Default)]
+pub struct PipelineOptions {
+    pub projection: ExtensionProjection,
+    pub ingestion: IngestionMode,
+    /// Window size and entry limit for [`stream_mapped_sr`].
+    pub stream: StreamOptions,
+    /// Site rules added to each [`MappedWindow::report`].
+    pub rules: ValidationRules,
+    /// Pseudonymize and date-shift Bundles before anything is staged.
+    pub deid: Option<Deidentifier>,
+    /// Stamp staged rows, and the mapping results and facts derived from
+    /// them, with their source position and this run.
+    pub provenance: Option<ProvenanceContext>,
 }
 
 #[derive(Debug, Error)]
@@ -29,13 +89,275 @@ pub enum PipelineError {
 }
 
 pub fn bundle_to_mapped_sr(bundle: &Bundle) -> Result<PipelineOutput, PipelineError> {
-    let (flats, exploded) = bundle_to_staging(bundle)?;
+    bundle_to_mapped_sr_with_options(bundle, &PipelineOptions::default())
+}
+
+/// Run the pipeline with explicit ingestion options.
+///
+/// `options.projection` selects the extensions copied into
+/// `StgServiceRequestFlat::extensions`.
+/// In [`IngestionMode::PartialSuccess`] entries that cannot be staged land in
+/// `PipelineOutput::quarantine` and only Bundle-level failures return an error.
+/// With `options.deid` the Bundle is de-identified first, and with
+/// `options.provenance` rows record the entry they were staged from.
+pub fn bundle_to_mapped_sr_with_options(
+    bundle: &Bundle,
+    options: &PipelineOptions,
+) -> Result<PipelineOutput, PipelineError> {
+    let bundle = deidentified(bundle, options);
+    let processed = process_bundle(&bundle)?;
+    map_bundle(&bundle, processed, options, |_, _| {})
+}
+
+/// Validate `bundle` against `options.rules` and run the pipeline on it.
+///
+/// Both see the de-identified Bundle when `options.deid` is set, so neither
+/// the report nor the output carries source identifiers.
+pub fn validate_and_map_sr(
+    bundle: &Bundle,
+    options: &PipelineOptions,
+) -> Result<(ValidationReport, PipelineOutput), PipelineError> {
+    let bundle = deidentified(bundle, options);
+    let processed = process_bundle(&bundle)?;
+    let report = validate_processed_with_rules(&processed, &options.rules);
+    Ok((report, map_bundle(&bundle, processed, options, |_, _| {})?))
+}
+
+fn deidentified<'a>(bundle: &'a Bundle, options: &PipelineOptions) -> Cow<'a, Bundle> {
+    match &options.deid {
+        Some(deid) => {
+            let mut bundle = bundle.clone();
+            deid.deidentify_bundle(&mut bundle);
+            Cow::Owned(bundle)
+        }
+        None => Cow::Borrowed(bundle),
+    }
+}
+
+/// `processed` is `bundle` run through [`process_bundle`]; `locate` refines
+/// the provenance of rows staged from `bundle.entry[index]`.
+fn map_bundle(
+    bundle: &Bundle,
+    processed: ProcessedBundle,
+    options: &PipelineOptions,
+    mut locate: impl FnMut(usize, &mut SourceProvenance),
+) -> Result<PipelineOutput, PipelineError> {
+    let (mut rows, sources, results, quarantine) = match options.ingestion {
+        IngestionMode::Atomic => {
+            let (rows, sources) = processed_to_staging_with_projection(
+                &processed,
+                ValidationMode::default(),
+                &options.projection,
+            )?
+            .value;
+            (
+                rows,
+                sources,
+                processed_to_result_staging(&processed),
+                Vec::new(),
+            )
+        }
+        IngestionMode::PartialSuccess => {
+            let staging = processed_to_staging_partial(
+                bundle,
+                &processed,
+                ValidationMode::default(),
+                &options.projection,
+            );
+            (
+                staging.rows,
+                staging.sources,
+                staging.results,
+                staging.quarantine,
+            )
+        }
+    };
+    if let Some(context) = &options.provenance {
+        context.stamp_bundle(&mut rows, &sources, bundle, |index, provenance| {
+            locate(index, provenance);
+        });
+    }
+    let (flats, exploded) = rows;
     let (mapping_results, dim_concepts) = map_staging_codes(exploded.clone());
+    let result_mapping_results = map_result_codes(&results.codes);
+    let resource_versions = processed
+        .source_indices()
+        .into_iter()
+        .zip(&processed.bundle.entry)
+        .filter_map(|(index, entry)| {
+            Some((index, SrVersion::from_resource(entry.resource.as_ref()?)?))
+        })
+        .collect();
+    let versions = staged_versions(&flats, &exploded, &sources.flats, resource_versions);
 
     Ok(PipelineOutput {
         flats,
         exploded_codes: exploded,
         mapping_results,
         dim_concepts,
+        results,
+        result_mapping_results,
+        entry_results: processed.entries,
+        quarantine,
+        versions,
+        deleted_sr_ids: bundle_sr_deletes(bundle),
+        changes: Vec::new(),
+    })
+}
+
+/// Map rows that were staged without a Bundle (e.g. by
+/// [`dfps_ingestion::csv_to_staging`]).
+pub fn staging_to_mapped_sr(rows: StagingRows) -> PipelineOutput {
+    staging_to_mapped_sr_with_options(rows, &PipelineOptions::default())
+}
+
+/// [`staging_to_mapped_sr`], de-identifying the rows first when
+/// `options.deid` is set. Rows are not stamped with `options.provenance`
+/// here: only the caller knows where they came from (see
+/// [`ProvenanceContext::stamp_csv`]).
+pub fn staging_to_mapped_sr_with_options(
+    mut rows: StagingRows,
+    options: &PipelineOptions,
+) -> PipelineOutput {
+    if let Some(deid) = &options.deid {
+        deid.deidentify_staging(&mut rows);
+    }
+    let (flats, exploded_codes) = rows;
+    let (mapping_results, dim_concepts) = map_staging_codes(exploded_codes.clone());
+    let versions = staged_versions(&flats, &exploded_codes, &[], HashMap::new());
+    PipelineOutput {
+        flats,
+        exploded_codes,
+        mapping_results,
+        dim_concepts,
+        versions,
+        ..PipelineOutput::default()
+    }
+}
+
+/// Reconcile `output` with the versions already ingested, for idempotent
+/// re-submission.
+///
+/// Deletes are applied first (as in a FHIR transaction), then every staged
+/// order is recorded in `ledger`. Rows of orders whose version is unchanged
+/// are dropped from `flats`, `exploded_codes` and `mapping_results`, so only
+/// created/updated orders flow on; `output.changes` lists every outcome.
+pub fn track_versions(output: &mut PipelineOutput, ledger: &mut VersionLedger) {
+    let mut changed = HashSet::new();
+    let mut unchanged = HashSet::new();
+    for sr_id in &output.deleted_sr_ids {
+        output.changes.extend(ledger.delete(sr_id));
+    }
+    for version in &output.versions {
+        let record = ledger.observe(version.clone());
+        if record.change == ChangeKind::Unchanged {
+            unchanged.insert(record.sr_id.clone());
+        } else {
+            changed.insert(record.sr_id.clone());
+        }
+        output.changes.push(record);
+    }
+    // An order submitted twice in one run is kept if either copy changed it.
+    unchanged.retain(|sr_id| !changed.contains(sr_id));
+    if unchanged.is_empty() {
+        return;
+    }
+
+    let dropped: HashSet<String> = output
+        .exploded_codes
+        .iter()
+        .filter(|code| unchanged.contains(&code.sr_id))
+        .map(|code| CodeElement::from(code).id)
+        .collect();
+    output.flats.retain(|flat| !unchanged.contains(&flat.sr_id));
+    output
+        .exploded_codes
+        .retain(|code| !unchanged.contains(&code.sr_id));
+    output
+        .mapping_results
+        .retain(|result| !dropped.contains(&result.code_element_id));
+}
+
+/// One version per staged row: taken from the resource at the row's source
+/// entry (`sources`, keys of `resource_versions`) when there is one, otherwise
+/// hashed from the staging rows. Matching by entry rather than by id keeps
+/// each delivery of an order submitted twice in one Bundle apart.
+fn staged_versions(
+    flats: &[StgServiceRequestFlat],
+    exploded: &[StgSrCodeExploded],
+    sources: &[usize],
+    mut resource_versions: HashMap<usize, SrVersion>,
+) -> Vec<SrVersion> {
+    flats
+        .iter()
+        .enumerate()
+        .map(|(row, flat)| {
+            sources
+                .get(row)
+                .and_then(|index| resource_versions.remove(index))
+                .unwrap_or_else(|| {
+                    let codes: Vec<_> = exploded
+                        .iter()
+                        .filter(|code| code.sr_id == flat.sr_id)
+                        .collect();
+                    SrVersion::from_staging(flat, &codes)
+                })
+        })
+        .collect()
+}
+
+/// Run the pipeline over every Bundle in `reader` without holding a whole
+/// Bundle in memory.
+///
+/// Entries are staged and mapped in windows of `options.stream.window_entries`
+/// (see [`BundleWindows`] for how references across windows resolve), so
+/// memory is bounded by the window size and the largest entry rather than the
+/// Bundle size. Transactions are the exception: they are mapped as one window
+/// to stay all-or-nothing. `entry_results` and quarantine indices are
+/// positions in the source Bundle.
+pub fn stream_mapped_sr<R: BufRead>(
+    reader: R,
+    options: &PipelineOptions,
+) -> impl Iterator<Item = Result<MappedWindow, PipelineError>> {
+    let options = options.clone();
+    BundleWindows::new(reader, &options.stream).map(move |window| map_window(window?, &options))
+}
+
+fn map_window(
+    mut window: BundleWindow,
+    options: &PipelineOptions,
+) -> Result<MappedWindow, PipelineError> {
+    if let Some(deid) = &options.deid {
+        deid.deidentify_bundle(&mut window.bundle);
+        for entry in &mut window.entries {
+            for reference in [&mut entry.full_url, &mut entry.response.location]
+                .into_iter()
+                .flatten()
+            {
+                deid.pseudonymize_reference(reference);
+            }
+        }
+    }
+    let processed = process_bundle(&window.bundle)?;
+    let report = window.validate_processed(&processed, &options.rules);
+    let mut output = map_bundle(&window.bundle, processed, options, |index, provenance| {
+        provenance.bundle_index = Some(window.header.index);
+        provenance.entry_index = window.source_index(index);
+        if let Some(position) = window.source_position(index) {
+            provenance.byte_offset = Some(position.byte_offset);
+            provenance.line = Some(position.line);
+        }
+    })?;
+    for entry in &mut output.quarantine {
+        entry.index = window.source_index(entry.index).unwrap_or(entry.index);
+    }
+    output.entry_results = window.entries;
+
+    Ok(MappedWindow {
+        header: window.header,
+        first_entry: window.first_entry,
+        last: window.last,
+        output,
+        report,
     })
 }
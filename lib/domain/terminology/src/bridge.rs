@@ -1,12 +1,13 @@
 use dfps_core::staging::StgSrCodeExploded;
 
 use crate::codesystem::{CodeSystemMeta, LicenseTier, SourceKind};
-use crate::registry::lookup_codesystem;
+use crate::registry::{self, CodeSystemRegistry};
+use crate::store::{self, TerminologyStore};
 
 #[derive(Debug, Clone)]
 pub struct EnrichedCode {
     pub staging: StgSrCodeExploded,
-    pub codesystem: Option<&'static CodeSystemMeta>,
+    pub codesystem: Option<CodeSystemMeta>,
     pub license_tier: Option<LicenseTier>,
     pub source_kind: Option<SourceKind>,
     canonical_system: Option<String>,
@@ -34,12 +35,19 @@ impl CodeKind {
 }
 
 impl EnrichedCode {
+    /// Enrich against the active registry ([`registry::active`]).
     pub fn from_staging(staging: StgSrCodeExploded) -> Self {
-        let canonical_system = canonicalize_system(staging.system.as_deref());
-        let codesystem = canonical_system
-            .as_deref()
-            .and_then(|url| lookup_codesystem(url));
+        Self::from_staging_with(staging, registry::active())
+    }
+
+    /// Enrich against `registry`; aliases (e.g. `urn:oid:` forms) resolve to
+    /// the registered URL.
+    pub fn from_staging_with(staging: StgSrCodeExploded, registry: &CodeSystemRegistry) -> Self {
+        let system = staging.system.as_deref();
+        let canonical_system = system.and_then(|system| registry.canonicalize(system));
+        let codesystem = system.and_then(|system| registry.lookup(system)).cloned();
         let (license_tier, source_kind) = codesystem
+            .as_ref()
             .map(|meta| (Some(meta.license_tier), Some(meta.source_kind)))
             .unwrap_or((None, None));
 
@@ -69,7 +77,7 @@ impl EnrichedCode {
             return CodeKind::MissingSystemOrCode;
         }
 
-        if let Some(meta) = self.codesystem {
+        if let Some(meta) = &self.codesystem {
             match meta.source_kind {
                 SourceKind::OboFoundry => CodeKind::OboBacked,
                 _ => match meta.license_tier {
@@ -88,6 +96,21 @@ impl EnrichedCode {
         self.canonical_system.as_deref()
     }
 
+    /// The staging display, else the concept display from the active
+    /// terminology store ([`store::active`]).
+    pub fn display(&self) -> Option<&str> {
+        self.display_with(store::active())
+    }
+
+    /// The staging display, else the concept display from `store`.
+    pub fn display_with<'a>(&'a self, store: &'a TerminologyStore) -> Option<&'a str> {
+        self.staging
+            .display
+            .as_deref()
+            .filter(|display| !display.trim().is_empty())
+            .or_else(|| store.display(self.canonical_system()?, self.staging.code.as_deref()?))
+    }
+
     pub fn license_label(&self) -> Option<&'static str> {
         self.license_tier.map(|tier| tier.as_str())
     }
@@ -97,21 +120,6 @@ impl EnrichedCode {
     }
 }
 
-fn canonicalize_system(value: Option<&str>) -> Option<String> {
-    let mut url = value?.trim().to_ascii_lowercase();
-    if url.is_empty() {
-        return None;
-    }
-    if url.ends_with('/') {
-        url.pop();
-    }
-    match url.as_str() {
-        "urn:oid:2.16.840.1.113883.6.96" => Some("http://snomed.info/sct".into()),
-        "urn:oid:2.16.840.1.113883.6.1" => Some("http://loinc.org".into()),
-        other => Some(other.to_string()),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,6 +130,7 @@ mod tests {
             system: system.map(|v| v.to_string()),
             code: code.map(|v| v.to_string()),
             display: None,
+            provenance: None,
         }
     }
 
@@ -156,6 +165,60 @@ mod tests {
             Some("C19951"),
         ));
         assert_eq!(enriched.code_kind(), CodeKind::OboBacked);
+        assert_eq!(
+            enriched.canonical_system(),
+            Some("http://purl.obolibrary.org/obo/NCIT")
+        );
+    }
+
+    #[test]
+    fn resolves_oid_aliases() {
+        let enriched = EnrichedCode::from_staging(staging(
+            Some("urn:oid:2.16.840.1.113883.6.1"),
+            Some("24606-6"),
+        ));
+        assert_eq!(enriched.canonical_system(), Some("http://loinc.org"));
+        assert_eq!(enriched.code_kind(), CodeKind::KnownOpenSystem);
+    }
+
+    #[test]
+    fn consults_a_site_registry() {
+        let registry = CodeSystemRegistry::from_toml_str(
+            r#"
+            [[code_systems]]
+            url = "http://example.org/custom"
+            name = "Custom"
+            license_tier = "internal_only"
+            source_kind = "local"
+            "#,
+        )
+        .unwrap();
+        let enriched = EnrichedCode::from_staging_with(
+            staging(Some("http://example.org/custom"), Some("ABC")),
+            &registry,
+        );
+        assert_eq!(enriched.code_kind(), CodeKind::KnownLicensedSystem);
+        assert_eq!(enriched.source_label(), Some("local"));
+    }
+
+    #[test]
+    fn falls_back_to_store_display() {
+        let store = TerminologyStore::from_json_str(
+            r#"{
+                "resourceType": "CodeSystem",
+                "url": "http://loinc.org",
+                "content": "fragment",
+                "concept": [{ "code": "24627-2", "display": "Chest CT" }]
+            }"#,
+        )
+        .unwrap();
+        let mut row = staging(Some("urn:oid:2.16.840.1.113883.6.1"), Some("24627-2"));
+        let enriched = EnrichedCode::from_staging(row.clone());
+        assert_eq!(enriched.display_with(&store), Some("Chest CT"));
+
+        row.display = Some("CT chest".into());
+        let enriched = EnrichedCode::from_staging(row);
+        assert_eq!(enriched.display_with(&store), Some("CT chest"));
     }
 
     #[test]
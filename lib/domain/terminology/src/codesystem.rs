@@ -41,32 +41,48 @@ impl SourceKind {
 }
 
 /// Metadata describing a code system/terminology entry.
+///
+/// Built-ins live in [`crate::registry`]; sites add or override entries with
+/// a registry file (see [`crate::registry::CodeSystemRegistry`]).
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CodeSystemMeta {
-    pub url: &'static str,
-    pub name: &'static str,
-    pub version: Option<&'static str>,
-    pub description: &'static str,
+    /// Canonical system URL.
+    pub url: String,
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    /// Other identifiers coders send for this system (`urn:oid:...`, legacy
+    /// URLs); resolved to [`Self::url`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
     pub license_tier: LicenseTier,
     pub source_kind: SourceKind,
 }
 
 impl CodeSystemMeta {
-    pub const fn new(
-        url: &'static str,
-        name: &'static str,
-        version: Option<&'static str>,
-        description: &'static str,
+    pub fn new(
+        url: impl Into<String>,
+        name: impl Into<String>,
+        version: Option<String>,
+        description: impl Into<String>,
         license_tier: LicenseTier,
         source_kind: SourceKind,
     ) -> Self {
         Self {
-            url,
-            name,
+            url: url.into(),
+            name: name.into(),
             version,
-            description,
+            description: description.into(),
+            aliases: Vec::new(),
             license_tier,
             source_kind,
         }
     }
+
+    pub fn with_alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
 }
@@ -2,10 +2,14 @@ pub mod bridge;
 pub mod codesystem;
 pub mod obo;
 pub mod registry;
+pub mod store;
 pub mod valueset;
 
 pub use bridge::{CodeKind, EnrichedCode};
 pub use codesystem::{CodeSystemMeta, LicenseTier, SourceKind};
 pub use obo::{OboOntology, list_ontologies, lookup_ontology};
-pub use registry::{is_licensed, is_open, list_code_systems, lookup_codesystem};
+pub use registry::{
+    CodeSystemRegistry, RegistryError, is_licensed, is_open, list_code_systems, lookup_codesystem,
+};
+pub use store::{CodeSystemContent, Concept, StoreError, TerminologyStore, ValueSetDefinition};
 pub use valueset::{ValueSetMeta, list_value_sets, lookup_value_set};
@@ -1,50 +1,260 @@
+//! Code system registry: the built-in systems merged with site definitions.
+//!
+//! The process-wide registry ([`active`]) starts as [`CodeSystemRegistry::builtin`]
+//! unless a binary [`install`]s one at startup, typically
+//! [`CodeSystemRegistry::from_env`]. A registry file adds systems or replaces
+//! built-ins with the same URL:
+//!
+//! ```toml
+//! [[code_systems]]
+//! url = "http://www.cms.gov/Medicare/Coding/ICD10"
+//! name = "ICD-10-PCS"
+//! version = "2025"
+//! aliases = ["urn:oid:2.16.840.1.113883.6.4"]
+//! license_tier = "open"           # licensed | open | internal_only
+//! source_kind = "fhir"            # fhir | umls | obo_foundry | local
+//! description = "..."             # optional
+//! ```
+//!
+//! URLs and aliases match case-insensitively and ignore a trailing `/`.
+
+use std::{collections::HashMap, env, fs, path::Path, sync::OnceLock};
+
+use serde::Deserialize;
+use thiserror::Error;
+
 use crate::codesystem::{CodeSystemMeta, LicenseTier, SourceKind};
 
-static CODE_SYSTEMS: [CodeSystemMeta; 4] = [
-    CodeSystemMeta::new(
-        "http://www.ama-assn.org/go/cpt",
-        "CPT",
-        None,
-        "Current Procedural Terminology (AMA).",
-        LicenseTier::Licensed,
-        SourceKind::Fhir,
-    ),
-    CodeSystemMeta::new(
-        "http://snomed.info/sct",
-        "SNOMED CT",
-        None,
-        "Systematized Nomenclature of Medicine -- Clinical Terms.",
-        LicenseTier::Licensed,
-        SourceKind::Fhir,
-    ),
-    CodeSystemMeta::new(
-        "http://loinc.org",
-        "LOINC",
-        None,
-        "Logical Observation Identifiers Names and Codes.",
-        LicenseTier::Open,
-        SourceKind::Fhir,
-    ),
-    CodeSystemMeta::new(
-        "http://purl.obolibrary.org/obo/NCIT",
-        "NCIt OBO",
-        None,
-        "NCI Thesaurus (OBO Foundry distribution).",
-        LicenseTier::Open,
-        SourceKind::OboFoundry,
-    ),
-];
-
-/// Iterate all registered code systems.
+static ACTIVE: OnceLock<CodeSystemRegistry> = OnceLock::new();
+
+#[derive(Debug, Error)]
+pub enum RegistryError {
+    #[error("invalid code system registry: {0}")]
+    Invalid(String),
+    #[error("a code system registry is already active")]
+    AlreadyActive,
+}
+
+/// Code systems by canonical URL, with aliases resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeSystemRegistry {
+    entries: Vec<CodeSystemMeta>,
+    /// Normalized URL or alias -> index into `entries`.
+    keys: HashMap<String, usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegistryFile {
+    #[serde(default)]
+    code_systems: Vec<CodeSystemMeta>,
+}
+
+impl CodeSystemRegistry {
+    /// Path of a registry file merged by [`Self::from_env`].
+    pub const ENV_VAR: &'static str = "DFPS_CODE_SYSTEMS";
+
+    /// CPT, SNOMED CT, LOINC and NCIt (OBO).
+    pub fn builtin() -> Self {
+        let mut registry = Self {
+            entries: Vec::new(),
+            keys: HashMap::new(),
+        };
+        for meta in builtin_code_systems() {
+            registry
+                .insert(meta)
+                .expect("built-in code systems do not collide");
+        }
+        registry
+    }
+
+    /// Built-ins merged with the systems of a TOML registry file.
+    pub fn from_toml_str(text: &str) -> Result<Self, RegistryError> {
+        let file: RegistryFile =
+            toml::from_str(text).map_err(|err| RegistryError::Invalid(err.message().into()))?;
+        Self::from_file(file)
+    }
+
+    /// Built-ins merged with the systems of a JSON registry file.
+    pub fn from_json_str(text: &str) -> Result<Self, RegistryError> {
+        let file: RegistryFile =
+            serde_json::from_str(text).map_err(|err| RegistryError::Invalid(err.to_string()))?;
+        Self::from_file(file)
+    }
+
+    /// Load a registry file, choosing JSON for `.json` files and TOML otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| RegistryError::Invalid(format!("{}: {err}", path.display())))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json_str(&text),
+            _ => Self::from_toml_str(&text),
+        }
+    }
+
+    /// Load the file named by [`Self::ENV_VAR`]; unset means built-ins only.
+    pub fn from_env() -> Result<Self, RegistryError> {
+        match env::var(Self::ENV_VAR) {
+            Ok(path) if !path.trim().is_empty() => Self::load(path.trim()),
+            _ => Ok(Self::builtin()),
+        }
+    }
+
+    fn from_file(file: RegistryFile) -> Result<Self, RegistryError> {
+        let mut registry = Self::builtin();
+        for meta in file.code_systems {
+            registry.insert(meta)?;
+        }
+        Ok(registry)
+    }
+
+    /// Add `meta`, replacing any entry with the same URL.
+    ///
+    /// Fails when the URL or name is empty, or an alias already names another
+    /// system.
+    pub fn insert(&mut self, meta: CodeSystemMeta) -> Result<(), RegistryError> {
+        let url = normalize(&meta.url)
+            .ok_or_else(|| RegistryError::Invalid("code system url must not be empty".into()))?;
+        if meta.name.trim().is_empty() {
+            return Err(RegistryError::Invalid(format!(
+                "code system '{}': name must not be empty",
+                meta.url
+            )));
+        }
+        let index = match self.keys.get(&url) {
+            Some(&index) if normalize(&self.entries[index].url).as_ref() == Some(&url) => index,
+            Some(&index) => {
+                return Err(RegistryError::Invalid(format!(
+                    "code system '{}' is already an alias of '{}'",
+                    meta.url, self.entries[index].url
+                )));
+            }
+            None => self.entries.len(),
+        };
+        for alias in &meta.aliases {
+            let key = normalize(alias).ok_or_else(|| {
+                RegistryError::Invalid(format!("code system '{}': empty alias", meta.url))
+            })?;
+            if let Some(&other) = self.keys.get(&key)
+                && other != index
+            {
+                return Err(RegistryError::Invalid(format!(
+                    "alias '{alias}' of '{}' already names '{}'",
+                    meta.url, self.entries[other].url
+                )));
+            }
+        }
+
+        if index < self.entries.len() {
+            self.keys.retain(|_, entry| *entry != index);
+            self.entries[index] = meta;
+        } else {
+            self.entries.push(meta);
+        }
+        let meta = &self.entries[index];
+        self.keys.insert(url, index);
+        for alias in &meta.aliases {
+            self.keys.extend(normalize(alias).map(|key| (key, index)));
+        }
+        Ok(())
+    }
+
+    /// Every registered system, built-ins first.
+    pub fn entries(&self) -> &[CodeSystemMeta] {
+        &self.entries
+    }
+
+    /// The system registered under `system` (URL or alias).
+    pub fn lookup(&self, system: &str) -> Option<&CodeSystemMeta> {
+        let key = normalize(system)?;
+        self.keys.get(&key).map(|&index| &self.entries[index])
+    }
+
+    /// Canonical URL for `system`: the registered URL when known, otherwise
+    /// the trimmed, lowercased input without a trailing `/`.
+    pub fn canonicalize(&self, system: &str) -> Option<String> {
+        match self.lookup(system) {
+            Some(meta) => Some(meta.url.clone()),
+            None => normalize(system),
+        }
+    }
+}
+
+impl Default for CodeSystemRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+pub(crate) fn normalize(system: &str) -> Option<String> {
+    let mut key = system.trim().to_ascii_lowercase();
+    if key.ends_with('/') {
+        key.pop();
+    }
+    (!key.is_empty()).then_some(key)
+}
+
+fn builtin_code_systems() -> [CodeSystemMeta; 4] {
+    [
+        CodeSystemMeta::new(
+            "http://www.ama-assn.org/go/cpt",
+            "CPT",
+            None,
+            "Current Procedural Terminology (AMA).",
+            LicenseTier::Licensed,
+            SourceKind::Fhir,
+        ),
+        CodeSystemMeta::new(
+            "http://snomed.info/sct",
+            "SNOMED CT",
+            None,
+            "Systematized Nomenclature of Medicine -- Clinical Terms.",
+            LicenseTier::Licensed,
+            SourceKind::Fhir,
+        )
+        .with_alias("urn:oid:2.16.840.1.113883.6.96"),
+        CodeSystemMeta::new(
+            "http://loinc.org",
+            "LOINC",
+            None,
+            "Logical Observation Identifiers Names and Codes.",
+            LicenseTier::Open,
+            SourceKind::Fhir,
+        )
+        .with_alias("urn:oid:2.16.840.1.113883.6.1"),
+        CodeSystemMeta::new(
+            "http://purl.obolibrary.org/obo/NCIT",
+            "NCIt OBO",
+            None,
+            "NCI Thesaurus (OBO Foundry distribution).",
+            LicenseTier::Open,
+            SourceKind::OboFoundry,
+        ),
+    ]
+}
+
+/// Make `registry` the process-wide registry. Call once at startup, before
+/// anything reads [`active`]; fails if a registry is already active.
+pub fn install(registry: CodeSystemRegistry) -> Result<(), RegistryError> {
+    ACTIVE
+        .set(registry)
+        .map_err(|_| RegistryError::AlreadyActive)
+}
+
+/// The process-wide registry; built-ins unless [`install`] ran first.
+pub fn active() -> &'static CodeSystemRegistry {
+    ACTIVE.get_or_init(CodeSystemRegistry::builtin)
+}
+
+/// Iterate all active code systems.
 pub fn list_code_systems() -> &'static [CodeSystemMeta] {
-    &CODE_SYSTEMS
+    active().entries()
 }
 
-/// Lookup a `CodeSystemMeta` by URL/identifier.
+/// Lookup an active `CodeSystemMeta` by URL or alias.
 pub fn lookup_codesystem(url: &str) -> Option<&'static CodeSystemMeta> {
-    CODE_SYSTEMS
-        .iter()
-        .find(|meta| meta.url.eq_ignore_ascii_case(url))
+    active().lookup(url)
 }
 
 /// Returns `true` if the code system requires a license.
@@ -69,8 +279,8 @@ mod tests {
     fn lookup_known_system() {
         let meta =
             lookup_codesystem("http://www.ama-assn.org/go/cpt").expect("CPT should be registered");
-        assert!(is_licensed(meta.url));
-        assert!(!is_open(meta.url));
+        assert!(is_licensed(&meta.url));
+        assert!(!is_open(&meta.url));
     }
 
     #[test]
@@ -82,8 +292,89 @@ mod tests {
     #[test]
     fn lookup_open_system() {
         let meta = lookup_codesystem("http://loinc.org").expect("LOINC should be registered");
-        assert!(is_open(meta.url));
-        assert!(!is_licensed(meta.url));
+        assert!(is_open(&meta.url));
+        assert!(!is_licensed(&meta.url));
         assert_eq!(meta.license_tier.as_str(), "open");
     }
+
+    #[test]
+    fn files_add_and_override_systems() {
+        let registry = CodeSystemRegistry::from_toml_str(
+            r#"
+            [[code_systems]]
+            url = "http://radlex.org"
+            name = "RadLex"
+            aliases = ["urn:oid:2.16.840.1.113883.6.256"]
+            license_tier = "open"
+            source_kind = "fhir"
+
+            [[code_systems]]
+            url = "http://loinc.org/"
+            name = "LOINC"
+            version = "2.78"
+            license_tier = "open"
+            source_kind = "fhir"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(registry.entries().len(), 5);
+        assert_eq!(
+            registry
+                .canonicalize("URN:OID:2.16.840.1.113883.6.256")
+                .as_deref(),
+            Some("http://radlex.org")
+        );
+        let loinc = registry.lookup("http://LOINC.org").unwrap();
+        assert_eq!(loinc.version.as_deref(), Some("2.78"));
+        // The override dropped the built-in alias along with the entry.
+        assert!(registry.lookup("urn:oid:2.16.840.1.113883.6.1").is_none());
+        assert_eq!(
+            registry
+                .canonicalize("http://Example.org/Local/")
+                .as_deref(),
+            Some("http://example.org/local")
+        );
+
+        let json = CodeSystemRegistry::from_json_str(
+            r#"{"code_systems":[{"url":"http://example.org/local","name":"Local",
+                "license_tier":"internal_only","source_kind":"local"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            json.lookup("http://example.org/local")
+                .unwrap()
+                .license_tier,
+            LicenseTier::InternalOnly
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_or_incomplete_definitions() {
+        let clash = CodeSystemRegistry::from_toml_str(
+            r#"
+            [[code_systems]]
+            url = "http://example.org/snomed-mirror"
+            name = "Mirror"
+            aliases = ["urn:oid:2.16.840.1.113883.6.96"]
+            license_tier = "licensed"
+            source_kind = "local"
+            "#,
+        );
+        assert!(
+            matches!(clash, Err(RegistryError::Invalid(message)) if message.contains("already names"))
+        );
+
+        let unnamed = CodeSystemRegistry::from_toml_str(
+            r#"
+            [[code_systems]]
+            url = "http://example.org/x"
+            name = " "
+            license_tier = "open"
+            source_kind = "local"
+            "#,
+        );
+        assert!(unnamed.is_err());
+        assert!(CodeSystemRegistry::from_toml_str("[[code_systems]]\nurl = \"x\"").is_err());
+    }
 }
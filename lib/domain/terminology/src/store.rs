@@ -0,0 +1,1238 @@
+//! In-memory terminology content imported from FHIR CodeSystem and ValueSet
+//! resources.
+//!
+//! A [`TerminologyStore`] is loaded from JSON files, a directory of them, or a
+//! Bundle, and answers code-level questions the registry metadata cannot:
+//!
+//! - concepts with their display, definition, designations and properties;
+//! - the `parent`/`child` hierarchy, from nested `concept`s and from
+//!   `parent`/`child` properties;
+//! - ValueSet membership from `compose.include`/`exclude` (concept lists,
+//!   filters and imported `valueSet`s) or, failing that, `expansion.contains`.
+//!
+//! Supported filter operators are `=`, `is-a`, `descendent-of`, `is-not-a`,
+//! `child-of`, `generalizes`, `in`, `not-in`, `regex` and `exists`; the
+//! `concept`/`code` property filters on the code itself, `display` on the
+//! display and anything else on the concept property of that name.
+//!
+//! Membership is three-valued: [`TerminologyStore::contains`] returns `None`
+//! when the answer depends on content that is not loaded (a filter over a
+//! code system without concepts here, or an unknown imported ValueSet). An
+//! include of a whole system admits every code when that system is not loaded
+//! or its `content` is not `complete`.
+//!
+//! System URLs match case-insensitively, ignoring a trailing `/`, and resolve
+//! aliases of the active [`crate::registry`] as well as `urn:oid:` identifiers
+//! of loaded CodeSystems. One version is kept per canonical URL; a later
+//! resource replaces an earlier one.
+//!
+//! The process-wide store ([`active`]) is empty unless a binary [`install`]s
+//! one at startup, typically [`TerminologyStore::from_env`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    env, fs,
+    path::Path,
+    sync::OnceLock,
+};
+
+use dfps_core::fhir::Coding;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::registry::{self, normalize};
+use crate::valueset::ValueSetMeta;
+
+static ACTIVE: OnceLock<TerminologyStore> = OnceLock::new();
+
+/// Nesting limit for ValueSets importing other ValueSets.
+const MAX_IMPORT_DEPTH: usize = 8;
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("invalid terminology resource: {0}")]
+    Invalid(String),
+    #[error("a terminology store is already active")]
+    AlreadyActive,
+}
+
+/// A designation (alternate display) of a concept.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Designation {
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default, rename = "use")]
+    pub usage: Option<Coding>,
+    pub value: String,
+}
+
+/// Value of a concept property.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Code(String),
+    Coding(Coding),
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    DateTime(String),
+    Decimal(f64),
+}
+
+impl PropertyValue {
+    /// Text form compared by filters; a Coding compares by its code.
+    pub fn as_text(&self) -> String {
+        match self {
+            PropertyValue::Code(value)
+            | PropertyValue::String(value)
+            | PropertyValue::DateTime(value) => value.clone(),
+            PropertyValue::Coding(coding) => coding.code.clone().unwrap_or_default(),
+            PropertyValue::Integer(value) => value.to_string(),
+            PropertyValue::Boolean(value) => value.to_string(),
+            PropertyValue::Decimal(value) => value.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConceptProperty {
+    pub code: String,
+    pub value: PropertyValue,
+}
+
+/// A concept of a loaded code system.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Concept {
+    pub code: String,
+    pub display: Option<String>,
+    pub definition: Option<String>,
+    pub designations: Vec<Designation>,
+    pub properties: Vec<ConceptProperty>,
+    /// Codes of the direct parents.
+    pub parents: Vec<String>,
+    /// Codes of the direct children.
+    pub children: Vec<String>,
+}
+
+impl Concept {
+    /// Values of the concept property `code`.
+    pub fn property(&self, code: &str) -> impl Iterator<Item = &PropertyValue> {
+        self.properties
+            .iter()
+            .filter(move |property| property.code == code)
+            .map(|property| &property.value)
+    }
+
+    /// Designation for `language`; `de` also matches `de-CH`.
+    pub fn designation(&self, language: &str) -> Option<&str> {
+        let exact = self.designations.iter().find(|designation| {
+            designation
+                .language
+                .as_deref()
+                .is_some_and(|lang| lang.eq_ignore_ascii_case(language))
+        });
+        exact
+            .or_else(|| {
+                self.designations.iter().find(|designation| {
+                    designation.language.as_deref().is_some_and(|lang| {
+                        lang.split('-')
+                            .next()
+                            .is_some_and(|primary| primary.eq_ignore_ascii_case(language))
+                    })
+                })
+            })
+            .map(|designation| designation.value.as_str())
+    }
+}
+
+/// Concepts of one CodeSystem resource.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeSystemContent {
+    pub url: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// `content` of the resource (`complete`, `fragment`, `not-present`, …).
+    pub content: Option<String>,
+    /// `urn:oid:` (or other URI) identifiers resolved to `url`.
+    pub identifiers: Vec<String>,
+    concepts: BTreeMap<String, Concept>,
+}
+
+impl CodeSystemContent {
+    pub fn concept(&self, code: &str) -> Option<&Concept> {
+        self.concepts.get(code)
+    }
+
+    pub fn concepts(&self) -> impl Iterator<Item = &Concept> {
+        self.concepts.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.concepts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.concepts.is_empty()
+    }
+
+    /// Whether every code of the system is listed; an absent `content`
+    /// counts as complete.
+    pub fn is_complete(&self) -> bool {
+        self.content
+            .as_deref()
+            .is_none_or(|content| content == "complete")
+    }
+
+    /// Whether `ancestor` is a strict ancestor of `code`.
+    pub fn subsumes(&self, ancestor: &str, code: &str) -> bool {
+        self.walk(code, |concept| &concept.parents)
+            .any(|found| found == ancestor)
+    }
+
+    /// Strict descendants of `code`, breadth first.
+    pub fn descendants<'a>(&'a self, code: &'a str) -> impl Iterator<Item = &'a str> {
+        self.walk(code, |concept| &concept.children)
+    }
+
+    fn walk<'a>(
+        &'a self,
+        code: &'a str,
+        next: fn(&Concept) -> &Vec<String>,
+    ) -> impl Iterator<Item = &'a str> {
+        let mut queue = VecDeque::from([code]);
+        let mut seen = HashSet::from([code]);
+        std::iter::from_fn(move || {
+            while let Some(current) = queue.pop_front() {
+                let Some(concept) = self.concepts.get(current) else {
+                    continue;
+                };
+                for related in next(concept) {
+                    if seen.insert(related.as_str()) {
+                        queue.push_back(related.as_str());
+                    }
+                }
+                if current != code {
+                    return Some(current);
+                }
+            }
+            None
+        })
+    }
+
+    fn filter_matches(&self, filter: &ConceptFilter, code: &str) -> bool {
+        let Some(concept) = self.concept(code) else {
+            return false;
+        };
+        let value = filter.value.as_str();
+        let is_a = || code == value || self.subsumes(value, code);
+        match filter.op {
+            FilterOp::IsA => is_a(),
+            FilterOp::IsNotA => !is_a(),
+            FilterOp::DescendentOf => code != value && self.subsumes(value, code),
+            FilterOp::ChildOf => concept.parents.iter().any(|parent| parent == value),
+            FilterOp::Generalizes => code == value || self.subsumes(code, value),
+            FilterOp::Equals => {
+                filter_values(concept, &filter.property).any(|found| found == value)
+            }
+            FilterOp::In | FilterOp::NotIn => {
+                let listed = filter_values(concept, &filter.property)
+                    .any(|found| value.split(',').any(|item| item.trim() == found));
+                listed == (filter.op == FilterOp::In)
+            }
+            FilterOp::Regex => Regex::new(&format!("^(?:{value})$")).is_ok_and(|pattern| {
+                filter_values(concept, &filter.property).any(|found| pattern.is_match(&found))
+            }),
+            FilterOp::Exists => {
+                filter_values(concept, &filter.property).next().is_some() == (value == "true")
+            }
+        }
+    }
+}
+
+fn filter_values<'a>(
+    concept: &'a Concept,
+    property: &'a str,
+) -> Box<dyn Iterator<Item = String> + 'a> {
+    match property {
+        "concept" | "code" => Box::new(std::iter::once(concept.code.clone())),
+        "display" => Box::new(concept.display.clone().into_iter()),
+        _ => Box::new(concept.property(property).map(PropertyValue::as_text)),
+    }
+}
+
+/// `ValueSet.compose` filter operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum FilterOp {
+    #[serde(rename = "=")]
+    Equals,
+    #[serde(rename = "is-a")]
+    IsA,
+    #[serde(rename = "descendent-of")]
+    DescendentOf,
+    #[serde(rename = "is-not-a")]
+    IsNotA,
+    #[serde(rename = "child-of")]
+    ChildOf,
+    #[serde(rename = "generalizes")]
+    Generalizes,
+    #[serde(rename = "in")]
+    In,
+    #[serde(rename = "not-in")]
+    NotIn,
+    #[serde(rename = "regex")]
+    Regex,
+    #[serde(rename = "exists")]
+    Exists,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ConceptFilter {
+    pub property: String,
+    pub op: FilterOp,
+    pub value: String,
+}
+
+/// A concept listed in a compose rule.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ConceptReference {
+    pub code: String,
+    #[serde(default)]
+    pub display: Option<String>,
+    #[serde(default, rename = "designation")]
+    pub designations: Vec<Designation>,
+}
+
+/// One `compose.include` or `compose.exclude` entry. Its parts are combined:
+/// a code matches when it is in `system`, listed in `concepts` (if any),
+/// passes every filter and is in every imported ValueSet.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConceptSetRule {
+    #[serde(default)]
+    pub system: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default, rename = "concept")]
+    pub concepts: Vec<ConceptReference>,
+    #[serde(default, rename = "filter")]
+    pub filters: Vec<ConceptFilter>,
+    #[serde(default, rename = "valueSet")]
+    pub value_sets: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpansionEntry {
+    pub system: String,
+    pub code: String,
+    pub display: Option<String>,
+}
+
+/// Definition of one ValueSet resource.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ValueSetDefinition {
+    pub url: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub include: Vec<ConceptSetRule>,
+    pub exclude: Vec<ConceptSetRule>,
+    /// `expansion.contains`, flattened.
+    pub expansion: Vec<ExpansionEntry>,
+}
+
+impl From<&ValueSetMeta> for ValueSetDefinition {
+    /// Every code of each of the `include_systems`.
+    fn from(meta: &ValueSetMeta) -> Self {
+        Self {
+            url: meta.url.to_string(),
+            name: Some(meta.name.to_string()),
+            include: meta
+                .include_systems
+                .iter()
+                .map(|system| ConceptSetRule {
+                    system: Some(system.to_string()),
+                    ..ConceptSetRule::default()
+                })
+                .collect(),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawCodeSystem {
+    url: Option<String>,
+    name: Option<String>,
+    version: Option<String>,
+    content: Option<String>,
+    #[serde(default)]
+    identifier: Vec<RawIdentifier>,
+    #[serde(default)]
+    concept: Vec<RawConcept>,
+}
+
+#[derive(Deserialize)]
+struct RawIdentifier {
+    value: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawConcept {
+    code: String,
+    display: Option<String>,
+    definition: Option<String>,
+    #[serde(default)]
+    designation: Vec<Designation>,
+    #[serde(default)]
+    property: Vec<RawProperty>,
+    #[serde(default)]
+    concept: Vec<RawConcept>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawProperty {
+    code: String,
+    value_code: Option<String>,
+    value_coding: Option<Coding>,
+    value_string: Option<String>,
+    value_integer: Option<i64>,
+    value_boolean: Option<bool>,
+    value_date_time: Option<String>,
+    value_decimal: Option<f64>,
+}
+
+impl RawProperty {
+    fn into_property(self) -> Option<ConceptProperty> {
+        let value = if let Some(value) = self.value_code {
+            PropertyValue::Code(value)
+        } else if let Some(value) = self.value_coding {
+            PropertyValue::Coding(value)
+        } else if let Some(value) = self.value_string {
+            PropertyValue::String(value)
+        } else if let Some(value) = self.value_integer {
+            PropertyValue::Integer(value)
+        } else if let Some(value) = self.value_boolean {
+            PropertyValue::Boolean(value)
+        } else if let Some(value) = self.value_date_time {
+            PropertyValue::DateTime(value)
+        } else {
+            PropertyValue::Decimal(self.value_decimal?)
+        };
+        Some(ConceptProperty {
+            code: self.code,
+            value,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RawValueSet {
+    url: Option<String>,
+    name: Option<String>,
+    version: Option<String>,
+    compose: Option<RawCompose>,
+    expansion: Option<RawExpansion>,
+}
+
+#[derive(Deserialize)]
+struct RawCompose {
+    #[serde(default)]
+    include: Vec<ConceptSetRule>,
+    #[serde(default)]
+    exclude: Vec<ConceptSetRule>,
+}
+
+#[derive(Deserialize)]
+struct RawExpansion {
+    #[serde(default)]
+    contains: Vec<RawContains>,
+}
+
+#[derive(Deserialize)]
+struct RawContains {
+    system: Option<String>,
+    code: Option<String>,
+    display: Option<String>,
+    #[serde(default)]
+    contains: Vec<RawContains>,
+}
+
+fn flatten_expansion(contains: Vec<RawContains>, out: &mut Vec<ExpansionEntry>) {
+    for entry in contains {
+        if let (Some(system), Some(code)) = (entry.system, entry.code) {
+            out.push(ExpansionEntry {
+                system,
+                code,
+                display: entry.display,
+            });
+        }
+        flatten_expansion(entry.contains, out);
+    }
+}
+
+/// CodeSystems and ValueSets by canonical URL.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TerminologyStore {
+    code_systems: HashMap<String, CodeSystemContent>,
+    /// Normalized identifier of a loaded CodeSystem -> its key.
+    identifiers: HashMap<String, String>,
+    value_sets: HashMap<String, ValueSetDefinition>,
+}
+
+impl TerminologyStore {
+    /// File or directory of CodeSystem/ValueSet JSON read by [`Self::from_env`].
+    pub const ENV_VAR: &'static str = "DFPS_TERMINOLOGY";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resources from one JSON document: a CodeSystem, a ValueSet, or a Bundle
+    /// of them.
+    pub fn from_json_str(text: &str) -> Result<Self, StoreError> {
+        let mut store = Self::new();
+        store.add_json_str(text)?;
+        Ok(store)
+    }
+
+    /// Load a JSON file, or every `*.json` file of a directory. Other
+    /// resource types found there are ignored.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let path = path.as_ref();
+        let read_error =
+            |err: std::io::Error| StoreError::Invalid(format!("{}: {err}", path.display()));
+        let mut files = Vec::new();
+        if path.is_dir() {
+            for entry in fs::read_dir(path).map_err(read_error)? {
+                let file = entry.map_err(read_error)?.path();
+                if file.extension().is_some_and(|ext| ext == "json") {
+                    files.push(file);
+                }
+            }
+            files.sort();
+        } else {
+            files.push(path.to_path_buf());
+        }
+
+        let mut store = Self::new();
+        for file in files {
+            let text = fs::read_to_string(&file)
+                .map_err(|err| StoreError::Invalid(format!("{}: {err}", file.display())))?;
+            store
+                .add_json_str(&text)
+                .map_err(|err| StoreError::Invalid(format!("{}: {err}", file.display())))?;
+        }
+        Ok(store)
+    }
+
+    /// Load the file or directory named by [`Self::ENV_VAR`]; unset means an
+    /// empty store.
+    pub fn from_env() -> Result<Self, StoreError> {
+        match env::var(Self::ENV_VAR) {
+            Ok(path) if !path.trim().is_empty() => Self::load(path.trim()),
+            _ => Ok(Self::new()),
+        }
+    }
+
+    pub fn add_json_str(&mut self, text: &str) -> Result<(), StoreError> {
+        let value: Value = serde_json::from_str(text)
+            .map_err(|err| StoreError::Invalid(format!("not JSON: {err}")))?;
+        self.add_value(&value)
+    }
+
+    /// Add a CodeSystem, a ValueSet, or the CodeSystems and ValueSets of a
+    /// Bundle; other resources are ignored.
+    pub fn add_value(&mut self, value: &Value) -> Result<(), StoreError> {
+        match value.get("resourceType").and_then(Value::as_str) {
+            Some("CodeSystem") => {
+                let raw: RawCodeSystem = serde_json::from_value(value.clone())
+                    .map_err(|err| StoreError::Invalid(format!("CodeSystem: {err}")))?;
+                self.insert_code_system(code_system_content(raw)?)
+            }
+            Some("ValueSet") => {
+                let raw: RawValueSet = serde_json::from_value(value.clone())
+                    .map_err(|err| StoreError::Invalid(format!("ValueSet: {err}")))?;
+                self.insert_value_set(value_set_definition(raw)?)
+            }
+            Some("Bundle") => {
+                let entries = value.get("entry").and_then(Value::as_array);
+                for resource in entries
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.get("resource"))
+                {
+                    self.add_value(resource)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Add `content`, replacing a code system with the same URL.
+    pub fn insert_code_system(&mut self, content: CodeSystemContent) -> Result<(), StoreError> {
+        let key = normalize(&content.url)
+            .ok_or_else(|| StoreError::Invalid("CodeSystem url must not be empty".into()))?;
+        for identifier in &content.identifiers {
+            if let Some(alias) = normalize(identifier) {
+                self.identifiers.insert(alias, key.clone());
+            }
+        }
+        self.code_systems.insert(key, content);
+        Ok(())
+    }
+
+    /// Add `definition`, replacing a ValueSet with the same URL.
+    ///
+    /// Fails when a compose rule names neither a system nor a ValueSet, or a
+    /// `regex` filter does not compile.
+    pub fn insert_value_set(&mut self, definition: ValueSetDefinition) -> Result<(), StoreError> {
+        let key = normalize(canonical_url(&definition.url))
+            .ok_or_else(|| StoreError::Invalid("ValueSet url must not be empty".into()))?;
+        let invalid =
+            |reason: String| StoreError::Invalid(format!("ValueSet {}: {reason}", definition.url));
+        for rule in definition.include.iter().chain(&definition.exclude) {
+            if rule.system.is_none() && rule.value_sets.is_empty() {
+                return Err(invalid("compose rule needs a system or valueSet".into()));
+            }
+            for filter in &rule.filters {
+                if filter.op == FilterOp::Regex
+                    && let Err(err) = Regex::new(&filter.value)
+                {
+                    return Err(invalid(format!("filter regex: {err}")));
+                }
+            }
+        }
+        self.value_sets.insert(key, definition);
+        Ok(())
+    }
+
+    pub fn code_systems(&self) -> impl Iterator<Item = &CodeSystemContent> {
+        self.code_systems.values()
+    }
+
+    pub fn value_sets(&self) -> impl Iterator<Item = &ValueSetDefinition> {
+        self.value_sets.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.code_systems.is_empty() && self.value_sets.is_empty()
+    }
+
+    /// Loaded code system for `system` (URL, registry alias or identifier).
+    pub fn code_system(&self, system: &str) -> Option<&CodeSystemContent> {
+        let key = self.system_key(system)?;
+        self.code_systems.get(&key)
+    }
+
+    /// Loaded ValueSet; a `|version` suffix is ignored.
+    pub fn value_set(&self, url: &str) -> Option<&ValueSetDefinition> {
+        self.value_sets.get(&normalize(canonical_url(url))?)
+    }
+
+    pub fn lookup(&self, system: &str, code: &str) -> Option<&Concept> {
+        self.code_system(system)?.concept(code)
+    }
+
+    pub fn display(&self, system: &str, code: &str) -> Option<&str> {
+        self.lookup(system, code)?.display.as_deref()
+    }
+
+    /// Display in `language` from the concept's designations.
+    pub fn designation(&self, system: &str, code: &str, language: &str) -> Option<&str> {
+        self.lookup(system, code)?.designation(language)
+    }
+
+    /// Whether the ValueSet contains the code; `None` when the ValueSet is not
+    /// loaded or the answer depends on content that is not. A code without a
+    /// system is checked against each system the ValueSet draws from.
+    pub fn contains(&self, value_set: &str, system: Option<&str>, code: &str) -> Option<bool> {
+        let definition = self.value_set(value_set)?;
+        match system {
+            Some(system) => self.contains_at(definition, system, code, 0),
+            None => {
+                let systems = self.systems_of(definition, 0);
+                if systems.is_empty() {
+                    return None;
+                }
+                any_true(
+                    systems
+                        .iter()
+                        .map(|system| self.contains_at(definition, system, code, 0)),
+                )
+            }
+        }
+    }
+
+    /// Whether the ValueSet draws any code from `system`.
+    pub fn includes_system(&self, value_set: &str, system: &str) -> bool {
+        let Some(definition) = self.value_set(value_set) else {
+            return false;
+        };
+        let key = self.system_key(system);
+        self.systems_of(definition, 0)
+            .iter()
+            .any(|included| self.system_key(included) == key)
+    }
+
+    /// Codes of the ValueSet, from its compose rules over loaded code systems
+    /// or else its stored expansion; `None` when neither can enumerate it.
+    pub fn expand(&self, value_set: &str) -> Option<Vec<ExpansionEntry>> {
+        let definition = self.value_set(value_set)?;
+        self.expand_at(definition, 0)
+            .or_else(|| (!definition.expansion.is_empty()).then(|| definition.expansion.clone()))
+    }
+
+    fn expand_at(
+        &self,
+        definition: &ValueSetDefinition,
+        depth: usize,
+    ) -> Option<Vec<ExpansionEntry>> {
+        if definition.include.is_empty() || depth > MAX_IMPORT_DEPTH {
+            return None;
+        }
+        let mut candidates = Vec::new();
+        for rule in &definition.include {
+            match &rule.system {
+                Some(system) if !rule.concepts.is_empty() => {
+                    candidates.extend(rule.concepts.iter().map(|concept| {
+                        ExpansionEntry {
+                            system: system.clone(),
+                            code: concept.code.clone(),
+                            display: concept.display.clone().or_else(|| {
+                                self.display(system, &concept.code).map(str::to_string)
+                            }),
+                        }
+                    }))
+                }
+                Some(system) => {
+                    let content = self
+                        .code_system(system)
+                        .filter(|content| content.is_complete())?;
+                    candidates.extend(content.concepts().map(|concept| ExpansionEntry {
+                        system: system.clone(),
+                        code: concept.code.clone(),
+                        display: concept.display.clone(),
+                    }));
+                }
+                None => {
+                    for url in &rule.value_sets {
+                        let imported = self.value_set(url)?;
+                        candidates.extend(self.expand_at(imported, depth + 1)?);
+                    }
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut entries = Vec::new();
+        for entry in candidates {
+            if !seen.insert((entry.system.clone(), entry.code.clone())) {
+                continue;
+            }
+            match self.contains_at(definition, &entry.system, &entry.code, depth) {
+                Some(true) => entries.push(entry),
+                Some(false) => {}
+                None => return None,
+            }
+        }
+        Some(entries)
+    }
+
+    fn contains_at(
+        &self,
+        definition: &ValueSetDefinition,
+        system: &str,
+        code: &str,
+        depth: usize,
+    ) -> Option<bool> {
+        let composed = if definition.include.is_empty() {
+            None
+        } else {
+            let included = any_true(
+                definition
+                    .include
+                    .iter()
+                    .map(|rule| self.rule_matches(rule, system, code, depth)),
+            );
+            let excluded = any_true(
+                definition
+                    .exclude
+                    .iter()
+                    .map(|rule| self.rule_matches(rule, system, code, depth)),
+            );
+            match (included, excluded) {
+                (Some(false), _) | (_, Some(true)) => Some(false),
+                (Some(true), Some(false)) => Some(true),
+                _ => None,
+            }
+        };
+        composed.or_else(|| {
+            let key = self.system_key(system);
+            (!definition.expansion.is_empty()).then(|| {
+                definition
+                    .expansion
+                    .iter()
+                    .any(|entry| entry.code == code && self.system_key(&entry.system) == key)
+            })
+        })
+    }
+
+    fn rule_matches(
+        &self,
+        rule: &ConceptSetRule,
+        system: &str,
+        code: &str,
+        depth: usize,
+    ) -> Option<bool> {
+        let mut checks = Vec::new();
+        if let Some(rule_system) = &rule.system {
+            if self.system_key(rule_system) != self.system_key(system) {
+                return Some(false);
+            }
+            let content = self.code_system(rule_system);
+            if !rule.concepts.is_empty() {
+                checks.push(Some(
+                    rule.concepts.iter().any(|concept| concept.code == code),
+                ));
+            }
+            if !rule.filters.is_empty() {
+                checks.push(match content {
+                    Some(content) if content.is_complete() || content.concept(code).is_some() => {
+                        Some(
+                            rule.filters
+                                .iter()
+                                .all(|filter| content.filter_matches(filter, code)),
+                        )
+                    }
+                    _ => None,
+                });
+            }
+            if rule.concepts.is_empty() && rule.filters.is_empty() {
+                checks.push(match content {
+                    Some(content) if content.is_complete() => Some(content.concept(code).is_some()),
+                    _ => Some(true),
+                });
+            }
+        }
+        for url in &rule.value_sets {
+            checks.push(match self.value_set(url) {
+                Some(imported) if depth < MAX_IMPORT_DEPTH => {
+                    self.contains_at(imported, system, code, depth + 1)
+                }
+                _ => None,
+            });
+        }
+        all_true(checks)
+    }
+
+    /// Systems the ValueSet includes, directly or through imports.
+    fn systems_of(&self, definition: &ValueSetDefinition, depth: usize) -> BTreeSet<String> {
+        let mut systems: BTreeSet<String> = definition
+            .expansion
+            .iter()
+            .map(|entry| entry.system.clone())
+            .collect();
+        for rule in &definition.include {
+            systems.extend(rule.system.clone());
+            if depth < MAX_IMPORT_DEPTH {
+                for imported in rule.value_sets.iter().filter_map(|url| self.value_set(url)) {
+                    systems.extend(self.systems_of(imported, depth + 1));
+                }
+            }
+        }
+        systems
+    }
+
+    fn system_key(&self, system: &str) -> Option<String> {
+        let key = normalize(&registry::active().canonicalize(system)?)?;
+        Some(self.identifiers.get(&key).cloned().unwrap_or(key))
+    }
+}
+
+/// `Some(true)` if any is, else `None` if any is unknown, else `Some(false)`.
+fn any_true(results: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    let mut unknown = false;
+    for result in results {
+        match result {
+            Some(true) => return Some(true),
+            None => unknown = true,
+            Some(false) => {}
+        }
+    }
+    (!unknown).then_some(false)
+}
+
+/// `Some(false)` if any is, else `None` if any is unknown, else `Some(true)`.
+fn all_true(results: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    any_true(results.into_iter().map(|result| result.map(|value| !value))).map(|value| !value)
+}
+
+fn code_system_content(raw: RawCodeSystem) -> Result<CodeSystemContent, StoreError> {
+    let url = raw
+        .url
+        .filter(|url| !url.trim().is_empty())
+        .ok_or_else(|| StoreError::Invalid("CodeSystem without url".into()))?;
+    let invalid = |reason: String| StoreError::Invalid(format!("CodeSystem {url}: {reason}"));
+
+    let mut concepts = BTreeMap::new();
+    let mut edges = Vec::new();
+    let mut pending: Vec<(Option<String>, RawConcept)> = raw
+        .concept
+        .into_iter()
+        .rev()
+        .map(|concept| (None, concept))
+        .collect();
+    while let Some((parent, raw_concept)) = pending.pop() {
+        let code = raw_concept.code;
+        if let Some(parent) = parent {
+            edges.push((parent, code.clone()));
+        }
+        let mut properties = Vec::new();
+        for property in raw_concept.property {
+            let Some(property) = property.into_property() else {
+                return Err(invalid(format!(
+                    "concept {code} has a property without a value"
+                )));
+            };
+            match (property.code.as_str(), &property.value) {
+                ("parent", PropertyValue::Code(parent)) => {
+                    edges.push((parent.clone(), code.clone()))
+                }
+                ("child", PropertyValue::Code(child)) => edges.push((code.clone(), child.clone())),
+                _ => {}
+            }
+            properties.push(property);
+        }
+        pending.extend(
+            raw_concept
+                .concept
+                .into_iter()
+                .rev()
+                .map(|child| (Some(code.clone()), child)),
+        );
+        let concept = Concept {
+            code: code.clone(),
+            display: raw_concept.display,
+            definition: raw_concept.definition,
+            designations: raw_concept.designation,
+            properties,
+            parents: Vec::new(),
+            children: Vec::new(),
+        };
+        if concepts.insert(code.clone(), concept).is_some() {
+            return Err(invalid(format!("duplicate concept {code}")));
+        }
+    }
+
+    let edges: BTreeSet<(String, String)> = edges.into_iter().collect();
+    for (parent, child) in edges {
+        if let Some(concept) = concepts.get_mut(&child) {
+            concept.parents.push(parent.clone());
+        }
+        if let Some(concept) = concepts.get_mut(&parent) {
+            concept.children.push(child);
+        }
+    }
+
+    Ok(CodeSystemContent {
+        url,
+        name: raw.name,
+        version: raw.version,
+        content: raw.content,
+        identifiers: raw
+            .identifier
+            .into_iter()
+            .filter_map(|identifier| identifier.value)
+            .collect(),
+        concepts,
+    })
+}
+
+fn value_set_definition(raw: RawValueSet) -> Result<ValueSetDefinition, StoreError> {
+    let url = raw
+        .url
+        .filter(|url| !url.trim().is_empty())
+        .ok_or_else(|| StoreError::Invalid("ValueSet without url".into()))?;
+    let (include, exclude) = raw
+        .compose
+        .map(|compose| (compose.include, compose.exclude))
+        .unwrap_or_default();
+    let mut expansion = Vec::new();
+    if let Some(raw_expansion) = raw.expansion {
+        flatten_expansion(raw_expansion.contains, &mut expansion);
+    }
+    Ok(ValueSetDefinition {
+        url,
+        name: raw.name,
+        version: raw.version,
+        include,
+        exclude,
+        expansion,
+    })
+}
+
+/// Canonical URL without a `|version` suffix.
+fn canonical_url(url: &str) -> &str {
+    url.split_once('|').map_or(url, |(url, _)| url)
+}
+
+/// Make `store` the process-wide store. Call once at startup, before anything
+/// reads [`active`]; fails if a store is already active.
+pub fn install(store: TerminologyStore) -> Result<(), StoreError> {
+    ACTIVE.set(store).map_err(|_| StoreError::AlreadyActive)
+}
+
+/// The process-wide store; empty unless [`install`] ran first.
+pub fn active() -> &'static TerminologyStore {
+    ACTIVE.get_or_init(TerminologyStore::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SYSTEM: &str = "https://dfps.example/fhir/CodeSystem/imaging-procedures";
+    const VALUE_SET: &str = "https://dfps.example/fhir/ValueSet/pet-procedures";
+
+    fn store() -> TerminologyStore {
+        TerminologyStore::from_json_str(
+            &json!({
+                "resourceType": "Bundle",
+                "type": "collection",
+                "entry": [
+                    { "resource": {
+                        "resourceType": "CodeSystem",
+                        "url": SYSTEM,
+                        "identifier": [{ "system": "urn:ietf:rfc:3986", "value": "urn:oid:1.2.3.4" }],
+                        "content": "complete",
+                        "concept": [{
+                            "code": "IMG",
+                            "display": "Imaging procedure",
+                            "concept": [
+                                {
+                                    "code": "PET",
+                                    "display": "PET imaging",
+                                    "designation": [{ "language": "de-CH", "value": "PET-Bildgebung" }],
+                                    "property": [{ "code": "modality", "valueCode": "PT" }],
+                                    "concept": [{
+                                        "code": "PETCT",
+                                        "display": "PET/CT",
+                                        "property": [{ "code": "modality", "valueCode": "PT" }]
+                                    }]
+                                },
+                                {
+                                    "code": "CT",
+                                    "display": "CT imaging",
+                                    "property": [{ "code": "modality", "valueCode": "CT" }]
+                                }
+                            ]
+                        }, {
+                            "code": "PETMR",
+                            "display": "PET/MR",
+                            "property": [
+                                { "code": "parent", "valueCode": "PET" },
+                                { "code": "modality", "valueCode": "PT" },
+                                { "code": "retired", "valueBoolean": true }
+                            ]
+                        }]
+                    }},
+                    { "resource": {
+                        "resourceType": "ValueSet",
+                        "url": VALUE_SET,
+                        "compose": {
+                            "include": [{
+                                "system": SYSTEM,
+                                "filter": [{ "property": "concept", "op": "is-a", "value": "PET" }]
+                            }],
+                            "exclude": [{
+                                "system": SYSTEM,
+                                "filter": [{ "property": "retired", "op": "exists", "value": "true" }]
+                            }]
+                        }
+                    }},
+                    { "resource": { "resourceType": "Patient", "id": "ignored" } }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn loads_concepts_designations_and_hierarchy() {
+        let store = store();
+        let content = store.code_system(SYSTEM).unwrap();
+        assert_eq!(content.len(), 5);
+
+        let pet = store.lookup("urn:oid:1.2.3.4", "PET").unwrap();
+        assert_eq!(pet.parents, ["IMG"]);
+        assert_eq!(pet.children, ["PETCT", "PETMR"]);
+        assert_eq!(
+            store.designation(SYSTEM, "PET", "de"),
+            Some("PET-Bildgebung")
+        );
+        assert_eq!(
+            store.display(&format!("{}/", SYSTEM.to_uppercase()), "PETMR"),
+            Some("PET/MR")
+        );
+
+        assert!(content.subsumes("IMG", "PETMR"));
+        assert!(!content.subsumes("CT", "PETCT"));
+        assert_eq!(
+            content.descendants("IMG").collect::<Vec<_>>(),
+            ["CT", "PET", "PETCT", "PETMR"]
+        );
+    }
+
+    #[test]
+    fn value_set_filters_and_excludes() {
+        let store = store();
+        assert_eq!(store.contains(VALUE_SET, Some(SYSTEM), "PETCT"), Some(true));
+        assert_eq!(
+            store.contains(VALUE_SET, Some(SYSTEM), "PETMR"),
+            Some(false)
+        );
+        assert_eq!(store.contains(VALUE_SET, Some(SYSTEM), "CT"), Some(false));
+        assert_eq!(
+            store.contains(VALUE_SET, Some("http://loinc.org"), "PET"),
+            Some(false)
+        );
+        assert_eq!(
+            store.contains(&format!("{VALUE_SET}|2.0"), None, "PET"),
+            Some(true)
+        );
+        assert!(store.includes_system(VALUE_SET, "urn:oid:1.2.3.4"));
+
+        let codes: Vec<_> = store
+            .expand(VALUE_SET)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.code, entry.display))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                ("PET".to_string(), Some("PET imaging".to_string())),
+                ("PETCT".to_string(), Some("PET/CT".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn membership_is_unknown_without_content() {
+        let mut store = store();
+        store
+            .add_value(&json!({
+                "resourceType": "ValueSet",
+                "url": "https://dfps.example/fhir/ValueSet/mixed",
+                "compose": { "include": [
+                    { "system": "http://snomed.info/sct",
+                      "filter": [{ "property": "concept", "op": "is-a", "value": "71388002" }] },
+                    { "system": "http://loinc.org" },
+                    { "system": SYSTEM, "concept": [{ "code": "CT" }] },
+                    { "valueSet": ["https://dfps.example/fhir/ValueSet/missing"] }
+                ]}
+            }))
+            .unwrap();
+        let mixed = "https://dfps.example/fhir/ValueSet/mixed";
+        assert_eq!(
+            store.contains(mixed, Some("http://snomed.info/sct"), "1"),
+            None
+        );
+        assert_eq!(
+            store.contains(mixed, Some("urn:oid:2.16.840.1.113883.6.1"), "1"),
+            Some(true)
+        );
+        assert_eq!(store.contains(mixed, Some(SYSTEM), "CT"), Some(true));
+        assert_eq!(store.contains(mixed, Some(SYSTEM), "PET"), None);
+        assert_eq!(
+            store.contains("https://dfps.example/fhir/ValueSet/unknown", None, "1"),
+            None
+        );
+        assert_eq!(store.expand(mixed), None);
+    }
+
+    #[test]
+    fn imports_expansions_and_operators() {
+        let mut store = store();
+        for value_set in [
+            json!({
+                "resourceType": "ValueSet",
+                "url": "https://dfps.example/fhir/ValueSet/pt-not-pet",
+                "compose": { "include": [{
+                    "system": SYSTEM,
+                    "valueSet": [VALUE_SET],
+                    "filter": [{ "property": "display", "op": "regex", "value": "PET/.*" }]
+                }]}
+            }),
+            json!({
+                "resourceType": "ValueSet",
+                "url": "https://dfps.example/fhir/ValueSet/modalities",
+                "compose": { "include": [{
+                    "system": SYSTEM,
+                    "filter": [
+                        { "property": "modality", "op": "in", "value": "PT, CT" },
+                        { "property": "concept", "op": "child-of", "value": "IMG" }
+                    ]
+                }]}
+            }),
+            json!({
+                "resourceType": "ValueSet",
+                "url": "https://dfps.example/fhir/ValueSet/expanded",
+                "expansion": { "contains": [{
+                    "system": "http://loinc.org", "code": "24627-2",
+                    "contains": [{ "system": "http://loinc.org", "code": "44136-0" }]
+                }]}
+            }),
+        ] {
+            store.add_value(&value_set).unwrap();
+        }
+
+        let pt = "https://dfps.example/fhir/ValueSet/pt-not-pet";
+        assert_eq!(store.contains(pt, Some(SYSTEM), "PETCT"), Some(true));
+        assert_eq!(store.contains(pt, Some(SYSTEM), "PET"), Some(false));
+
+        let modalities = "https://dfps.example/fhir/ValueSet/modalities";
+        let codes: Vec<_> = store
+            .expand(modalities)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.code)
+            .collect();
+        assert_eq!(codes, ["CT", "PET"]);
+
+        let expanded = "https://dfps.example/fhir/ValueSet/expanded";
+        assert_eq!(
+            store.contains(expanded, Some("http://loinc.org"), "44136-0"),
+            Some(true)
+        );
+        assert_eq!(store.contains(expanded, None, "1-8"), Some(false));
+        assert_eq!(store.expand(expanded).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rejects_malformed_resources() {
+        for resource in [
+            json!({ "resourceType": "CodeSystem", "concept": [] }),
+            json!({ "resourceType": "CodeSystem", "url": SYSTEM,
+                    "concept": [{ "code": "A" }, { "code": "A" }] }),
+            json!({ "resourceType": "ValueSet", "url": VALUE_SET,
+                    "compose": { "include": [{ "concept": [{ "code": "A" }] }] } }),
+            json!({ "resourceType": "ValueSet", "url": VALUE_SET,
+                    "compose": { "include": [{ "system": SYSTEM,
+                        "filter": [{ "property": "concept", "op": "sounds-like", "value": "A" }] }] } }),
+            json!({ "resourceType": "ValueSet", "url": VALUE_SET,
+                    "compose": { "include": [{ "system": SYSTEM,
+                        "filter": [{ "property": "display", "op": "regex", "value": "(" }] }] } }),
+        ] {
+            assert!(
+                TerminologyStore::new().add_value(&resource).is_err(),
+                "{resource}"
+            );
+        }
+    }
+}
@@ -44,6 +44,17 @@ impl PipelineMetrics {
         mappings: &[MappingResult],
     ) {
         self.bundle_count += 1;
+        self.record_rows(flats, codes, mappings);
+    }
+
+    /// Count rows without counting a Bundle (streamed windows before a
+    /// Bundle's last one).
+    pub fn record_rows(
+        &mut self,
+        flats: &[StgServiceRequestFlat],
+        codes: &[StgSrCodeExploded],
+        mappings: &[MappingResult],
+    ) {
         self.flats_count += flats.len();
         self.exploded_count += codes.len();
         self.mapping_count += mappings.len();
@@ -1,3 +1,5 @@
+use std::path::{Path, PathBuf};
+
 use dfps_core::{fhir, order::ServiceRequest};
 use once_cell::sync::Lazy;
 
@@ -24,6 +26,24 @@ const FHIR_BUNDLE_UNKNOWN_CODE: &str =
     include_str!("../fixtures/regression/fhir_bundle_unknown_code.json");
 const FHIR_BUNDLE_MISSING_ENCOUNTER: &str =
     include_str!("../fixtures/regression/fhir_bundle_missing_encounter.json");
+const FHIR_BUNDLE_PET_CT_RESULTS: &str =
+    include_str!("../fixtures/regression/fhir_bundle_pet_ct_results.json");
+const FHIR_BUNDLE_TRANSACTION: &str =
+    include_str!("../fixtures/regression/fhir_bundle_transaction.json");
+const FHIR_BUNDLE_PARTIAL: &str = include_str!("../fixtures/regression/fhir_bundle_partial.json");
+const FHIR_BUNDLE_SR_EXTENSIONS: &str =
+    include_str!("../fixtures/regression/fhir_bundle_sr_extensions.json");
+const HL7V2_ORDERS: &str = include_str!("../fixtures/hl7v2/orders.hl7");
+const CSV_ORDERS: &str = include_str!("../fixtures/csv/orders.csv");
+const CSV_ORDERS_MAPPING: &str = include_str!("../fixtures/csv/orders_mapping.toml");
+const SITE_VALIDATION_RULES: &str = include_str!("../fixtures/validation/site_rules.toml");
+const PET_BINDINGS: &str = include_str!("../fixtures/validation/pet_bindings.toml");
+const DEID_RESEARCH_SHARE: &str = include_str!("../fixtures/deid/research_share.toml");
+const CODE_SYSTEMS: &str = include_str!("../fixtures/terminology/code_systems.toml");
+const FHIRPATH_CASES: &str = include_str!("../fixtures/fhirpath/cases.json");
+const FHIRPATH_PATIENT: &str = include_str!("../fixtures/fhirpath/patient-example.json");
+const FHIRPATH_OBSERVATION: &str = include_str!("../fixtures/fhirpath/observation-example.json");
+const FHIRPATH_ORDER_BUNDLE: &str = include_str!("../fixtures/fhirpath/order-bundle.json");
 
 pub fn baseline_service_request() -> ServiceRequest {
     ensure_env_loaded();
@@ -72,3 +92,120 @@ pub fn fhir_bundle_missing_encounter() -> fhir::Bundle {
     serde_json::from_str(FHIR_BUNDLE_MISSING_ENCOUNTER)
         .expect("missing-encounter bundle should be valid JSON")
 }
+
+pub fn fhir_bundle_pet_ct_results() -> fhir::Bundle {
+    ensure_env_loaded();
+    serde_json::from_str(FHIR_BUNDLE_PET_CT_RESULTS)
+        .expect("pet-ct results bundle should be valid JSON")
+}
+
+pub fn fhir_bundle_transaction() -> fhir::Bundle {
+    ensure_env_loaded();
+    serde_json::from_str(FHIR_BUNDLE_TRANSACTION).expect("transaction bundle should be valid JSON")
+}
+
+pub fn fhir_bundle_sr_extensions() -> fhir::Bundle {
+    ensure_env_loaded();
+    serde_json::from_str(FHIR_BUNDLE_SR_EXTENSIONS)
+        .expect("sr-extensions bundle should be valid JSON")
+}
+
+pub fn fhir_bundle_partial() -> fhir::Bundle {
+    ensure_env_loaded();
+    serde_json::from_str(FHIR_BUNDLE_PARTIAL).expect("partial bundle should be valid JSON")
+}
+
+/// Directory holding a small Bulk Data export (manifest plus one NDJSON file
+/// per resource type).
+pub fn bulk_export_dir() -> PathBuf {
+    ensure_env_loaded();
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/bulk_export")
+}
+
+/// Directory holding the DFPS imaging ServiceRequest profile (snapshot) and
+/// the ValueSets its required bindings use.
+pub fn profiles_dir() -> PathBuf {
+    ensure_env_loaded();
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/profiles")
+}
+
+/// Canonical URL of the profile in [`profiles_dir`].
+pub const IMAGING_SR_PROFILE: &str =
+    "https://dfps.example/fhir/StructureDefinition/dfps-imaging-servicerequest";
+
+/// Two HL7 v2 order messages (ORM^O01 with PV1/TQ1/NTE, OMI^O23 with IPC),
+/// one segment per line.
+pub fn hl7v2_orders() -> &'static str {
+    ensure_env_loaded();
+    HL7V2_ORDERS
+}
+
+/// Partner CSV order extract: two clean rows, one bad status, one without a
+/// patient.
+pub fn csv_orders() -> &'static str {
+    ensure_env_loaded();
+    CSV_ORDERS
+}
+
+/// TOML column mapping for [`csv_orders`].
+pub fn csv_orders_mapping() -> &'static str {
+    ensure_env_loaded();
+    CSV_ORDERS_MAPPING
+}
+
+/// Site validation rules (TOML): ServiceRequest priority/category/occurrence
+/// and Patient gender.
+pub fn site_validation_rules() -> &'static str {
+    ensure_env_loaded();
+    SITE_VALIDATION_RULES
+}
+
+/// ValueSet bindings (TOML) for PET orders: `code` against the registry's PET
+/// procedures (required) and the imaging-procedures ValueSet in
+/// [`profiles_dir`] (extensible), `category` against imaging ordering
+/// (preferred).
+pub fn pet_bindings() -> &'static str {
+    ensure_env_loaded();
+    PET_BINDINGS
+}
+
+/// De-identification config (TOML) for research shares: 180-day date shift
+/// and free text scrubbed of phone numbers, MRNs and slash dates.
+pub fn deid_research_share() -> &'static str {
+    ensure_env_loaded();
+    DEID_RESEARCH_SHARE
+}
+
+/// Code system registry (TOML): ICD-10-PCS and RadLex with their OIDs, plus
+/// an internal-only local system.
+pub fn code_systems() -> &'static str {
+    ensure_env_loaded();
+    CODE_SYSTEMS
+}
+
+/// Directory of FHIR terminology resources: the local imaging orderables
+/// CodeSystem (hierarchy, designations, properties) and ValueSets over it
+/// using filters, excludes and imports.
+pub fn terminology_dir() -> PathBuf {
+    ensure_env_loaded();
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/terminology/fhir")
+}
+
+/// FHIRPath conformance corpus (JSON): HL7 suite cases for the supported
+/// subset plus `ofType`/`resolve()` cases; inputs via [`fhirpath_input`].
+pub fn fhirpath_cases() -> serde_json::Value {
+    ensure_env_loaded();
+    serde_json::from_str(FHIRPATH_CASES).expect("fhirpath corpus should be valid JSON")
+}
+
+/// Input resource named by a [`fhirpath_cases`] group.
+pub fn fhirpath_input(name: &str) -> serde_json::Value {
+    ensure_env_loaded();
+    let text = match name {
+        "patient-example.json" => FHIRPATH_PATIENT,
+        "observation-example.json" => FHIRPATH_OBSERVATION,
+        "order-bundle.json" => FHIRPATH_ORDER_BUNDLE,
+        other => panic!("unknown fhirpath corpus input '{other}'"),
+    };
+    serde_json::from_str(text).expect("fhirpath corpus input should be valid JSON")
+}
@@ -1,4 +1,10 @@
-use dfps_pipeline::bundle_to_mapped_sr;
+use std::io;
+
+use dfps_ingestion::validate_bundle;
+use dfps_pipeline::{
+    IngestionMode, PipelineOptions, PipelineOutput, StreamOptions, bundle_to_mapped_sr,
+    stream_mapped_sr,
+};
 use dfps_test_suite::regression;
 
 #[test]
@@ -16,5 +22,178 @@ fn bundle_maps_to_ncit_concepts_end_to_end() {
         .iter()
         .filter_map(|result| result.ncit_id.as_deref())
         .collect();
-    assert!(ncit_ids.iter().any(|id| *id == "NCIT:C19951"));
+    assert!(ncit_ids.contains(&"NCIT:C19951"));
+}
+
+fn ndjson(bundles: &[dfps_core::fhir::Bundle]) -> Vec<u8> {
+    bundles
+        .iter()
+        .flat_map(|bundle| {
+            let mut line = serde_json::to_vec(bundle).expect("bundle json");
+            line.push(b'\n');
+            line
+        })
+        .collect()
+}
+
+fn tiny_windows(ingestion: IngestionMode) -> PipelineOptions {
+    PipelineOptions {
+        ingestion,
+        stream: StreamOptions {
+            window_entries: 1,
+            ..StreamOptions::default()
+        },
+        ..PipelineOptions::default()
+    }
+}
+
+#[test]
+fn streamed_windows_match_whole_bundle_output() {
+    let bundles = [
+        regression::baseline_fhir_bundle(),
+        regression::fhir_bundle_pet_ct_results(),
+        regression::fhir_bundle_transaction(),
+        regression::fhir_bundle_extra_codings(),
+    ];
+    let input = ndjson(&bundles);
+
+    for (index, bundle) in bundles.iter().enumerate() {
+        let whole = bundle_to_mapped_sr(bundle).expect("whole bundle output");
+        let windows: Vec<_> =
+            stream_mapped_sr(input.as_slice(), &tiny_windows(IngestionMode::Atomic))
+                .map(|window| window.expect("window output"))
+                .filter(|window| window.header.index == index)
+                .collect();
+        assert!(windows.last().is_some_and(|window| window.last));
+        let streamed_issues: Vec<_> = windows
+            .iter()
+            .flat_map(|window| {
+                window
+                    .report
+                    .issues
+                    .iter()
+                    .map(|issue| issue.id.to_string())
+            })
+            .collect();
+        let whole_issues: Vec<_> = validate_bundle(bundle)
+            .issues
+            .iter()
+            .map(|issue| issue.id.to_string())
+            .collect();
+        if index == 3 {
+            // The order precedes its Patient; references into later windows
+            // are treated as external.
+            assert_eq!(streamed_issues, vec!["VAL_SR_SUBJECT_PATIENT_NOT_FOUND"]);
+        } else {
+            assert_eq!(streamed_issues, whole_issues);
+        }
+
+        let streamed = |rows: fn(&PipelineOutput) -> serde_json::Value| {
+            windows
+                .iter()
+                .flat_map(|window| rows(&window.output).as_array().cloned().unwrap_or_default())
+                .collect::<Vec<_>>()
+        };
+        let whole_rows = |value: serde_json::Value| value.as_array().cloned().unwrap_or_default();
+        assert_eq!(
+            streamed(|output| serde_json::to_value(&output.flats).unwrap()),
+            whole_rows(serde_json::to_value(&whole.flats).unwrap())
+        );
+        assert_eq!(
+            streamed(|output| serde_json::to_value(&output.mapping_results).unwrap()),
+            whole_rows(serde_json::to_value(&whole.mapping_results).unwrap())
+        );
+        assert_eq!(
+            streamed(|output| serde_json::to_value(&output.results.observations).unwrap()),
+            whole_rows(serde_json::to_value(&whole.results.observations).unwrap())
+        );
+        let entry_results: Vec<_> = windows
+            .iter()
+            .flat_map(|window| window.output.entry_results.clone())
+            .collect();
+        assert_eq!(entry_results, whole.entry_results);
+    }
+}
+
+#[test]
+fn streamed_partial_ingestion_quarantines_by_bundle_position() {
+    let input = ndjson(&[regression::fhir_bundle_partial()]);
+    let quarantined: Vec<_> = stream_mapped_sr(
+        input.as_slice(),
+        &tiny_windows(IngestionMode::PartialSuccess),
+    )
+    .map(|window| window.expect("window output"))
+    .flat_map(|window| window.output.quarantine)
+    .map(|entry| entry.index)
+    .collect();
+
+    assert_eq!(quarantined, vec![2, 3]);
+}
+
+/// Generates one Bundle of `remaining` ServiceRequests on the fly, so the
+/// test input itself is never held in memory.
+struct GeneratedBundle {
+    remaining: usize,
+    next: usize,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl io::Read for GeneratedBundle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.chunk.len() {
+            self.chunk = if self.next == 0 {
+                br#"{"resourceType":"Bundle","type":"collection","entry":[{"fullUrl":"urn:uuid:pat","resource":{"resourceType":"Patient","id":"p1"}}"#.to_vec()
+            } else if self.next <= self.remaining {
+                format!(
+                    r#",{{"resource":{{"resourceType":"ServiceRequest","id":"SR-{}","status":"active","intent":"order","subject":{{"reference":"urn:uuid:pat"}},"code":{{"coding":[{{"system":"http://www.ama-assn.org/go/cpt","code":"78815"}}]}}}}}}"#,
+                    self.next
+                )
+                .into_bytes()
+            } else if self.next == self.remaining + 1 {
+                b"]}".to_vec()
+            } else {
+                return Ok(0);
+            };
+            self.next += 1;
+            self.pos = 0;
+        }
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[test]
+fn streamed_windows_stay_bounded_on_large_bundles() {
+    let orders = 20_000;
+    let input = io::BufReader::new(GeneratedBundle {
+        remaining: orders,
+        next: 0,
+        chunk: Vec::new(),
+        pos: 0,
+    });
+    let options = PipelineOptions {
+        stream: StreamOptions {
+            window_entries: 500,
+            ..StreamOptions::default()
+        },
+        ..PipelineOptions::default()
+    };
+
+    let mut flats = 0;
+    for window in stream_mapped_sr(input, &options) {
+        let window = window.expect("window output");
+        assert!(window.output.entry_results.len() <= 500);
+        assert!(
+            window
+                .output
+                .flats
+                .iter()
+                .all(|flat| flat.patient_id == "p1")
+        );
+        flats += window.output.flats.len();
+    }
+    assert_eq!(flats, orders);
 }
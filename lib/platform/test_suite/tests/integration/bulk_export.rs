@@ -0,0 +1,50 @@
+use dfps_ingestion::{BulkExport, IngestionError, validate_bundle};
+use dfps_pipeline::bundle_to_mapped_sr;
+use dfps_test_suite::regression;
+
+#[test]
+fn export_orders_are_joined_with_their_patients_and_encounters() {
+    let export = BulkExport::open(regression::bulk_export_dir()).expect("export manifest");
+    assert_eq!(export.unjoined_types(), vec!["Observation"]);
+
+    let bundles: Vec<_> = export
+        .service_request_bundles(2)
+        .expect("context index")
+        .collect::<Result<_, _>>()
+        .expect("export bundles");
+    assert_eq!(bundles.len(), 2);
+
+    let contents = |bundle: &dfps_core::fhir::Bundle| -> Vec<String> {
+        bundle
+            .entry
+            .iter()
+            .map(|entry| entry.resource_id().unwrap_or_default().to_string())
+            .collect()
+    };
+    assert_eq!(
+        contents(&bundles[0]),
+        vec!["PAT-BULK-1", "ENC-BULK-1", "SR-BULK-1", "SR-BULK-2"]
+    );
+    assert_eq!(contents(&bundles[1]), vec!["PAT-BULK-2", "SR-BULK-3"]);
+    assert_eq!(
+        bundles[1].entry[0].full_url.as_deref(),
+        Some("https://fhir.example.org/r4/Patient/PAT-BULK-2")
+    );
+
+    let mut patients = Vec::new();
+    for bundle in &bundles {
+        assert!(validate_bundle(bundle).issues.is_empty());
+        let output = bundle_to_mapped_sr(bundle).expect("pipeline output");
+        assert_eq!(output.mapping_results.len(), output.exploded_codes.len());
+        patients.extend(output.flats.into_iter().map(|flat| flat.patient_id));
+    }
+    assert_eq!(patients, vec!["PAT-BULK-1", "PAT-BULK-1", "PAT-BULK-2"]);
+}
+
+#[test]
+fn missing_manifest_is_reported() {
+    let missing = regression::bulk_export_dir().join("no-such-export");
+    let err = BulkExport::open(&missing).expect_err("no manifest");
+    assert!(matches!(err, IngestionError::InvalidExport(_)));
+    assert_eq!(err.code(), "invalid_export");
+}
@@ -0,0 +1,38 @@
+use dfps_core::mapping::MappingState;
+use dfps_ingestion::{CsvMapping, ValidationMode, csv_to_staging};
+use dfps_pipeline::staging_to_mapped_sr;
+use dfps_test_suite::regression;
+
+#[test]
+fn csv_extract_stages_and_maps_clean_rows() {
+    let mapping = CsvMapping::from_toml_str(regression::csv_orders_mapping()).expect("mapping");
+    let staging = csv_to_staging(
+        regression::csv_orders().as_bytes(),
+        &mapping,
+        ValidationMode::Lenient,
+    )
+    .expect("extract");
+
+    assert_eq!((staging.rows_read, staging.rows_rejected), (4, 2));
+    let issues: Vec<_> = staging
+        .issues
+        .iter()
+        .map(|issue| (issue.line, issue.issue.id.as_str()))
+        .collect();
+    assert_eq!(
+        issues,
+        vec![(4, "VAL_SR_STATUS_INVALID"), (5, "VAL_SR_SUBJECT_MISSING")]
+    );
+
+    let (flats, _) = &staging.rows;
+    assert_eq!(flats[0].status, "active");
+    assert_eq!(flats[0].reason_codes.len(), 1);
+    assert!(flats[1].reason_codes.is_empty());
+
+    let output = staging_to_mapped_sr(staging.rows);
+    assert_eq!(output.flats.len(), 2);
+    assert_eq!(output.mapping_results.len(), 2);
+    let pet_ct = &output.mapping_results[0];
+    assert_eq!(pet_ct.ncit_id.as_deref(), Some("NCIT:C19951"));
+    assert_eq!(pet_ct.state, MappingState::AutoMapped);
+}
@@ -1,5 +1,8 @@
-use dfps_datamart::from_pipeline_output;
-use dfps_pipeline::bundle_to_mapped_sr;
+use dfps_datamart::{Datamart, from_pipeline_output, order_result_facts};
+use dfps_pipeline::{
+    ChangeKind, IngestionMode, PipelineOptions, VersionLedger, bundle_to_mapped_sr,
+    bundle_to_mapped_sr_with_options, track_versions, validate_and_map_sr,
+};
 
 #[test]
 fn baseline_bundle_maps_into_datamart() {
@@ -35,3 +38,174 @@ fn unknown_code_creates_no_match_dim() {
         .expect("no-match dim present");
     assert_eq!(dim.ncit_id, "NO_MATCH");
 }
+
+#[test]
+fn pet_ct_results_link_to_their_order() {
+    let bundle = dfps_test_suite::regression::fhir_bundle_pet_ct_results();
+    let output = bundle_to_mapped_sr(&bundle).expect("pipeline output");
+
+    assert_eq!(output.results.observations.len(), 1);
+    assert_eq!(output.results.diagnostic_reports.len(), 1);
+    assert_eq!(output.results.imaging_studies.len(), 1);
+    assert_eq!(
+        output.result_mapping_results.len(),
+        output.results.codes.len()
+    );
+    assert!(
+        output
+            .result_mapping_results
+            .iter()
+            .all(|result| !result.code_element_id.starts_with("SR-"))
+    );
+
+    let facts = order_result_facts(&output);
+    assert_eq!(facts.len(), 3);
+    assert!(facts.iter().all(|fact| fact.sr_id == "SR-000001"));
+    let mut types: Vec<_> = facts.iter().map(|fact| fact.result_type.as_str()).collect();
+    types.sort();
+    assert_eq!(types, ["DiagnosticReport", "ImagingStudy", "Observation"]);
+}
+
+#[test]
+fn unstageable_results_are_reported_without_failing_the_bundle() {
+    let mut bundle = dfps_test_suite::regression::fhir_bundle_pet_ct_results();
+    let index = bundle
+        .entry
+        .iter()
+        .position(|entry| entry.resource_type() == Some("Observation"))
+        .expect("fixture has an Observation");
+    bundle.entry[index]
+        .resource
+        .as_mut()
+        .and_then(|resource| resource.as_object_mut())
+        .expect("Observation resource")
+        .remove("id");
+
+    let (report, output) =
+        validate_and_map_sr(&bundle, &PipelineOptions::default()).expect("pipeline output");
+    assert_eq!(output.flats.len(), 1);
+    assert!(output.results.observations.is_empty());
+    assert_eq!(output.results.diagnostic_reports.len(), 1);
+
+    let issue = report
+        .issues
+        .iter()
+        .find(|issue| issue.id == "VAL_RESULT_NOT_STAGED")
+        .expect("unstaged Observation reported");
+    assert_eq!(
+        issue.path.as_deref(),
+        Some(format!("Bundle.entry[{index}]").as_str())
+    );
+}
+
+#[test]
+fn transaction_bundle_reports_entry_responses() {
+    let bundle = dfps_test_suite::regression::fhir_bundle_transaction();
+    let output = bundle_to_mapped_sr(&bundle).expect("pipeline output");
+
+    assert_eq!(output.flats.len(), 1);
+    assert_eq!(output.flats[0].patient_id, "PAT-000001");
+
+    let statuses: Vec<_> = output
+        .entry_results
+        .iter()
+        .map(|result| result.response.status.as_str())
+        .collect();
+    assert_eq!(statuses, ["201 Created", "201 Created", "204 No Content"]);
+    assert_eq!(
+        output.entry_results[1].response.location.as_deref(),
+        Some("ServiceRequest/SR-000001")
+    );
+}
+
+#[test]
+fn partial_ingestion_builds_facts_from_healthy_entries() {
+    let bundle = dfps_test_suite::regression::fhir_bundle_partial();
+    assert!(bundle_to_mapped_sr(&bundle).is_err());
+
+    let options = PipelineOptions {
+        ingestion: IngestionMode::PartialSuccess,
+        ..PipelineOptions::default()
+    };
+    let output = bundle_to_mapped_sr_with_options(&bundle, &options).expect("partial output");
+    assert_eq!(output.flats.len(), 1);
+    assert_eq!(output.quarantine.len(), 2);
+
+    let (dims, facts) = from_pipeline_output(&output);
+    assert_eq!(dims.patients.len(), 1);
+    assert_eq!(facts.len(), 1);
+}
+
+#[test]
+fn reingesting_a_bundle_upserts_facts() {
+    let mut bundle = dfps_test_suite::regression::baseline_fhir_bundle();
+    let mut ledger = VersionLedger::new();
+    let mut mart = Datamart::new();
+    let mut ingest = |bundle: &dfps_core::fhir::Bundle| {
+        let mut output = bundle_to_mapped_sr(bundle).expect("pipeline output");
+        track_versions(&mut output, &mut ledger);
+        mart.apply(&output);
+        output
+            .changes
+            .iter()
+            .map(|change| change.change)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(ingest(&bundle), vec![ChangeKind::Created]);
+    assert_eq!(ingest(&bundle), vec![ChangeKind::Unchanged]);
+
+    let sr = bundle
+        .entry
+        .iter_mut()
+        .filter_map(|entry| entry.resource.as_mut())
+        .find(|resource| resource["resourceType"] == "ServiceRequest")
+        .expect("baseline ServiceRequest");
+    sr["status"] = "completed".into();
+    assert_eq!(ingest(&bundle), vec![ChangeKind::Updated]);
+
+    let facts: Vec<_> = mart.facts().collect();
+    assert!(!facts.is_empty());
+    assert!(facts.iter().all(|fact| fact.status == "completed"));
+    let expected = bundle_to_mapped_sr(&bundle).unwrap().mapping_results.len();
+    assert_eq!(facts.len(), expected);
+}
+
+#[test]
+fn repeated_order_in_one_bundle_keeps_each_version() {
+    let delivery = |version: &str, status: &str| {
+        serde_json::json!({
+            "resource": {
+                "resourceType": "ServiceRequest",
+                "id": "SR-TWICE",
+                "meta": { "versionId": version },
+                "status": status,
+                "intent": "order",
+                "subject": { "reference": "Patient/PAT-TWICE" }
+            }
+        })
+    };
+    let bundle: dfps_core::fhir::Bundle = serde_json::from_value(serde_json::json!({
+        "resourceType": "Bundle",
+        "type": "collection",
+        "entry": [
+            { "resource": { "resourceType": "Patient", "id": "PAT-TWICE" } },
+            delivery("1", "active"),
+            delivery("2", "completed")
+        ]
+    }))
+    .expect("bundle");
+
+    let output = bundle_to_mapped_sr(&bundle).expect("pipeline output");
+    assert_eq!(output.flats.len(), 2);
+    let versions: Vec<_> = output
+        .versions
+        .iter()
+        .map(|version| version.version_id.as_deref())
+        .collect();
+    assert_eq!(versions, vec![Some("1"), Some("2")]);
+    assert_ne!(
+        output.versions[0].content_hash,
+        output.versions[1].content_hash
+    );
+}
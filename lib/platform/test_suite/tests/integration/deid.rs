@@ -0,0 +1,99 @@
+use dfps_ingestion::{DeidConfig, Deidentifier};
+use dfps_pipeline::{
+    PipelineOptions, StreamOptions, bundle_to_mapped_sr_with_options, stream_mapped_sr,
+    validate_and_map_sr,
+};
+use dfps_test_suite::regression;
+use serde_json::json;
+
+const KEY: &str = "research-share-key-0001";
+const SOURCE_IDS: [&str; 3] = ["PAT-000001", "ENC-000001", "SR-000001"];
+
+fn options(window_entries: usize) -> PipelineOptions {
+    let config = DeidConfig::from_toml_str(regression::deid_research_share()).expect("config");
+    PipelineOptions {
+        stream: StreamOptions {
+            window_entries,
+            ..StreamOptions::default()
+        },
+        deid: Some(Deidentifier::new(KEY, config).expect("deidentifier")),
+        ..PipelineOptions::default()
+    }
+}
+
+fn bundle_with_free_text() -> dfps_core::fhir::Bundle {
+    let mut bundle = regression::baseline_fhir_bundle();
+    let order = bundle.entry[2].resource.as_mut().expect("order");
+    order["description"] = json!("PET/CT, call 555-867-5309 re MRN: 12345 seen 4/2/2024");
+    order["note"] = json!([{ "text": "Patient phone 555.867.5309" }]);
+    bundle
+}
+
+#[test]
+fn deidentified_outputs_carry_no_source_ids_or_free_text() {
+    let options = options(StreamOptions::DEFAULT_WINDOW_ENTRIES);
+    let deid = options.deid.as_ref().unwrap();
+    let (report, output) = validate_and_map_sr(&bundle_with_free_text(), &options).expect("map");
+    assert!(report.issues.is_empty(), "{:?}", report.issues);
+
+    let flat = &output.flats[0];
+    assert_eq!(flat.sr_id, deid.pseudonym("ServiceRequest", "SR-000001"));
+    assert_eq!(flat.patient_id, deid.pseudonym("Patient", "PAT-000001"));
+    assert_eq!(
+        flat.encounter_id.as_deref(),
+        Some(deid.pseudonym("Encounter", "ENC-000001").as_str())
+    );
+    assert_eq!(
+        flat.description,
+        "PET/CT, call [PHONE] re [MRN] seen [DATE]"
+    );
+    assert_eq!(flat.notes, vec!["Patient phone [PHONE]".to_string()]);
+    let shift = deid.shift_days("PAT-000001");
+    assert!(shift != 0 && shift.abs() <= 180);
+    let authored: dfps_core::value::FhirDateTime = "2024-05-01T12:00:00Z".parse().unwrap();
+    assert_eq!(flat.ordered_at, Some(authored.shift_days(shift)));
+
+    let rendered = serde_json::to_string(&(
+        &output.flats,
+        &output.exploded_codes,
+        &output.mapping_results,
+    ))
+    .unwrap();
+    let locations: Vec<_> = output
+        .entry_results
+        .iter()
+        .filter_map(|entry| entry.response.location.as_deref())
+        .collect();
+    for id in SOURCE_IDS {
+        assert!(!rendered.contains(id), "{id} leaked: {rendered}");
+        assert!(!locations.iter().any(|location| location.contains(id)));
+    }
+}
+
+#[test]
+fn streamed_windows_use_the_same_pseudonyms_and_shift() {
+    let bundle = bundle_with_free_text();
+    let whole = bundle_to_mapped_sr_with_options(&bundle, &options(100)).expect("map");
+
+    let mut line = serde_json::to_vec(&bundle).unwrap();
+    line.push(b'\n');
+    let windows: Vec<_> = stream_mapped_sr(line.as_slice(), &options(1))
+        .collect::<Result<_, _>>()
+        .expect("stream");
+    let flats: Vec<_> = windows
+        .iter()
+        .flat_map(|window| window.output.flats.clone())
+        .collect();
+    assert_eq!(flats, whole.flats);
+    let locations: Vec<_> = windows
+        .iter()
+        .flat_map(|window| &window.output.entry_results)
+        .filter_map(|entry| entry.response.location.clone())
+        .collect();
+    for id in SOURCE_IDS {
+        assert!(
+            locations.iter().all(|location| !location.contains(id)),
+            "{locations:?}"
+        );
+    }
+}
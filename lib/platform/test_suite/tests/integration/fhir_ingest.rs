@@ -1,5 +1,9 @@
+use dfps_core::fhir::{DomainResource, ServiceRequest};
 use dfps_fake_data::raw_fhir::fake_fhir_bundle_scenario_with_seed;
-use dfps_ingestion::{IngestionError, bundle_to_staging};
+use dfps_ingestion::{
+    ExtensionProjection, IngestionError, ValidationMode, bundle_to_encounter_staging,
+    bundle_to_patient_staging, bundle_to_staging, bundle_to_staging_with_projection,
+};
 use dfps_test_suite::regression;
 use proptest::prelude::*;
 
@@ -18,6 +22,21 @@ proptest! {
             .unwrap_or(0);
         prop_assert_eq!(exploded.len(), expected);
     }
+
+    #[test]
+    fn patient_and_encounter_rows_link_to_service_request(seed in 0u64..1_000_000) {
+        let scenario = fake_fhir_bundle_scenario_with_seed(seed);
+        let (flats, _) = bundle_to_staging(&scenario.bundle).expect("staging conversion");
+        let patients = bundle_to_patient_staging(&scenario.bundle).expect("patient rows");
+        let encounters = bundle_to_encounter_staging(&scenario.bundle).expect("encounter rows");
+
+        prop_assert_eq!(patients.len(), 1);
+        prop_assert!(patients[0].mrn.is_some());
+        prop_assert_eq!(&patients[0].patient_id, &flats[0].patient_id);
+        prop_assert_eq!(encounters.len(), 1);
+        prop_assert_eq!(encounters[0].patient_id.as_ref(), Some(&flats[0].patient_id));
+        prop_assert_eq!(Some(&encounters[0].encounter_id), flats[0].encounter_id.as_ref());
+    }
 }
 
 #[test]
@@ -71,3 +90,60 @@ fn uppercase_status_and_intent_are_normalized() {
     assert_eq!(flats[0].intent, "order");
     assert_eq!(exploded.len(), 1);
 }
+
+#[test]
+fn extensions_and_meta_survive_decode() {
+    let bundle = regression::fhir_bundle_sr_extensions();
+    let sr = bundle
+        .find::<ServiceRequest>("SR-EXT-1")
+        .expect("ServiceRequest entry")
+        .expect("typed ServiceRequest");
+
+    let meta = sr.meta().expect("meta");
+    assert_eq!(meta.version_id.as_deref(), Some("7"));
+    assert_eq!(meta.tag[0].code.as_deref(), Some("pet-ct-pathway"));
+    assert!(
+        sr.claims_profile("http://hl7.org/fhir/us/core/StructureDefinition/us-core-servicerequest")
+    );
+    assert_eq!(
+        sr.extension_value("https://dfps.example/fhir/StructureDefinition/protocol-id")
+            .and_then(|value| value.as_str()),
+        Some("PROTO-2024-07")
+    );
+    assert!(
+        sr.modifier_extension("https://dfps.example/fhir/StructureDefinition/priority-override")
+            .is_some()
+    );
+
+    let encoded = serde_json::to_value(&sr).expect("encode");
+    let reparsed: ServiceRequest = serde_json::from_value(encoded).expect("decode");
+    assert_eq!(reparsed.meta, sr.meta);
+    assert_eq!(reparsed.extension, sr.extension);
+    assert_eq!(reparsed.modifier_extension, sr.modifier_extension);
+}
+
+#[test]
+fn projected_extensions_become_staging_columns() {
+    let bundle = regression::fhir_bundle_sr_extensions();
+    let projection = ExtensionProjection::parse(
+        "priority_override=https://dfps.example/fhir/StructureDefinition/priority-override,\
+         protocol_id=https://dfps.example/fhir/StructureDefinition/protocol-id,\
+         tracer=https://dfps.example/fhir/StructureDefinition/tracer,\
+         missing=https://dfps.example/fhir/StructureDefinition/absent",
+    )
+    .expect("projection spec");
+
+    let (flats, _) =
+        bundle_to_staging_with_projection(&bundle, ValidationMode::Lenient, &projection)
+            .expect("staging with projection")
+            .value;
+    let columns = &flats[0].extensions;
+    assert_eq!(columns["priority_override"], "stat");
+    assert_eq!(columns["protocol_id"], "PROTO-2024-07");
+    assert_eq!(columns["tracer"], "http://snomed.info/sct|764755009");
+    assert!(!columns.contains_key("missing"));
+    assert_eq!(flats[0].priority.as_deref(), Some("routine"));
+
+    let (flats, _) = bundle_to_staging(&bundle).expect("default staging");
+    assert!(flats[0].extensions.is_empty());
+}
@@ -0,0 +1,62 @@
+use dfps_core::{
+    fhir,
+    fhirpath::{FhirPath, FhirPathError},
+};
+use dfps_ingestion::BundleResolver;
+use dfps_test_suite::regression;
+use serde_json::Value;
+
+#[test]
+fn conformance_corpus_passes() {
+    let corpus = regression::fhirpath_cases();
+    let mut failures = Vec::new();
+    let mut total = 0;
+
+    for group in corpus["groups"].as_array().expect("groups") {
+        let input = regression::fhirpath_input(group["input"].as_str().expect("input"));
+        for case in group["cases"].as_array().expect("cases") {
+            total += 1;
+            let name = case["name"].as_str().expect("name");
+            let expression = case["expression"].as_str().expect("expression");
+            let result =
+                FhirPath::parse(expression).and_then(|path| match case["entry"].as_u64() {
+                    Some(entry) => {
+                        let bundle: fhir::Bundle =
+                            serde_json::from_value(input.clone()).expect("bundle input");
+                        BundleResolver::new(&bundle).evaluate(&path, entry as usize)
+                    }
+                    None => path.evaluate(&input),
+                });
+            let outcome = match (case["error"].as_str(), &result) {
+                (Some("parse"), Err(FhirPathError::Parse(_)))
+                | (Some("eval"), Err(FhirPathError::Eval(_))) => None,
+                (None, Ok(values)) if Some(values) == case["expected"].as_array() => None,
+                _ => Some(format!(
+                    "{}/{name}: `{expression}` gave {result:?}, expected {}",
+                    group["name"],
+                    case.get("expected").unwrap_or(&case["error"])
+                )),
+            };
+            failures.extend(outcome);
+        }
+    }
+
+    assert!(total > 100, "corpus unexpectedly small: {total} cases");
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn typed_resources_match_their_json_form() {
+    let input = regression::fhirpath_input("patient-example.json");
+    let patient: fhir::Patient = serde_json::from_value(input.clone()).expect("patient decodes");
+    for expression in [
+        "Patient.identifier.where(type.coding.code = 'MR').value",
+        "gender = 'male' and deceased.ofType(boolean).not()",
+        "managingOrganization.reference",
+    ] {
+        let path = FhirPath::parse(expression).expect("expression parses");
+        let typed = path.evaluate_resource(&patient).expect("typed evaluation");
+        assert_eq!(typed, path.evaluate(&input).unwrap(), "{expression}");
+        assert_ne!(typed, Vec::<Value>::new(), "{expression}");
+    }
+}
@@ -0,0 +1,71 @@
+use dfps_core::mapping::MappingState;
+use dfps_ingestion::{
+    hl7v2::{self, MLLP_END, MLLP_START, Message, MessageReader},
+    validate_bundle,
+};
+use dfps_pipeline::bundle_to_mapped_sr;
+use dfps_test_suite::regression;
+
+fn read_messages(input: &[u8]) -> Vec<String> {
+    MessageReader::new(input)
+        .collect::<Result<_, _>>()
+        .expect("framed messages")
+}
+
+#[test]
+fn newline_and_mllp_framing_yield_the_same_messages() {
+    let text = regression::hl7v2_orders();
+    let plain = read_messages(text.as_bytes());
+    assert_eq!(plain.len(), 2);
+
+    let mut framed = Vec::new();
+    for message in &plain {
+        framed.push(MLLP_START);
+        framed.extend(message.as_bytes());
+        framed.extend([MLLP_END, b'\r']);
+    }
+    assert_eq!(read_messages(&framed), plain);
+}
+
+#[test]
+fn orders_flow_through_validation_staging_and_mapping() {
+    let mut flats = Vec::new();
+    let mut mappings = Vec::new();
+    for message in read_messages(regression::hl7v2_orders().as_bytes()) {
+        let message = Message::parse(&message).expect("ER7 message");
+        let bundle = hl7v2::message_to_bundle(&message).expect("order bundle");
+        assert!(
+            validate_bundle(&bundle).issues.is_empty(),
+            "{:?}",
+            validate_bundle(&bundle).issues
+        );
+        let output = bundle_to_mapped_sr(&bundle).expect("pipeline output");
+        flats.extend(output.flats);
+        mappings.extend(output.mapping_results);
+    }
+
+    let orders: Vec<_> = flats
+        .iter()
+        .map(|flat| {
+            (
+                flat.sr_id.as_str(),
+                flat.patient_id.as_str(),
+                flat.encounter_id.as_deref(),
+                flat.status.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        orders,
+        vec![
+            ("SR-HL7-1", "PAT-HL7-1", Some("ENC-HL7-1"), "active"),
+            ("SR-HL7-2", "PAT-HL7-1", None, "active"),
+        ]
+    );
+
+    let pet_ct = mappings
+        .iter()
+        .find(|result| result.code_element_id.contains("78815"))
+        .expect("CPT 78815 mapping");
+    assert_ne!(pet_ct.state, MappingState::NoMatch);
+}
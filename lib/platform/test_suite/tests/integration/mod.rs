@@ -1,6 +1,13 @@
+mod bulk_export;
+mod csv_extract;
 mod datamart;
+mod deid;
 mod fhir_ingest;
+mod fhirpath;
+mod hl7v2;
 mod mapping;
+mod provenance;
 mod regression;
+mod terminology;
 mod validation;
 mod web_api;
@@ -0,0 +1,146 @@
+use dfps_core::provenance::SourceProvenance;
+use dfps_datamart::from_pipeline_output;
+use dfps_ingestion::{CsvMapping, ValidationMode, csv_to_staging};
+use dfps_pipeline::{
+    ChangeKind, PipelineOptions, ProvenanceContext, StreamOptions, VersionLedger,
+    bundle_to_mapped_sr_with_options, staging_to_mapped_sr, stream_mapped_sr, track_versions,
+};
+use dfps_test_suite::regression;
+
+fn run(run_id: &str) -> ProvenanceContext {
+    ProvenanceContext::new(run_id, "2024-06-01T09:00:00Z".parse().unwrap())
+        .with_source_uri("orders.ndjson")
+}
+
+fn options(window_entries: usize) -> PipelineOptions {
+    PipelineOptions {
+        stream: StreamOptions {
+            window_entries,
+            ..StreamOptions::default()
+        },
+        provenance: Some(run("run-1")),
+        ..PipelineOptions::default()
+    }
+}
+
+#[test]
+fn provenance_flows_from_staging_to_mapping_and_facts() {
+    let mut bundle = regression::baseline_fhir_bundle();
+    bundle.id = Some("b-1".into());
+    bundle.entry[2].full_url = Some("urn:uuid:sr-1".into());
+    let output = bundle_to_mapped_sr_with_options(&bundle, &options(100)).expect("map");
+
+    let expected = SourceProvenance {
+        source_uri: Some("orders.ndjson".into()),
+        bundle_id: Some("b-1".into()),
+        entry_index: Some(2),
+        entry_full_url: Some("urn:uuid:sr-1".into()),
+        ..SourceProvenance::new("run-1", "2024-06-01T09:00:00Z".parse().unwrap())
+    };
+    assert_eq!(output.flats[0].provenance.as_ref(), Some(&expected));
+    assert!(!output.exploded_codes.is_empty());
+    for code in &output.exploded_codes {
+        assert_eq!(code.provenance.as_ref(), Some(&expected));
+    }
+    for result in &output.mapping_results {
+        assert_eq!(result.provenance.as_ref(), Some(&expected));
+    }
+    let (_, facts) = from_pipeline_output(&output);
+    assert_eq!(facts[0].provenance.as_ref(), Some(&expected));
+}
+
+#[test]
+fn streamed_entries_record_their_byte_offset_and_line() {
+    let bundle = regression::baseline_fhir_bundle();
+    let mut input = b"{\"resourceType\":\"Patient\",\"id\":\"p0\"}\n".to_vec();
+    input.extend(serde_json::to_vec_pretty(&bundle).unwrap());
+    input.push(b'\n');
+
+    let windows: Vec<_> = stream_mapped_sr(input.as_slice(), &options(1))
+        .collect::<Result<_, _>>()
+        .expect("stream");
+    let flat = windows
+        .iter()
+        .flat_map(|window| &window.output.flats)
+        .next()
+        .expect("staged order");
+    let provenance = flat.provenance.as_ref().expect("provenance");
+    assert_eq!(provenance.bundle_index, Some(1));
+    assert_eq!(provenance.entry_index, Some(2));
+
+    let offset = provenance.byte_offset.expect("offset") as usize;
+    let text = std::str::from_utf8(&input).unwrap();
+    assert!(text[offset..].starts_with('{'));
+    assert!(text[offset..].contains("\"ServiceRequest\""));
+    let line = text[..offset].matches('\n').count() as u64 + 1;
+    assert_eq!(provenance.line, Some(line));
+}
+
+#[test]
+fn csv_rows_record_their_line_and_rerun_is_unchanged() {
+    let mapping = CsvMapping::from_toml_str(regression::csv_orders_mapping()).expect("mapping");
+    let stage = |run_id: &str| {
+        let mut staging = csv_to_staging(
+            regression::csv_orders().as_bytes(),
+            &mapping,
+            ValidationMode::Lenient,
+        )
+        .expect("extract");
+        run(run_id).stamp_csv(&mut staging);
+        staging_to_mapped_sr(staging.rows)
+    };
+
+    let mut first = stage("run-1");
+    let lines: Vec<_> = first
+        .flats
+        .iter()
+        .map(|flat| flat.provenance.as_ref().and_then(|p| p.line))
+        .collect();
+    assert_eq!(lines, vec![Some(2), Some(3)]);
+    assert_eq!(
+        first.mapping_results[0].provenance.as_ref().unwrap().run_id,
+        "run-1"
+    );
+
+    let mut ledger = VersionLedger::default();
+    track_versions(&mut first, &mut ledger);
+    let mut second = stage("run-2");
+    track_versions(&mut second, &mut ledger);
+    assert!(
+        second
+            .changes
+            .iter()
+            .all(|change| change.change == ChangeKind::Unchanged)
+    );
+}
+
+#[test]
+fn rows_sharing_an_order_id_keep_their_own_position() {
+    let mut bundle = regression::baseline_fhir_bundle();
+    let duplicate = bundle.entry[2].clone();
+    bundle.entry.push(duplicate);
+    let output = bundle_to_mapped_sr_with_options(&bundle, &options(100)).expect("map");
+    let entries: Vec<_> = output
+        .flats
+        .iter()
+        .map(|flat| flat.provenance.as_ref().and_then(|p| p.entry_index))
+        .collect();
+    assert_eq!(entries, vec![Some(2), Some(3)]);
+
+    let mapping = CsvMapping::from_toml_str(regression::csv_orders_mapping()).expect("mapping");
+    let mut csv = regression::csv_orders().to_string();
+    let first_row = csv.lines().nth(1).unwrap().to_string();
+    csv.push_str(&first_row);
+    csv.push('\n');
+    let mut staging =
+        csv_to_staging(csv.as_bytes(), &mapping, ValidationMode::Lenient).expect("extract");
+    run("run-1").stamp_csv(&mut staging);
+    let lines: Vec<_> = staging
+        .rows
+        .0
+        .iter()
+        .map(|flat| flat.provenance.as_ref().and_then(|p| p.line))
+        .collect();
+    let appended = csv.lines().count() as u64;
+    assert_eq!(lines, vec![Some(2), Some(3), Some(appended)]);
+}
@@ -0,0 +1,195 @@
+use dfps_core::staging::StgSrCodeExploded;
+use dfps_ingestion::validation::{BindingStrength, Profiles, ValueSetBinding};
+use dfps_terminology::{
+    CodeKind, CodeSystemRegistry, EnrichedCode, LicenseTier, TerminologyStore, store::PropertyValue,
+};
+use dfps_test_suite::regression;
+
+const LOCAL: &str = "http://hospital.example.org/fhir/CodeSystem/imaging-orders";
+const PET: &str = "http://hospital.example.org/fhir/ValueSet/pet-orderables";
+const ONCOLOGY: &str = "http://hospital.example.org/fhir/ValueSet/oncology-imaging";
+const CPT: &str = "http://www.ama-assn.org/go/cpt";
+
+fn code(system: &str, code: &str) -> StgSrCodeExploded {
+    StgSrCodeExploded {
+        sr_id: "SR-1".into(),
+        system: Some(system.into()),
+        code: Some(code.into()),
+        display: None,
+        provenance: None,
+    }
+}
+
+#[test]
+fn site_registry_adds_systems_resolved_by_url_or_oid() {
+    let registry = CodeSystemRegistry::from_toml_str(regression::code_systems()).expect("registry");
+    let names: Vec<_> = registry
+        .entries()
+        .iter()
+        .map(|meta| meta.name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "CPT",
+            "SNOMED CT",
+            "LOINC",
+            "NCIt OBO",
+            "ICD-10-PCS",
+            "RadLex",
+            "Local imaging orderables"
+        ]
+    );
+
+    let icd = EnrichedCode::from_staging_with(
+        code("urn:oid:2.16.840.1.113883.6.4", "BW24Y0Z"),
+        &registry,
+    );
+    assert_eq!(
+        icd.canonical_system(),
+        Some("http://www.cms.gov/Medicare/Coding/ICD10")
+    );
+    assert_eq!(icd.code_kind(), CodeKind::KnownOpenSystem);
+    assert_eq!(
+        icd.codesystem.as_ref().unwrap().version.as_deref(),
+        Some("2025")
+    );
+
+    let local = EnrichedCode::from_staging_with(
+        code(
+            "http://hospital.example.org/fhir/CodeSystem/imaging-orders/",
+            "PETCT-1",
+        ),
+        &registry,
+    );
+    assert_eq!(local.license_tier, Some(LicenseTier::InternalOnly));
+    assert_eq!(local.code_kind(), CodeKind::KnownLicensedSystem);
+    assert_eq!(local.source_label(), Some("local"));
+
+    // Without the site file these systems stay unknown.
+    let builtin = EnrichedCode::from_staging_with(
+        code("http://radlex.org", "RPID144"),
+        &CodeSystemRegistry::builtin(),
+    );
+    assert_eq!(builtin.code_kind(), CodeKind::UnknownSystem);
+}
+
+#[test]
+fn imported_code_system_gives_concepts_and_hierarchy() {
+    let store = TerminologyStore::load(regression::terminology_dir()).expect("terminology load");
+    assert_eq!(store.code_systems().count(), 1);
+    assert_eq!(store.value_sets().count(), 2);
+
+    let content = store
+        .code_system("urn:oid:2.16.840.1.999999.1.12")
+        .expect("resolved by identifier");
+    assert_eq!(content.version.as_deref(), Some("2025.1"));
+    assert_eq!(content.len(), 8);
+
+    let pet = store.lookup(LOCAL, "PETCT-WB").expect("concept");
+    assert_eq!(pet.display.as_deref(), Some("PET/CT whole body"));
+    assert_eq!(pet.parents, ["NM"]);
+    assert_eq!(
+        store.designation(LOCAL, "PETCT-WB", "es"),
+        Some("PET/TC de cuerpo entero")
+    );
+    match pet.property("radlex").next() {
+        Some(PropertyValue::Coding(coding)) => assert_eq!(coding.code.as_deref(), Some("RPID1514")),
+        other => panic!("unexpected radlex property {other:?}"),
+    }
+
+    // `parent` properties join the nested hierarchy.
+    assert_eq!(
+        store.lookup(LOCAL, "NM").unwrap().children,
+        ["PETCT-BRAIN", "PETCT-WB", "PETMR-WB", "SPECT-MPI"]
+    );
+    assert!(content.subsumes("IMG", "PETMR-WB"));
+    assert_eq!(
+        content.descendants("CT").collect::<Vec<_>>(),
+        ["CT-CHEST-C"]
+    );
+
+    // Mapping falls back to these displays for rows that carry none.
+    let enriched = EnrichedCode::from_staging(code(LOCAL, "CT-CHEST-C"));
+    assert_eq!(
+        enriched.display_with(&store),
+        Some("CT chest with contrast")
+    );
+}
+
+#[test]
+fn value_sets_resolve_code_level_membership() {
+    let store = TerminologyStore::load(regression::terminology_dir()).expect("terminology load");
+    let cases = [
+        (PET, LOCAL, "PETCT-WB", Some(true)),
+        (PET, LOCAL, "SPECT-MPI", Some(false)),
+        (PET, LOCAL, "PETMR-WB", Some(false)),
+        (PET, LOCAL, "NM", Some(false)),
+        (PET, CPT, "78815", Some(true)),
+        (PET, CPT, "70450", Some(false)),
+        (ONCOLOGY, LOCAL, "CT-CHEST-C", Some(true)),
+        (ONCOLOGY, LOCAL, "PETCT-BRAIN", Some(true)),
+        (ONCOLOGY, LOCAL, "SPECT-MPI", Some(false)),
+        // SNOMED CT is not loaded, so its is-a filter cannot be decided.
+        (ONCOLOGY, "http://snomed.info/sct", "441550003", None),
+    ];
+    for (value_set, system, code, expected) in cases {
+        assert_eq!(
+            store.contains(value_set, Some(system), code),
+            expected,
+            "{value_set} {system}|{code}"
+        );
+    }
+
+    let expansion: Vec<_> = store
+        .expand(PET)
+        .expect("enumerable")
+        .into_iter()
+        .map(|entry| (entry.code, entry.display))
+        .collect();
+    assert_eq!(
+        expansion,
+        vec![
+            ("PETCT-BRAIN".to_string(), Some("PET/CT brain".to_string())),
+            (
+                "PETCT-WB".to_string(),
+                Some("PET/CT whole body".to_string())
+            ),
+            (
+                "78815".to_string(),
+                Some("PET with concurrently acquired CT; skull base to mid-thigh".to_string())
+            ),
+            ("78816".to_string(), None),
+        ]
+    );
+    assert_eq!(store.expand(ONCOLOGY), None);
+}
+
+#[test]
+fn bindings_check_codes_against_imported_value_sets() {
+    let profiles = Profiles::load(regression::terminology_dir()).expect("profiles load");
+    let binding =
+        ValueSetBinding::new("ServiceRequest.code", PET, BindingStrength::Required).unwrap();
+    let order = |system: &str, code: &str| {
+        serde_json::json!({
+            "resourceType": "ServiceRequest",
+            "id": "SR-1",
+            "status": "active",
+            "intent": "order",
+            "subject": { "reference": "Patient/P-1" },
+            "code": { "coding": [{ "system": system, "code": code }] }
+        })
+    };
+    let ids = |system: &str, code: &str| -> Vec<String> {
+        binding
+            .check(&order(system, code), &profiles)
+            .into_iter()
+            .map(|issue| issue.id)
+            .collect()
+    };
+
+    assert!(ids("urn:oid:2.16.840.1.999999.1.12", "PETCT-BRAIN").is_empty());
+    assert_eq!(ids(LOCAL, "SPECT-MPI"), ["VAL_BINDING_CODE"]);
+    assert_eq!(ids(LOCAL, "PETMR-WB"), ["VAL_BINDING_CODE"]);
+    assert_eq!(ids("http://loinc.org", "24627-2"), ["VAL_BINDING_SYSTEM"]);
+}
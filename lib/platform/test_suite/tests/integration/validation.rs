@@ -1,4 +1,6 @@
-use dfps_ingestion::validation::validate_bundle;
+use dfps_ingestion::validation::{
+    Profiles, ValidationRules, ValidationSeverity, validate_bundle, validate_bundle_with_rules,
+};
 use dfps_test_suite::regression;
 
 #[test]
@@ -47,3 +49,197 @@ fn missing_encounter_bundle_reports_warning() {
             .any(|issue| issue.id == "VAL_SR_ENCOUNTER_NOT_FOUND")
     );
 }
+
+#[test]
+fn site_rules_add_issues_alongside_built_in_checks() {
+    let rules = ValidationRules::from_toml_str(regression::site_validation_rules()).expect("rules");
+    let mut bundle = regression::baseline_fhir_bundle();
+
+    let report = validate_bundle_with_rules(&bundle, &rules);
+    let issues: Vec<_> = report
+        .issues
+        .iter()
+        .map(|issue| (issue.id.as_str(), issue.requirement_ref(), issue.severity))
+        .collect();
+    assert_eq!(
+        issues,
+        vec![
+            (
+                "VAL_SITE_PATIENT_GENDER_MISSING",
+                "R_SiteDemographics",
+                ValidationSeverity::Info
+            ),
+            (
+                "VAL_SITE_SR_PRIORITY_MISSING",
+                "R_SitePriority",
+                ValidationSeverity::Warning
+            ),
+        ]
+    );
+    assert!(!report.has_errors());
+
+    let sr = bundle
+        .entry
+        .iter_mut()
+        .filter_map(|entry| entry.resource.as_mut())
+        .find(|resource| resource["resourceType"] == "ServiceRequest")
+        .expect("baseline ServiceRequest");
+    sr["status"] = "completed".into();
+    let report = validate_bundle_with_rules(&bundle, &rules);
+    assert!(report.has_errors());
+    assert!(
+        report
+            .issues
+            .iter()
+            .any(|issue| issue.id == "VAL_SITE_SR_COMPLETED_WITHOUT_OCCURRENCE")
+    );
+    assert!(!validate_bundle(&bundle).has_errors());
+}
+
+#[test]
+fn profiles_report_element_paths_for_declared_and_default_profiles() {
+    let profiles = Profiles::load(regression::profiles_dir()).expect("profiles load");
+    assert!(profiles.get(regression::IMAGING_SR_PROFILE).is_some());
+    let mut bundle = regression::baseline_fhir_bundle();
+
+    // Nothing declares the profile and there is no default: not checked.
+    let rules = ValidationRules::default().with_profiles(profiles.clone());
+    assert!(
+        validate_bundle_with_rules(&bundle, &rules)
+            .issues
+            .is_empty()
+    );
+
+    let rules = ValidationRules::default().with_profiles(
+        profiles
+            .clone()
+            .with_default(regression::IMAGING_SR_PROFILE)
+            .expect("default profile"),
+    );
+    let report = validate_bundle_with_rules(&bundle, &rules);
+    let issues: Vec<_> = report
+        .issues
+        .iter()
+        .map(|issue| (issue.id.as_str(), issue.path.as_deref(), issue.severity))
+        .collect();
+    assert_eq!(
+        issues,
+        vec![
+            (
+                "VAL_PROFILE_MUST_SUPPORT",
+                Some("ServiceRequest.identifier"),
+                ValidationSeverity::Info
+            ),
+            (
+                "VAL_PROFILE_MUST_SUPPORT",
+                Some("ServiceRequest.occurrence"),
+                ValidationSeverity::Info
+            ),
+        ]
+    );
+
+    let sr = bundle
+        .entry
+        .iter_mut()
+        .filter_map(|entry| entry.resource.as_mut())
+        .find(|resource| resource["resourceType"] == "ServiceRequest")
+        .expect("baseline ServiceRequest");
+    sr["meta"] =
+        serde_json::json!({ "profile": [format!("{}|0.1.0", regression::IMAGING_SR_PROFILE)] });
+    sr["intent"] = "plan".into();
+    sr["code"]["coding"][1]["code"] = "0000000".into();
+    sr["code"]["coding"][0] = serde_json::json!({ "code": "78815" });
+    let rules = ValidationRules::default().with_profiles(profiles);
+    let report = validate_bundle_with_rules(&bundle, &rules);
+    let errors: Vec<_> = report
+        .issues
+        .iter()
+        .filter(|issue| issue.severity == ValidationSeverity::Error)
+        .map(|issue| {
+            (
+                issue.id.as_str(),
+                issue.path.as_deref(),
+                issue.requirement_ref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                "VAL_PROFILE_FIXED_VALUE",
+                Some("ServiceRequest.intent"),
+                "R_Profile"
+            ),
+            (
+                "VAL_PROFILE_CARDINALITY",
+                Some("ServiceRequest.code.coding[0].system"),
+                "R_Profile"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn bindings_check_code_and_category_against_value_sets() {
+    let rules = ValidationRules::from_toml_str(regression::pet_bindings())
+        .expect("bindings")
+        .with_profiles(Profiles::load(regression::profiles_dir()).expect("profiles load"));
+    assert_eq!(rules.bindings().len(), 3);
+    let mut bundle = regression::baseline_fhir_bundle();
+
+    let issues =
+        |bundle: &dfps_core::fhir::Bundle| -> Vec<(String, Option<String>, ValidationSeverity)> {
+            validate_bundle_with_rules(bundle, &rules)
+                .issues
+                .into_iter()
+                .filter(|issue| issue.requirement_ref() == "R_Binding")
+                .map(|issue| (issue.id, issue.path, issue.severity))
+                .collect()
+        };
+    // SNOMED categories are outside the (preferred) imaging-ordering systems.
+    assert_eq!(
+        issues(&bundle),
+        vec![(
+            "VAL_BINDING_SYSTEM".to_string(),
+            Some("ServiceRequest.category[0]".to_string()),
+            ValidationSeverity::Info
+        )]
+    );
+
+    let set_codings = |bundle: &mut dfps_core::fhir::Bundle, codings: serde_json::Value| {
+        let sr = bundle
+            .entry
+            .iter_mut()
+            .filter_map(|entry| entry.resource.as_mut())
+            .find(|resource| resource["resourceType"] == "ServiceRequest")
+            .expect("baseline ServiceRequest");
+        sr["code"]["coding"] = codings;
+    };
+    // CPT is a PET system, but 70450 is not an imaging procedure.
+    set_codings(
+        &mut bundle,
+        serde_json::json!([{ "system": "http://www.ama-assn.org/go/cpt", "code": "70450" }]),
+    );
+    let report = issues(&bundle);
+    assert!(report.contains(&(
+        "VAL_BINDING_CODE".to_string(),
+        Some("ServiceRequest.code".to_string()),
+        ValidationSeverity::Warning
+    )));
+    assert!(
+        !report
+            .iter()
+            .any(|(_, _, severity)| *severity == ValidationSeverity::Error)
+    );
+
+    set_codings(
+        &mut bundle,
+        serde_json::json!([{ "system": "http://loinc.org", "code": "24627-2" }]),
+    );
+    assert!(issues(&bundle).contains(&(
+        "VAL_BINDING_SYSTEM".to_string(),
+        Some("ServiceRequest.code".to_string()),
+        ValidationSeverity::Error
+    )));
+}
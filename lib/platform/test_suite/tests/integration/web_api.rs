@@ -14,6 +14,8 @@ use dfps_core::{
     staging::{StgServiceRequestFlat, StgSrCodeExploded},
 };
 use dfps_observability::PipelineMetrics;
+use dfps_pipeline::ValidationRules;
+use dfps_terminology::{CodeSystemMeta, LicenseTier};
 use dfps_test_suite::regression;
 
 use http_body_util::BodyExt;
@@ -30,6 +32,7 @@ struct MapBundlesBody {
     exploded_codes: Vec<StgSrCodeExploded>,
     mapping_results: Vec<MappingResult>,
     // dim_concepts: Vec<DimNCITConcept>,
+    quarantine: Vec<serde_json::Value>,
 }
 
 #[derive(Deserialize)]
@@ -129,6 +132,165 @@ async fn map_bundles_unknown_code_surfaces_no_match() {
     assert_eq!(result.reason.as_deref(), Some("missing_system_or_code"));
 }
 
+#[tokio::test]
+async fn map_bundles_partial_mode_quarantines_bad_entries() {
+    let app = app();
+    let bundle = regression::fhir_bundle_partial();
+    let payload = serde_json::to_vec(&bundle).expect("serialize bundle");
+
+    let atomic = Request::builder()
+        .method("POST")
+        .uri("/api/map-bundles")
+        .header("content-type", "application/json")
+        .body(Body::from(payload.clone()))
+        .expect("request body");
+    let (status, _): (StatusCode, serde_json::Value) = send_json(&app, atomic).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let partial = Request::builder()
+        .method("POST")
+        .uri("/api/map-bundles?mode=partial")
+        .header("content-type", "application/json")
+        .body(Body::from(payload))
+        .expect("request body");
+    let (status, body): (StatusCode, MapBundlesBody) = send_json(&app, partial).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.flats.len(), 1);
+    assert_eq!(body.flats[0].sr_id, "SR-PARTIAL-OK");
+    assert_eq!(body.mapping_results.len(), 1);
+    assert_eq!(body.quarantine.len(), 2);
+    assert_eq!(body.quarantine[0]["index"], 2);
+    assert_eq!(body.quarantine[0]["error"]["code"], "invalid_status");
+    assert_eq!(
+        body.quarantine[0]["entry"]["resource"]["id"],
+        "SR-PARTIAL-BAD-STATUS"
+    );
+    assert_eq!(body.quarantine[1]["index"], 3);
+    assert_eq!(body.quarantine[1]["error"]["code"], "missing_field");
+    assert!(
+        body.quarantine[1]["issues"]
+            .as_array()
+            .expect("issues")
+            .iter()
+            .any(|issue| issue["id"] == "VAL_SR_SUBJECT_MISSING")
+    );
+}
+
+#[tokio::test]
+async fn map_bundles_rejects_unknown_mode() {
+    let app = app();
+    let payload = serde_json::to_vec(&regression::baseline_fhir_bundle()).expect("serialize");
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/map-bundles?mode=eventually")
+        .header("content-type", "application/json")
+        .body(Body::from(payload))
+        .expect("request body");
+    let (status, body): (StatusCode, serde_json::Value) = send_json(&app, request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_query");
+    assert!(body["message"].as_str().unwrap().contains("unknown mode"));
+}
+
+#[tokio::test]
+async fn map_bundles_operation_outcome_format_reports_fhir_issues() {
+    let app = app();
+    let payload = serde_json::to_vec(&regression::fhir_bundle_partial()).expect("serialize");
+
+    let atomic = Request::builder()
+        .method("POST")
+        .uri("/api/map-bundles?format=operation-outcome")
+        .header("content-type", "application/json")
+        .body(Body::from(payload.clone()))
+        .expect("request body");
+    let response = app.clone().oneshot(atomic).await.expect("router responded");
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.headers()["content-type"], "application/fhir+json");
+    let bytes = BodyExt::collect(response.into_body())
+        .await
+        .expect("collect response body")
+        .to_bytes();
+    let outcome: serde_json::Value = serde_json::from_slice(&bytes).expect("valid JSON body");
+    assert_eq!(outcome["resourceType"], "OperationOutcome");
+    assert!(outcome["id"].is_string());
+    assert_eq!(outcome["issue"][0]["severity"], "error");
+    assert_eq!(outcome["issue"][0]["code"], "code-invalid");
+    assert_eq!(
+        outcome["issue"][0]["details"]["coding"][0]["code"],
+        "invalid_status"
+    );
+
+    let partial = Request::builder()
+        .method("POST")
+        .uri("/api/map-bundles?mode=partial&format=operation-outcome")
+        .header("content-type", "application/json")
+        .body(Body::from(payload))
+        .expect("request body");
+    let (status, body): (StatusCode, serde_json::Value) = send_json(&app, partial).await;
+    assert_eq!(status, StatusCode::OK);
+    let issues = body["outcome"]["issue"].as_array().expect("outcome issues");
+    assert!(issues.iter().any(|issue| {
+        issue["expression"][0] == "Bundle.entry[3].resource.subject" && issue["code"] == "required"
+    }));
+
+    let unknown = Request::builder()
+        .method("POST")
+        .uri("/api/map-bundles?format=xml")
+        .header("content-type", "application/json")
+        .body(Body::from("{}"))
+        .expect("request body");
+    let (status, body): (StatusCode, serde_json::Value) = send_json(&app, unknown).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_query");
+}
+
+#[tokio::test]
+async fn map_bundles_reports_configured_validation_failures() {
+    let rules = ValidationRules::from_toml_str(
+        r#"
+        [[rules]]
+        id = "VAL_SITE_SR_REVOKED_ONLY"
+        severity = "error"
+        requirement = "R_SiteStatus"
+        expression = "status = 'revoked'"
+        message = "Only revoked orders are accepted at this site."
+        "#,
+    )
+    .expect("rules");
+    let app = api_router(ApiState::default().with_rules(rules));
+    let payload = serde_json::to_vec(&regression::baseline_fhir_bundle()).expect("serialize");
+
+    let json = Request::builder()
+        .method("POST")
+        .uri("/api/map-bundles")
+        .header("content-type", "application/json")
+        .body(Body::from(payload.clone()))
+        .expect("request body");
+    let (status, body): (StatusCode, serde_json::Value) = send_json(&app, json).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["flats"].as_array().expect("flats").len(), 1);
+    let issues = body["validation"][0]["issues"].as_array().expect("issues");
+    assert!(
+        issues
+            .iter()
+            .any(|issue| issue["id"] == "VAL_SITE_SR_REVOKED_ONLY" && issue["severity"] == "error")
+    );
+
+    let outcome = Request::builder()
+        .method("POST")
+        .uri("/api/map-bundles?format=operation-outcome")
+        .header("content-type", "application/json")
+        .body(Body::from(payload))
+        .expect("request body");
+    let (status, body): (StatusCode, serde_json::Value) = send_json(&app, outcome).await;
+    assert_eq!(status, StatusCode::OK);
+    let issues = body["outcome"]["issue"].as_array().expect("outcome issues");
+    assert!(issues.iter().any(|issue| {
+        issue["severity"] == "error"
+            && issue["details"]["coding"][0]["code"] == "VAL_SITE_SR_REVOKED_ONLY"
+    }));
+}
+
 #[tokio::test]
 async fn metrics_summary_tracks_processed_bundles() {
     let app = app();
@@ -168,6 +330,24 @@ async fn metrics_summary_tracks_processed_bundles() {
     assert_eq!(health.status, "ok");
 }
 
+#[tokio::test]
+async fn code_systems_lists_active_registry() {
+    let app = app();
+    let request = Request::builder()
+        .method("GET")
+        .uri("/api/code-systems")
+        .body(Body::empty())
+        .expect("code systems request");
+    let (status, entries): (StatusCode, Vec<CodeSystemMeta>) = send_json(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    let snomed = entries
+        .iter()
+        .find(|meta| meta.url == "http://snomed.info/sct")
+        .expect("SNOMED CT is built in");
+    assert_eq!(snomed.license_tier, LicenseTier::Licensed);
+    assert_eq!(snomed.aliases, vec!["urn:oid:2.16.840.1.113883.6.96"]);
+}
+
 #[tokio::test]
 async fn ci_smoke_server_runs_endpoints() {
     let (addr, shutdown_tx, handle) = spawn_http_server().await;
@@ -8,6 +8,7 @@ fn staging_with_display(display: String) -> StgSrCodeExploded {
         system: Some("http://snomed.info/sct".into()),
         code: Some("999999".into()),
         display: Some(display),
+        provenance: None,
     }
 }
 